@@ -0,0 +1,25 @@
+//! Feeds arbitrary bytes as the log through parsing and transforming, which must never panic.
+//!
+//! Run with `cargo +nightly fuzz run assimilate_str` from the root of the repository.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tlcfi_assimilator::assimilate::{assimilate_str, AssimilateOptions, Mapping};
+
+const MAPPING: &str = include_str!("../../vlog_tlcfi_mapping.txt");
+
+fuzz_target!(|data: &[u8]| {
+    let log = String::from_utf8_lossy(data);
+    let mapping: Mapping = MAPPING.parse().unwrap();
+    for is_chronological in [false, true].iter() {
+        let _ = assimilate_str(
+            &log,
+            &mapping,
+            &AssimilateOptions {
+                is_chronological: *is_chronological,
+                ..Default::default()
+            },
+        );
+    }
+});
@@ -0,0 +1,127 @@
+//! Collapses amber into green or red, for consumers that only know whether a signal group may be driven through.
+
+use std::str::FromStr;
+
+use serde::Serialize;
+use tlcfi_assimilator::{SignalState, TimestampedChanges};
+
+/// What amber signal states become.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize)]
+pub enum AmberPolicy {
+    /// Amber stays amber
+    #[default]
+    Keep,
+    /// Amber becomes green, as traffic may still pass
+    AsGreen,
+    /// Amber becomes red, as traffic should stop
+    AsRed,
+}
+
+impl AmberPolicy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AmberPolicy::Keep => "keep",
+            AmberPolicy::AsGreen => "as-green",
+            AmberPolicy::AsRed => "as-red",
+        }
+    }
+
+    fn collapsed_amber(&self) -> Option<SignalState> {
+        match self {
+            AmberPolicy::Keep => None,
+            AmberPolicy::AsGreen => Some(SignalState::Green),
+            AmberPolicy::AsRed => Some(SignalState::Red),
+        }
+    }
+}
+
+impl FromStr for AmberPolicy {
+    type Err = String;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        match arg {
+            "keep" => Ok(AmberPolicy::Keep),
+            "as-green" => Ok(AmberPolicy::AsGreen),
+            "as-red" => Ok(AmberPolicy::AsRed),
+            _ => Err(format!(
+                "'{}' isn't an amber policy, use as-green, as-red or keep",
+                arg
+            )),
+        }
+    }
+}
+
+/// Replaces the amber signal states in the changes according to the policy. Flashing amber is left alone,
+/// since it means the signal group is out of regular control rather than about to turn red.
+///
+/// Returns the number of replaced states.
+pub fn collapse_amber(changes: &mut [TimestampedChanges], policy: AmberPolicy) -> usize {
+    let collapsed_amber = match policy.collapsed_amber() {
+        Some(collapsed_amber) => collapsed_amber,
+        None => return 0,
+    };
+    let mut collapsed_count = 0;
+    for state in changes
+        .iter_mut()
+        .flat_map(|change| change.signal_states.iter_mut())
+        .filter(|state| **state == SignalState::Amber)
+    {
+        *state = collapsed_amber;
+        collapsed_count += 1;
+    }
+    collapsed_count
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_test_changes() -> Vec<TimestampedChanges> {
+        vec![TimestampedChanges {
+            ms_from_beginning: 100,
+            signal_names: vec!["02".to_string(), "03".to_string(), "04".to_string()],
+            signal_states: vec![
+                SignalState::Amber,
+                SignalState::AmberFlashing,
+                SignalState::Green,
+            ],
+            ..Default::default()
+        }]
+    }
+
+    #[test]
+    fn amber_should_become_the_state_of_the_policy() {
+        for (policy, expected_state) in [
+            (AmberPolicy::Keep, SignalState::Amber),
+            (AmberPolicy::AsGreen, SignalState::Green),
+            (AmberPolicy::AsRed, SignalState::Red),
+        ]
+        .iter()
+        {
+            let mut changes = get_test_changes();
+
+            let collapsed_count = collapse_amber(&mut changes, *policy);
+
+            assert_eq!(
+                changes[0].signal_states,
+                vec![
+                    *expected_state,
+                    SignalState::AmberFlashing,
+                    SignalState::Green
+                ]
+            );
+            assert_eq!(
+                collapsed_count,
+                if *policy == AmberPolicy::Keep { 0 } else { 1 }
+            );
+        }
+    }
+
+    #[test]
+    fn parsing_an_amber_policy_should_accept_the_three_policies() {
+        assert_eq!("keep".parse(), Ok(AmberPolicy::Keep));
+        assert_eq!("as-green".parse(), Ok(AmberPolicy::AsGreen));
+        assert_eq!("as-red".parse(), Ok(AmberPolicy::AsRed));
+        assert!("green".parse::<AmberPolicy>().is_err());
+    }
+}
@@ -0,0 +1,152 @@
+//! Appends the VLog messages of a later log to an existing VLog file, so logs converted one after the other can be
+//! viewed as a single session.
+//!
+//! The new change messages continue from the last time reference of the file, new time references are inserted
+//! every 5 minutes as usual.
+
+use std::fs;
+
+use chrono::NaiveDateTime;
+use tlcfi_assimilator::TimestampedChanges;
+
+use crate::{vlog_decoding::MessageClock, LineEnding};
+
+/// The end of an existing VLog file that new messages are appended to.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AppendTarget {
+    pub file_name: String,
+    /// The messages already in the file, for verifying the combined output
+    pub messages: Vec<String>,
+    /// The time of the last time reference, which the deltas of appended change messages are relative to
+    pub last_reference: NaiveDateTime,
+    /// The time of the last message with a time, the appended changes have to come after it
+    pub last_message_time: NaiveDateTime,
+    /// The line ending of the file, which the appended messages use as well
+    pub line_ending: LineEnding,
+}
+
+/// Reads the end of the VLog file to append to.
+pub fn read_append_target(file_name: &str) -> Result<AppendTarget, String> {
+    let content = fs::read_to_string(file_name)
+        .map_err(|error| format!("failed to read the VLog file '{}': {}", file_name, error))?;
+    let messages: Vec<String> = content
+        .lines()
+        .map(|line| line.trim_end_matches('\r').to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+    let mut clock = MessageClock::default();
+    let mut last_reference = None;
+    let mut last_message_time = None;
+    for message in &messages {
+        if let Some(message_time) = clock.time_of(message) {
+            if message.starts_with("01") {
+                last_reference = Some(message_time);
+            }
+            last_message_time = last_message_time.max(Some(message_time));
+        }
+    }
+    let last_reference = last_reference
+        .ok_or_else(|| format!("the VLog file '{}' has no time reference", file_name))?;
+    Ok(AppendTarget {
+        file_name: file_name.to_string(),
+        messages,
+        last_reference,
+        last_message_time: last_message_time.unwrap_or(last_reference),
+        line_ending: if content.contains("\r\n") || !content.contains('\n') {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Lf
+        },
+    })
+}
+
+/// Checks that the log to append starts after the existing file ends, so the combined timeline only goes forward.
+pub fn check_start(target: &AppendTarget, start_date_time: &NaiveDateTime) -> Result<(), String> {
+    if *start_date_time < target.last_message_time {
+        Err(format!(
+            "the log starts at {}, before the VLog file '{}' ends at {}",
+            start_date_time, target.file_name, target.last_message_time
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// How far the start of the log to append is after the last time reference of the file.
+/// [check_start](fn.check_start.html) makes sure it isn't before it.
+pub fn offset_ms(target: &AppendTarget, start_date_time: &NaiveDateTime) -> u64 {
+    (*start_date_time - target.last_reference)
+        .num_milliseconds()
+        .max(0) as u64
+}
+
+/// Makes the changes relative to the last time reference of the file instead of the start of the log.
+pub fn shift_changes(changes: &mut [TimestampedChanges], offset_ms: u64) {
+    for change in changes {
+        change.ms_from_beginning += offset_ms;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn write_vlog(name: &str, content: &str) -> String {
+        let file_name = std::env::temp_dir()
+            .join(name)
+            .to_string_lossy()
+            .to_string();
+        fs::write(&file_name, content).unwrap();
+        file_name
+    }
+
+    #[test]
+    fn the_target_should_continue_from_the_last_time_reference() {
+        let file_name = write_vlog(
+            "tlcfi_assimilator_append_target.vlg",
+            "012021121511000000\r\n04030000333033312020\r\n0E00010001\r\n012021121511050000\r\n0E0A010002\r\n",
+        );
+
+        let target = read_append_target(&file_name).unwrap();
+        fs::remove_file(&file_name).unwrap();
+
+        assert_eq!(
+            target.last_reference,
+            NaiveDate::from_ymd(2021, 12, 15).and_hms(11, 5, 0)
+        );
+        assert_eq!(
+            target.last_message_time,
+            NaiveDate::from_ymd(2021, 12, 15).and_hms(11, 5, 16)
+        );
+        assert_eq!(target.line_ending, LineEnding::Crlf);
+        assert_eq!(target.messages.len(), 5);
+        assert!(check_start(
+            &target,
+            &NaiveDate::from_ymd(2021, 12, 15).and_hms(11, 5, 10)
+        )
+        .is_err());
+        assert!(check_start(
+            &target,
+            &NaiveDate::from_ymd(2021, 12, 15).and_hms(12, 0, 0)
+        )
+        .is_ok());
+        assert_eq!(
+            offset_ms(
+                &target,
+                &NaiveDate::from_ymd(2021, 12, 15).and_hms(12, 0, 0)
+            ),
+            3_300_000
+        );
+    }
+
+    #[test]
+    fn a_file_without_time_reference_should_not_be_appended_to() {
+        let file_name = write_vlog("tlcfi_assimilator_append_empty.vlg", "");
+
+        let result = read_append_target(&file_name);
+        fs::remove_file(&file_name).unwrap();
+
+        assert!(result.unwrap_err().ends_with("has no time reference"));
+    }
+}
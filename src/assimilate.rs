@@ -0,0 +1,372 @@
+//! Runs the whole conversion on a log and mapping in memory, without touching the filesystem,
+//! for embedding the assimilator in other programs and for fuzzing the parser and transformer together.
+
+use std::{collections::BTreeSet, collections::HashMap, fmt, str::FromStr};
+
+use chrono::NaiveDateTime;
+
+use crate::{
+    tlcfi_parsing,
+    vlog_transformer::{self, TransformOptions, VLogTransformer},
+    AssimilationData, Console, ConversionMode, ConversionStats, MessageDirection,
+    SignalStateEncoding, TimeSource, Verbosity,
+};
+
+/// The content of a VLog TLC FI mapping file: the TLC name and the VLog ids of the TLC FI names.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Mapping {
+    pub tlc_name: String,
+    pub signals: HashMap<String, i16>,
+    pub detectors: HashMap<String, i16>,
+}
+
+impl FromStr for Mapping {
+    type Err = AssimilateError;
+
+    /// Parses the content of a mapping file. A mapping file without signals or detectors has empty mappings for them.
+    fn from_str(content: &str) -> Result<Self, Self::Err> {
+        let tlc_name = vlog_transformer::read_tlc_name(content.as_bytes())
+            .ok_or_else(|| AssimilateError::InvalidMapping("no TLC name was found".to_string()))?;
+        let read_mappings = |mapping_type: &str| {
+            vlog_transformer::read_mappings(content.as_bytes(), mapping_type)
+                .map_err(|error| AssimilateError::InvalidMapping(error.to_string()))
+        };
+        Ok(Mapping {
+            tlc_name,
+            signals: read_mappings("Signals")?,
+            detectors: read_mappings("Detectors")?,
+        })
+    }
+}
+
+/// Sets how [assimilate_str](fn.assimilate_str.html) reads the log and what it converts.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AssimilateOptions {
+    /// The moment the first message in the log was sent, taken from the first log line when None
+    pub start_date_time: Option<NaiveDateTime>,
+    pub is_chronological: bool,
+    /// The direction of the messages that are converted
+    pub direction: MessageDirection,
+    pub invert_direction: bool,
+    /// Where the times of the messages are taken from
+    pub time_source: TimeSource,
+    pub conversion_mode: ConversionMode,
+    pub signal_state_encoding: SignalStateEncoding,
+}
+
+/// Why a log couldn't be assimilated.
+#[derive(Debug, PartialEq, Clone)]
+pub enum AssimilateError {
+    /// The mapping file content can't be parsed
+    InvalidMapping(String),
+    /// The start date time wasn't given and there is no log line to take it from
+    NoStartDateTime,
+    /// The log has no signal or detector messages that can be converted
+    NoMessages,
+    /// Names in the log that the mapping doesn't have
+    UnmappedNames {
+        signals: Vec<String>,
+        detectors: Vec<String>,
+    },
+}
+
+impl fmt::Display for AssimilateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssimilateError::InvalidMapping(reason) => write!(f, "invalid mapping: {}", reason),
+            AssimilateError::NoStartDateTime => write!(
+                f,
+                "no start date time was given and the log has no line to take it from"
+            ),
+            AssimilateError::NoMessages => write!(
+                f,
+                "the log contains no signal or detector messages that can be converted"
+            ),
+            AssimilateError::UnmappedNames { signals, detectors } => write!(
+                f,
+                "names missing from the mapping, signals: [{}], detectors: [{}]",
+                signals.join(", "),
+                detectors.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AssimilateError {}
+
+/// Converts the lines of a TLC-FI log into VLog messages, starting with the time reference and V-Log information message.
+///
+/// Warnings about the log aren't printed, lines that can't be parsed are skipped like they are when converting a file.
+pub fn assimilate_str(
+    log: &str,
+    mapping: &Mapping,
+    options: &AssimilateOptions,
+) -> Result<Vec<String>, AssimilateError> {
+    let mut console = Console::new(Verbosity::Quiet);
+    let sorted_lines = tlcfi_parsing::read_lines(
+        log.as_bytes(),
+        options.is_chronological,
+        &mut console,
+        &mut |_| {},
+    )
+    // Reading a str can't fail
+    .unwrap_or_default();
+    let start_time = match options.start_date_time {
+        Some(start_date_time) => start_date_time,
+        None => sorted_lines
+            .iter()
+            .find_map(|line| tlcfi_parsing::log_line_time(line))
+            .ok_or(AssimilateError::NoStartDateTime)?,
+    };
+
+    let mut data = AssimilationData {
+        start_time,
+        conversion_mode: options.conversion_mode,
+        time_source: options.time_source,
+        console,
+        sorted_lines,
+        ..Default::default()
+    };
+    tlcfi_parsing::parse_lines(
+        &mut data,
+        options.direction,
+        options.invert_direction,
+        false,
+        &mut |_| {},
+    )
+    .expect("Lenient parsing doesn't fail");
+    if data.changes.is_empty() {
+        return Err(AssimilateError::NoMessages);
+    }
+    check_names_are_mapped(&data, mapping)?;
+
+    let mut transformer = VLogTransformer::with_mappings(
+        &start_time,
+        mapping.signals.clone(),
+        mapping.detectors.clone(),
+        TransformOptions {
+            conversion_mode: options.conversion_mode,
+            verbosity: Verbosity::Quiet,
+            signal_state_encoding: options.signal_state_encoding,
+            ..Default::default()
+        },
+    );
+    let mut stats = ConversionStats::default();
+    let mut vlog_messages = transformer.start(&mapping.tlc_name, &mut stats);
+    vlog_messages.extend(transformer.transform(data.changes, &mut stats));
+    Ok(vlog_messages)
+}
+
+/// The transformer needs every name in the changes to be mapped.
+fn check_names_are_mapped(
+    data: &AssimilationData,
+    mapping: &Mapping,
+) -> Result<(), AssimilateError> {
+    let mut signals = BTreeSet::new();
+    let mut detectors = BTreeSet::new();
+    for change in &data.changes {
+        signals.extend(
+            change
+                .signal_names
+                .iter()
+                .filter(|name| !mapping.signals.contains_key(*name)),
+        );
+        detectors.extend(
+            change
+                .detector_names
+                .iter()
+                .filter(|name| !mapping.detectors.contains_key(*name)),
+        );
+    }
+    if signals.is_empty() && detectors.is_empty() {
+        Ok(())
+    } else {
+        Err(AssimilateError::UnmappedNames {
+            signals: signals.into_iter().cloned().collect(),
+            detectors: detectors.into_iter().cloned().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TEST_MAPPING: &str =
+        "// TLC\n3031\n\n// Signals\n0, 02\n1, 03\n\n// Detectors\n0, D611\n";
+
+    fn log_line(time: &str, json: &str) -> String {
+        format!("2021-12-15 {} INFO  tlcFiMessages:41 - IN - {}", time, json)
+    }
+
+    fn signal_json(ticks: &str, id: &str, state: &str) -> String {
+        format!(
+            "{{\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{{\"ticks\":{},\"update\":[{{\"objects\":{{\"ids\":[\"{}\"],\"type\":3}},\"states\":[{{\"state\":{}}}]}}]}}}}",
+            ticks, id, state
+        )
+    }
+
+    #[test]
+    fn a_log_in_memory_should_give_the_same_messages_as_a_file() {
+        let log = std::fs::read_to_string("./tlcfi_with_amber.txt").unwrap();
+        let mapping: Mapping = std::fs::read_to_string("./vlog_tlcfi_mapping.txt")
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        let vlog_messages = assimilate_str(&log, &mapping, &AssimilateOptions::default()).unwrap();
+
+        assert_eq!(
+            vlog_messages,
+            vec![
+                "012021121512000000",
+                "040300003330333120202020202020202020202020202020",
+                "0E00010001",
+                "0E01E10002",
+                "0E03C10000",
+            ]
+        );
+    }
+
+    #[test]
+    fn a_faulty_detector_should_be_written_between_its_other_states() {
+        let detector_json = |ticks: &str, state: &str| {
+            signal_json(ticks, "D611", state).replace("\"type\":3", "\"type\":4")
+        };
+        let log = [
+            log_line("12:00:02,000", &detector_json("3000", "1")),
+            log_line("12:00:01,000", &detector_json("2000", "2")),
+            log_line("12:00:00,000", &detector_json("1000", "0")),
+        ]
+        .join("\n");
+
+        let vlog_messages = assimilate_str(
+            &log,
+            &TEST_MAPPING.parse().unwrap(),
+            &AssimilateOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            vlog_messages[2..].to_vec(),
+            vec!["0600010000", "0600A10002", "0601410001"]
+        );
+    }
+
+    #[test]
+    fn a_night_flash_should_be_written_as_flashing_amber() {
+        let log = [
+            log_line("12:05:06,000", &signal_json("307000", "02", "3")),
+            log_line("12:00:06,000", &signal_json("7000", "02", "9")),
+            log_line("12:00:03,000", &signal_json("4000", "02", "8")),
+            log_line("12:00:00,000", &signal_json("1000", "02", "6")),
+        ]
+        .join("\n");
+
+        let vlog_messages = assimilate_str(
+            &log,
+            &TEST_MAPPING.parse().unwrap(),
+            &AssimilateOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            vlog_messages[2..].to_vec(),
+            vec![
+                "0E00010001",
+                "0E01E10002",
+                "0E03C10005",
+                "012021121512050600",
+                "0E00010000"
+            ]
+        );
+    }
+
+    #[test]
+    fn a_mapping_without_a_tlc_name_should_be_rejected() {
+        assert!(matches!(
+            "// Signals\n0, 02\n".parse::<Mapping>(),
+            Err(AssimilateError::InvalidMapping(_))
+        ));
+        assert!(matches!(
+            "// TLC\n3031\n\n// Signals\n0 02\n".parse::<Mapping>(),
+            Err(AssimilateError::InvalidMapping(_))
+        ));
+        assert_eq!(TEST_MAPPING.parse::<Mapping>().unwrap().signals.len(), 2);
+    }
+
+    #[test]
+    fn names_missing_from_the_mapping_should_be_returned() {
+        let log = log_line("12:00:00,000", &signal_json("1000", "04", "6"));
+
+        assert_eq!(
+            assimilate_str(
+                &log,
+                &TEST_MAPPING.parse().unwrap(),
+                &AssimilateOptions::default()
+            ),
+            Err(AssimilateError::UnmappedNames {
+                signals: vec!["04".to_string()],
+                detectors: Vec::new()
+            })
+        );
+    }
+
+    #[test]
+    fn faulty_messages_should_be_skipped_without_panicking() {
+        let mapping: Mapping = TEST_MAPPING.parse().unwrap();
+        let faulty_lines = vec![
+            // Sets the first tick without a change, so the next tick going back has no previous tick
+            log_line(
+                "12:00:00,000",
+                "{\"method\":\"UpdateState\",\"params\":{\"ticks\":5000,\"update\":[{\"objects\":{\"type\":5}}]}}",
+            ),
+            log_line("12:00:00,000", "{\"method\":\"UpdateState\",\"params\":{\"ticks\":-1}}"),
+            log_line("12:00:00,000", "{\"method\":\"UpdateState\",\"params\":{\"ticks\":1e30}}"),
+            log_line("12:00:01,000", &signal_json("2000", "02", "10")),
+            log_line("12:00:01,000", &signal_json("2000", "02", "-6")),
+            log_line("12:00:01,000", &signal_json("1e30", "02", "6")),
+            log_line("12:00:01,000", &signal_json("1000", "02\"]}},{\"x\":[\"03", "6")),
+            log_line(
+                "12:00:01,000",
+                "{\"method\":\"UpdateState\",\"params\":{\"ticks\":2000,\"update\":[{\"objects\":{\"ids\":[\"02\",\"03\"],\"type\":3},\"states\":[{\"state\":6}]}]}}",
+            ),
+            log_line(
+                "12:00:01,000",
+                "{\"method\":\"UpdateState\",\"params\":{\"ticks\":2000,\"update\":[{\"objects\":{\"ids\":[\"02\"],\"type\":-3}}]}}",
+            ),
+            "2021-12-15 12:00:01,00é - IN - {}".to_string(),
+        ];
+        // The last change goes back to before the last time reference
+        let mut lines = vec![
+            log_line("12:00:00,000", &signal_json("5000", "02", "6")),
+            log_line("12:00:00,000", &signal_json("400000", "03", "6")),
+            log_line("12:00:00,000", &signal_json("6000", "02", "3")),
+        ];
+        lines.extend(faulty_lines.clone());
+
+        let vlog_messages = assimilate_str(
+            &lines.join("\n"),
+            &mapping,
+            &AssimilateOptions {
+                is_chronological: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(vlog_messages.len(), 6);
+        assert_eq!(
+            assimilate_str(
+                &faulty_lines.join("\n"),
+                &mapping,
+                &AssimilateOptions::default()
+            ),
+            Err(AssimilateError::NoMessages)
+        );
+        assert_eq!(
+            assimilate_str("\u{0}\u{ff}", &mapping, &AssimilateOptions::default()),
+            Err(AssimilateError::NoStartDateTime)
+        );
+    }
+}
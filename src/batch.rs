@@ -0,0 +1,293 @@
+//! Converts every log in a directory on its own, for converting a day's worth of logs of several controllers at once.
+//!
+//! A log that fails doesn't stop the batch, its failure shows up in the result table at the end.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use tlcfi_assimilator::Console;
+
+use crate::{AppArgs, ConversionFailure, EXIT_CODE_INPUT_ERROR};
+
+const BATCH_HELP: &str = "\
+TLC-FI Assimilator batch
+
+USAGE:
+  tlcfi_assimilator batch [OPTIONS] --input-dir STRING --mapping-dir STRING --output-dir STRING
+
+FLAGS:
+  -h, --help                Prints help information
+  -q, --quiet               Only prints errors and the result table
+  -v, --verbose             Also prints per line diagnostics, tick overflows and resets and mappings
+
+OPTIONS:
+  --input-dir STRING        Converts every *.txt log in this directory
+  --mapping-dir STRING      Uses the mapping file with the same name as the log from this directory, or the only
+                            file in it
+  --output-dir STRING       Writes the VLog files to this directory
+  --chronological           Reads the logs as chronological (newest last), --chronological false still works
+  --reverse                 Reads the logs as newest first, which is the default
+  --direction STRING        Which messages are converted: in, out or both [default: in]
+  --invert-direction        Treat OUT messages as coming from the TLC and IN messages as going to it
+  --overwrite               Replaces VLog files that already exist
+";
+
+/// The directories of a batch and the options every log is converted with.
+#[derive(Debug, Clone)]
+pub struct BatchArgs {
+    input_dir: String,
+    mapping_dir: String,
+    output_dir: String,
+    app_args: AppArgs,
+}
+
+/// How converting a single log of the batch ended.
+#[derive(Debug)]
+pub struct BatchResult {
+    log_file: String,
+    outcome: Result<Vec<String>, ConversionFailure>,
+}
+
+/// Parses the `batch` arguments, converts the logs and prints the result table. Returns the exit code.
+pub fn run_batch() -> i32 {
+    let mut pargs = pico_args::Arguments::from_env();
+    // Drop the "batch" subcommand itself
+    let _ = pargs.subcommand();
+
+    if pargs.contains(["-h", "--help"]) {
+        println!("{}", BATCH_HELP);
+        return 0;
+    }
+
+    let batch_args = match parse_batch_args(&mut pargs) {
+        Ok(batch_args) => batch_args,
+        Err(error) => {
+            error.print(BATCH_HELP);
+            return crate::EXIT_CODE_ARGUMENT_ERROR;
+        }
+    };
+
+    let console = Console::new(batch_args.app_args.verbosity);
+    let results = match convert_all(&batch_args) {
+        Ok(results) => results,
+        Err(error) => {
+            console.error(&error);
+            return EXIT_CODE_INPUT_ERROR;
+        }
+    };
+    for line in result_table(&results) {
+        println!("{}", line);
+    }
+    batch_exit_code(&results)
+}
+
+/// Reads the options of the `batch` subcommand.
+fn parse_batch_args(pargs: &mut pico_args::Arguments) -> Result<BatchArgs, crate::ArgsError> {
+    let app_args = AppArgs {
+        is_chronological: crate::parse_is_chronological(pargs)?,
+        direction: pargs.opt_value_from_str("--direction")?.unwrap_or_default(),
+        invert_direction: pargs.contains("--invert-direction"),
+        overwrite: pargs.contains("--overwrite"),
+        verbosity: crate::parse_verbosity(pargs)?,
+        // A progress line per log would push the result table out of sight
+        show_progress: false,
+        ..Default::default()
+    };
+    Ok(BatchArgs {
+        input_dir: pargs.value_from_fn("--input-dir", check_dir_existence)?,
+        mapping_dir: pargs.value_from_fn("--mapping-dir", check_dir_existence)?,
+        output_dir: pargs.value_from_fn("--output-dir", check_dir_existence)?,
+        app_args,
+    })
+}
+
+pub fn check_dir_existence(dir: &str) -> Result<String, String> {
+    if Path::new(dir).is_dir() {
+        Ok(dir.to_string())
+    } else {
+        Err(format!(
+            "Directory passed as argument '{}' doesn't exist",
+            dir
+        ))
+    }
+}
+
+/// Converts every `*.txt` log of the input directory, in the order of their names.
+/// Each log gets its own start time from its first line and its own VLog file name.
+pub fn convert_all(batch_args: &BatchArgs) -> Result<Vec<BatchResult>, String> {
+    let mut log_files: Vec<PathBuf> = fs::read_dir(&batch_args.input_dir)
+        .map_err(|error| {
+            format!(
+                "Error: failed to read the input directory '{}': {}",
+                batch_args.input_dir, error
+            )
+        })?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "txt"))
+        .collect();
+    log_files.sort();
+
+    Ok(log_files
+        .iter()
+        .map(|log_file| {
+            let outcome =
+                find_mapping_file(&batch_args.mapping_dir, log_file).and_then(|mapping_file| {
+                    crate::convert(&AppArgs {
+                        tlcfi_log_file: log_file.to_string_lossy().to_string(),
+                        vlog_tlcfi_mapping_file: mapping_file,
+                        output_dir: Some(batch_args.output_dir.clone()),
+                        ..batch_args.app_args.clone()
+                    })
+                    .map(|report| report.file_names)
+                });
+            BatchResult {
+                log_file: log_file
+                    .file_name()
+                    .map_or_else(String::new, |name| name.to_string_lossy().to_string()),
+                outcome,
+            }
+        })
+        .collect())
+}
+
+/// The mapping file with the same name as the log, or the only file of the mapping directory.
+fn find_mapping_file(mapping_dir: &str, log_file: &Path) -> Result<String, ConversionFailure> {
+    let mapping_failure = |message: String| ConversionFailure {
+        exit_code: crate::EXIT_CODE_MAPPING_ERROR,
+        message,
+    };
+    if let Some(log_name) = log_file.file_name() {
+        let same_name = Path::new(mapping_dir).join(log_name);
+        if same_name.is_file() {
+            return Ok(same_name.to_string_lossy().to_string());
+        }
+    }
+    let mapping_files: Vec<PathBuf> = fs::read_dir(mapping_dir)
+        .map_err(|error| {
+            mapping_failure(format!(
+                "Error: failed to read the mapping directory '{}': {}",
+                mapping_dir, error
+            ))
+        })?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    match mapping_files.as_slice() {
+        [mapping_file] => Ok(mapping_file.to_string_lossy().to_string()),
+        _ => Err(mapping_failure(format!(
+            "Error: no mapping file named '{}' in '{}', and it doesn't hold a single mapping file",
+            log_file.file_name().unwrap_or_default().to_string_lossy(),
+            mapping_dir
+        ))),
+    }
+}
+
+/// One line per log with the written files or the first line of the failure.
+fn result_table(results: &[BatchResult]) -> Vec<String> {
+    let width = results
+        .iter()
+        .map(|result| result.log_file.len())
+        .max()
+        .unwrap_or(0)
+        .max("LOG".len());
+    let mut lines = vec![format!("{:width$}  RESULT", "LOG", width = width)];
+    for result in results {
+        let outcome = match &result.outcome {
+            Ok(file_names) => format!("ok: {}", file_names.join(", ")),
+            Err(failure) => format!(
+                "failed ({}): {}",
+                failure.exit_code,
+                failure.message.lines().next().unwrap_or_default()
+            ),
+        };
+        lines.push(format!(
+            "{:width$}  {}",
+            result.log_file,
+            outcome,
+            width = width
+        ));
+    }
+    lines.push(format!(
+        "{} of {} log(s) converted",
+        results
+            .iter()
+            .filter(|result| result.outcome.is_ok())
+            .count(),
+        results.len()
+    ));
+    lines
+}
+
+/// Succeeds when every log converted, otherwise exits with the code of the first failure.
+fn batch_exit_code(results: &[BatchResult]) -> i32 {
+    results
+        .iter()
+        .find_map(|result| result.outcome.as_ref().err())
+        .map_or(0, |failure| failure.exit_code)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tlcfi_assimilator::Verbosity;
+
+    #[test]
+    fn a_failing_log_should_not_stop_the_batch() {
+        let batch_dir = std::env::temp_dir().join("tlcfi_assimilator_batch");
+        let _ = fs::remove_dir_all(&batch_dir);
+        let (input_dir, mapping_dir, output_dir) = (
+            batch_dir.join("logs"),
+            batch_dir.join("mappings"),
+            batch_dir.join("vlog"),
+        );
+        for dir in [&input_dir, &mapping_dir, &output_dir].iter() {
+            fs::create_dir_all(dir).unwrap();
+        }
+        let log = fs::read_to_string("./tlcfi_with_amber.txt").unwrap();
+        fs::write(input_dir.join("a_noon.txt"), &log).unwrap();
+        fs::write(
+            input_dir.join("b_afternoon.txt"),
+            log.replace(" 12:", " 13:"),
+        )
+        .unwrap();
+        fs::write(input_dir.join("c_corrupt.txt"), "\u{0}garbage\n").unwrap();
+        fs::write(input_dir.join("notes.md"), "not a log").unwrap();
+        fs::copy("./vlog_tlcfi_mapping.txt", mapping_dir.join("3031.txt")).unwrap();
+        let batch_args = BatchArgs {
+            input_dir: input_dir.to_string_lossy().to_string(),
+            mapping_dir: mapping_dir.to_string_lossy().to_string(),
+            output_dir: output_dir.to_string_lossy().to_string(),
+            app_args: AppArgs {
+                verbosity: Verbosity::Quiet,
+                show_progress: false,
+                ..Default::default()
+            },
+        };
+
+        let results = convert_all(&batch_args).unwrap();
+        let mut vlog_files: Vec<String> = fs::read_dir(&output_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        vlog_files.sort();
+        let table = result_table(&results);
+        fs::remove_dir_all(&batch_dir).unwrap();
+
+        assert_eq!(
+            vlog_files,
+            vec!["3031_20211215_120000.vlg", "3031_20211215_130000.vlg"]
+        );
+        assert_eq!(results.len(), 3);
+        assert!(results[0].outcome.is_ok());
+        assert!(results[1].outcome.is_ok());
+        assert_eq!(
+            results[2].outcome.as_ref().err().unwrap().exit_code,
+            crate::EXIT_CODE_NO_MESSAGES
+        );
+        assert!(table[3].starts_with("c_corrupt.txt    failed (5): "));
+        assert_eq!(table[4], "2 of 3 log(s) converted");
+        assert_eq!(batch_exit_code(&results), crate::EXIT_CODE_NO_MESSAGES);
+    }
+}
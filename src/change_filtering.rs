@@ -0,0 +1,329 @@
+//! Filters that reduce the [TimestampedChanges](struct.TimestampedChanges.html) before they are transformed into VLog messages.
+
+use std::collections::HashMap;
+
+use regex::Regex;
+use serde::{Serialize, Serializer};
+use tlcfi_assimilator::TimestampedChanges;
+
+/// The prefix of a name in a filter that makes it a regular expression, e.g. `re:^D7`.
+const REGEX_PREFIX: &str = "re:";
+
+/// A name in a filter, either the literal TLC-FI name or a regular expression matched against the names.
+#[derive(Debug, Clone)]
+pub enum NamePattern {
+    Literal(String),
+    Regex(Regex),
+}
+
+impl NamePattern {
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Literal(literal) => literal == name,
+            Self::Regex(regex) => regex.is_match(name),
+        }
+    }
+
+    /// The pattern as it's given on the command line.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Literal(literal) => literal.clone(),
+            Self::Regex(regex) => format!("{}{}", REGEX_PREFIX, regex.as_str()),
+        }
+    }
+}
+
+impl From<&str> for NamePattern {
+    fn from(name: &str) -> Self {
+        Self::Literal(name.to_string())
+    }
+}
+
+// Regexes are compared by their pattern
+impl PartialEq for NamePattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.describe() == other.describe()
+    }
+}
+
+impl Eq for NamePattern {}
+
+impl Serialize for NamePattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.describe())
+    }
+}
+
+/// Which entity names to keep when filtering changes.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub enum NameFilter {
+    /// Only keep the entities matching these patterns
+    Include(Vec<NamePattern>),
+    /// Keep every entity except the ones matching these patterns
+    Exclude(Vec<NamePattern>),
+}
+
+impl NameFilter {
+    fn patterns(&self) -> &[NamePattern] {
+        match self {
+            Self::Include(patterns) | Self::Exclude(patterns) => patterns,
+        }
+    }
+
+    fn keeps(&self, name: &str) -> bool {
+        let matches = self.patterns().iter().any(|pattern| pattern.matches(name));
+        match self {
+            Self::Include(_) => matches,
+            Self::Exclude(_) => !matches,
+        }
+    }
+}
+
+/// Only keeps the signals allowed by the given filter in the given changes.
+/// Changes that end up without any signals or detectors are removed entirely.
+/// Detectors are unaffected.
+///
+/// Returns the patterns from the filter that matched none of the names in the changes, so typos can be reported.
+pub fn filter_signals(changes: &mut Vec<TimestampedChanges>, filter: &NameFilter) -> Vec<String> {
+    let mut kept_by_name = HashMap::new();
+
+    for change in changes.iter_mut() {
+        retain_names(
+            &mut change.signal_names,
+            &mut change.signal_states,
+            filter,
+            &mut kept_by_name,
+        );
+    }
+
+    remove_empty_changes(changes);
+    find_unseen_patterns(filter, &kept_by_name)
+}
+
+/// Only keeps the detectors allowed by the given filter in the given changes.
+/// Changes that end up without any signals or detectors are removed entirely.
+/// Signals are unaffected.
+///
+/// Returns the patterns from the filter that matched none of the names in the changes, so typos can be reported.
+pub fn filter_detectors(
+    changes: &mut Vec<TimestampedChanges>,
+    filter: &NameFilter,
+) -> Vec<String> {
+    let mut kept_by_name = HashMap::new();
+
+    for change in changes.iter_mut() {
+        retain_names(
+            &mut change.detector_names,
+            &mut change.detector_states,
+            filter,
+            &mut kept_by_name,
+        );
+    }
+
+    remove_empty_changes(changes);
+    find_unseen_patterns(filter, &kept_by_name)
+}
+
+/// Keeps the names the filter keeps. Whether a name is kept is decided once and remembered in `kept_by_name`,
+/// so regexes aren't matched again for every change.
+fn retain_names<S: Copy>(
+    names: &mut Vec<String>,
+    states: &mut Vec<S>,
+    filter: &NameFilter,
+    kept_by_name: &mut HashMap<String, bool>,
+) {
+    let mut kept_names = Vec::new();
+    let mut kept_states = Vec::new();
+    for (name, state) in names.iter().zip(states.iter()) {
+        let is_kept = match kept_by_name.get(name) {
+            Some(is_kept) => *is_kept,
+            None => *kept_by_name
+                .entry(name.to_string())
+                .or_insert_with(|| filter.keeps(name)),
+        };
+        if is_kept {
+            kept_names.push(name.to_string());
+            kept_states.push(*state);
+        }
+    }
+    *names = kept_names;
+    *states = kept_states;
+}
+
+fn find_unseen_patterns(filter: &NameFilter, kept_by_name: &HashMap<String, bool>) -> Vec<String> {
+    filter
+        .patterns()
+        .iter()
+        .filter(|pattern| !kept_by_name.keys().any(|name| pattern.matches(name)))
+        .map(NamePattern::describe)
+        .collect()
+}
+
+fn remove_empty_changes(changes: &mut Vec<TimestampedChanges>) {
+    changes.retain(|change| !change.is_empty());
+}
+
+/// Parses a comma separated list of entity names, e.g. `03,04,71`.
+pub fn parse_name_list(arg: &str) -> Result<Vec<String>, String> {
+    let names: Vec<String> = arg.split(',').map(|name| name.trim().to_string()).collect();
+    if names.iter().any(|name| name.is_empty()) {
+        Err(format!(
+            "Failed to transform argument {} into a list of names, expected something like 03,04,71",
+            arg
+        ))
+    } else {
+        Ok(names)
+    }
+}
+
+/// Parses a comma separated list of names and regular expressions starting with `re:`, e.g. `03,re:^7`.
+pub fn parse_name_patterns(arg: &str) -> Result<Vec<NamePattern>, String> {
+    parse_name_list(arg)?
+        .into_iter()
+        .map(|name| match name.strip_prefix(REGEX_PREFIX) {
+            Some(pattern) => Regex::new(pattern)
+                .map(NamePattern::Regex)
+                .map_err(|error| {
+                    format!(
+                        "Failed to transform '{}' into a regular expression: {}",
+                        pattern, error
+                    )
+                }),
+            None => Ok(NamePattern::Literal(name)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tlcfi_assimilator::{DetectorState, SignalState};
+
+    fn get_test_changes() -> Vec<TimestampedChanges> {
+        vec![
+            TimestampedChanges {
+                ms_from_beginning: 100,
+                signal_names: vec!["02".to_string(), "03".to_string(), "71".to_string()],
+                signal_states: vec![SignalState::Red, SignalState::Green, SignalState::Amber],
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 200,
+                signal_names: vec!["08".to_string()],
+                signal_states: vec![SignalState::Green],
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 300,
+                detector_names: vec!["D611".to_string()],
+                detector_states: vec![DetectorState::OCCUPIED],
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn filtering_signals_should_only_keep_the_given_signals_and_their_states() {
+        let mut changes = get_test_changes();
+
+        filter_signals(
+            &mut changes,
+            &NameFilter::Include(vec!["03".into(), "71".into()]),
+        );
+
+        assert_eq!(changes[0].signal_names, vec!["03", "71"]);
+        assert_eq!(
+            changes[0].signal_states,
+            vec![SignalState::Green, SignalState::Amber]
+        );
+    }
+
+    #[test]
+    fn filtering_signals_should_remove_emptied_changes_but_keep_detectors() {
+        let mut changes = get_test_changes();
+
+        filter_signals(&mut changes, &NameFilter::Include(vec!["03".into()]));
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].ms_from_beginning, 100);
+        assert_eq!(changes[1].detector_names, vec!["D611"]);
+    }
+
+    #[test]
+    fn filtering_signals_should_return_names_that_were_never_seen() {
+        let mut changes = get_test_changes();
+
+        let unseen_names = filter_signals(
+            &mut changes,
+            &NameFilter::Include(vec!["03".into(), "99".into()]),
+        );
+
+        assert_eq!(unseen_names, vec!["99"]);
+    }
+
+    #[test]
+    fn including_detectors_should_only_keep_the_given_detectors() {
+        let mut changes = get_test_changes();
+        changes[2].detector_names.push("D612".to_string());
+        changes[2].detector_states.push(DetectorState::FREE);
+
+        filter_detectors(&mut changes, &NameFilter::Include(vec!["D612".into()]));
+
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[2].detector_names, vec!["D612"]);
+        assert_eq!(changes[2].detector_states, vec![DetectorState::FREE]);
+    }
+
+    #[test]
+    fn excluding_detectors_should_remove_emptied_changes_but_keep_signals() {
+        let mut changes = get_test_changes();
+
+        let unseen_names =
+            filter_detectors(&mut changes, &NameFilter::Exclude(vec!["D611".into()]));
+
+        assert!(unseen_names.is_empty());
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().all(|change| change.detector_names.is_empty()));
+    }
+
+    #[test]
+    fn literal_names_and_regexes_should_be_mixed_in_one_filter() {
+        let mut changes = get_test_changes();
+        changes[2].detector_names =
+            vec!["D611".to_string(), "D712".to_string(), "D713".to_string()];
+        changes[2].detector_states = vec![DetectorState::FREE; 3];
+
+        let filter = NameFilter::Include(parse_name_patterns("D611, re:^D7,re:^D9").unwrap());
+        let unseen_patterns = filter_detectors(&mut changes, &filter);
+
+        assert_eq!(changes[2].detector_names, vec!["D611", "D712", "D713"]);
+        assert_eq!(unseen_patterns, vec!["re:^D9"]);
+
+        let filter = NameFilter::Exclude(parse_name_patterns("re:^D7,D611").unwrap());
+        filter_detectors(&mut changes, &filter);
+
+        assert_eq!(changes.len(), 2);
+    }
+
+    #[test]
+    fn an_invalid_regex_should_be_rejected_with_the_regex_error() {
+        let error = parse_name_patterns("D611,re:D7(").unwrap_err();
+
+        assert!(error.starts_with("Failed to transform 'D7(' into a regular expression: "));
+        assert!(error.contains("unclosed group"));
+    }
+
+    #[test]
+    fn parse_name_list_should_split_on_commas_and_trim() {
+        assert_eq!(
+            parse_name_list("03, 04,71"),
+            Ok(vec!["03".to_string(), "04".to_string(), "71".to_string()])
+        );
+    }
+
+    #[test]
+    fn parse_name_list_with_empty_names_should_return_err() {
+        assert!(parse_name_list("").is_err());
+        assert!(parse_name_list("03,,04").is_err());
+    }
+}
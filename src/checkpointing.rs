@@ -0,0 +1,112 @@
+//! Saves how far a conversion got, so `--checkpoint FILE` can continue an interrupted conversion of a large log
+//! where it stopped instead of starting over.
+//!
+//! A checkpoint holds the byte offsets in the log and the VLog file after the last converted batch of lines,
+//! with the parsing and time reference state the next batch continues from and the stats of the converted lines.
+//! It also holds the [options fingerprint](../conversion_cache/fn.options_fingerprint.html) of the conversion, so it
+//! isn't continued with options or a mapping file that would give different output.
+
+use std::{fs, io};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use tlcfi_assimilator::ConversionStats;
+
+/// The number of log lines that are converted between saving checkpoints.
+pub const LINES_PER_CHECKPOINT: usize = 100_000;
+
+/// The state of a conversion after a batch of lines was written.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub tlcfi_log_file: String,
+    pub vlog_file: String,
+    /// The fingerprint of the options and the mapping file the conversion was started with
+    pub options_fingerprint: String,
+    /// The start of the conversion in the time of the log
+    pub start_date_time: NaiveDateTime,
+    /// The byte offset in the log after the converted lines
+    pub log_offset: u64,
+    /// The size of the VLog file with the messages of the converted lines
+    pub vlog_offset: u64,
+    pub first_tick: Option<u64>,
+    pub first_line_time: Option<NaiveDateTime>,
+    pub previous_tick: Option<u64>,
+    pub bonus_ms: Option<u64>,
+    pub ms_of_last_time_reference: u64,
+    /// The changes and the VLog messages of the converted lines
+    pub change_count: usize,
+    pub vlog_message_count: usize,
+    /// The stats of the converted lines, which the summary of the resumed conversion continues from
+    pub stats: ConversionStats,
+}
+
+/// Loads the checkpoint of an interrupted conversion, None when there is no checkpoint file.
+pub fn load(file_name: &str) -> Result<Option<Checkpoint>, String> {
+    let content = match fs::read_to_string(file_name) {
+        Ok(content) => content,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => {
+            return Err(format!(
+                "failed to read the checkpoint '{}': {}",
+                file_name, error
+            ))
+        }
+    };
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|error| format!("the checkpoint '{}' isn't valid: {}", file_name, error))
+}
+
+/// Saves the checkpoint next to the file and then moves it over it, so an interruption leaves the previous one intact.
+pub fn save(file_name: &str, checkpoint: &Checkpoint) -> io::Result<()> {
+    let new_file_name = format!("{}.new", file_name);
+    let content = serde_json::to_string_pretty(checkpoint).map_err(io::Error::other)?;
+    fs::write(&new_file_name, content)?;
+    fs::rename(&new_file_name, file_name)
+}
+
+/// Removes the checkpoint of a conversion that finished.
+pub fn remove(file_name: &str) -> io::Result<()> {
+    match fs::remove_file(file_name) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn a_saved_checkpoint_should_be_loaded() {
+        let file_name = std::env::temp_dir().join("tlcfi_assimilator_checkpoint.json");
+        let file_name = file_name.to_str().unwrap();
+        let checkpoint = Checkpoint {
+            tlcfi_log_file: "tlcfi.txt".to_string(),
+            vlog_file: "3031_20211215_110000.vlg".to_string(),
+            options_fingerprint: "5e0c".to_string(),
+            start_date_time: chrono::NaiveDate::from_ymd(2021, 12, 15).and_hms_milli(11, 0, 0, 250),
+            log_offset: 12_345,
+            vlog_offset: 678,
+            first_tick: Some(1000),
+            first_line_time: None,
+            previous_tick: Some(311_000),
+            bonus_ms: None,
+            ms_of_last_time_reference: 300_000,
+            change_count: 12,
+            vlog_message_count: 15,
+            stats: ConversionStats {
+                lines_read: 100,
+                first_tick: Some(1000),
+                ..Default::default()
+            },
+        };
+
+        save(file_name, &checkpoint).unwrap();
+        let loaded = load(file_name);
+        remove(file_name).unwrap();
+
+        assert_eq!(loaded, Ok(Some(checkpoint)));
+        assert_eq!(load(file_name), Ok(None));
+    }
+}
@@ -0,0 +1,193 @@
+//! Finds steps of the logger's wall clock, e.g. when NTP adjusts it mid-file, from line times that move differently from the ticks.
+//! The VLog timing comes from the ticks, only what is taken from the line times needs the correction.
+
+use chrono::{Duration, NaiveDateTime};
+use tlcfi_assimilator::{log_pattern::LogPattern, tlcfi_parsing, ClockStep, TickUnit};
+
+/// How far the line times may drift from the ticks between two messages, more is a clock step.
+/// Logging latency stays well below this.
+pub const CLOCK_STEP_THRESHOLD_MS: i64 = 5_000;
+
+/// Finds where the line times of the messages from the TLC move more than `threshold_ms` differently from their ticks.
+/// Ticks going back, by an overflow or a controller reset, can't be compared and are skipped.
+pub fn find_clock_steps<S: AsRef<str>>(
+    sorted_lines: impl IntoIterator<Item = S>,
+    log_pattern: &LogPattern,
+    invert_direction: bool,
+    tick_unit: TickUnit,
+    threshold_ms: i64,
+) -> Vec<ClockStep> {
+    let mut clock_steps: Vec<ClockStep> = Vec::new();
+    let mut previous: Option<(NaiveDateTime, u64)> = None;
+    for (line_index, line) in sorted_lines.into_iter().enumerate() {
+        let (line_time, tick) =
+            match line_time_and_tick(line.as_ref(), log_pattern, invert_direction) {
+                Some(line_time_and_tick) => line_time_and_tick,
+                None => continue,
+            };
+        if let Some((previous_line_time, previous_tick)) = previous {
+            if tick >= previous_tick {
+                let line_ms = (line_time - previous_line_time).num_milliseconds();
+                let step_ms = line_ms - tick_unit.to_ms(tick - previous_tick) as i64;
+                if step_ms.abs() > threshold_ms {
+                    clock_steps.push(ClockStep {
+                        line_index,
+                        before: previous_line_time,
+                        after: line_time,
+                        until: line_time,
+                        step_ms,
+                        repaired: false,
+                    });
+                }
+            }
+        }
+        if let Some(clock_step) = clock_steps.last_mut() {
+            clock_step.until = line_time;
+        }
+        previous = Some((line_time, tick));
+    }
+    clock_steps
+}
+
+/// Moves the line times from every step on back by the steps so far, so they follow the ticks again.
+pub fn repair_line_times(sorted_lines: &mut [String], clock_steps: &mut [ClockStep]) {
+    let mut correction_ms = 0;
+    let mut steps = clock_steps.iter_mut().peekable();
+    for (line_index, line) in sorted_lines.iter_mut().enumerate() {
+        while let Some(clock_step) = steps.next_if(|step| step.line_index <= line_index) {
+            correction_ms += clock_step.step_ms;
+            clock_step.repaired = true;
+        }
+        if correction_ms == 0 {
+            continue;
+        }
+        if let Some(line_time) = tlcfi_parsing::log_line_time(line) {
+            let repaired_time = line_time - Duration::milliseconds(correction_ms);
+            // The repaired timestamp is written like the original one, which has its milliseconds after a comma or a
+            // dot, or has none
+            let timestamp_length = tlcfi_parsing::log_line_timestamp(line).map_or(0, str::len);
+            let format = match &line[19..timestamp_length] {
+                "" => "%Y-%m-%d %H:%M:%S",
+                fraction if fraction.starts_with('.') => "%Y-%m-%d %H:%M:%S.%3f",
+                _ => "%Y-%m-%d %H:%M:%S,%3f",
+            };
+            line.replace_range(
+                ..timestamp_length,
+                &repaired_time.format(format).to_string(),
+            );
+        }
+    }
+}
+
+/// Describes a step for the summary.
+pub fn describe(clock_step: &ClockStep) -> String {
+    format!(
+        "the log clock jumped {} {}.{:03} s between {} and {}, line times until {} {}",
+        if clock_step.step_ms < 0 {
+            "back"
+        } else {
+            "forward"
+        },
+        clock_step.step_ms.abs() / 1000,
+        clock_step.step_ms.abs() % 1000,
+        clock_step.before,
+        clock_step.after,
+        clock_step.until,
+        if clock_step.repaired {
+            "were corrected"
+        } else {
+            "are off, ticks were used for the timing"
+        }
+    )
+}
+
+fn line_time_and_tick(
+    line: &str,
+    log_pattern: &LogPattern,
+    invert_direction: bool,
+) -> Option<(NaiveDateTime, u64)> {
+    let filtered_line = line.replace("\"\"", "\"");
+    let split_line = log_pattern.split(&filtered_line);
+    if split_line.len() != 3 || !tlcfi_parsing::is_from_tlc(split_line[1], invert_direction) {
+        return None;
+    }
+    Some((
+        log_pattern.line_time(line)?,
+        tlcfi_parsing::find_tick(split_line[2])?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn line(time: &str, ticks: u64) -> String {
+        format!(
+            "2021-12-15 {} INFO  tlcFiMessages:41 - IN - {{\"params\":{{\"ticks\":{}}}}}",
+            time, ticks
+        )
+    }
+
+    #[test]
+    fn line_times_going_back_while_the_ticks_continue_should_be_a_step() {
+        let lines = vec![
+            line("12:00:00,000", 1000),
+            line("12:00:03,040", 4000),
+            line("11:59:36,000", 7000),
+            line("11:59:39,000", 10000),
+        ];
+
+        let clock_steps = find_clock_steps(
+            &lines,
+            &LogPattern::default(),
+            false,
+            TickUnit::Milliseconds,
+            CLOCK_STEP_THRESHOLD_MS,
+        );
+
+        assert_eq!(clock_steps.len(), 1);
+        assert_eq!(clock_steps[0].line_index, 2);
+        assert_eq!(clock_steps[0].step_ms, -30_040);
+        assert_eq!(clock_steps[0].until.to_string(), "2021-12-15 11:59:39");
+        assert!(describe(&clock_steps[0]).starts_with("the log clock jumped back 30.040 s"));
+    }
+
+    #[test]
+    fn ticks_going_back_should_not_be_a_step() {
+        let lines = vec![line("12:00:00,000", 100_000), line("12:00:01,000", 1000)];
+
+        assert!(find_clock_steps(
+            &lines,
+            &LogPattern::default(),
+            false,
+            TickUnit::Milliseconds,
+            CLOCK_STEP_THRESHOLD_MS
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn repairing_should_move_the_line_times_after_the_step() {
+        let mut lines = vec![
+            line("12:00:00,000", 1000),
+            "2021-12-15 11:59:33,500 INFO  notes".to_string(),
+            line("11:59:34,000", 5000),
+            line("11:59:35,000", 6000),
+        ];
+        let mut clock_steps = find_clock_steps(
+            &lines,
+            &LogPattern::default(),
+            false,
+            TickUnit::Milliseconds,
+            CLOCK_STEP_THRESHOLD_MS,
+        );
+
+        repair_line_times(&mut lines, &mut clock_steps);
+
+        assert!(clock_steps[0].repaired);
+        assert_eq!(&lines[0][..23], "2021-12-15 12:00:00,000");
+        assert_eq!(&lines[1][..23], "2021-12-15 11:59:33,500");
+        assert_eq!(&lines[2][..23], "2021-12-15 12:00:04,000");
+        assert_eq!(&lines[3][..23], "2021-12-15 12:00:05,000");
+    }
+}
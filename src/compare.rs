@@ -0,0 +1,403 @@
+//! Compares two VLog files by what their messages mean, to see whether a new mapping file or version changes the output.
+//!
+//! The state changes of both files are aligned by the time they describe, so differences in the formatting of the
+//! messages, like line endings, the order of ids in a message or where time references are inserted, aren't reported.
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDateTime;
+
+use crate::{
+    decode::{self, IdNames},
+    vlog_decoding::{self, DecodedMessage, MessageContent},
+    EXIT_CODE_DIFFERENCES, EXIT_CODE_INPUT_ERROR,
+};
+
+const COMPARE_HELP: &str = "\
+TLC-FI Assimilator compare
+
+USAGE:
+  tlcfi_assimilator compare [OPTIONS] <VLOG_FILE> <OTHER_VLOG_FILE>
+
+FLAGS:
+  -h, --help                Prints help information
+
+OPTIONS:
+  --mapping-file STRING     Shows the TLC-FI names of this mapping file instead of the VLog ids
+
+ARGS:
+  <VLOG_FILE>               The VLog file to compare, which may be gzip compressed
+  <OTHER_VLOG_FILE>         The VLog file to compare it with, which may be gzip compressed
+
+Exits with 0 when the files don't differ and with 6 when they do.
+";
+
+/// Whether a state belongs to a signal group or a detector, since they have separate VLog ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ObjectKind {
+    Signal,
+    Detector,
+    Output,
+}
+
+/// What is described at a moment, by the messages of a file with that time.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Subject {
+    /// A signal group or detector with its VLog id
+    Object(ObjectKind, i16),
+    /// A message that isn't decoded, which is compared as it is
+    Unknown(String),
+}
+
+/// The states of every subject per moment, in the order the file gives them.
+type Timeline = BTreeMap<(Option<NaiveDateTime>, Subject), Vec<i16>>;
+
+/// A way in which the second file differs from the first.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Difference {
+    /// The V-Log information messages or the time the files start at differ
+    Preamble {
+        description: &'static str,
+        first: String,
+        second: String,
+    },
+    /// The states of an object at a moment, which are empty in the file that has no message for it
+    States {
+        time: Option<NaiveDateTime>,
+        kind: ObjectKind,
+        vlog_id: i16,
+        first: Vec<i16>,
+        second: Vec<i16>,
+    },
+    /// A message of an unknown type that is only in one of the files
+    UnknownMessage {
+        time: Option<NaiveDateTime>,
+        message: String,
+        in_first: bool,
+    },
+}
+
+impl Difference {
+    /// Describes the difference with the names of the files, and the names of the objects when they are known.
+    pub fn describe(&self, first_file: &str, second_file: &str, id_names: &IdNames) -> String {
+        match self {
+            Difference::Preamble {
+                description,
+                first,
+                second,
+            } => format!(
+                "preamble: {} {} in {}, {} in {}",
+                description, first, first_file, second, second_file
+            ),
+            Difference::States {
+                time,
+                kind,
+                vlog_id,
+                first,
+                second,
+            } => {
+                let (names, state_name, kind_name): (_, fn(i16) -> String, _) = match kind {
+                    ObjectKind::Signal => (&id_names.signals, decode::signal_state_name, "signal"),
+                    ObjectKind::Detector => {
+                        (&id_names.detectors, decode::detector_state_name, "detector")
+                    }
+                    ObjectKind::Output => (&id_names.outputs, decode::output_state_name, "output"),
+                };
+                let describe_states = |states: &[i16], file: &str| {
+                    let states: Vec<String> =
+                        states.iter().map(|state| state_name(*state)).collect();
+                    format!("{} in {}", states.join(" then "), file)
+                };
+                let states = if first.is_empty() {
+                    format!("only {}", describe_states(second, second_file))
+                } else if second.is_empty() {
+                    format!("only {}", describe_states(first, first_file))
+                } else {
+                    format!(
+                        "{}, {}",
+                        describe_states(first, first_file),
+                        describe_states(second, second_file)
+                    )
+                };
+                format!(
+                    "{} {} {}: {}",
+                    format_time(time),
+                    kind_name,
+                    decode::id_name(names, *vlog_id),
+                    states
+                )
+            }
+            Difference::UnknownMessage {
+                time,
+                message,
+                in_first,
+            } => format!(
+                "{} message {} only in {}",
+                format_time(time),
+                message,
+                if *in_first { first_file } else { second_file }
+            ),
+        }
+    }
+}
+
+/// Parses the `compare` arguments, compares the files and prints the differences. Returns the exit code.
+pub fn run_compare() -> i32 {
+    let mut pargs = pico_args::Arguments::from_env();
+    // Drop the "compare" subcommand itself
+    let _ = pargs.subcommand();
+
+    if pargs.contains(["-h", "--help"]) {
+        println!("{}", COMPARE_HELP);
+        return 0;
+    }
+
+    let (first_file, second_file, mapping_file) = match parse_compare_args(&mut pargs) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("Error: {}.", error);
+            println!("{}", COMPARE_HELP);
+            return 1;
+        }
+    };
+
+    let id_names = match mapping_file {
+        Some(mapping_file) => match IdNames::load(&mapping_file) {
+            Ok(id_names) => id_names,
+            Err(error) => {
+                eprintln!("Error: {}", error);
+                return EXIT_CODE_INPUT_ERROR;
+            }
+        },
+        None => IdNames::default(),
+    };
+    let read = |file_name: &str| {
+        decode::read_vlog_messages(file_name)
+            .map(|messages| vlog_decoding::decode_messages(messages.iter().map(String::as_str)))
+            .map_err(|error| {
+                format!(
+                    "Error: failed to read the VLog file '{}': {}",
+                    file_name, error
+                )
+            })
+    };
+    let (first, second) = match (read(&first_file), read(&second_file)) {
+        (Ok(first), Ok(second)) => (first, second),
+        (Err(error), _) | (_, Err(error)) => {
+            eprintln!("{}", error);
+            return EXIT_CODE_INPUT_ERROR;
+        }
+    };
+
+    let differences = compare_messages(&first, &second);
+    for difference in &differences {
+        println!(
+            "{}",
+            difference.describe(&first_file, &second_file, &id_names)
+        );
+    }
+    if differences.is_empty() {
+        println!("The files don't differ.");
+        0
+    } else {
+        println!("The files differ in {} places.", differences.len());
+        EXIT_CODE_DIFFERENCES
+    }
+}
+
+fn parse_compare_args(
+    pargs: &mut pico_args::Arguments,
+) -> Result<(String, String, Option<String>), pico_args::Error> {
+    let mapping_file = pargs.opt_value_from_fn("--mapping-file", crate::check_file_existence)?;
+    let first_file = pargs.free_from_fn(crate::check_file_existence)?;
+    let second_file = pargs.free_from_fn(crate::check_file_existence)?;
+    Ok((first_file, second_file, mapping_file))
+}
+
+/// Finds the differences in the preambles, then the differences in the states ordered by time.
+pub fn compare_messages(first: &[DecodedMessage], second: &[DecodedMessage]) -> Vec<Difference> {
+    let mut differences = compare_preambles(first, second);
+
+    let first_timeline = to_timeline(first);
+    let second_timeline = to_timeline(second);
+    let mut keys: Vec<&(Option<NaiveDateTime>, Subject)> = first_timeline
+        .keys()
+        .chain(second_timeline.keys())
+        .collect();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        let first_states = first_timeline.get(key).cloned().unwrap_or_default();
+        let second_states = second_timeline.get(key).cloned().unwrap_or_default();
+        if first_states == second_states {
+            continue;
+        }
+        let (time, subject) = key;
+        differences.push(match subject {
+            Subject::Object(kind, vlog_id) => Difference::States {
+                time: *time,
+                kind: *kind,
+                vlog_id: *vlog_id,
+                first: first_states,
+                second: second_states,
+            },
+            Subject::Unknown(message) => Difference::UnknownMessage {
+                time: *time,
+                message: message.clone(),
+                in_first: first_states.len() > second_states.len(),
+            },
+        });
+    }
+    differences
+}
+
+/// Compares the V-Log information and the first time reference of the files.
+fn compare_preambles(first: &[DecodedMessage], second: &[DecodedMessage]) -> Vec<Difference> {
+    let vlog_information = |messages: &[DecodedMessage]| {
+        messages.iter().find_map(|decoded| match &decoded.content {
+            MessageContent::VLogInformation { version, tlc_name } => {
+                Some((version.clone(), tlc_name.clone()))
+            }
+            _ => None,
+        })
+    };
+    let start = |messages: &[DecodedMessage]| {
+        messages
+            .iter()
+            .find(|decoded| decoded.content == MessageContent::TimeReference)
+            .and_then(|decoded| decoded.time)
+    };
+    let describe = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+
+    let mut differences = Vec::new();
+    let (first_information, second_information) =
+        (vlog_information(first), vlog_information(second));
+    for (description, first_value, second_value) in [
+        (
+            "version",
+            first_information
+                .as_ref()
+                .map(|(version, _)| version.clone()),
+            second_information
+                .as_ref()
+                .map(|(version, _)| version.clone()),
+        ),
+        (
+            "TLC",
+            first_information.map(|(_, tlc_name)| tlc_name),
+            second_information.map(|(_, tlc_name)| tlc_name),
+        ),
+        (
+            "start",
+            start(first).map(|time| format_time(&Some(time))),
+            start(second).map(|time| format_time(&Some(time))),
+        ),
+    ] {
+        if first_value != second_value {
+            differences.push(Difference::Preamble {
+                description,
+                first: describe(first_value),
+                second: describe(second_value),
+            });
+        }
+    }
+    differences
+}
+
+/// The states of the change messages and the unknown messages, by their time and subject.
+fn to_timeline(messages: &[DecodedMessage]) -> Timeline {
+    let mut timeline = Timeline::new();
+    for decoded in messages {
+        let (kind, states) = match &decoded.content {
+            MessageContent::DetectionInformation(states) => (ObjectKind::Detector, states),
+            MessageContent::ExternalSignalGroupStatus(states) => (ObjectKind::Signal, states),
+            MessageContent::OutputInformation(states) => (ObjectKind::Output, states),
+            MessageContent::Unknown => {
+                timeline
+                    .entry((decoded.time, Subject::Unknown(decoded.message.clone())))
+                    .or_default()
+                    .push(0);
+                continue;
+            }
+            MessageContent::TimeReference | MessageContent::VLogInformation { .. } => continue,
+        };
+        for (vlog_id, vlog_state) in states {
+            timeline
+                .entry((decoded.time, Subject::Object(kind, *vlog_id)))
+                .or_default()
+                .push(*vlog_state);
+        }
+    }
+    timeline
+}
+
+fn format_time(time: &Option<NaiveDateTime>) -> String {
+    match time {
+        Some(time) => time.format("%F %T%.3f").to_string(),
+        None => "-".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decode(messages: &[&str]) -> Vec<DecodedMessage> {
+        vlog_decoding::decode_messages(messages.iter().cloned())
+    }
+
+    const INFO_3031: &str = "040300003330333120202020202020202020202020202020";
+
+    #[test]
+    fn differently_formatted_messages_with_the_same_meaning_should_not_differ() {
+        let first = decode(&[
+            "012021121511000000",
+            INFO_3031,
+            "0E00A20A010B02\r",
+            "0603010C01",
+        ]);
+        // Another time reference, the ids in another order and the detector change relative to the new reference
+        let second = decode(&[
+            "012021121511000000",
+            INFO_3031,
+            "0E00A20B020A01",
+            "012021121511000200",
+            "0601C10C01",
+        ]);
+
+        assert_eq!(compare_messages(&first, &second), Vec::new());
+    }
+
+    #[test]
+    fn changed_missing_and_extra_states_should_differ() {
+        let first = decode(&[
+            "012021121511000000",
+            INFO_3031,
+            "0E00A20A010B02",
+            "0601010C01",
+        ]);
+        let second = decode(&[
+            "012021121511000000",
+            "040300003330333220202020202020202020202020202020",
+            "0E00A10A00",
+            "0601410C01",
+        ]);
+
+        let differences = compare_messages(&first, &second);
+        let descriptions: Vec<String> = differences
+            .iter()
+            .map(|difference| difference.describe("a.vlg", "b.vlg", &IdNames::default()))
+            .collect();
+
+        assert_eq!(
+            descriptions,
+            vec![
+                "preamble: TLC 3031 in a.vlg, 3032 in b.vlg",
+                "2021-12-15 11:00:01.000 signal #10: Green in a.vlg, Red in b.vlg",
+                "2021-12-15 11:00:01.000 signal #11: only Amber in a.vlg",
+                "2021-12-15 11:00:01.600 detector #12: only OCCUPIED in a.vlg",
+                "2021-12-15 11:00:02.000 detector #12: only OCCUPIED in b.vlg",
+            ]
+        );
+    }
+}
@@ -0,0 +1,117 @@
+//! Reads gzip compressed TLC-FI logs and VLog files as a stream, so archived logs don't have to be decompressed on disk
+//! or in memory before converting them.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+};
+
+use flate2::read::MultiGzDecoder;
+
+const GZIP_MAGIC_BYTES: [u8; 2] = [0x1f, 0x8b];
+
+/// Opens a file for reading lines, decompressing it when it has a `.gz` extension or starts with the gzip magic bytes.
+/// A file of several concatenated gzip members, as `cat` or log rotation makes them, is read as one.
+pub fn open_log(file_name: &str) -> io::Result<Box<dyn BufRead>> {
+    let mut reader = BufReader::new(File::open(file_name)?);
+    if is_gzip_file_name(file_name) || reader.fill_buf()?.starts_with(&GZIP_MAGIC_BYTES) {
+        Ok(Box::new(BufReader::new(GzipReader {
+            file_name: file_name.to_string(),
+            decoder: MultiGzDecoder::new(reader),
+        })))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Whether the file is gzip compressed, judging by its `.gz` extension or its first bytes.
+pub fn is_gzip_file(file_name: &str) -> bool {
+    if is_gzip_file_name(file_name) {
+        return true;
+    }
+    let mut magic_bytes = [0; 2];
+    File::open(file_name)
+        .and_then(|mut file| file.read_exact(&mut magic_bytes))
+        .is_ok()
+        && magic_bytes == GZIP_MAGIC_BYTES
+}
+
+/// Whether the file name looks like a gzip compressed file.
+pub fn is_gzip_file_name(file_name: &str) -> bool {
+    file_name.ends_with(".gz")
+}
+
+/// Decompresses a file, naming it in the errors. They aren't `InvalidData` errors, so they can't be taken for a line
+/// that isn't UTF-8, which is skipped instead of ending the log.
+struct GzipReader<R: Read> {
+    file_name: String,
+    decoder: MultiGzDecoder<R>,
+}
+
+impl<R: Read> Read for GzipReader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.decoder.read(buffer).map_err(|error| {
+            io::Error::other(format!(
+                "failed to decompress '{}': {}",
+                self.file_name, error
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::{fs, io::Write};
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn read_file(file_name: &str, data: &[u8]) -> io::Result<Vec<u8>> {
+        fs::write(file_name, data).unwrap();
+        let mut read = Vec::new();
+        let result = open_log(file_name).and_then(|mut log| log.read_to_end(&mut read));
+        fs::remove_file(file_name).unwrap();
+        result.map(|_| read)
+    }
+
+    #[test]
+    fn concatenated_members_should_be_read_as_one_log() {
+        let file_name = std::env::temp_dir().join("tlcfi_assimilator_concatenated.txt.gz");
+        let compressed = [gzip(b"first\n"), gzip(b"second\n")].concat();
+
+        let read = read_file(file_name.to_str().unwrap(), &compressed);
+
+        assert_eq!(read.unwrap(), b"first\nsecond\n".to_vec());
+    }
+
+    #[test]
+    fn a_log_without_the_extension_should_be_recognized_by_its_first_bytes() {
+        let file_name = std::env::temp_dir().join("tlcfi_assimilator_compressed.txt");
+
+        let read = read_file(file_name.to_str().unwrap(), &gzip(b"line\n"));
+
+        assert_eq!(read.unwrap(), b"line\n".to_vec());
+        assert!(!is_gzip_file("./tlcfi.txt"));
+        assert!(is_gzip_file("./tlcfi.txt.gz"));
+    }
+
+    #[test]
+    fn a_corrupt_log_should_fail_with_its_name() {
+        let file_name = std::env::temp_dir().join("tlcfi_assimilator_corrupt.txt.gz");
+        let mut corrupt = gzip(b"a line that is long enough to be corrupted\n");
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xFF;
+
+        let error = read_file(file_name.to_str().unwrap(), &corrupt).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::Other);
+        assert!(error
+            .to_string()
+            .contains("tlcfi_assimilator_corrupt.txt.gz"));
+    }
+}
@@ -0,0 +1,429 @@
+//! Reads command line options from a TOML configuration file, given with `--config`.
+//!
+//! The keys are the long option names, e.g. `start-date-time = 2021-12-15T11:00:00`. Flags take a boolean,
+//! lists like `filter-signals` can also be given as an array. The mapping file is set with `mapping-file`, or its older
+//! name `vlog-tlcfi-mapping-file`, and a directory of mapping files with `mapping-dir`.
+//! Tables only group options, the keys in them are read like the top level ones.
+
+use std::{ffi::OsString, fs};
+
+use toml::{Table, Value};
+
+/// The key of the mapping file, which can also be given as the deprecated free standing argument.
+const MAPPING_FILE_KEY: &str = "mapping-file";
+
+/// The name of the mapping file key from before `--mapping-file` existed.
+const MAPPING_FILE_ALIAS: &str = "vlog-tlcfi-mapping-file";
+
+/// Options that are set by their presence.
+const FLAG_OPTIONS: [&str; 24] = [
+    "dry-run",
+    "validate-mapping",
+    "list-objects",
+    "follow",
+    "renumber-ids",
+    "mapping-from-log",
+    "reset-snapshot",
+    "reverse",
+    "invert-direction",
+    "output-utc",
+    "repair-clock-steps",
+    "pace",
+    "tee",
+    "no-progress",
+    "strict",
+    "strict-rpc",
+    "overwrite",
+    "dedupe",
+    "skip-until-first-signal",
+    "signals-only",
+    "detectors-only",
+    "detailed-signal-states",
+    "quiet",
+    "verbose",
+];
+
+/// Options that take a value.
+const VALUE_OPTIONS: [&str; 46] = [
+    "mapping-dir",
+    "generate-mapping",
+    "max-id-gap",
+    "max-errors",
+    "reject-file",
+    "predictions-csv",
+    "summary-json",
+    "cache-dir",
+    "append",
+    "checkpoint",
+    "watch",
+    "watch-pattern",
+    "output-dir",
+    "log-dir",
+    "date",
+    "output-template",
+    "name-date",
+    "split",
+    "line-ending",
+    "max-messages",
+    "max-output-size",
+    "size-policy",
+    "amber-policy",
+    "chronological",
+    "direction",
+    "time-source",
+    "min-mapped-fraction",
+    "pace-speed",
+    "duration",
+    "limit",
+    "filter-signals",
+    "filter-detectors",
+    "exclude-detectors",
+    "start-date-time",
+    "epoch-ms",
+    "start-tolerance",
+    "first-tick",
+    "tick-unit",
+    "log-levels",
+    "logger-name",
+    "log-pattern",
+    "update-methods",
+    "format",
+    "timezone",
+    "tlc-name",
+    "tlcfi-log-file",
+];
+
+/// Options that can't be used together, so setting one on the command line overrides all of them from the file.
+const EXCLUSIVE_OPTIONS: [&[&str]; 5] = [
+    &[MAPPING_FILE_KEY, "mapping-dir"],
+    &["quiet", "verbose"],
+    &["chronological", "reverse"],
+    &["signals-only", "detectors-only"],
+    &["filter-detectors", "exclude-detectors"],
+];
+
+/// Short forms of options, as they can appear on the command line.
+const SHORT_OPTIONS: [(&str, &str); 2] = [("quiet", "-q"), ("verbose", "-v")];
+
+/// The options of a configuration file, in the order they were given.
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    options: Vec<(String, String)>,
+    pub mapping_file: Option<String>,
+}
+
+impl Config {
+    /// Loads and checks the given configuration file.
+    /// Every value is validated with the same function as its command line option, so errors can name the file and key.
+    pub fn load(file_name: &str) -> Result<Config, String> {
+        let contents = fs::read_to_string(file_name).map_err(|error| {
+            format!("failed to read the config file '{}': {}", file_name, error)
+        })?;
+        let config = Config::parse(file_name, &contents)?;
+        for (key, value) in &config.options {
+            let mut pargs = pico_args::Arguments::from_vec(option_args(key, value));
+            crate::parse_options(&mut pargs).map_err(|error| {
+                format!(
+                    "the value of '{}' in the config file '{}' is invalid: {}",
+                    key,
+                    file_name,
+                    error.cause()
+                )
+            })?;
+        }
+        if let Some(mapping_file) = &config.mapping_file {
+            crate::check_file_existence(mapping_file).map_err(|error| {
+                format!(
+                    "the value of '{}' in the config file '{}' is invalid: {}",
+                    MAPPING_FILE_KEY, file_name, error
+                )
+            })?;
+        }
+        Ok(config)
+    }
+
+    fn parse(file_name: &str, contents: &str) -> Result<Config, String> {
+        let table: Table = contents.parse().map_err(|error| {
+            format!(
+                "the config file '{}' isn't valid TOML: {}",
+                file_name, error
+            )
+        })?;
+        let mut config = Config::default();
+        config
+            .add_table(table)
+            .map_err(|error| format!("{} in the config file '{}'", error, file_name))?;
+        Ok(config)
+    }
+
+    fn add_table(&mut self, table: Table) -> Result<(), String> {
+        for (key, value) in table {
+            match value {
+                Value::Table(table) => self.add_table(table)?,
+                value => self.add(&key, value)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, key: &str, value: Value) -> Result<(), String> {
+        let is_mapping_file = key == MAPPING_FILE_KEY || key == MAPPING_FILE_ALIAS;
+        if self.options.iter().any(|(other, _)| other == key)
+            || (is_mapping_file && self.mapping_file.is_some())
+        {
+            return Err(format!("the key '{}' is given twice", key));
+        }
+        if is_mapping_file {
+            match value {
+                Value::String(mapping_file) => self.mapping_file = Some(mapping_file),
+                _ => return Err(format!("the value of '{}' should be a string", key)),
+            }
+        } else if FLAG_OPTIONS.contains(&key) {
+            match value {
+                Value::Boolean(true) => self.options.push((key.to_string(), String::new())),
+                Value::Boolean(false) => {}
+                _ => return Err(format!("the value of '{}' should be true or false", key)),
+            }
+        } else if VALUE_OPTIONS.contains(&key) {
+            let arg = value_to_arg(value)
+                .ok_or_else(|| format!("the value of '{}' can't be a table", key))?;
+            self.options.push((key.to_string(), arg));
+        } else {
+            return Err(format!("unknown key '{}'", key));
+        }
+        Ok(())
+    }
+
+    /// Returns the options of the file as command line arguments,
+    /// leaving out the ones that the given command line arguments already set.
+    pub fn args_not_in(&self, command_line_args: &[OsString]) -> Vec<OsString> {
+        let mut args = Vec::new();
+        for (key, value) in &self.options {
+            if !is_overridden(key, command_line_args) {
+                args.extend(option_args(key, value));
+            }
+        }
+        args
+    }
+
+    /// Returns the mapping file of the file, unless the given command line arguments give a mapping file or directory.
+    pub fn mapping_file_not_in(&self, command_line_args: &[OsString]) -> Option<String> {
+        if is_overridden(MAPPING_FILE_KEY, command_line_args) {
+            None
+        } else {
+            self.mapping_file.clone()
+        }
+    }
+}
+
+/// Whether the command line arguments set the option, or one that can't be used together with it.
+fn is_overridden(key: &str, command_line_args: &[OsString]) -> bool {
+    let is_on_command_line = |key: &str| {
+        let short_option = SHORT_OPTIONS
+            .iter()
+            .find(|(long, _)| *long == key)
+            .map(|(_, short)| *short);
+        command_line_args
+            .iter()
+            .filter_map(|arg| arg.to_str())
+            .any(|arg| arg == format!("--{}", key) || Some(arg) == short_option)
+    };
+    match EXCLUSIVE_OPTIONS.iter().find(|group| group.contains(&key)) {
+        Some(group) => group.iter().any(|key| is_on_command_line(key)),
+        None => is_on_command_line(key),
+    }
+}
+
+/// The command line arguments for an option, where flags have an empty value.
+fn option_args(key: &str, value: &str) -> Vec<OsString> {
+    let mut args = vec![OsString::from(format!("--{}", key))];
+    if !FLAG_OPTIONS.contains(&key) {
+        args.push(OsString::from(value));
+    }
+    args
+}
+
+/// The command line value of a TOML value, where an array is a comma separated list. A table has none.
+fn value_to_arg(value: Value) -> Option<String> {
+    match value {
+        Value::String(string) => Some(string),
+        Value::Integer(integer) => Some(integer.to_string()),
+        Value::Float(float) => Some(float.to_string()),
+        Value::Boolean(boolean) => Some(boolean.to_string()),
+        Value::Datetime(date_time) => Some(date_time.to_string()),
+        Value::Array(values) => values
+            .into_iter()
+            .map(value_to_arg)
+            .collect::<Option<Vec<String>>>()
+            .map(|args| args.join(",")),
+        Value::Table(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parsing_a_config_should_turn_values_into_arguments() {
+        let config = Config::parse(
+            "test.toml",
+            "# comment\n\
+             tlcfi-log-file = \"tlcfi.txt\" # trailing comment\n\
+             filter-signals = [\"03\", \"04\"]\n\
+             duration = 3600\n\
+             pace = true\n\
+             dry-run = false\n\
+             vlog-tlcfi-mapping-file = 'vlog_tlcfi_mapping.txt'\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.args_not_in(&[]),
+            vec![
+                "--tlcfi-log-file",
+                "tlcfi.txt",
+                "--filter-signals",
+                "03,04",
+                "--duration",
+                "3600",
+                "--pace"
+            ]
+        );
+        assert_eq!(
+            config.mapping_file,
+            Some("vlog_tlcfi_mapping.txt".to_string())
+        );
+    }
+
+    #[test]
+    fn command_line_options_should_leave_out_the_same_and_exclusive_options_of_the_file() {
+        let config = Config::parse(
+            "test.toml",
+            "duration = 3600\nquiet = true\nsignals-only = true\n",
+        )
+        .unwrap();
+
+        let args = config.args_not_in(&[
+            OsString::from("--duration"),
+            OsString::from("60"),
+            OsString::from("-v"),
+        ]);
+
+        assert_eq!(args, vec!["--signals-only"]);
+    }
+
+    #[test]
+    fn the_mapping_file_should_be_set_with_its_key_or_its_older_name() {
+        let config = Config::parse("test.toml", "mapping-file = 'a.txt'\n").unwrap();
+        let aliased = Config::parse("test.toml", "vlog-tlcfi-mapping-file = 'a.txt'\n").unwrap();
+        let twice = Config::parse(
+            "test.toml",
+            "mapping-file = 'a.txt'\nvlog-tlcfi-mapping-file = 'b.txt'\n",
+        );
+
+        assert_eq!(config.mapping_file, Some("a.txt".to_string()));
+        assert_eq!(aliased, config);
+        assert_eq!(
+            twice.unwrap_err(),
+            "the key 'vlog-tlcfi-mapping-file' is given twice in the config file 'test.toml'"
+        );
+    }
+
+    #[test]
+    fn a_mapping_file_or_dir_on_the_command_line_should_override_both_of_the_file() {
+        let with_file = Config::parse("test.toml", "mapping-file = 'a.txt'\n").unwrap();
+        let with_dir = Config::parse(
+            "test.toml",
+            "mapping-dir = 'mappings'\ngenerate-mapping = 'generated.txt'\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            with_file.mapping_file_not_in(&[OsString::from("--mapping-dir"), OsString::from("d")]),
+            None
+        );
+        assert_eq!(
+            with_file.mapping_file_not_in(&[]),
+            Some("a.txt".to_string())
+        );
+        assert_eq!(
+            with_dir.args_not_in(&[OsString::from("--mapping-file"), OsString::from("b.txt")]),
+            vec!["--generate-mapping", "generated.txt"]
+        );
+        assert_eq!(
+            with_dir.args_not_in(&[]),
+            vec![
+                "--mapping-dir",
+                "mappings",
+                "--generate-mapping",
+                "generated.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn parsing_a_config_with_an_unknown_key_should_name_the_file_and_key() {
+        let error = Config::parse("test.toml", "pace = true\nspeed = 10\n").unwrap_err();
+
+        assert_eq!(error, "unknown key 'speed' in the config file 'test.toml'");
+    }
+
+    #[test]
+    fn the_options_in_tables_should_be_read_like_top_level_ones() {
+        let config = Config::parse(
+            "test.toml",
+            "pace = true\n\
+             [input]\n\
+             start-date-time = 2021-12-15T11:00:00\n\
+             pace-speed = 2.5\n\
+             [output]\n\
+             pace = false\n",
+        );
+
+        assert_eq!(
+            config.unwrap_err(),
+            "the key 'pace' is given twice in the config file 'test.toml'"
+        );
+        let config = Config::parse(
+            "test.toml",
+            "[input]\nstart-date-time = 2021-12-15T11:00:00\n[pacing]\npace-speed = 2.5\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.args_not_in(&[]),
+            vec![
+                "--start-date-time",
+                "2021-12-15T11:00:00",
+                "--pace-speed",
+                "2.5"
+            ]
+        );
+    }
+
+    #[test]
+    fn parsing_a_config_that_isnt_toml_should_name_the_file() {
+        let error = Config::parse("test.toml", "pace = \n").unwrap_err();
+
+        assert!(error.starts_with("the config file 'test.toml' isn't valid TOML: "));
+    }
+
+    #[test]
+    fn loading_a_config_should_validate_its_values_and_name_the_invalid_key() {
+        let file_name = std::env::temp_dir().join("tlcfi_assimilator_config.toml");
+        let file_name = file_name.to_str().unwrap();
+        fs::write(file_name, "start-date-time = 2021-12-15T11:00:00\n").unwrap();
+        let loaded = Config::load(file_name);
+        fs::write(file_name, "[output]\nduration = \"an hour\"\n").unwrap();
+        let invalid = Config::load(file_name);
+        fs::remove_file(file_name).unwrap();
+
+        assert!(loaded.is_ok());
+        let error = invalid.unwrap_err();
+        assert!(error.starts_with(&format!(
+            "the value of 'duration' in the config file '{}' is invalid: ",
+            file_name
+        )));
+        assert!(!error.contains("failed to parse"));
+    }
+}
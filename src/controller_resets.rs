@@ -0,0 +1,102 @@
+//! Makes controller resets visible in the VLog, instead of pretending states persisted across them.
+
+use std::collections::BTreeSet;
+
+use tlcfi_assimilator::{ControllerReset, DetectorState, SignalState, TimestampedChanges};
+
+/// Inserts a snapshot at the moment of every reset, in which all signal groups seen in the changes are unavailable and
+/// all detectors are faulty, which VLog shows as not available.
+/// The snapshot is placed right after the changes read before the reset, so the states reported right after the reset win.
+/// Detectors removed from the session before a reset stay faulty in its snapshot, like they became at their removal.
+pub fn insert_reset_snapshots(changes: &mut Vec<TimestampedChanges>, resets: &[ControllerReset]) {
+    let signal_names: BTreeSet<String> = changes
+        .iter()
+        .flat_map(|change| change.signal_names.iter().cloned())
+        .collect();
+    let detector_names: BTreeSet<String> = changes
+        .iter()
+        .flat_map(|change| change.detector_names.iter().cloned())
+        .collect();
+
+    // Later resets go first, so the number of changes before each reset still points at the right place
+    for reset in resets.iter().rev() {
+        let mut snapshot = Vec::new();
+        if !signal_names.is_empty() {
+            snapshot.push(TimestampedChanges {
+                ms_from_beginning: reset.ms_from_beginning,
+                signal_names: signal_names.iter().cloned().collect(),
+                signal_states: vec![SignalState::Unavailable; signal_names.len()],
+                ..Default::default()
+            });
+        }
+        if !detector_names.is_empty() {
+            snapshot.push(TimestampedChanges {
+                ms_from_beginning: reset.ms_from_beginning,
+                detector_names: detector_names.iter().cloned().collect(),
+                detector_states: vec![DetectorState::FAULTY; detector_names.len()],
+                ..Default::default()
+            });
+        }
+        let index = reset.changes_before.min(changes.len());
+        changes.splice(index..index, snapshot);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn a_snapshot_should_be_inserted_between_the_changes_before_and_after_the_reset() {
+        let mut changes = vec![
+            TimestampedChanges {
+                ms_from_beginning: 1000,
+                signal_names: vec!["02".to_string()],
+                signal_states: vec![SignalState::Green],
+                detector_names: vec!["D611".to_string()],
+                detector_states: vec![DetectorState::OCCUPIED],
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 2000,
+                signal_names: vec!["03".to_string()],
+                signal_states: vec![SignalState::Red],
+                ..Default::default()
+            },
+        ];
+        let resets = [ControllerReset {
+            ms_from_beginning: 2000,
+            previous_tick: 3000,
+            tick: 10,
+            changes_before: 1,
+        }];
+
+        insert_reset_snapshots(&mut changes, &resets);
+
+        assert_eq!(changes.len(), 4);
+        assert_eq!(changes[1].ms_from_beginning, 2000);
+        assert_eq!(changes[1].signal_names, vec!["02", "03"]);
+        assert_eq!(
+            changes[1].signal_states,
+            vec![SignalState::Unavailable, SignalState::Unavailable]
+        );
+        assert_eq!(changes[2].ms_from_beginning, 2000);
+        assert_eq!(changes[2].detector_names, vec!["D611"]);
+        assert_eq!(changes[2].detector_states, vec![DetectorState::FAULTY]);
+        assert_eq!(changes[3].signal_names, vec!["03"]);
+    }
+
+    #[test]
+    fn without_resets_the_changes_should_be_untouched() {
+        let mut changes = vec![TimestampedChanges {
+            ms_from_beginning: 1000,
+            detector_names: vec!["D611".to_string()],
+            detector_states: vec![DetectorState::OCCUPIED],
+            ..Default::default()
+        }];
+
+        insert_reset_snapshots(&mut changes, &[]);
+
+        assert_eq!(changes.len(), 1);
+    }
+}
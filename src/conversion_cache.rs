@@ -0,0 +1,243 @@
+//! Reuses earlier conversions of unchanged inputs, so a batch doesn't convert the same archived logs again.
+//!
+//! A cache entry is a directory named after a hash of the input files and the options, holding the VLog file
+//! and a `diagnostics.json` with what the conversion reported, including its stats for the summaries.
+
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tlcfi_assimilator::ConversionStats;
+
+use crate::AppArgs;
+
+const DIAGNOSTICS_FILE_NAME: &str = "diagnostics.json";
+
+/// What an earlier conversion resulted in.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct CachedConversion {
+    pub file_names: Vec<String>,
+    pub change_count: usize,
+    pub vlog_message_count: usize,
+    pub warnings: Vec<String>,
+    pub summary: Vec<String>,
+    pub stats: ConversionStats,
+    /// The moment the output starts at
+    pub start_time: NaiveDateTime,
+    pub tlc_name: String,
+}
+
+/// Returns the key for converting with the given arguments, based on the contents of the logs and the
+/// [options fingerprint](fn.options_fingerprint.html).
+pub fn cache_key(app_args: &AppArgs) -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    hasher.update(options_fingerprint(app_args)?);
+    for file_name in &crate::log_files(app_args) {
+        hash_file(file_name, &mut hasher)?;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Returns a fingerprint of the options and the contents of the mapping file, which changes when the output of a log would.
+///
+/// The options are fingerprinted from their JSON serialization, so a new option changes the fingerprint without having to be
+/// listed here. Only the options that can't change the output are reset, as well as the file paths since the contents of the
+/// files are what counts.
+pub fn options_fingerprint(app_args: &AppArgs) -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    // The mapping from the log depends on the log alone
+    if !app_args.vlog_tlcfi_mapping_file.is_empty() {
+        hash_file(&app_args.vlog_tlcfi_mapping_file, &mut hasher)?;
+    }
+    let output_options = AppArgs {
+        tlcfi_log_file: String::new(),
+        vlog_tlcfi_mapping_file: String::new(),
+        verbosity: Default::default(),
+        pace: false,
+        pace_speed: 1.0,
+        show_progress: true,
+        overwrite: false,
+        cache_dir: None,
+        checkpoint: None,
+        ..app_args.clone()
+    };
+    let serialized_options = serde_json::to_vec(&output_options)
+        .map_err(|error| format!("failed to serialize the options: {}", error))?;
+    hasher.update(serialized_options);
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn hash_file(file_name: &str, hasher: &mut Sha256) -> Result<(), String> {
+    File::open(file_name)
+        .and_then(|mut file| io::copy(&mut file, hasher))
+        .map(|_| ())
+        .map_err(|error| format!("failed to read '{}' for the cache: {}", file_name, error))
+}
+
+/// Where the output file with the given index is kept in a cache entry. Each file gets its own directory, since the
+/// output template can give files in different directories the same name.
+fn entry_file(entry_dir: &Path, index: usize, file_name: &str) -> Option<PathBuf> {
+    Path::new(file_name)
+        .file_name()
+        .map(|name| entry_dir.join(index.to_string()).join(name))
+}
+
+/// Reads what the earlier conversion with the key resulted in, if the cache has one for it.
+pub fn lookup(cache_dir: &str, key: &str) -> Option<CachedConversion> {
+    let entry_dir = Path::new(cache_dir).join(key);
+    let diagnostics = fs::read_to_string(entry_dir.join(DIAGNOSTICS_FILE_NAME)).ok()?;
+    serde_json::from_str(&diagnostics).ok()
+}
+
+/// Copies the VLog files of a cached conversion to where the conversion wrote them, replacing files that are there.
+/// Returns None when a file can't be restored.
+pub fn restore(cache_dir: &str, key: &str, conversion: &CachedConversion) -> Option<()> {
+    let entry_dir = Path::new(cache_dir).join(key);
+    for (index, file_name) in conversion.file_names.iter().enumerate() {
+        crate::create_parent_dirs(file_name).ok()?;
+        fs::copy(entry_file(&entry_dir, index, file_name)?, file_name).ok()?;
+    }
+    Some(())
+}
+
+/// Stores a conversion in the cache under the given key.
+pub fn store(cache_dir: &str, key: &str, conversion: &CachedConversion) -> Result<PathBuf, String> {
+    let entry_dir = Path::new(cache_dir).join(key);
+    let store_error = |error: std::io::Error| {
+        format!(
+            "failed to store the conversion in '{}': {}",
+            entry_dir.display(),
+            error
+        )
+    };
+    fs::create_dir_all(&entry_dir).map_err(store_error)?;
+    for (index, file_name) in conversion.file_names.iter().enumerate() {
+        let entry_file = entry_file(&entry_dir, index, file_name)
+            .ok_or_else(|| format!("'{}' isn't the name of a file to cache", file_name))?;
+        crate::create_parent_dirs(&entry_file.to_string_lossy()).map_err(store_error)?;
+        fs::copy(file_name, entry_file).map_err(store_error)?;
+    }
+    let diagnostics = serde_json::to_string_pretty(conversion)
+        .map_err(|error| store_error(io::Error::other(error)))?;
+    // The diagnostics are written last, so an interrupted store doesn't leave an entry that looks complete
+    fs::write(entry_dir.join(DIAGNOSTICS_FILE_NAME), diagnostics).map_err(store_error)?;
+    Ok(entry_dir)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tlcfi_assimilator::Verbosity;
+
+    fn get_test_app_args() -> AppArgs {
+        AppArgs {
+            vlog_tlcfi_mapping_file: "./vlog_tlcfi_mapping.txt".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_changed_option_should_bust_the_cache() {
+        let changed_args = AppArgs {
+            duration_in_s: Some(60),
+            ..get_test_app_args()
+        };
+
+        assert_ne!(cache_key(&get_test_app_args()), cache_key(&changed_args));
+    }
+
+    #[test]
+    fn options_that_do_not_change_the_output_should_keep_the_cache_key() {
+        let quiet_args = AppArgs {
+            verbosity: Verbosity::Quiet,
+            show_progress: false,
+            cache_dir: Some("elsewhere".to_string()),
+            ..get_test_app_args()
+        };
+
+        assert_eq!(cache_key(&get_test_app_args()), cache_key(&quiet_args));
+    }
+
+    #[test]
+    fn a_stored_conversion_should_be_restored_to_its_output_dir() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_cache_test");
+        let _ = fs::remove_dir_all(&test_dir);
+        let cache_dir = test_dir.join("cache");
+        let cache_dir = cache_dir.to_str().unwrap();
+        // The output template can place files with the same name in different directories
+        let file_names: Vec<String> = ["20211215/3031.vlg", "20211216/3031.vlg"]
+            .iter()
+            .map(|name| {
+                test_dir
+                    .join("vlog")
+                    .join(name)
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        for file_name in &file_names {
+            crate::create_parent_dirs(file_name).unwrap();
+            fs::write(file_name, format!("{}\r\n", file_name)).unwrap();
+        }
+        let conversion = CachedConversion {
+            file_names: file_names.clone(),
+            change_count: 2,
+            vlog_message_count: 1,
+            warnings: vec!["Warning: something happened".to_string()],
+            summary: vec!["Summary:".to_string()],
+            stats: ConversionStats {
+                lines_read: 10,
+                signal_change_messages: 1,
+                ..Default::default()
+            },
+            start_time: chrono::NaiveDate::from_ymd(2021, 12, 15).and_hms(11, 0, 0),
+            tlc_name: "3031".to_string(),
+        };
+
+        store(cache_dir, "key", &conversion).unwrap();
+        fs::remove_dir_all(test_dir.join("vlog")).unwrap();
+        let looked_up = lookup(cache_dir, "key");
+        let restored = restore(cache_dir, "key", &conversion);
+        let restored_files: Vec<String> = file_names
+            .iter()
+            .map(|file_name| fs::read_to_string(file_name).unwrap())
+            .collect();
+        let restored_again = restore(cache_dir, "key", &conversion);
+        let restored_again_file = fs::read_to_string(&file_names[0]).unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert_eq!(looked_up, Some(conversion));
+        assert!(restored.is_some());
+        assert_eq!(
+            restored_files,
+            file_names
+                .iter()
+                .map(|file_name| format!("{}\r\n", file_name))
+                .collect::<Vec<String>>()
+        );
+        // Restoring over the files that are already there doesn't empty them
+        assert!(restored_again.is_some());
+        assert_eq!(restored_again_file, restored_files[0]);
+        assert_eq!(lookup(cache_dir, "key"), None);
+    }
+
+    #[test]
+    fn the_options_fingerprint_should_not_depend_on_the_file_paths() {
+        let moved_args = AppArgs {
+            tlcfi_log_file: "elsewhere/tlcfi.txt".to_string(),
+            ..get_test_app_args()
+        };
+
+        assert_eq!(
+            options_fingerprint(&get_test_app_args()),
+            options_fingerprint(&moved_args)
+        );
+        assert_eq!(options_fingerprint(&get_test_app_args()).unwrap().len(), 64);
+    }
+}
@@ -0,0 +1,226 @@
+//! A machine readable summary of a conversion, so pipelines can record what happened without scraping the console.
+//!
+//! The summary is written and read back as JSON with serde.
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{ControllerReset, ConversionStats, LineErrorKind, SessionInfo, VLogMessageType};
+
+/// The format of the start time in the JSON, ISO 8601 like `--start-date-time`.
+const START_TIME_FORMAT: &str = "%FT%T%.3f";
+
+/// What a conversion read, wrote and ran into.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ConversionSummary {
+    /// The log files that were read, more than one for rotated logs
+    pub input_files: Vec<String>,
+    /// The moment the VLog files start at, None when nothing was converted
+    #[serde(with = "start_time_format")]
+    pub start_time: Option<NaiveDateTime>,
+    /// The TLC name the VLog files were written for, None when no file was written
+    pub tlc_name: Option<String>,
+    pub output_files: Vec<String>,
+    /// The number of written VLog messages per type, by the snake case name of the type
+    pub messages: BTreeMap<String, usize>,
+    /// The number of skipped log lines per reason, by the reasons of the reject file, `before-start`,
+    /// `other-level` and `other-logger`
+    pub skipped_lines: BTreeMap<String, usize>,
+    pub tick_overflows: usize,
+    pub controller_resets: Vec<ControllerReset>,
+    /// The sessions the log registers, with the tick each of them started at. Summaries of earlier versions don't have them.
+    #[serde(default)]
+    pub sessions: Vec<SessionInfo>,
+    /// Whether changes were left out because the number of converted changes was limited. Summaries of earlier versions
+    /// don't say.
+    #[serde(default)]
+    pub truncated: bool,
+    /// How long the conversion took on the wall clock
+    pub runtime_ms: u64,
+}
+
+impl ConversionSummary {
+    /// Takes the message counts, skipped lines and tick events from the stats, and the number of VLog information
+    /// messages, which the stats don't count. The other fields are left empty.
+    pub fn from_stats(
+        stats: &ConversionStats,
+        vlog_information_messages: usize,
+    ) -> ConversionSummary {
+        let messages = [
+            (VLogMessageType::TimeReference, stats.time_references),
+            (VLogMessageType::VLogInformation, vlog_information_messages),
+            (
+                VLogMessageType::DetectionInformation,
+                stats.detector_change_messages,
+            ),
+            (
+                VLogMessageType::ExternalSignalGroupStatus,
+                stats.signal_change_messages,
+            ),
+            (
+                VLogMessageType::OutputInformation,
+                stats.output_change_messages,
+            ),
+        ]
+        .iter()
+        .map(|(message_type, count)| (message_type_name(message_type).to_string(), *count))
+        .collect();
+        let line_errors = &stats.line_errors;
+        let skipped_lines = [
+            (
+                LineErrorKind::Unsplittable.reject_reason(),
+                line_errors.unsplittable,
+            ),
+            (
+                LineErrorKind::InvalidJson.reject_reason(),
+                line_errors.invalid_json,
+            ),
+            (
+                LineErrorKind::UnknownState.reject_reason(),
+                line_errors.unknown_states,
+            ),
+            (
+                LineErrorKind::MalformedMessage.reject_reason(),
+                line_errors.malformed_messages,
+            ),
+            // The skipped lines that aren't line errors are the ones before the first tick
+            ("no-first-tick", stats.lines_skipped - line_errors.total()),
+            ("before-start", stats.lines_before_start),
+            ("other-level", stats.lines_of_other_levels),
+            ("other-logger", stats.lines_of_other_loggers),
+        ]
+        .iter()
+        .map(|(reason, count)| (reason.to_string(), *count))
+        .collect();
+        ConversionSummary {
+            messages,
+            skipped_lines,
+            tick_overflows: stats.tick_overflows,
+            controller_resets: stats.controller_resets.clone(),
+            sessions: stats.sessions.clone(),
+            truncated: stats.truncated_changes > 0,
+            ..Default::default()
+        }
+    }
+}
+
+/// The name of a message type in the summary, e.g. `time_reference`.
+fn message_type_name(message_type: &VLogMessageType) -> &'static str {
+    match message_type {
+        VLogMessageType::TimeReference => "time_reference",
+        VLogMessageType::VLogInformation => "vlog_information",
+        VLogMessageType::DetectionInformation => "detection_information",
+        VLogMessageType::ExternalSignalGroupStatus => "external_signal_group_status",
+        VLogMessageType::OutputInformation => "output_information",
+    }
+}
+
+/// Reads and writes the start time like `--start-date-time`.
+mod start_time_format {
+    use chrono::NaiveDateTime;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use super::START_TIME_FORMAT;
+
+    pub fn serialize<S: Serializer>(
+        start_time: &Option<NaiveDateTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match start_time {
+            Some(start_time) => {
+                serializer.serialize_some(&start_time.format(START_TIME_FORMAT).to_string())
+            }
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<NaiveDateTime>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|start_time| {
+                NaiveDateTime::parse_from_str(&start_time, START_TIME_FORMAT)
+                    .map_err(D::Error::custom)
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::LineErrors;
+
+    #[test]
+    fn a_summary_should_be_read_back_from_its_json() {
+        let stats = ConversionStats {
+            lines_skipped: 3,
+            line_errors: LineErrors {
+                unsplittable: 1,
+                unknown_states: 1,
+                ..Default::default()
+            },
+            time_references: 2,
+            signal_change_messages: 5,
+            tick_overflows: 1,
+            controller_resets: vec![ControllerReset {
+                ms_from_beginning: 2000,
+                previous_tick: 3000,
+                tick: 500,
+                changes_before: 2,
+            }],
+            sessions: vec![SessionInfo {
+                tick: Some(1000),
+                username: Some("tlcfi".to_string()),
+                protocol_version: Some("1.1.0".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let summary = ConversionSummary {
+            input_files: vec!["tlcfi.txt".to_string()],
+            start_time: Some(
+                NaiveDateTime::parse_from_str("2021-12-15T11:00:00.000", START_TIME_FORMAT)
+                    .unwrap(),
+            ),
+            tlc_name: Some("3031".to_string()),
+            output_files: vec!["3031_20211215_110000.vlg".to_string()],
+            runtime_ms: 12,
+            ..ConversionSummary::from_stats(&stats, 1)
+        };
+
+        let json = serde_json::to_string(&summary).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["messages"]["external_signal_group_status"], 5);
+        assert_eq!(value["skipped_lines"]["no-first-tick"], 1);
+        assert_eq!(value["start_time"], "2021-12-15T11:00:00.000");
+        assert_eq!(
+            serde_json::from_str::<ConversionSummary>(&json).unwrap(),
+            summary
+        );
+    }
+
+    #[test]
+    fn a_summary_of_an_earlier_version_should_be_read_without_sessions() {
+        let json = r#"{
+            "input_files": ["tlcfi.txt"],
+            "start_time": null,
+            "tlc_name": null,
+            "output_files": [],
+            "messages": {},
+            "skipped_lines": {},
+            "tick_overflows": 0,
+            "controller_resets": [],
+            "runtime_ms": 3
+        }"#;
+
+        let summary: ConversionSummary = serde_json::from_str(json).unwrap();
+
+        assert!(summary.sessions.is_empty());
+        assert!(!summary.truncated);
+        assert!(serde_json::from_str::<ConversionSummary>(r#"{"start_time": "11:00"}"#).is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! Finds the logs of a day in a directory of logs, for `--log-dir DIR --date DATE`.
+//!
+//! A log is of the day when its name has the date in it, like `tlcfi-2021-12-15.txt` or `tlcfi_20211215.txt`,
+//! or when its oldest line is logged on that day. The oldest line is the first or the last line of the file,
+//! depending on whether the log is chronological.
+
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use regex::Regex;
+use tlcfi_assimilator::log_pattern::LogPattern;
+
+use crate::reverse_reading::ReverseLines;
+
+/// Parses the `--date` option, a date like 2021-12-15.
+pub fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("--date takes a date like 2021-12-15, not '{}'", date))
+}
+
+/// The logs of the directory that are of the date, in the order of their names, with the time of their oldest line.
+/// Fails with the dates the logs are of when none of them is of the date.
+pub fn find_logs_of_date(
+    log_dir: &str,
+    date: &NaiveDate,
+    log_pattern: &LogPattern,
+) -> Result<Vec<(String, Option<NaiveDateTime>)>, String> {
+    let mut log_files: Vec<String> = fs::read_dir(log_dir)
+        .map_err(|error| format!("failed to read the log directory '{}': {}", log_dir, error))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    log_files.sort();
+
+    let mut available_dates = BTreeSet::new();
+    let mut logs_of_date = Vec::new();
+    for log_file in log_files {
+        let file_name = std::path::Path::new(&log_file)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let name_date = date_in_name(&file_name);
+        let oldest_line_time = oldest_line_time(&log_file, log_pattern);
+        let oldest_line_date = oldest_line_time.map(|time| time.date());
+        available_dates.extend(name_date.or(oldest_line_date));
+        if name_date == Some(*date) || oldest_line_date == Some(*date) {
+            logs_of_date.push((log_file, oldest_line_time));
+        }
+    }
+    if logs_of_date.is_empty() {
+        let available_dates: Vec<String> = available_dates
+            .iter()
+            .map(|date| date.to_string())
+            .collect();
+        return Err(format!(
+            "no log in '{}' is of {}, the logs are of: {}",
+            log_dir,
+            date,
+            if available_dates.is_empty() {
+                "none".to_string()
+            } else {
+                available_dates.join(", ")
+            }
+        ));
+    }
+    Ok(logs_of_date)
+}
+
+/// The start of the conversion of the logs of the date: the oldest line on that date, or midnight when the oldest
+/// log starts the day before.
+pub fn start_of_date(
+    date: &NaiveDate,
+    logs_of_date: &[(String, Option<NaiveDateTime>)],
+) -> NaiveDateTime {
+    let midnight = date.and_hms(0, 0, 0);
+    logs_of_date
+        .iter()
+        .filter_map(|(_, oldest_line_time)| *oldest_line_time)
+        .min()
+        .map_or(midnight, |oldest_line_time| oldest_line_time.max(midnight))
+}
+
+/// The earlier of the times of the first and the last log line of the file.
+fn oldest_line_time(log_file: &str, log_pattern: &LogPattern) -> Option<NaiveDateTime> {
+    let first_line_time = crate::read_start_date_time(log_file, log_pattern).ok();
+    let last_line_time = File::open(log_file)
+        .and_then(ReverseLines::new)
+        .ok()
+        .and_then(|lines| {
+            lines
+                .map_while(Result::ok)
+                .find(|line| crate::is_log_line(line, log_pattern))
+        })
+        .and_then(|line| crate::get_start_date_time_from_file(&[line], log_pattern).ok());
+    first_line_time.into_iter().chain(last_line_time).min()
+}
+
+/// The date in a file name, written like 2021-12-15 or 20211215.
+fn date_in_name(file_name: &str) -> Option<NaiveDate> {
+    let date_pattern = Regex::new(r"(\d{4})-?(\d{2})-?(\d{2})").unwrap();
+    let date = date_pattern.captures_iter(file_name).find_map(|captures| {
+        NaiveDate::from_ymd_opt(
+            captures[1].parse().ok()?,
+            captures[2].parse().ok()?,
+            captures[3].parse().ok()?,
+        )
+    });
+    date
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn the_logs_of_the_date_should_be_found_by_name_or_oldest_line() {
+        let log_dir = std::env::temp_dir().join("tlcfi_assimilator_dated_logs");
+        let _ = fs::remove_dir_all(&log_dir);
+        fs::create_dir_all(&log_dir).unwrap();
+        let log = fs::read_to_string("./tlcfi_with_amber.txt").unwrap();
+        fs::write(log_dir.join("tlcfi-2021-12-14.txt"), "").unwrap();
+        fs::write(log_dir.join("tlcfi-2021-12-15.txt"), "").unwrap();
+        fs::write(log_dir.join("tlcfi.txt.1"), &log).unwrap();
+        fs::write(
+            log_dir.join("tlcfi.txt"),
+            log.replace("2021-12-15", "2021-12-16"),
+        )
+        .unwrap();
+        let log_dir_name = log_dir.to_string_lossy().to_string();
+
+        let logs_of_date = find_logs_of_date(
+            &log_dir_name,
+            &NaiveDate::from_ymd(2021, 12, 15),
+            &LogPattern::default(),
+        );
+        let without_logs = find_logs_of_date(
+            &log_dir_name,
+            &NaiveDate::from_ymd(2021, 12, 17),
+            &LogPattern::default(),
+        );
+        fs::remove_dir_all(&log_dir).unwrap();
+
+        let logs_of_date = logs_of_date.unwrap();
+        let file_names: Vec<&str> = logs_of_date
+            .iter()
+            .map(|(log_file, _)| &log_file[log_dir_name.len() + 1..])
+            .collect();
+        assert_eq!(file_names, vec!["tlcfi-2021-12-15.txt", "tlcfi.txt.1"]);
+        assert_eq!(
+            start_of_date(&NaiveDate::from_ymd(2021, 12, 15), &logs_of_date),
+            NaiveDate::from_ymd(2021, 12, 15).and_hms(12, 0, 0)
+        );
+        assert_eq!(
+            without_logs.unwrap_err(),
+            format!(
+                "no log in '{}' is of 2021-12-17, the logs are of: 2021-12-14, 2021-12-15, 2021-12-16",
+                log_dir_name
+            )
+        );
+    }
+
+    #[test]
+    fn a_first_line_the_day_before_should_start_the_conversion_at_midnight() {
+        let date = NaiveDate::from_ymd(2021, 12, 15);
+        let logs_of_date = vec![(
+            "tlcfi-2021-12-15.txt".to_string(),
+            Some(NaiveDate::from_ymd(2021, 12, 14).and_hms(23, 58, 0)),
+        )];
+
+        assert_eq!(start_of_date(&date, &logs_of_date), date.and_hms(0, 0, 0));
+    }
+}
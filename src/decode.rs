@@ -0,0 +1,333 @@
+//! Renders a VLog file as text, one line per message, to see what a conversion wrote without decoding hex by hand.
+//!
+//! Every line has the time the message describes, the message type and its contents, e.g.
+//! `2021-12-15 11:00:01.000 DetectionInformation D611=OCCUPIED`. Messages of unknown types are shown as they are.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use tlcfi_assimilator::{vlog_transformer, DetectorState, OutputState, SignalState};
+
+use crate::{
+    compressed_logs,
+    vlog_decoding::{self, DecodedMessage, MessageContent},
+    EXIT_CODE_INPUT_ERROR,
+};
+
+const DECODE_HELP: &str = "\
+TLC-FI Assimilator decode
+
+USAGE:
+  tlcfi_assimilator decode [OPTIONS] <VLOG_FILE>
+
+FLAGS:
+  -h, --help                Prints help information
+
+OPTIONS:
+  --mapping-file STRING     Shows the TLC-FI names of this mapping file instead of the VLog ids
+
+ARGS:
+  <VLOG_FILE>               The VLog file to decode, which may be gzip compressed
+";
+
+/// The width of a rendered time, so the message types line up when a message has no time.
+const TIME_WIDTH: usize = 23;
+
+/// The TLC-FI names of the VLog ids, to show the names instead of the ids.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct IdNames {
+    pub signals: HashMap<i16, String>,
+    pub detectors: HashMap<i16, String>,
+    pub outputs: HashMap<i16, String>,
+}
+
+impl IdNames {
+    /// Inverts the mappings of the mapping file. A type of mappings that isn't in the file, or has rows that can't be
+    /// read, leaves its ids unnamed. When several names of a type have the same VLog id, the first one in the file
+    /// names it. The type of a message decides the mappings, so a signal and a detector with the same id don't clash.
+    pub fn load(mapping_file: &str) -> Result<IdNames, String> {
+        vlog_transformer::load_tlc_name(mapping_file).map_err(|error| {
+            format!(
+                "failed to read the mapping file '{}': {}",
+                mapping_file, error
+            )
+        })?;
+        let invert = |mapping_type| {
+            let section = File::open(mapping_file).and_then(|file| {
+                vlog_transformer::read_mapping_section(BufReader::new(file), mapping_type)
+            });
+            let mut names = HashMap::new();
+            if let Ok(section) = section {
+                if section.problems.is_empty() {
+                    for row in section.rows {
+                        names.entry(row.vlog_id).or_insert(row.name);
+                    }
+                }
+            }
+            names
+        };
+        Ok(IdNames {
+            signals: invert("Signals"),
+            detectors: invert("Detectors"),
+            outputs: invert("Outputs"),
+        })
+    }
+}
+
+/// Parses the `decode` arguments and prints the VLog file as text. Returns the exit code.
+pub fn run_decode() -> i32 {
+    let mut pargs = pico_args::Arguments::from_env();
+    // Drop the "decode" subcommand itself
+    let _ = pargs.subcommand();
+
+    if pargs.contains(["-h", "--help"]) {
+        println!("{}", DECODE_HELP);
+        return 0;
+    }
+
+    let (vlog_file, mapping_file) = match parse_decode_args(&mut pargs) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("Error: {}.", error);
+            println!("{}", DECODE_HELP);
+            return 1;
+        }
+    };
+
+    let id_names = match mapping_file {
+        Some(mapping_file) => match IdNames::load(&mapping_file) {
+            Ok(id_names) => id_names,
+            Err(error) => {
+                eprintln!("Error: {}", error);
+                return EXIT_CODE_INPUT_ERROR;
+            }
+        },
+        None => IdNames::default(),
+    };
+    let messages = match read_vlog_messages(&vlog_file) {
+        Ok(messages) => messages,
+        Err(error) => {
+            eprintln!(
+                "Error: failed to read the VLog file '{}': {}",
+                vlog_file, error
+            );
+            return EXIT_CODE_INPUT_ERROR;
+        }
+    };
+    for decoded in vlog_decoding::decode_messages(messages.iter().map(String::as_str)) {
+        println!("{}", render(&decoded, &id_names));
+    }
+    0
+}
+
+fn parse_decode_args(
+    pargs: &mut pico_args::Arguments,
+) -> Result<(String, Option<String>), pico_args::Error> {
+    let mapping_file = pargs.opt_value_from_fn("--mapping-file", crate::check_file_existence)?;
+    let vlog_file = pargs.free_from_fn(crate::check_file_existence)?;
+    Ok((vlog_file, mapping_file))
+}
+
+/// Reads the lines of a VLog file, which may be gzip compressed.
+pub fn read_vlog_messages(file_name: &str) -> std::io::Result<Vec<String>> {
+    compressed_logs::open_log(file_name)?.lines().collect()
+}
+
+/// Renders a message as its time, its type and its contents.
+pub fn render(decoded: &DecodedMessage, id_names: &IdNames) -> String {
+    let time = match decoded.time {
+        Some(time) => time.format("%F %T%.3f").to_string(),
+        None => "-".to_string(),
+    };
+    let type_name = match decoded.content.message_type() {
+        Some(message_type) => format!("{:?}", message_type),
+        None => "Unknown".to_string(),
+    };
+    let contents = match &decoded.content {
+        MessageContent::TimeReference => String::new(),
+        MessageContent::VLogInformation { version, tlc_name } => {
+            format!(" version {}, TLC {}", version, tlc_name)
+        }
+        MessageContent::DetectionInformation(states) => {
+            render_states(states, &id_names.detectors, detector_state_name)
+        }
+        MessageContent::ExternalSignalGroupStatus(states) => {
+            render_states(states, &id_names.signals, signal_state_name)
+        }
+        MessageContent::OutputInformation(states) => {
+            render_states(states, &id_names.outputs, output_state_name)
+        }
+        MessageContent::Unknown => format!(" {}", decoded.message),
+    };
+    format!(
+        "{:<width$} {}{}",
+        time,
+        type_name,
+        contents,
+        width = TIME_WIDTH
+    )
+}
+
+/// Renders ids and states as `name=state`.
+fn render_states(
+    states: &[(i16, i16)],
+    names: &HashMap<i16, String>,
+    state_name: fn(i16) -> String,
+) -> String {
+    states
+        .iter()
+        .map(|(vlog_id, vlog_state)| {
+            format!(" {}={}", id_name(names, *vlog_id), state_name(*vlog_state))
+        })
+        .collect()
+}
+
+/// The TLC-FI name of a VLog id, or the id after a `#` when it isn't known.
+pub fn id_name(names: &HashMap<i16, String>, vlog_id: i16) -> String {
+    names
+        .get(&vlog_id)
+        .cloned()
+        .unwrap_or_else(|| format!("#{}", vlog_id))
+}
+
+/// The name of the signal state written as the VLog value, or the value for unknown states.
+pub fn signal_state_name(vlog_state: i16) -> String {
+    SignalState::from_vlog_state(vlog_state)
+        .map(|state| format!("{:?}", state))
+        .unwrap_or_else(|| format!("state {}", vlog_state))
+}
+
+/// The name of the detector state written as the VLog value, or the value for unknown states.
+pub fn detector_state_name(vlog_state: i16) -> String {
+    DetectorState::from_vlog_state(vlog_state)
+        .map(|state| format!("{:?}", state))
+        .unwrap_or_else(|| format!("state {}", vlog_state))
+}
+
+/// The name of the output state written as the VLog value, or the value for unknown states.
+pub fn output_state_name(vlog_state: i16) -> String {
+    OutputState::from_vlog_state(vlog_state)
+        .map(|state| format!("{:?}", state))
+        .unwrap_or_else(|| format!("state {}", vlog_state))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use tlcfi_assimilator::{
+        vlog_transformer::{TransformOptions, VLogTransformer},
+        ConversionStats, SignalStateEncoding, TimestampedChanges,
+    };
+
+    #[test]
+    fn the_expected_output_should_render_with_the_names_of_the_mapping() {
+        let messages = read_vlog_messages("./expected_vlog_output.vlg").unwrap();
+        let id_names = IdNames::load("./vlog_tlcfi_mapping.txt").unwrap();
+
+        let rendered: Vec<String> =
+            vlog_decoding::decode_messages(messages.iter().map(String::as_str))
+                .iter()
+                .map(|decoded| render(decoded, &id_names))
+                .collect();
+
+        assert_eq!(rendered[0], "2021-12-15 11:00:00.000 TimeReference");
+        assert_eq!(
+            rendered[1],
+            "-                       VLogInformation version 030000, TLC 3031"
+        );
+        assert!(rendered[2].starts_with("2021-12-15 11:00:00.000 DetectionInformation D"));
+        assert!(rendered.iter().all(|line| !line.contains('#')));
+    }
+
+    #[test]
+    fn the_first_name_of_a_duplicate_vlog_id_should_name_it() {
+        let mapping_file = std::env::temp_dir().join("tlcfi_assimilator_duplicate_vlog_id.txt");
+        std::fs::write(
+            &mapping_file,
+            "// TLC\n3031\n\n// Signals\n0, 08\n1, 09\n0, 8\n\n// Detectors\n0, D081\n",
+        )
+        .unwrap();
+
+        let id_names = IdNames::load(mapping_file.to_str().unwrap()).unwrap();
+        let rendered = render(
+            &vlog_decoding::decode_messages(vec!["0E00010001"])[0],
+            &id_names,
+        );
+        std::fs::remove_file(&mapping_file).unwrap();
+
+        assert_eq!(id_names.signals[&0], "08");
+        assert_eq!(id_names.signals[&1], "09");
+        assert_eq!(id_names.detectors[&0], "D081");
+        assert!(rendered.ends_with("ExternalSignalGroupStatus 08=Green"));
+    }
+
+    #[test]
+    fn unknown_ids_states_and_messages_should_be_shown_raw() {
+        let render_message = |message| {
+            render(
+                &vlog_decoding::decode_messages(vec![message])[0],
+                &IdNames::default(),
+            )
+        };
+
+        assert_eq!(
+            render_message("0E0A120A030B"),
+            "-                       Unknown 0E0A120A030B"
+        );
+        assert_eq!(
+            render_message("0E0A120A030B01"),
+            "-                       ExternalSignalGroupStatus #10=state 3 #11=Green"
+        );
+    }
+
+    #[test]
+    fn encoded_changes_should_decode_to_the_same_ids_and_states() {
+        let start_date_time =
+            NaiveDateTime::parse_from_str("2021-12-15T11:00:00.000", "%FT%T%.3f").unwrap();
+        let signal_mapping = [("02".to_string(), 3), ("05".to_string(), 7)]
+            .iter()
+            .cloned()
+            .collect();
+        let mut transformer = VLogTransformer::with_mappings(
+            &start_date_time,
+            signal_mapping,
+            HashMap::new(),
+            TransformOptions {
+                signal_state_encoding: SignalStateEncoding::Detailed,
+                ..Default::default()
+            },
+        );
+        let mut stats = ConversionStats::default();
+        let mut messages = transformer.start("3031", &mut stats);
+        messages.extend(transformer.transform(
+            vec![TimestampedChanges {
+                ms_from_beginning: 1500,
+                signal_names: vec!["05".to_string(), "02".to_string()],
+                signal_states: vec![SignalState::GreenPermissive, SignalState::Amber],
+                ..Default::default()
+            }],
+            &mut stats,
+        ));
+
+        let decoded = vlog_decoding::decode_messages(messages.iter().map(String::as_str));
+
+        assert_eq!(
+            decoded[2].time,
+            Some(start_date_time + chrono::Duration::milliseconds(1500))
+        );
+        assert_eq!(
+            decoded[2].content,
+            MessageContent::ExternalSignalGroupStatus(vec![
+                (3, SignalState::Amber.to_vlog_state()),
+                (
+                    7,
+                    SignalState::GreenPermissive.to_vlog_state_with(SignalStateEncoding::Detailed)
+                )
+            ])
+        );
+    }
+}
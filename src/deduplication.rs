@@ -0,0 +1,169 @@
+//! Drops state changes that repeat the last state of a signal group or detector, for TLCs that resend their full
+//! state every few seconds even when nothing changed.
+
+use std::collections::HashMap;
+
+use tlcfi_assimilator::{DetectorState, SignalState, TimestampedChanges};
+
+/// How many repeated states were dropped.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct SuppressedStates {
+    pub signals: usize,
+    pub detectors: usize,
+}
+
+/// Remembers the last state of every signal group and detector, so it keeps working across the batches of `--follow`.
+#[derive(Debug, Default)]
+pub struct Deduplicator {
+    signal_states: HashMap<String, SignalState>,
+    detector_states: HashMap<String, DetectorState>,
+}
+
+impl Deduplicator {
+    /// Removes the states that equal the last state of their object from the changes, and the changes that end up
+    /// empty. The first state of every object is kept.
+    pub fn dedupe(&mut self, changes: &mut Vec<TimestampedChanges>) -> SuppressedStates {
+        let mut suppressed = SuppressedStates::default();
+        for change in changes.iter_mut() {
+            suppressed.signals += retain_new_states(
+                &mut change.signal_names,
+                &mut change.signal_states,
+                &mut self.signal_states,
+            );
+            suppressed.detectors += retain_new_states(
+                &mut change.detector_names,
+                &mut change.detector_states,
+                &mut self.detector_states,
+            );
+        }
+        changes.retain(|change| !change.is_empty());
+        suppressed
+    }
+}
+
+/// Keeps the names whose state differs from their last one and remembers the states. Returns the number removed.
+fn retain_new_states<S: Copy + PartialEq>(
+    names: &mut Vec<String>,
+    states: &mut Vec<S>,
+    last_states: &mut HashMap<String, S>,
+) -> usize {
+    let mut kept_names = Vec::new();
+    let mut kept_states = Vec::new();
+    for (name, state) in names.iter().zip(states.iter()) {
+        if last_states.insert(name.clone(), *state) != Some(*state) {
+            kept_names.push(name.clone());
+            kept_states.push(*state);
+        }
+    }
+    let removed = names.len() - kept_names.len();
+    *names = kept_names;
+    *states = kept_states;
+    removed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn detector_change(
+        ms_from_beginning: u64,
+        states: &[(&str, DetectorState)],
+    ) -> TimestampedChanges {
+        TimestampedChanges {
+            ms_from_beginning,
+            detector_names: states.iter().map(|(name, _)| name.to_string()).collect(),
+            detector_states: states.iter().map(|(_, state)| *state).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn resent_states_should_be_dropped_and_changed_ones_kept() {
+        let mut changes = vec![
+            detector_change(
+                0,
+                &[("D611", DetectorState::FREE), ("D612", DetectorState::FREE)],
+            ),
+            detector_change(
+                5000,
+                &[
+                    ("D611", DetectorState::FREE),
+                    ("D612", DetectorState::OCCUPIED),
+                ],
+            ),
+            detector_change(10_000, &[("D611", DetectorState::FREE)]),
+            TimestampedChanges {
+                ms_from_beginning: 10_000,
+                signal_names: vec!["02".to_string()],
+                signal_states: vec![SignalState::Red],
+                ..Default::default()
+            },
+        ];
+
+        let suppressed = Deduplicator::default().dedupe(&mut changes);
+
+        assert_eq!(
+            changes,
+            vec![
+                detector_change(
+                    0,
+                    &[("D611", DetectorState::FREE), ("D612", DetectorState::FREE)]
+                ),
+                detector_change(5000, &[("D612", DetectorState::OCCUPIED)]),
+                TimestampedChanges {
+                    ms_from_beginning: 10_000,
+                    signal_names: vec!["02".to_string()],
+                    signal_states: vec![SignalState::Red],
+                    ..Default::default()
+                },
+            ]
+        );
+        assert_eq!(
+            suppressed,
+            SuppressedStates {
+                signals: 0,
+                detectors: 2
+            }
+        );
+    }
+
+    #[test]
+    fn a_detector_becoming_faulty_should_be_kept_as_a_change() {
+        let mut changes = vec![
+            detector_change(0, &[("D611", DetectorState::FREE)]),
+            detector_change(5000, &[("D611", DetectorState::FAULTY)]),
+            detector_change(6000, &[("D611", DetectorState::FAULTY)]),
+            detector_change(10_000, &[("D611", DetectorState::OCCUPIED)]),
+        ];
+
+        let suppressed = Deduplicator::default().dedupe(&mut changes);
+
+        let kept_states: Vec<DetectorState> = changes
+            .iter()
+            .flat_map(|change| change.detector_states.clone())
+            .collect();
+        assert_eq!(
+            kept_states,
+            vec![
+                DetectorState::FREE,
+                DetectorState::FAULTY,
+                DetectorState::OCCUPIED
+            ]
+        );
+        assert_eq!(suppressed.detectors, 1);
+    }
+
+    #[test]
+    fn the_last_states_should_be_remembered_between_batches() {
+        let mut deduplicator = Deduplicator::default();
+        let mut first_batch = vec![detector_change(0, &[("D611", DetectorState::OCCUPIED)])];
+        let mut second_batch = vec![detector_change(5000, &[("D611", DetectorState::OCCUPIED)])];
+
+        deduplicator.dedupe(&mut first_batch);
+        let suppressed = deduplicator.dedupe(&mut second_batch);
+
+        assert_eq!(first_batch.len(), 1);
+        assert!(second_batch.is_empty());
+        assert_eq!(suppressed.detectors, 1);
+    }
+}
@@ -0,0 +1,179 @@
+//! Names the VLog files from a template with placeholders, e.g. `{date}/{tlc}/{tlc}-{date}T{time}.{ext}` for an archive
+//! with a directory per day and controller.
+
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+/// The template of the VLog file names when none is given, e.g. `3031_20211215_110000.vlg`.
+pub const DEFAULT_TEMPLATE: &str = "{tlc}_{date}_{time}.{ext}";
+
+/// The extension of VLog files, filled in for `{ext}`.
+const VLOG_EXTENSION: &str = "vlg";
+
+/// A value that can be filled into the template.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+enum Placeholder {
+    /// The name of the TLC from the mapping file
+    Tlc,
+    /// The start date as `yyyymmdd`
+    Date,
+    /// The start time as `hhmmss`
+    Time,
+    /// The start date time as ISO 8601 without fractions, e.g. `2021-12-15T11:00:00`
+    StartIso,
+    /// The extension of VLog files
+    Ext,
+}
+
+impl Placeholder {
+    const ALL: [Placeholder; 5] = [
+        Placeholder::Tlc,
+        Placeholder::Date,
+        Placeholder::Time,
+        Placeholder::StartIso,
+        Placeholder::Ext,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Placeholder::Tlc => "tlc",
+            Placeholder::Date => "date",
+            Placeholder::Time => "time",
+            Placeholder::StartIso => "start_iso",
+            Placeholder::Ext => "ext",
+        }
+    }
+
+    fn value(&self, tlc_name: &str, start_date_time: &NaiveDateTime) -> String {
+        match self {
+            Placeholder::Tlc => tlc_name.to_string(),
+            Placeholder::Date => start_date_time.format("%Y%m%d").to_string(),
+            Placeholder::Time => start_date_time.format("%H%M%S").to_string(),
+            Placeholder::StartIso => start_date_time.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            Placeholder::Ext => VLOG_EXTENSION.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+enum Segment {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+/// A file name template, in which placeholders like `{tlc}` are replaced. A `/` in it places the files in directories.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct FileNameTemplate {
+    segments: Vec<Segment>,
+}
+
+impl FileNameTemplate {
+    /// Fills in the placeholders for a VLog file that starts at the given date time.
+    pub fn expand(&self, tlc_name: &str, start_date_time: &NaiveDateTime) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.clone(),
+                Segment::Placeholder(placeholder) => placeholder.value(tlc_name, start_date_time),
+            })
+            .collect()
+    }
+}
+
+impl Default for FileNameTemplate {
+    fn default() -> Self {
+        DEFAULT_TEMPLATE
+            .parse()
+            .expect("The default template only has known placeholders")
+    }
+}
+
+impl FromStr for FileNameTemplate {
+    type Err = String;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut rest = arg;
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                segments.push(Segment::Text(rest[..open].to_string()));
+            }
+            let close = rest[open..]
+                .find('}')
+                .ok_or_else(|| format!("the template '{}' has a '{{' without a '}}'", arg))?
+                + open;
+            let name = &rest[open + 1..close];
+            let placeholder = Placeholder::ALL
+                .iter()
+                .find(|placeholder| placeholder.name() == name)
+                .ok_or_else(|| {
+                    format!(
+                        "'{{{}}}' isn't a placeholder, use {}",
+                        name,
+                        Placeholder::ALL
+                            .iter()
+                            .map(|placeholder| format!("{{{}}}", placeholder.name()))
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    )
+                })?;
+            segments.push(Segment::Placeholder(*placeholder));
+            rest = &rest[close + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Text(rest.to_string()));
+        }
+        if segments.is_empty() {
+            return Err("the template is empty".to_string());
+        }
+        Ok(FileNameTemplate { segments })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn start_date_time() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2021-12-15 11:00:05", "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn expand(template: &str) -> String {
+        template
+            .parse::<FileNameTemplate>()
+            .unwrap()
+            .expand("3031", &start_date_time())
+    }
+
+    #[test]
+    fn the_default_template_should_name_files_by_tlc_and_start() {
+        assert_eq!(
+            FileNameTemplate::default().expand("3031", &start_date_time()),
+            "3031_20211215_110005.vlg"
+        );
+    }
+
+    #[test]
+    fn templates_should_fill_in_every_placeholder() {
+        assert_eq!(
+            expand("{date}/{tlc}/{tlc}-{date}T{time}.{ext}"),
+            "20211215/3031/3031-20211215T110005.vlg"
+        );
+        assert_eq!(expand("{start_iso}.vlg"), "2021-12-15T11:00:05.vlg");
+        assert_eq!(expand("vlog"), "vlog");
+    }
+
+    #[test]
+    fn unknown_placeholders_should_list_the_supported_ones() {
+        let error = "{tlc}_{hour}.vlg".parse::<FileNameTemplate>().unwrap_err();
+
+        assert_eq!(
+            error,
+            "'{hour}' isn't a placeholder, use {tlc}, {date}, {time}, {start_iso}, {ext}"
+        );
+        assert!("{tlc".parse::<FileNameTemplate>().is_err());
+        assert!("".parse::<FileNameTemplate>().is_err());
+    }
+}
@@ -0,0 +1,448 @@
+//! Follows a TLC-FI log that is still being written, appending the VLog messages of new lines to the output as they arrive.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Seek, SeekFrom, Write},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+use tlcfi_assimilator::AssimilationData;
+
+use crate::{
+    amber_policy, change_filtering, controller_resets, deduplication::Deduplicator,
+    output_verification::OutputVerifier, pacing::Pacer, vlog_transformer::VLogTransformer, AppArgs,
+};
+
+/// How long to wait before looking for new lines again when the log didn't grow.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Set by the Ctrl-C handler, so following stops after writing the current batch, or at once when pacing.
+pub static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Reads the lines that are added to a log file.
+pub struct LogTail {
+    reader: BufReader<File>,
+    /// The start of a line whose end hasn't been written yet
+    partial_line: String,
+    /// The byte offset in the file after the last complete line
+    offset: u64,
+    /// The most lines a call returns, all available lines when None
+    max_lines: Option<usize>,
+}
+
+impl LogTail {
+    pub fn open(file_name: &str) -> io::Result<LogTail> {
+        LogTail::open_at(file_name, 0, None)
+    }
+
+    /// Opens the log to continue reading at the byte offset, which has to be the start of a line.
+    pub fn open_at(file_name: &str, offset: u64, max_lines: Option<usize>) -> io::Result<LogTail> {
+        let mut file = File::open(file_name)?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(LogTail {
+            reader: BufReader::new(file),
+            partial_line: String::new(),
+            offset,
+            max_lines,
+        })
+    }
+
+    /// Returns the complete lines that were added since the last call, up to the maximum number of lines.
+    /// A line that is still being written is kept until its end arrives.
+    pub fn read_new_lines(&mut self) -> io::Result<Vec<String>> {
+        let mut lines = Vec::new();
+        while self
+            .max_lines
+            .is_none_or(|max_lines| lines.len() < max_lines)
+            && self.reader.read_line(&mut self.partial_line)? > 0
+        {
+            if self.partial_line.ends_with('\n') {
+                lines.push(
+                    self.partial_line
+                        .trim_end_matches(&['\r', '\n'][..])
+                        .to_string(),
+                );
+                self.offset += self.partial_line.len() as u64;
+                self.partial_line.clear();
+            }
+        }
+        Ok(lines)
+    }
+
+    /// Returns the last line of a log that won't grow anymore, which doesn't have to end with a line break.
+    pub fn read_unterminated_line(&mut self) -> io::Result<Option<String>> {
+        self.reader.read_line(&mut self.partial_line)?;
+        if self.partial_line.is_empty() {
+            return Ok(None);
+        }
+        let line = self.partial_line.trim_end_matches('\r').to_string();
+        self.offset += self.partial_line.len() as u64;
+        self.partial_line.clear();
+        Ok(Some(line))
+    }
+
+    /// The byte offset in the file after the lines that were returned.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+/// Converts the lines that are added to the log, keeping the parsing and time reference state between batches.
+pub struct Follower<'a> {
+    log_tail: LogTail,
+    data: AssimilationData,
+    transformer: VLogTransformer,
+    deduplicator: Deduplicator,
+    app_args: &'a AppArgs,
+    change_count: usize,
+}
+
+impl<'a> Follower<'a> {
+    /// Continues from the state after converting the existing content of the log.
+    pub fn new(
+        log_tail: LogTail,
+        data: AssimilationData,
+        transformer: VLogTransformer,
+        deduplicator: Deduplicator,
+        app_args: &'a AppArgs,
+    ) -> Follower<'a> {
+        Follower {
+            log_tail,
+            data,
+            transformer,
+            deduplicator,
+            app_args,
+            change_count: 0,
+        }
+    }
+
+    /// Reads the lines added since the last batch and returns their VLog messages.
+    pub fn next_batch(&mut self) -> io::Result<Vec<String>> {
+        let lines = self.log_tail.read_new_lines()?;
+        self.convert_lines(lines)
+    }
+
+    /// Converts the last line of a log that won't grow anymore, when it doesn't end with a line break.
+    pub fn finish(&mut self) -> io::Result<Vec<String>> {
+        match self.log_tail.read_unterminated_line()? {
+            Some(line) => self.convert_lines(vec![line]),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn convert_lines(&mut self, lines: Vec<String>) -> io::Result<Vec<String>> {
+        if lines.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.data.sorted_lines = lines;
+        let known_resets = self.data.stats.controller_resets.len();
+        crate::read_lines_and_save_changes(&mut self.data, self.app_args).map_err(|problem| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "strict mode stopped at a line that can't be parsed: {}\n{}",
+                    problem.reason, problem.line
+                ),
+            )
+        })?;
+        if let Some(message) =
+            crate::too_many_line_errors(&self.data.stats.line_errors, self.app_args.max_errors)
+        {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+
+        // Earlier changes are already written, so the reset indices are relative to this batch
+        let mut changes = std::mem::take(&mut self.data.changes);
+        if self.app_args.reset_snapshot {
+            controller_resets::insert_reset_snapshots(
+                &mut changes,
+                &self.data.stats.controller_resets[known_resets..],
+            );
+        }
+        if let Some(filter) = &self.app_args.signal_filter {
+            change_filtering::filter_signals(&mut changes, filter);
+        }
+        if let Some(filter) = &self.app_args.detector_filter {
+            change_filtering::filter_detectors(&mut changes, filter);
+        }
+        self.data.stats.collapsed_amber_states +=
+            amber_policy::collapse_amber(&mut changes, self.app_args.amber_policy);
+        if self.app_args.dedupe {
+            let suppressed = self.deduplicator.dedupe(&mut changes);
+            self.data.stats.suppressed_signal_states += suppressed.signals;
+            self.data.stats.suppressed_detector_states += suppressed.detectors;
+        }
+        // A name that isn't in the mapping would stop the transformer, it was only checked for the earlier lines
+        let mapping_problems = crate::find_mapping_problems(&changes, self.app_args);
+        if !mapping_problems.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "the mapping file can't be used for the new lines: {}",
+                    mapping_problems.join("; ")
+                ),
+            ));
+        }
+        self.change_count += changes.len();
+        Ok(self.transformer.transform(changes, &mut self.data.stats))
+    }
+
+    /// The number of changes converted from the lines that were added.
+    pub fn change_count(&self) -> usize {
+        self.change_count
+    }
+
+    /// The byte offset in the log after the lines that were converted.
+    pub fn log_offset(&self) -> u64 {
+        self.log_tail.offset()
+    }
+
+    /// The parsing state and stats of the lines that were converted.
+    pub fn data(&self) -> &AssimilationData {
+        &self.data
+    }
+
+    /// The time reference the next messages are relative to.
+    pub fn ms_of_last_time_reference(&self) -> u64 {
+        self.transformer.ms_of_last_time_reference()
+    }
+
+    /// Gives back the state of the conversion, for the summary.
+    pub fn into_data(self) -> AssimilationData {
+        self.data
+    }
+}
+
+/// Appends the VLog messages of new log lines to the writer until Ctrl-C is pressed, flushing after every batch.
+/// Returns the number of appended messages.
+/// The messages are checked on the way by the verifier. In strict mode following stops at the first defect.
+/// With a pacer every message waits until it's due, the ones that aren't due yet when Ctrl-C is pressed aren't written.
+pub fn follow<W: Write>(
+    follower: &mut Follower,
+    writer: &mut W,
+    verifier: &mut OutputVerifier,
+    mut pacer: Option<&mut Pacer>,
+) -> io::Result<usize> {
+    stop_on_interrupt();
+    let mut appended_messages = 0;
+    while !STOP_REQUESTED.load(Ordering::SeqCst) {
+        let vlog_messages = follower.next_batch()?;
+        if vlog_messages.is_empty() {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+        appended_messages += write_batch(
+            writer,
+            &vlog_messages,
+            verifier,
+            follower.app_args,
+            pacer.as_deref_mut(),
+        )?;
+    }
+    Ok(appended_messages)
+}
+
+/// Writes the VLog messages of a batch, checking them on the way by the verifier, and flushes them.
+/// Returns the number of written messages, which is less than the batch when the pacer was stopped.
+/// In strict mode the first defect is an error.
+pub fn write_batch<W: Write>(
+    writer: &mut W,
+    vlog_messages: &[String],
+    verifier: &mut OutputVerifier,
+    app_args: &AppArgs,
+    mut pacer: Option<&mut Pacer>,
+) -> io::Result<usize> {
+    let due_messages = vlog_messages
+        .iter()
+        .take_while(|message| pacer.as_mut().is_none_or(|pacer| pacer.wait_for(message)));
+    let mut written_messages = 0;
+    for message in verifier.pass(due_messages) {
+        crate::write_vlog_line(writer, message, app_args.line_ending)?;
+        written_messages += 1;
+    }
+    writer.flush()?;
+    if app_args.strict {
+        if let Some(defect) = verifier.defects().first() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "the output failed the integrity check, {}",
+                    defect.describe()
+                ),
+            ));
+        }
+    }
+    Ok(written_messages)
+}
+
+/// Lets Ctrl-C stop following instead of the program, so the output file is closed cleanly.
+#[cfg(unix)]
+pub fn stop_on_interrupt() {
+    extern "C" fn request_stop(_signal: libc::c_int) {
+        STOP_REQUESTED.store(true, Ordering::SeqCst);
+    }
+    // Only an atomic store happens in the handler, which is safe to do from a signal handler
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            request_stop as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+/// Without a handler Ctrl-C stops the program, which leaves complete messages since every batch is flushed.
+#[cfg(not(unix))]
+pub fn stop_on_interrupt() {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{
+        fs::{self, OpenOptions},
+        time::Instant,
+    };
+    use tlcfi_assimilator::ConversionStats;
+
+    use crate::vlog_transformer::TransformOptions;
+
+    const FIRST_LINE: &str = r#"2021-12-15 11:00:00,000 INFO tlcFiMessages:41 - IN - {"jsonrpc":"2.0","method":"UpdateState","params":{"ticks":1000,"update":[{"objects":{"ids":["02"],"type":3},"states":[{"state":6}]}]}}"#;
+
+    fn line_at(ticks: u64, state: u8) -> String {
+        format!(
+            r#"2021-12-15 11:00:00,000 INFO tlcFiMessages:41 - IN - {{"jsonrpc":"2.0","method":"UpdateState","params":{{"ticks":{},"update":[{{"objects":{{"ids":["02"],"type":3}},"states":[{{"state":{}}}]}}]}}}}"#,
+            ticks, state
+        )
+    }
+
+    #[test]
+    fn a_partially_written_line_should_wait_for_its_end() {
+        let log_file = std::env::temp_dir().join("tlcfi_assimilator_follow_tail.txt");
+        fs::write(&log_file, "first\r\nsec").unwrap();
+        let mut log_tail = LogTail::open(log_file.to_str().unwrap()).unwrap();
+
+        let first_lines = log_tail.read_new_lines().unwrap();
+        let mut file = OpenOptions::new().append(true).open(&log_file).unwrap();
+        write!(file, "ond\nthird\n").unwrap();
+        let next_lines = log_tail.read_new_lines().unwrap();
+        fs::remove_file(&log_file).unwrap();
+
+        assert_eq!(first_lines, vec!["first"]);
+        assert_eq!(next_lines, vec!["second", "third"]);
+    }
+
+    fn app_args_for(log_file: &std::path::Path) -> AppArgs {
+        AppArgs {
+            tlcfi_log_file: log_file.to_str().unwrap().to_string(),
+            vlog_tlcfi_mapping_file: "./vlog_tlcfi_mapping.txt".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// A follower of the log from its start at 2021-12-15 11:00:00.
+    fn follower_of(app_args: &AppArgs) -> Follower<'_> {
+        let start_date_time = chrono::NaiveDate::from_ymd(2021, 12, 15).and_hms(11, 0, 0);
+        let data = AssimilationData {
+            start_time: start_date_time,
+            ..Default::default()
+        };
+        let transformer = VLogTransformer::new(
+            &start_date_time,
+            &app_args.vlog_tlcfi_mapping_file,
+            TransformOptions::default(),
+        );
+        Follower::new(
+            LogTail::open(&app_args.tlcfi_log_file).unwrap(),
+            data,
+            transformer,
+            Deduplicator::default(),
+            app_args,
+        )
+    }
+
+    #[test]
+    fn appended_lines_should_be_converted_with_the_state_of_earlier_batches() {
+        let log_file = std::env::temp_dir().join("tlcfi_assimilator_follow_batches.txt");
+        fs::write(&log_file, format!("{}\n", FIRST_LINE)).unwrap();
+        let app_args = app_args_for(&log_file);
+        let mut follower = follower_of(&app_args);
+
+        let first_batch = follower.next_batch().unwrap();
+        let mut file = OpenOptions::new().append(true).open(&log_file).unwrap();
+        // 200 and 310 seconds after the first tick
+        writeln!(file, "{}\n{}", line_at(201_000, 3), line_at(311_000, 6)).unwrap();
+        let second_batch = follower.next_batch().unwrap();
+        let empty_batch = follower.next_batch().unwrap();
+        fs::remove_file(&log_file).unwrap();
+
+        assert_eq!(first_batch, vec!["0E00010001".to_string()]);
+        assert_eq!(
+            second_batch,
+            vec![
+                "0E7D010000".to_string(),
+                "012021121511051000".to_string(),
+                "0E00010001".to_string()
+            ]
+        );
+        assert!(empty_batch.is_empty());
+        assert_eq!(
+            follower.into_data().stats,
+            ConversionStats {
+                lines_read: 3,
+                in_lines: 3,
+                signal_change_messages: 3,
+                time_references: 1,
+                first_written_change_ms: Some(0),
+                last_written_change_ms: Some(310_000),
+                first_tick: Some(1000),
+                last_tick: Some(311_000),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn a_stop_request_should_end_following_while_a_message_is_paced() {
+        let log_file = std::env::temp_dir().join("tlcfi_assimilator_follow_paced.txt");
+        // The second change is 200 seconds after the first
+        fs::write(
+            &log_file,
+            format!("{}\n{}\n", FIRST_LINE, line_at(201_000, 3)),
+        )
+        .unwrap();
+        let app_args = app_args_for(&log_file);
+        let mut follower = follower_of(&app_args);
+        let mut pacer = Pacer::new(1.0).stopped_by(&STOP_REQUESTED);
+        // The time reference at the start of the VLog file, which was written before following
+        assert!(pacer.wait_for("012021121511000000"));
+        let stopper = thread::spawn(|| {
+            thread::sleep(Duration::from_millis(300));
+            STOP_REQUESTED.store(true, Ordering::SeqCst);
+        });
+
+        let started_at = Instant::now();
+        let mut output = Vec::new();
+        let appended_messages = follow(
+            &mut follower,
+            &mut output,
+            &mut OutputVerifier::default(),
+            Some(&mut pacer),
+        )
+        .unwrap();
+        let following_time = started_at.elapsed();
+        stopper.join().unwrap();
+        STOP_REQUESTED.store(false, Ordering::SeqCst);
+        fs::remove_file(&log_file).unwrap();
+
+        assert!(following_time < Duration::from_secs(10));
+        assert_eq!(appended_messages, 1);
+        assert_eq!(
+            String::from_utf8(output)
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            vec!["0E00010001"]
+        );
+    }
+}
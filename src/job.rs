@@ -0,0 +1,706 @@
+//! A JSON interface for orchestration: a job description is read from stdin and a single JSON result is written to stdout.
+//!
+//! A job looks like this, where the options use the names of the command line options:
+//!
+//! ```json
+//! {
+//!     "inputs": { "tlcfi_log_file": "tlcfi.txt" },
+//!     "mapping": { "path": "vlog_tlcfi_mapping.txt" },
+//!     "options": { "start-date-time": "2021-12-15T11:00:00.000", "chronological": false },
+//!     "outputs": { "output_dir": "vlog", "reject_file": "rejected.txt" }
+//! }
+//! ```
+//!
+//! Instead of a `path`, the mapping can be given `inline` as the contents of a mapping file, but not both.
+//! The `outputs` name the files the job writes besides its result, they can all be left out.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Read,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tlcfi_assimilator::{
+    ClockStep, ControllerReset, ConversionMode, ConversionStats, LineErrors, RetiredObject,
+    RpcError, SessionInfo, SignalStateEncoding, SpecialVehicleEvent, Verbosity,
+};
+
+use crate::{
+    change_filtering::{self, NameFilter},
+    mapping_check, output_size, output_splitting, AppArgs, ConversionFailure, ConversionReport,
+    EXIT_CODE_INPUT_ERROR,
+};
+
+/// A job as it's read from stdin. A job with a field it doesn't know, like a misspelled option, is refused.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct JobSpec {
+    inputs: JobInputs,
+    mapping: JobMapping,
+    options: JobOptions,
+    outputs: JobOutputs,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct JobInputs {
+    tlcfi_log_file: Option<String>,
+}
+
+/// The mapping file, either its `path` or its contents `inline`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct JobMapping {
+    path: Option<String>,
+    inline: Option<String>,
+}
+
+/// The options of a job, named like the command line options. The values are read as text or numbers, so they can be
+/// validated by the command line parsers.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct JobOptions {
+    cache_dir: Option<Value>,
+    dry_run: bool,
+    name_date: Option<Value>,
+    max_id_gap: Option<Value>,
+    renumber_ids: bool,
+    mapping_from_log: bool,
+    split: Option<Value>,
+    line_ending: Option<Value>,
+    max_messages: Option<Value>,
+    max_output_size: Option<Value>,
+    size_policy: Option<Value>,
+    reset_snapshot: bool,
+    chronological: bool,
+    signals_only: bool,
+    detectors_only: bool,
+    detailed_signal_states: bool,
+    amber_policy: Option<Value>,
+    dedupe: bool,
+    skip_until_first_signal: bool,
+    strict: bool,
+    strict_rpc: bool,
+    max_errors: Option<Value>,
+    overwrite: bool,
+    direction: Option<Value>,
+    invert_direction: bool,
+    time_source: Option<Value>,
+    repair_clock_steps: bool,
+    min_mapped_fraction: Option<Value>,
+    duration: Option<Value>,
+    limit: Option<Value>,
+    filter_signals: Option<Value>,
+    filter_detectors: Option<Value>,
+    exclude_detectors: Option<Value>,
+    start_date_time: Option<Value>,
+    start_tolerance: Option<Value>,
+    first_tick: Option<Value>,
+    tick_unit: Option<Value>,
+    log_levels: Option<Value>,
+    logger_name: Option<Value>,
+    log_pattern: Option<Value>,
+    update_methods: Option<Value>,
+    timezone: Option<Value>,
+    output_utc: bool,
+    tlc_name: Option<Value>,
+}
+
+/// Where the VLog files go and the other files the job writes, named like the inputs.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct JobOutputs {
+    output_dir: Option<String>,
+    output_template: Option<String>,
+    reject_file: Option<String>,
+    predictions_csv: Option<String>,
+}
+
+/// The result of a job as it's written to stdout.
+#[derive(Debug, Default, Serialize)]
+struct JobResult {
+    success: bool,
+    exit_code: i32,
+    error: Option<String>,
+    output_file: Option<String>,
+    output_files: Vec<String>,
+    changes: usize,
+    vlog_messages: usize,
+    problems: Vec<String>,
+    warnings: Vec<String>,
+    /// Only a conversion that ran has stats
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<JobStats>,
+}
+
+/// The counts of a conversion and what it found in the log.
+#[derive(Debug, Serialize)]
+struct JobStats {
+    lines_read: usize,
+    lines_skipped: usize,
+    line_errors: JobLineErrors,
+    lines_before_start: usize,
+    lines_of_other_levels: usize,
+    lines_of_other_loggers: usize,
+    intersection_state_changes: usize,
+    unknown_intersection_states: usize,
+    other_methods: BTreeMap<String, usize>,
+    null_signal_states: usize,
+    null_detector_states: usize,
+    null_output_states: usize,
+    unmapped_output_states: usize,
+    in_lines: usize,
+    out_lines: usize,
+    signal_change_messages: usize,
+    detector_change_messages: usize,
+    output_change_messages: usize,
+    time_references: usize,
+    tick_overflows: usize,
+    collapsed_amber_states: usize,
+    suppressed_signal_states: usize,
+    suppressed_detector_states: usize,
+    output_bytes: u64,
+    estimated_output_bytes: u64,
+    controller_resets: Vec<JobControllerReset>,
+    clock_steps: Vec<JobClockStep>,
+    retired_objects: Vec<RetiredObject>,
+    special_vehicle_events: Vec<SpecialVehicleEvent>,
+    sessions: Vec<SessionInfo>,
+    rpc_errors: Vec<JobRpcError>,
+}
+
+/// The counts of the lines that couldn't be parsed, without the lines themselves.
+#[derive(Debug, Serialize)]
+struct JobLineErrors {
+    unsplittable: usize,
+    invalid_json: usize,
+    unknown_states: usize,
+    malformed_messages: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct JobControllerReset {
+    ms_from_beginning: u64,
+    previous_tick: u64,
+    tick: u64,
+}
+
+/// A clock step with the line times as they're shown in the summary, e.g. `2021-12-15 11:00:00`.
+#[derive(Debug, Serialize)]
+struct JobClockStep {
+    before: String,
+    after: String,
+    until: String,
+    step_ms: i64,
+    repaired: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct JobRpcError {
+    line_time: Option<String>,
+    code: Option<i64>,
+    message: Option<String>,
+    id: Option<String>,
+}
+
+impl From<ConversionStats> for JobStats {
+    fn from(stats: ConversionStats) -> Self {
+        JobStats {
+            lines_read: stats.lines_read,
+            lines_skipped: stats.lines_skipped,
+            line_errors: JobLineErrors::from(&stats.line_errors),
+            lines_before_start: stats.lines_before_start,
+            lines_of_other_levels: stats.lines_of_other_levels,
+            lines_of_other_loggers: stats.lines_of_other_loggers,
+            intersection_state_changes: stats.intersection_state_changes,
+            unknown_intersection_states: stats.unknown_intersection_states,
+            other_methods: stats.other_methods,
+            null_signal_states: stats.null_signal_states,
+            null_detector_states: stats.null_detector_states,
+            null_output_states: stats.null_output_states,
+            unmapped_output_states: stats.unmapped_output_states,
+            in_lines: stats.in_lines,
+            out_lines: stats.out_lines,
+            signal_change_messages: stats.signal_change_messages,
+            detector_change_messages: stats.detector_change_messages,
+            output_change_messages: stats.output_change_messages,
+            time_references: stats.time_references,
+            tick_overflows: stats.tick_overflows,
+            collapsed_amber_states: stats.collapsed_amber_states,
+            suppressed_signal_states: stats.suppressed_signal_states,
+            suppressed_detector_states: stats.suppressed_detector_states,
+            output_bytes: stats.output_bytes,
+            estimated_output_bytes: stats.estimated_output_bytes,
+            controller_resets: stats
+                .controller_resets
+                .iter()
+                .map(JobControllerReset::from)
+                .collect(),
+            clock_steps: stats.clock_steps.iter().map(JobClockStep::from).collect(),
+            retired_objects: stats.retired_objects,
+            special_vehicle_events: stats.special_vehicle_events,
+            sessions: stats.sessions,
+            rpc_errors: stats.rpc_errors.iter().map(JobRpcError::from).collect(),
+        }
+    }
+}
+
+impl From<&LineErrors> for JobLineErrors {
+    fn from(line_errors: &LineErrors) -> Self {
+        JobLineErrors {
+            unsplittable: line_errors.unsplittable,
+            invalid_json: line_errors.invalid_json,
+            unknown_states: line_errors.unknown_states,
+            malformed_messages: line_errors.malformed_messages,
+        }
+    }
+}
+
+impl From<&ControllerReset> for JobControllerReset {
+    fn from(reset: &ControllerReset) -> Self {
+        JobControllerReset {
+            ms_from_beginning: reset.ms_from_beginning,
+            previous_tick: reset.previous_tick,
+            tick: reset.tick,
+        }
+    }
+}
+
+impl From<&ClockStep> for JobClockStep {
+    fn from(clock_step: &ClockStep) -> Self {
+        JobClockStep {
+            before: clock_step.before.to_string(),
+            after: clock_step.after.to_string(),
+            until: clock_step.until.to_string(),
+            step_ms: clock_step.step_ms,
+            repaired: clock_step.repaired,
+        }
+    }
+}
+
+impl From<&RpcError> for JobRpcError {
+    fn from(rpc_error: &RpcError) -> Self {
+        JobRpcError {
+            line_time: rpc_error.line_time.map(|line_time| line_time.to_string()),
+            code: rpc_error.code,
+            message: rpc_error.message.clone(),
+            id: rpc_error.id.clone(),
+        }
+    }
+}
+
+/// Counts the inline mappings written to temporary files, so each job gets its own file.
+static INLINE_MAPPING_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Reads a job from stdin, runs it and writes the result to stdout. Returns the exit code.
+pub fn run_job() -> i32 {
+    let mut job_spec = String::new();
+    let result = match std::io::stdin().read_to_string(&mut job_spec) {
+        Ok(_) => run_job_spec(&job_spec),
+        Err(error) => failure_result(
+            EXIT_CODE_INPUT_ERROR,
+            &format!("Failed to read stdin: {}", error),
+        ),
+    };
+    match serde_json::to_string(&result) {
+        Ok(result_json) => println!("{}", result_json),
+        Err(error) => eprintln!("Error: failed to write the job result: {}", error),
+    }
+    result.exit_code
+}
+
+/// Runs the job described by the given JSON and returns its result.
+fn run_job_spec(job_spec: &str) -> JobResult {
+    let job_spec: JobSpec = match serde_json::from_str(job_spec) {
+        Ok(job_spec) => job_spec,
+        Err(error) => {
+            return failure_result(
+                EXIT_CODE_INPUT_ERROR,
+                &format!("Failed to parse the job: {}", error),
+            )
+        }
+    };
+
+    let inline_mapping_file = match write_inline_mapping(&job_spec.mapping) {
+        Ok(inline_mapping_file) => inline_mapping_file,
+        Err(error) => return failure_result(EXIT_CODE_INPUT_ERROR, &error),
+    };
+    let result = match to_app_args(&job_spec, &inline_mapping_file) {
+        Ok(app_args) => to_result(crate::convert(&app_args)),
+        Err(error) => failure_result(EXIT_CODE_INPUT_ERROR, &error),
+    };
+    if let Some(inline_mapping_file) = inline_mapping_file {
+        let _ = fs::remove_file(inline_mapping_file);
+    }
+    result
+}
+
+/// Writes an inline mapping to a temporary file, since the mappings are loaded from files.
+fn write_inline_mapping(mapping: &JobMapping) -> Result<Option<PathBuf>, String> {
+    match &mapping.inline {
+        Some(inline_mapping) => {
+            let file_name = std::env::temp_dir().join(format!(
+                "tlcfi_assimilator_job_{}_{}_mapping.txt",
+                std::process::id(),
+                INLINE_MAPPING_COUNT.fetch_add(1, Ordering::SeqCst)
+            ));
+            fs::write(&file_name, inline_mapping)
+                .map_err(|error| format!("Failed to store the inline mapping: {}", error))?;
+            Ok(Some(file_name))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Builds [AppArgs](../struct.AppArgs.html) from a job, validating values with the same functions as the command line.
+fn to_app_args(
+    job_spec: &JobSpec,
+    inline_mapping_file: &Option<PathBuf>,
+) -> Result<AppArgs, String> {
+    let options = &job_spec.options;
+    let outputs = &job_spec.outputs;
+    let mapping_from_log = options.mapping_from_log;
+    let vlog_tlcfi_mapping_file = match (inline_mapping_file, &job_spec.mapping.path) {
+        (Some(_), Some(_)) => {
+            return Err(
+                "The mapping of the job has both a path and inline contents, give only one"
+                    .to_string(),
+            )
+        }
+        (Some(inline_mapping_file), None) => inline_mapping_file.to_string_lossy().to_string(),
+        (None, Some(path)) => crate::check_file_existence(path)?,
+        // The log has the mapping itself
+        (None, None) if mapping_from_log => String::new(),
+        (None, None) => {
+            return Err("The job needs a mapping with either a path or inline contents".to_string())
+        }
+    };
+    let tlcfi_log_file = match &job_spec.inputs.tlcfi_log_file {
+        Some(path) => crate::check_file_existence(path)?,
+        None => return Err("The job needs inputs.tlcfi_log_file".to_string()),
+    };
+
+    if options.output_utc && options.timezone.is_none() {
+        return Err("Option 'output-utc' in the job needs 'timezone'".to_string());
+    }
+    let filter_detectors = value_option(
+        &options.filter_detectors,
+        "filter-detectors",
+        change_filtering::parse_name_patterns,
+    )?;
+    let exclude_detectors = value_option(
+        &options.exclude_detectors,
+        "exclude-detectors",
+        change_filtering::parse_name_patterns,
+    )?;
+    let time_zone = value_option(&options.timezone, "timezone", |arg| arg.parse())?;
+    let defaults = AppArgs::default();
+
+    Ok(AppArgs {
+        dry_run: options.dry_run,
+        renumber_ids: options.renumber_ids,
+        mapping_from_log,
+        max_id_gap: value_option(&options.max_id_gap, "max-id-gap", |arg| {
+            arg.parse::<u16>().map_err(|error| error.to_string())
+        })?
+        .unwrap_or(defaults.max_id_gap),
+        reset_snapshot: options.reset_snapshot,
+        name_date: value_option(&options.name_date, "name-date", |arg| arg.parse())?
+            .unwrap_or(defaults.name_date),
+        is_chronological: options.chronological,
+        conversion_mode: match (options.signals_only, options.detectors_only) {
+            (true, true) => {
+                return Err("signals-only and detectors-only can't be used together".to_string())
+            }
+            (true, false) => ConversionMode::SignalsOnly,
+            (false, true) => ConversionMode::DetectorsOnly,
+            (false, false) => ConversionMode::All,
+        },
+        direction: value_option(&options.direction, "direction", |arg| arg.parse())?
+            .unwrap_or_default(),
+        invert_direction: options.invert_direction,
+        time_source: value_option(&options.time_source, "time-source", |arg| arg.parse())?
+            .unwrap_or_default(),
+        repair_clock_steps: options.repair_clock_steps,
+        strict: options.strict,
+        strict_rpc: options.strict_rpc,
+        max_errors: value_option(&options.max_errors, "max-errors", |arg| {
+            arg.parse::<usize>().map_err(|error| error.to_string())
+        })?,
+        reject_file: outputs.reject_file.clone(),
+        predictions_csv: outputs.predictions_csv.clone(),
+        overwrite: options.overwrite,
+        tlc_name: value_option(&options.tlc_name, "tlc-name", crate::parse_tlc_name)?,
+        signal_state_encoding: if options.detailed_signal_states {
+            SignalStateEncoding::Detailed
+        } else {
+            SignalStateEncoding::Collapsed
+        },
+        amber_policy: value_option(&options.amber_policy, "amber-policy", |arg| arg.parse())?
+            .unwrap_or_default(),
+        dedupe: options.dedupe,
+        skip_until_first_signal: options.skip_until_first_signal,
+        min_mapped_fraction: value_option(
+            &options.min_mapped_fraction,
+            "min-mapped-fraction",
+            mapping_check::parse_fraction,
+        )?
+        .unwrap_or(defaults.min_mapped_fraction),
+        duration_in_s: value_option(&options.duration, "duration", crate::parse_duration)?,
+        limit: value_option(&options.limit, "limit", crate::parse_limit)?,
+        first_tick: value_option(&options.first_tick, "first-tick", crate::parse_first_tick)?,
+        tick_unit: value_option(&options.tick_unit, "tick-unit", |arg| arg.parse())?
+            .unwrap_or_default(),
+        log_levels: value_option(&options.log_levels, "log-levels", crate::parse_log_levels)?,
+        logger_name: value_option(&options.logger_name, "logger-name", |logger_name| {
+            Ok(logger_name.to_string())
+        })?,
+        log_pattern: value_option(&options.log_pattern, "log-pattern", |arg| arg.parse())?
+            .unwrap_or_default(),
+        update_methods: value_option(
+            &options.update_methods,
+            "update-methods",
+            crate::parse_update_methods,
+        )?
+        .unwrap_or_default(),
+        start_tolerance_s: value_option(
+            &options.start_tolerance,
+            "start-tolerance",
+            crate::parse_duration,
+        )?
+        .unwrap_or(defaults.start_tolerance_s),
+        signal_filter: value_option(
+            &options.filter_signals,
+            "filter-signals",
+            change_filtering::parse_name_patterns,
+        )?
+        .map(NameFilter::Include),
+        detector_filter: match (filter_detectors, exclude_detectors) {
+            (Some(_), Some(_)) => {
+                return Err(
+                    "filter-detectors and exclude-detectors can't be used together".to_string(),
+                )
+            }
+            (Some(names), None) => Some(NameFilter::Include(names)),
+            (None, Some(names)) => Some(NameFilter::Exclude(names)),
+            (None, None) => None,
+        },
+        start_date_time: value_option(&options.start_date_time, "start-date-time", |arg| {
+            Ok(arg.to_string())
+        })?
+        .map(|start_date_time| crate::parse_start_date_time(&start_date_time, time_zone))
+        .transpose()?,
+        time_zone,
+        output_utc: options.output_utc,
+        cache_dir: value_option(&options.cache_dir, "cache-dir", |cache_dir| {
+            Ok(cache_dir.to_string())
+        })?,
+        output_dir: outputs.output_dir.clone(),
+        output_template: outputs
+            .output_template
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map_err(|error| format!("Output 'output_template' in the job is invalid: {}", error))?
+            .unwrap_or_default(),
+        split: value_option(&options.split, "split", |arg| arg.parse())?.unwrap_or_default(),
+        line_ending: value_option(&options.line_ending, "line-ending", |arg| arg.parse())?
+            .unwrap_or_default(),
+        max_messages: value_option(
+            &options.max_messages,
+            "max-messages",
+            output_splitting::parse_max_messages,
+        )?,
+        max_output_size: value_option(
+            &options.max_output_size,
+            "max-output-size",
+            output_size::parse_size,
+        )?,
+        size_policy: value_option(&options.size_policy, "size-policy", |arg| arg.parse())?
+            .unwrap_or_default(),
+        tlcfi_log_file,
+        // Nothing but the JSON result may be written to stdout
+        verbosity: Verbosity::Quiet,
+        vlog_tlcfi_mapping_file,
+        ..defaults
+    })
+}
+
+/// Reads an option as text, so numbers like `"duration": 3600` can be validated by the command line parsers.
+fn value_option<T>(
+    value: &Option<Value>,
+    key: &str,
+    parse: fn(&str) -> Result<T, String>,
+) -> Result<Option<T>, String> {
+    let value = match value {
+        None => return Ok(None),
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Number(number)) => number.to_string(),
+        Some(_) => {
+            return Err(format!(
+                "Option '{}' in the job should be a string or a number",
+                key
+            ))
+        }
+    };
+    parse(&value)
+        .map(Some)
+        .map_err(|error| format!("Option '{}' in the job is invalid: {}", key, error))
+}
+
+fn to_result(conversion: Result<ConversionReport, ConversionFailure>) -> JobResult {
+    match conversion {
+        Ok(report) => {
+            let exit_code = if report.problems.is_empty() {
+                0
+            } else {
+                EXIT_CODE_INPUT_ERROR
+            };
+            JobResult {
+                success: exit_code == 0,
+                exit_code,
+                error: None,
+                output_file: report.file_names.first().cloned(),
+                output_files: report.file_names,
+                changes: report.change_count,
+                vlog_messages: report.vlog_message_count,
+                problems: report.problems,
+                warnings: report.warnings,
+                stats: Some(report.stats.into()),
+            }
+        }
+        Err(failure) => failure_result(failure.exit_code, &failure.message),
+    }
+}
+
+fn failure_result(exit_code: i32, error: &str) -> JobResult {
+    JobResult {
+        exit_code,
+        error: Some(error.to_string()),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn a_job_with_a_mapping_path_should_convert_the_log() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_job");
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(&test_dir).unwrap();
+        let job_spec = serde_json::json!({
+            "inputs": { "tlcfi_log_file": "./tlcfi.txt" },
+            "mapping": { "path": "./vlog_tlcfi_mapping.txt" },
+            "options": {
+                "start-date-time": "2021-12-15T11:00:03.000",
+                "signals-only": true,
+                "overwrite": true,
+            },
+            "outputs": { "output_dir": test_dir.to_string_lossy() },
+        });
+
+        let result = run_job_spec(&job_spec.to_string());
+        std::fs::remove_dir_all(&test_dir).unwrap();
+
+        assert!(result.success);
+        assert_eq!(
+            result.output_file,
+            Some(
+                test_dir
+                    .join("3031_20211215_110003.vlg")
+                    .to_string_lossy()
+                    .to_string()
+            )
+        );
+        assert!(result.vlog_messages > 2);
+        let result_json = serde_json::to_value(&result).unwrap();
+        assert_eq!(result_json["error"], Value::Null);
+        assert!(
+            result_json["stats"]["signal_change_messages"]
+                .as_u64()
+                .unwrap()
+                > 0
+        );
+    }
+
+    #[test]
+    fn a_dry_run_job_with_an_inline_mapping_should_report_missing_names() {
+        let job_spec = r#"{
+            "inputs": { "tlcfi_log_file": "./tlcfi.txt" },
+            "mapping": { "inline": "// TLC\n3031\n\n// Signals\n0, 02\n" },
+            "options": { "dry-run": true, "signals-only": true, "duration": 60 }
+        }"#;
+
+        let result = run_job_spec(job_spec);
+
+        assert!(!result.success);
+        assert_eq!(result.exit_code, EXIT_CODE_INPUT_ERROR);
+        assert!(result.problems[0].starts_with("signals missing from the mapping file"));
+        assert_eq!(result.output_file, None);
+    }
+
+    #[test]
+    fn a_job_should_have_either_a_mapping_path_or_an_inline_mapping() {
+        let job_spec_with = |mapping: &str| {
+            format!(
+                r#"{{ "inputs": {{ "tlcfi_log_file": "./tlcfi.txt" }}, "mapping": {} }}"#,
+                mapping
+            )
+        };
+
+        let with_both = run_job_spec(&job_spec_with(
+            r#"{ "path": "./vlog_tlcfi_mapping.txt", "inline": "// TLC\n3031\n" }"#,
+        ));
+        let with_neither = run_job_spec(&job_spec_with("{}"));
+
+        assert_eq!(with_both.exit_code, EXIT_CODE_INPUT_ERROR);
+        assert_eq!(
+            with_both.error.unwrap(),
+            "The mapping of the job has both a path and inline contents, give only one"
+        );
+        assert_eq!(with_neither.exit_code, EXIT_CODE_INPUT_ERROR);
+        assert_eq!(
+            with_neither.error.unwrap(),
+            "The job needs a mapping with either a path or inline contents"
+        );
+    }
+
+    #[test]
+    fn a_job_with_an_invalid_option_should_fail_with_the_option_name() {
+        let job_spec = r#"{
+            "inputs": { "tlcfi_log_file": "./tlcfi.txt" },
+            "mapping": { "path": "./vlog_tlcfi_mapping.txt" },
+            "options": { "duration": "1 day" }
+        }"#;
+
+        let result = run_job_spec(job_spec);
+
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("'duration'"));
+    }
+
+    #[test]
+    fn a_job_with_an_unknown_option_should_fail() {
+        let job_spec = r#"{
+            "inputs": { "tlcfi_log_file": "./tlcfi.txt" },
+            "mapping": { "path": "./vlog_tlcfi_mapping.txt" },
+            "options": { "colour": "blue" }
+        }"#;
+
+        let result = run_job_spec(job_spec);
+
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("unknown field `colour`"));
+    }
+
+    #[test]
+    fn invalid_json_should_fail() {
+        assert_eq!(run_job_spec("{ not json").exit_code, EXIT_CODE_INPUT_ERROR);
+    }
+}
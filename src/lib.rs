@@ -2,10 +2,27 @@
 //!
 //! `tlcfi_assimilator` is a program that makes sense of tlcfi data generated by SmartTraffic logs.
 
+pub mod assimilate;
+pub mod conversion_summary;
+pub mod log_pattern;
+pub mod name_normalization;
+pub mod time_zone;
+pub mod tlcfi_parsing;
+pub mod vlog_transformer;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
 use chrono::{NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use log_pattern::LogPattern;
 
 /// A set of changes with a time delta to the first decoded message in milliseconds.
-/// It will have either signal names and states, or detector names and states.
+/// It will have either signal names and states, detector names and states, output names and states, or intersection
+/// names and states.
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct TimestampedChanges {
     pub ms_from_beginning: u64,
@@ -13,107 +30,1237 @@ pub struct TimestampedChanges {
     pub signal_states: Vec<SignalState>,
     pub detector_names: Vec<String>,
     pub detector_states: Vec<DetectorState>,
+    /// The states of the outputs of the TLC, which are only written when the mapping file maps them
+    pub output_names: Vec<String>,
+    pub output_states: Vec<OutputState>,
+    /// The control states of intersections, which VLog has no message for yet
+    pub intersection_names: Vec<String>,
+    pub intersection_states: Vec<IntersectionState>,
+}
+
+impl TimestampedChanges {
+    /// Whether there are no changes of any object left.
+    pub fn is_empty(&self) -> bool {
+        self.signal_names.is_empty()
+            && self.detector_names.is_empty()
+            && self.output_names.is_empty()
+            && self.intersection_names.is_empty()
+    }
+}
+
+/// Which kinds of TLC-FI objects are converted.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize)]
+pub enum ConversionMode {
+    #[default]
+    All,
+    SignalsOnly,
+    DetectorsOnly,
+}
+
+impl ConversionMode {
+    pub fn converts_signals(&self) -> bool {
+        *self != Self::DetectorsOnly
+    }
+
+    pub fn converts_detectors(&self) -> bool {
+        *self != Self::SignalsOnly
+    }
+
+    /// Outputs are neither signals nor detectors, so only a conversion of all objects includes them.
+    pub fn converts_outputs(&self) -> bool {
+        *self == Self::All
+    }
+}
+
+/// Which direction of TLC-FI messages feeds the converter.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize)]
+pub enum MessageDirection {
+    /// The messages from the TLC
+    #[default]
+    In,
+    /// The messages to the TLC, like the state updates SmartTraffic sends during shadow-control trials
+    Out,
+    /// The messages of both directions, merged by their tick
+    Both,
+}
+
+impl MessageDirection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MessageDirection::In => "in",
+            MessageDirection::Out => "out",
+            MessageDirection::Both => "both",
+        }
+    }
+
+    /// Whether a line with this direction token (`IN` or `OUT`) is converted.
+    /// With `invert_direction` the tokens are from the TLC's perspective, so they swap meaning.
+    pub fn accepts(&self, direction: &str, invert_direction: bool) -> bool {
+        match self {
+            MessageDirection::In => tlcfi_parsing::is_from_tlc(direction, invert_direction),
+            MessageDirection::Out => tlcfi_parsing::is_from_tlc(direction, !invert_direction),
+            MessageDirection::Both => direction.contains("IN") || direction.contains("OUT"),
+        }
+    }
+}
+
+impl FromStr for MessageDirection {
+    type Err = String;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        match arg {
+            "in" => Ok(MessageDirection::In),
+            "out" => Ok(MessageDirection::Out),
+            "both" => Ok(MessageDirection::Both),
+            _ => Err(format!("'{}' isn't a direction, use in, out or both", arg)),
+        }
+    }
+}
+
+/// Where the time of a TLC-FI message is taken from.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize)]
+pub enum TimeSource {
+    /// The ticks of the messages, counting the milliseconds (or another [TickUnit](enum.TickUnit.html)) since the TLC started
+    #[default]
+    Ticks,
+    /// The times at the start of the log lines, for logs whose ticks jump when the TLC restarts
+    Log,
+}
+
+impl TimeSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TimeSource::Ticks => "ticks",
+            TimeSource::Log => "log",
+        }
+    }
+}
+
+impl FromStr for TimeSource {
+    type Err = String;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        match arg {
+            "ticks" => Ok(TimeSource::Ticks),
+            "log" => Ok(TimeSource::Log),
+            _ => Err(format!("'{}' isn't a time source, use ticks or log", arg)),
+        }
+    }
+}
+
+/// How long a TLC-FI tick is. The ticks of most TLCs count milliseconds, some count smaller units.
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize)]
+pub enum TickUnit {
+    #[default]
+    Milliseconds,
+    /// Ticks of 0.1 ms
+    HundredMicroseconds,
+    /// This many ticks per millisecond
+    Custom(f64),
+}
+
+impl TickUnit {
+    pub fn ticks_per_ms(&self) -> f64 {
+        match self {
+            TickUnit::Milliseconds => 1.0,
+            TickUnit::HundredMicroseconds => 10.0,
+            TickUnit::Custom(ticks_per_ms) => *ticks_per_ms,
+        }
+    }
+
+    /// The milliseconds of a number of ticks, rounded to whole milliseconds.
+    pub fn to_ms(&self, ticks: u64) -> u64 {
+        match self {
+            TickUnit::Milliseconds => ticks,
+            _ => (ticks as f64 / self.ticks_per_ms()).round() as u64,
+        }
+    }
+}
+
+impl FromStr for TickUnit {
+    type Err = String;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        match arg {
+            "ms" => Ok(TickUnit::Milliseconds),
+            "100us" => Ok(TickUnit::HundredMicroseconds),
+            _ => match arg.strip_prefix("custom:").map(str::parse::<f64>) {
+                Some(Ok(ticks_per_ms)) if ticks_per_ms.is_finite() && ticks_per_ms > 0.0 => {
+                    Ok(TickUnit::Custom(ticks_per_ms))
+                }
+                _ => Err(format!(
+                    "'{}' isn't a tick unit, use ms, 100us or custom: with the number of ticks per millisecond (e.g. custom:10)",
+                    arg
+                )),
+            },
+        }
+    }
+}
+
+/// How much is printed to the console.
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize)]
+pub enum Verbosity {
+    /// Only errors
+    Quiet,
+    /// Informational messages and warnings, with warnings that occur for many lines summarised
+    #[default]
+    Normal,
+    /// Everything, including per line diagnostics
+    Verbose,
+}
+
+/// A category of warnings, with how often it occurred and the details of the first occurrence.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct CollectedWarning {
+    pub count: usize,
+    pub sample: String,
+}
+
+/// Counts warnings that can occur for many lines or changes per category, keeping the first occurrence as a sample,
+/// so every category can be reported once instead of once per line.
+///
+/// A category describes the warning with `{}` where the amount goes, like `skipped {} lines that did not split on '- '`.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct WarningCollector {
+    categories: BTreeMap<String, CollectedWarning>,
+}
+
+impl WarningCollector {
+    /// Counts an occurrence of the category. The details are kept when it's the first one.
+    pub fn add(&mut self, category: &str, details: &str) {
+        self.categories
+            .entry(category.to_string())
+            .or_insert_with(|| CollectedWarning {
+                count: 0,
+                sample: details.to_string(),
+            })
+            .count += 1;
+    }
+
+    /// Adds the occurrences of another collector, keeping the samples of the categories that are already here.
+    pub fn merge(&mut self, other: &WarningCollector) {
+        for (category, warning) in &other.categories {
+            self.categories
+                .entry(category.clone())
+                .or_insert_with(|| CollectedWarning {
+                    count: 0,
+                    sample: warning.sample.clone(),
+                })
+                .count += warning.count;
+        }
+    }
+
+    pub fn get(&self, category: &str) -> Option<&CollectedWarning> {
+        self.categories.get(category)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.categories.is_empty()
+    }
+
+    /// Returns one line per category with the amount filled in, ordered by category.
+    pub fn summary_lines(&self) -> Vec<String> {
+        self.categories
+            .iter()
+            .map(|(category, warning)| category.replace("{}", &warning.count.to_string()))
+            .collect()
+    }
+
+    /// Returns the summary line and sample of every category, ordered by category.
+    pub fn summary_lines_with_samples(&self) -> Vec<(String, &str)> {
+        self.summary_lines()
+            .into_iter()
+            .zip(
+                self.categories
+                    .values()
+                    .map(|warning| warning.sample.as_str()),
+            )
+            .collect()
+    }
+}
+
+/// Prints to the console according to a [Verbosity](enum.Verbosity.html).
+/// Warnings that can occur for many lines are collected per category so they can be summarised instead of flooding the terminal.
+#[derive(Debug, Default)]
+pub struct Console {
+    pub verbosity: Verbosity,
+    warnings: Vec<String>,
+    repeated_warnings: WarningCollector,
+}
+
+impl Console {
+    pub fn new(verbosity: Verbosity) -> Self {
+        Self {
+            verbosity,
+            ..Default::default()
+        }
+    }
+
+    /// Prints an informational message to stderr, unless quiet, so stdout is left for output like VLog messages.
+    pub fn info(&self, message: &str) {
+        if self.verbosity >= Verbosity::Normal {
+            eprintln!("{}", message);
+        }
+    }
+
+    /// Prints a diagnostic message to stderr, only when verbose.
+    pub fn verbose(&self, message: &str) {
+        if self.verbosity >= Verbosity::Verbose {
+            eprintln!("{}", message);
+        }
+    }
+
+    /// Prints a warning, unless quiet. The warning is kept for [all_warnings](#method.all_warnings).
+    pub fn warning(&mut self, message: &str) {
+        self.warnings.push(message.to_string());
+        if self.verbosity >= Verbosity::Normal {
+            eprintln!("{}", message);
+        }
+    }
+
+    /// Prints a report to stderr, unless quiet, so it doesn't mix with regular output.
+    pub fn report(&self, message: &str) {
+        if self.verbosity >= Verbosity::Normal {
+            eprintln!("{}", message);
+        }
+    }
+
+    /// Prints an error, regardless of verbosity.
+    pub fn error(&self, message: &str) {
+        eprintln!("{}", message);
+    }
+
+    /// Counts a warning that can occur for many lines, like `skipped {} lines that did not split on '- '`.
+    /// The details are only printed when verbose, otherwise the category shows up in the summary.
+    ///
+    /// # Arguments
+    ///
+    /// * `category` - A description of the warning with `{}` where the amount goes
+    /// * `details` - The specifics of this occurrence, e.g. the line itself
+    pub fn repeated_warning(&mut self, category: &str, details: &str) {
+        self.repeated_warnings.add(category, details);
+        if self.verbosity >= Verbosity::Verbose {
+            eprintln!("{}: {}", category.replace("{}", "1"), details);
+        }
+    }
+
+    /// Adds repeated warnings that were collected elsewhere, like by the
+    /// [VLogTransformer](vlog_transformer/struct.VLogTransformer.html), to the summary.
+    pub fn add_repeated_warnings(&mut self, warnings: &WarningCollector) {
+        self.repeated_warnings.merge(warnings);
+    }
+
+    /// Returns one line per category of repeated warnings, with the amount filled in.
+    pub fn summary_lines(&self) -> Vec<String> {
+        self.repeated_warnings.summary_lines()
+    }
+
+    /// Prints the summary of repeated warnings with the first occurrence of each, unless quiet.
+    pub fn print_summary(&self) {
+        if self.verbosity >= Verbosity::Normal {
+            for (line, sample) in self.repeated_warnings.summary_lines_with_samples() {
+                eprintln!("Warning: {}", line);
+                eprintln!("  first: {}", sample);
+            }
+        }
+    }
+
+    /// Returns every warning given so far, followed by the summary of repeated warnings.
+    pub fn all_warnings(&self) -> Vec<String> {
+        let mut all_warnings = self.warnings.clone();
+        all_warnings.extend(
+            self.summary_lines()
+                .iter()
+                .map(|line| format!("Warning: {}", line)),
+        );
+        all_warnings
+    }
+}
+
+/// A controller reset, detected by the tick jumping back without overflowing.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct ControllerReset {
+    /// The ms from beginning at which the reset happened
+    pub ms_from_beginning: u64,
+    /// The last tick before the reset
+    pub previous_tick: u64,
+    /// The first tick after the reset
+    pub tick: u64,
+    /// How many changes were read before the reset, which places it among changes of the same moment
+    pub changes_before: usize,
+}
+
+impl ControllerReset {
+    /// How many ticks the controller jumped back.
+    pub fn tick_discontinuity(&self) -> u64 {
+        self.previous_tick - self.tick
+    }
+}
+
+/// A step of the logger's wall clock, e.g. by NTP, found by the line times moving differently from the ticks.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct ClockStep {
+    /// The index in the sorted lines of the first line after the step
+    pub line_index: usize,
+    /// The line time of the last line before the step
+    pub before: NaiveDateTime,
+    /// The line time of the first line after the step
+    pub after: NaiveDateTime,
+    /// The line time of the last line with ticks before the next step or the end of the log
+    pub until: NaiveDateTime,
+    /// How far the line times moved compared to the ticks, negative when the clock was set back
+    pub step_ms: i64,
+    /// Whether the line times from the step on were corrected
+    pub repaired: bool,
+}
+
+/// A signal group or detector that the TLC-FI removed from the session, after which its states are meaningless.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct RetiredObject {
+    /// The ms from beginning at which the object was removed
+    pub ms_from_beginning: u64,
+    pub name: String,
+    pub is_detector: bool,
+}
+
+impl RetiredObject {
+    /// Describes the object for warnings and reports, e.g. `detector 'D611'`.
+    pub fn describe(&self) -> String {
+        let object_type = if self.is_detector {
+            "detector"
+        } else {
+            "signal"
+        };
+        format!("{} '{}'", object_type, self.name)
+    }
+}
+
+/// An announcement of a special vehicle, like a public transport or an emergency vehicle (KAR/SRM), which the TLC-FI
+/// sends as the state of a special vehicle event generator, object type 7.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct SpecialVehicleEvent {
+    /// The ms from beginning of the message with the event
+    pub ms_from_beginning: u64,
+    /// The id of the event generator
+    pub name: String,
+    /// The fields of the event as they are in the message, like `vehicleType`, `lineNr` and `direction`.
+    /// Every field is optional, so only the ones the message has are here.
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl SpecialVehicleEvent {
+    /// Describes the event for reports, e.g. `special vehicle event 'KAR1' (lineNr=12, vehicleType=1)`.
+    pub fn describe(&self) -> String {
+        let attributes: Vec<String> = self
+            .attributes
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        if attributes.is_empty() {
+            format!("special vehicle event '{}'", self.name)
+        } else {
+            format!(
+                "special vehicle event '{}' ({})",
+                self.name,
+                attributes.join(", ")
+            )
+        }
+    }
+}
+
+/// A prediction of the TLC of when a future state of a signal group ends, from the `predictions` of a signal state.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SignalPrediction {
+    pub signal_name: String,
+    /// The ms from beginning of the message with the prediction
+    pub ms_from_beginning: u64,
+    pub predicted_state: SignalState,
+    /// The predicted ends of the state in ms from beginning, None when the prediction doesn't have them
+    pub likely_end_ms: Option<u64>,
+    pub min_end_ms: Option<u64>,
+    pub max_end_ms: Option<u64>,
+}
+
+/// The metadata of a TLC-FI session, from the `Register` request that starts it and the response of the TLC to it.
+/// A log with reconnects has a session for every registration. The fields the messages don't have are None.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct SessionInfo {
+    /// The first tick after the registration, None when no message with a tick followed it
+    pub tick: Option<u64>,
+    pub username: Option<String>,
+    /// The application type the client registered as
+    pub application_type: Option<u64>,
+    /// The TLC-FI version, like `1.1.0`
+    pub protocol_version: Option<String>,
+    /// The URI the client registered with
+    pub uri: Option<String>,
+    /// The id the TLC gave the session in its response
+    pub session_id: Option<String>,
+}
+
+impl SessionInfo {
+    /// Describes the session for reports, e.g. `session 'abc' of 'tlcfi' (TLC-FI 1.1.0) from tick 1000`.
+    pub fn describe(&self) -> String {
+        let mut description = match &self.session_id {
+            Some(session_id) => format!("session '{}'", session_id),
+            None => "session".to_string(),
+        };
+        if let Some(username) = &self.username {
+            description.push_str(&format!(" of '{}'", username));
+        }
+        if let Some(protocol_version) = &self.protocol_version {
+            description.push_str(&format!(" (TLC-FI {})", protocol_version));
+        }
+        match self.tick {
+            Some(tick) => description.push_str(&format!(" from tick {}", tick)),
+            None => description.push_str(" without ticks"),
+        }
+        description
+    }
+}
+
+/// A JSON-RPC error response, which the TLC sends when it rejects a request, e.g. of a session that is unhealthy.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct RpcError {
+    /// The time of the log line, None when it isn't known
+    pub line_time: Option<NaiveDateTime>,
+    pub code: Option<i64>,
+    pub message: Option<String>,
+    /// The id of the request that was rejected
+    pub id: Option<String>,
+}
+
+impl RpcError {
+    /// Describes the error for reports, e.g. `error -32601 'Method not found' for request 12 at 2021-12-15 12:00:00`.
+    pub fn describe(&self) -> String {
+        let mut description = match self.code {
+            Some(code) => format!("error {}", code),
+            None => "error".to_string(),
+        };
+        if let Some(message) = &self.message {
+            description.push_str(&format!(" '{}'", message));
+        }
+        if let Some(id) = &self.id {
+            description.push_str(&format!(" for request {}", id));
+        }
+        if let Some(line_time) = self.line_time {
+            description.push_str(&format!(" at {}", line_time));
+        }
+        description
+    }
+}
+
+/// The objects of a TLC with their indices, from the `ReadMeta` responses in a log. The indices are used as VLog ids
+/// by `--mapping-from-log`.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct TlcMeta {
+    /// The id of the intersection, None when the log has no meta of it
+    pub tlc_name: Option<String>,
+    /// The indices of the signal groups, by their normalized name
+    pub signals: HashMap<String, i16>,
+    pub detectors: HashMap<String, i16>,
+    pub outputs: HashMap<String, i16>,
+}
+
+impl TlcMeta {
+    /// Whether the log had no meta at all.
+    pub fn is_empty(&self) -> bool {
+        self.tlc_name.is_none()
+            && self.signals.is_empty()
+            && self.detectors.is_empty()
+            && self.outputs.is_empty()
+    }
+}
+
+/// What was wrong with a log line that was skipped because it couldn't be parsed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LineErrorKind {
+    /// The line doesn't split into three parts on `- `
+    Unsplittable,
+    /// The TLC-FI message isn't valid JSON
+    InvalidJson,
+    /// A signal group or detector state isn't a TLC-FI state
+    UnknownState,
+    /// A field of the TLC-FI message is missing or has the wrong type
+    MalformedMessage,
+}
+
+impl LineErrorKind {
+    /// The reason a line with this error is written to the reject file with.
+    pub fn reject_reason(&self) -> &'static str {
+        match self {
+            LineErrorKind::Unsplittable => "no-split",
+            LineErrorKind::InvalidJson => "json-error",
+            LineErrorKind::UnknownState => "unknown-state",
+            LineErrorKind::MalformedMessage => "malformed-message",
+        }
+    }
+}
+
+/// A log line that was skipped, for the reject file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RejectedLine {
+    /// The index of the line in the sorted lines, oldest first
+    pub line_index: usize,
+    /// Why the line was skipped, e.g. `no-split` or `no-first-tick`
+    pub reason: &'static str,
+    pub line: String,
+}
+
+/// Counts the log lines that couldn't be parsed by what was wrong with them, and keeps the first few.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct LineErrors {
+    pub unsplittable: usize,
+    pub invalid_json: usize,
+    pub unknown_states: usize,
+    pub malformed_messages: usize,
+    /// The first lines that couldn't be parsed, at most `MAX_FIRST_LINES`
+    pub first_lines: Vec<String>,
+}
+
+impl LineErrors {
+    /// How many of the lines that couldn't be parsed are kept to show.
+    pub const MAX_FIRST_LINES: usize = 5;
+
+    pub fn add(&mut self, kind: LineErrorKind, line: &str) {
+        match kind {
+            LineErrorKind::Unsplittable => self.unsplittable += 1,
+            LineErrorKind::InvalidJson => self.invalid_json += 1,
+            LineErrorKind::UnknownState => self.unknown_states += 1,
+            LineErrorKind::MalformedMessage => self.malformed_messages += 1,
+        }
+        if self.first_lines.len() < LineErrors::MAX_FIRST_LINES {
+            self.first_lines.push(line.to_string());
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.unsplittable + self.invalid_json + self.unknown_states + self.malformed_messages
+    }
+
+    /// The counts per kind, e.g. `2 unsplittable, 1 invalid JSON, 0 unknown state, 0 malformed message`.
+    pub fn describe(&self) -> String {
+        format!(
+            "{} unsplittable, {} invalid JSON, {} unknown state, {} malformed message",
+            self.unsplittable, self.invalid_json, self.unknown_states, self.malformed_messages
+        )
+    }
+}
+
+/// Counts what happened during a conversion, for the summary after a run.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ConversionStats {
+    pub lines_read: usize,
+    pub lines_skipped: usize,
+    /// The skipped lines that couldn't be parsed, as opposed to the ones before the first tick
+    pub line_errors: LineErrors,
+    /// Lines logged before the given start time, which aren't parsed
+    pub lines_before_start: usize,
+    /// Lines with a log level that isn't parsed
+    pub lines_of_other_levels: usize,
+    /// Lines without the logger name of the TLC-FI messages, which aren't parsed
+    pub lines_of_other_loggers: usize,
+    /// The skipped lines that were written to the reject file
+    pub rejected_lines: usize,
+    /// The predictions of signal states that were written to the predictions CSV
+    pub signal_predictions: usize,
+    /// Lines labelled `IN`, regardless of direction inversion
+    pub in_lines: usize,
+    /// Lines labelled `OUT`, regardless of direction inversion
+    pub out_lines: usize,
+    pub signal_change_messages: usize,
+    pub detector_change_messages: usize,
+    pub output_change_messages: usize,
+    pub time_references: usize,
+    /// The ms from beginning of the first change that resulted in VLog messages
+    pub first_written_change_ms: Option<u64>,
+    /// The ms from beginning of the last change that resulted in VLog messages
+    pub last_written_change_ms: Option<u64>,
+    pub tick_overflows: usize,
+    pub controller_resets: Vec<ControllerReset>,
+    /// Objects removed from the session, in the order they were removed
+    pub retired_objects: Vec<RetiredObject>,
+    /// Announcements of special vehicles, in the order of the log
+    pub special_vehicle_events: Vec<SpecialVehicleEvent>,
+    /// The sessions the log registers, in the order of the log
+    pub sessions: Vec<SessionInfo>,
+    /// The error responses of the TLC, in the order of the log
+    pub rpc_errors: Vec<RpcError>,
+    /// The tick of the first signal or detector update
+    pub first_tick: Option<u64>,
+    /// The tick of the last signal or detector update
+    pub last_tick: Option<u64>,
+    /// The size of the written VLog files as estimated before writing them, without compression
+    pub estimated_output_bytes: u64,
+    /// The size of the written VLog files, without compression
+    pub output_bytes: u64,
+    /// Messages from the TLC with a type this program doesn't convert
+    pub unsupported_messages: usize,
+    /// The unsupported messages that aren't state updates, per method
+    pub other_methods: BTreeMap<String, usize>,
+    /// Changes of the control state of an intersection, which aren't written to VLog
+    pub intersection_state_changes: usize,
+    /// Intersection states with a TLC-FI number this program doesn't know, which are skipped
+    pub unknown_intersection_states: usize,
+    /// Signal group entries of updates without a state, like the ones that only update predictions
+    pub null_signal_states: usize,
+    /// Detector entries of updates without a state
+    pub null_detector_states: usize,
+    /// Output entries of updates without a state
+    pub null_output_states: usize,
+    /// Output states that weren't written because the mapping file doesn't map their output
+    pub unmapped_output_states: usize,
+    /// Amber signal states written as green or red because of the amber policy
+    pub collapsed_amber_states: usize,
+    /// Signal states dropped because they repeat the last state of their signal group
+    pub suppressed_signal_states: usize,
+    /// Detector states dropped because they repeat the last state of their detector
+    pub suppressed_detector_states: usize,
+    /// Changes left out because the number of converted changes was limited
+    pub truncated_changes: usize,
+    /// Steps of the logger's wall clock, during which the line times are off
+    pub clock_steps: Vec<ClockStep>,
+    /// Warnings of the VLog transformer, which has no console, for the summary of the console
+    pub transform_warnings: WarningCollector,
+}
+
+impl ConversionStats {
+    /// Adds the counts of writing a part of the output, whose ms are relative to `offset_ms`.
+    pub fn add_written_part(&mut self, part: &ConversionStats, offset_ms: u64) {
+        self.signal_change_messages += part.signal_change_messages;
+        self.detector_change_messages += part.detector_change_messages;
+        self.output_change_messages += part.output_change_messages;
+        self.unmapped_output_states += part.unmapped_output_states;
+        self.time_references += part.time_references;
+        if let Some(first_ms) = part.first_written_change_ms {
+            self.first_written_change_ms
+                .get_or_insert(first_ms + offset_ms);
+        }
+        if let Some(last_ms) = part.last_written_change_ms {
+            self.last_written_change_ms = Some(last_ms + offset_ms);
+        }
+        self.transform_warnings.merge(&part.transform_warnings);
+    }
 }
 
 #[derive(Debug)]
 pub struct AssimilationData {
     pub start_time: NaiveDateTime,
+    pub conversion_mode: ConversionMode,
+    pub time_source: TimeSource,
+    pub tick_unit: TickUnit,
+    /// How the lines are split into their time, their direction and their TLC-FI message
+    pub log_pattern: LogPattern,
+    /// The log levels of the lines that are parsed, in upper case, all levels when None
+    pub log_levels: Option<Vec<String>>,
+    /// The token the part of a line before the direction must have to be parsed, any line when None
+    pub logger_name: Option<String>,
+    /// Methods of vendors that send their state updates like `UpdateState`, besides `UpdateState` and `NotifyEvent`
+    pub update_methods: Vec<String>,
+    pub console: Console,
     pub sorted_lines: Vec<String>,
     pub first_tick: Option<u64>,
+    /// The line time of the first message, which the times are relative to when the time source is the log
+    pub first_line_time: Option<NaiveDateTime>,
     pub previous_tick: Option<u64>,
     pub bonus_ms: Option<u64>,
     pub changes: Vec<TimestampedChanges>,
+    pub stats: ConversionStats,
+    /// The names from the log that normalizing changed, so each is only warned about once
+    pub normalized_names: BTreeSet<String>,
+    /// The skipped lines, only kept when this isn't None
+    pub rejected_lines: Option<Vec<RejectedLine>>,
+    /// The predictions of the signal states, only kept when this isn't None
+    pub predictions: Option<Vec<SignalPrediction>>,
+    /// The objects of the TLC from the meta in the log
+    pub meta: TlcMeta,
 }
 
 impl Default for AssimilationData {
     fn default() -> Self { 
         Self {
             start_time: NaiveDateTime::parse_from_str("2015-09-05 23:56:04", "%Y-%m-%d %H:%M:%S").unwrap(),
+            conversion_mode: ConversionMode::All,
+            time_source: TimeSource::Ticks,
+            tick_unit: TickUnit::Milliseconds,
+            log_pattern: LogPattern::default(),
+            log_levels: None,
+            logger_name: None,
+            update_methods: Vec::new(),
+            console: Console::default(),
             sorted_lines: Vec::new(),
             first_tick: Option::None,
+            first_line_time: None,
             previous_tick: Option::None,
             bonus_ms: Option::None, 
             changes: Vec::new(),
+            stats: ConversionStats::default(),
+            normalized_names: BTreeSet::new(),
+            rejected_lines: None,
+            predictions: None,
+            meta: TlcMeta::default(),
+        }
+    }
+}
+
+/// The VLog3 message types this crate can produce.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum VLogMessageType {
+    /// 1 - Tijd referentiebericht
+    TimeReference,
+    /// 4 - V-Log informatie
+    VLogInformation,
+    /// 6 - Detectie informatie
+    DetectionInformation,
+    /// 14 - Externe signaalgroep status
+    ExternalSignalGroupStatus,
+    /// 16 - Uitgang WUS informatie
+    OutputInformation,
+}
+
+impl VLogMessageType {
+    /// The number identifying this message type in VLog
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::TimeReference => 1,
+            Self::VLogInformation => 4,
+            Self::DetectionInformation => 6,
+            Self::ExternalSignalGroupStatus => 14,
+            Self::OutputInformation => 16,
         }
     }
 }
 
+const SUPPORTED_VLOG_MESSAGES: [VLogMessageType; 5] = [
+    VLogMessageType::TimeReference,
+    VLogMessageType::VLogInformation,
+    VLogMessageType::DetectionInformation,
+    VLogMessageType::ExternalSignalGroupStatus,
+    VLogMessageType::OutputInformation,
+];
+
+/// Returns every [VLogMessageType](enum.VLogMessageType.html) this version of the crate can emit.
+pub fn supported_vlog_messages() -> &'static [VLogMessageType] {
+    &SUPPORTED_VLOG_MESSAGES
+}
+
+/// Optional capabilities that downstream tooling can query instead of parsing documentation.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Capabilities {
+    /// Whether VLog status snapshots (full state messages) are emitted
+    pub status_snapshots: bool,
+    /// Whether VLog can be written in its binary form
+    pub binary_output: bool,
+    /// Whether priority (public transport, special vehicle) messages are emitted
+    pub prio_messages: bool,
+    /// Whether TLC-FI requests are interpreted
+    pub requests: bool,
+}
+
+/// Returns the [Capabilities](struct.Capabilities.html) of this version of the crate.
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        status_snapshots: false,
+        binary_output: false,
+        prio_messages: false,
+        requests: false,
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum SignalState {
     Unavailable,
     Dark,
+    /// Stop and remain, TLC-FI state 3
     Red,
+    /// Stop then proceed, TLC-FI state 2
+    RedStopThenProceed,
+    /// Pre-movement, TLC-FI state 4, the red-amber some countries show before green
+    PreMovement,
+    /// Permissive or protected clearance, TLC-FI state 7 or 8
     Amber,
+    /// Protected movement allowed, TLC-FI state 6
     Green,
+    /// Permissive movement allowed, TLC-FI state 5
+    GreenPermissive,
+    /// Caution, conflicting traffic, TLC-FI state 9, which is the flashing amber of a night flash
     AmberFlashing,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// How [SignalStates](enum.SignalState.html) are encoded in VLog.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize)]
+pub enum SignalStateEncoding {
+    /// Only the standard VLog external signal group states, so both reds and both greens are collapsed
+    #[default]
+    Collapsed,
+    /// Also encodes a stop then proceed red, a pre-movement red-amber and a permissive green with their own values,
+    /// for consumers that know these values beyond the standard external signal group states
+    Detailed,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum DetectorState {
     FREE,
     OCCUPIED,
+    /// A defective detector, TLC-FI state 2, which is written as not available in VLog
+    FAULTY,
 }
 
-impl From<u64> for SignalState {
+/// The state of an output of the TLC, TLC-FI object type 5.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OutputState {
+    Off,
+    On,
+}
+
+impl OutputState {
+    /// Returns the [OutputState](enum.OutputState.html) corresponding to the given TLC-FI output state, None for unknown states
+    pub fn from_tlc_fi_state(tlc_fi_state: u64) -> Option<Self> {
+        match tlc_fi_state {
+            0 => Some(OutputState::Off),
+            1 => Some(OutputState::On),
+            _ => None,
+        }
+    }
+
+    /// Transforms an [OutputState](enum.OutputState.html) to the value corresponding to that state in VLog
+    pub fn to_vlog_state(&self) -> i16 {
+        match self {
+            Self::Off => 0,
+            Self::On => 1,
+        }
+    }
+
+    /// Returns the [OutputState](enum.OutputState.html) that is written as the given VLog value, None for unknown values
+    pub fn from_vlog_state(vlog_state: i16) -> Option<Self> {
+        match vlog_state {
+            0 => Some(Self::Off),
+            1 => Some(Self::On),
+            _ => None,
+        }
+    }
+}
+
+/// The control state of an intersection, TLC-FI object type 2.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IntersectionState {
+    Error,
+    /// All signal groups dark, TLC-FI state 1
+    Dark,
+    /// Flashing amber, TLC-FI state 2
+    Standby,
+    AllRed,
+    SwitchingOn,
+    SwitchingOff,
+    /// Control by the signal plan, TLC-FI state 6
+    Control,
+}
+
+impl IntersectionState {
+    /// Returns the [IntersectionState](enum.IntersectionState.html) corresponding to the given TLC-FI intersection state, None for unknown states
+    pub fn from_tlc_fi_state(tlc_fi_state: u64) -> Option<Self> {
+        match tlc_fi_state {
+            0 => Some(IntersectionState::Error),
+            1 => Some(IntersectionState::Dark),
+            2 => Some(IntersectionState::Standby),
+            3 => Some(IntersectionState::AllRed),
+            4 => Some(IntersectionState::SwitchingOn),
+            5 => Some(IntersectionState::SwitchingOff),
+            6 => Some(IntersectionState::Control),
+            _ => None,
+        }
+    }
+}
+
+/// The state table of the signal groups: every [SignalState](enum.SignalState.html) with the TLC-FI values it's read
+/// from, its VLog value and its VLog value in the [detailed](enum.SignalStateEncoding.html) encoding.
+///
+/// | SignalState        | TLC-FI                                       | VLog | VLog detailed |
+/// |--------------------|----------------------------------------------|------|---------------|
+/// | Unavailable        | 0, unavailable                               | 4    | 4             |
+/// | Dark               | 1, dark                                      | 4    | 4             |
+/// | RedStopThenProceed | 2, stop then proceed (flashing red)          | 0    | 6             |
+/// | Red                | 3, stop and remain                           | 0    | 0             |
+/// | PreMovement        | 4, pre-movement (red-amber)                  | 0    | 8             |
+/// | GreenPermissive    | 5, permissive movement allowed               | 1    | 7             |
+/// | Green              | 6, protected movement allowed                | 1    | 1             |
+/// | Amber              | 7 and 8, permissive and protected clearance  | 2    | 2             |
+/// | AmberFlashing      | 9, caution conflicting traffic (night flash) | 5    | 5             |
+///
+/// Pre-movement still means stop, so it's written as red unless the encoding is detailed.
+/// Reading a VLog value takes the first state that is written as it, so dark reads as unavailable.
+const SIGNAL_STATE_TABLE: [(SignalState, &[u64], i16, i16); 9] = [
+    (SignalState::Unavailable, &[0], 4, 4),
+    (SignalState::Dark, &[1], 4, 4),
+    (SignalState::RedStopThenProceed, &[2], 0, 6),
+    (SignalState::Red, &[3], 0, 0),
+    (SignalState::PreMovement, &[4], 0, 8),
+    (SignalState::GreenPermissive, &[5], 1, 7),
+    (SignalState::Green, &[6], 1, 1),
+    (SignalState::Amber, &[7, 8], 2, 2),
+    (SignalState::AmberFlashing, &[9], 5, 5),
+];
+
+/// A TLC-FI signal state that isn't in the state table of the signal groups.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct UnknownSignalState(pub u64);
+
+impl fmt::Display for UnknownSignalState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown signal state {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownSignalState {}
+
+impl TryFrom<u64> for SignalState {
+    type Error = UnknownSignalState;
+
     /// Returns the [SignalState](enum.SignalState.html) corresponding to the given TLC-FI signal state represented as a number
     ///
     /// # Arguments
     ///
     /// * `tlc_fi_state` - A u64 that represents a TLC-FI signal state
-    fn from(tlc_fi_state: u64) -> Self {
-        match tlc_fi_state {
-            0 => SignalState::Unavailable,
-            1 => SignalState::Dark,
-            2 | 3 => SignalState::Red,
-            5 | 6 => SignalState::Green,
-            7 | 8 => SignalState::Amber,
-            9 => SignalState::AmberFlashing,
-            _ => panic!(
-                "Don't know what SignalState to transform '{}' into.",
-                tlc_fi_state
-            ),
-        }
+    fn try_from(tlc_fi_state: u64) -> Result<Self, Self::Error> {
+        SignalState::from_tlc_fi_state(tlc_fi_state).ok_or(UnknownSignalState(tlc_fi_state))
     }
 }
 
 impl SignalState {
+    /// Returns the [SignalState](enum.SignalState.html) corresponding to the given TLC-FI signal state, None for unknown states
+    pub fn from_tlc_fi_state(tlc_fi_state: u64) -> Option<Self> {
+        SIGNAL_STATE_TABLE
+            .iter()
+            .find(|(_, tlc_fi_states, _, _)| tlc_fi_states.contains(&tlc_fi_state))
+            .map(|(state, _, _, _)| *state)
+    }
+
+    /// The row of this state in the state table of the signal groups.
+    fn table_row(&self) -> &'static (SignalState, &'static [u64], i16, i16) {
+        SIGNAL_STATE_TABLE
+            .iter()
+            .find(|(state, _, _, _)| state == self)
+            .expect("Every SignalState is in the state table")
+    }
+
     /// Transforms a [SignalState](enum.SignalState.html) to the value corresponding to that state in VLog
     pub fn to_vlog_state(&self) -> i16 {
-        match self {
-            Self::Unavailable => 4,
-            Self::Dark => 4,
-            Self::Red => 0,
-            Self::Green => 1,
-            Self::Amber => 2,
-            Self::AmberFlashing => 5,
+        self.table_row().2
+    }
+
+    /// Transforms a [SignalState](enum.SignalState.html) to its VLog value in the given [SignalStateEncoding](enum.SignalStateEncoding.html)
+    pub fn to_vlog_state_with(&self, encoding: SignalStateEncoding) -> i16 {
+        match encoding {
+            SignalStateEncoding::Collapsed => self.table_row().2,
+            SignalStateEncoding::Detailed => self.table_row().3,
         }
     }
+
+    /// Returns the [SignalState](enum.SignalState.html) that is written as the given VLog value, in either
+    /// [SignalStateEncoding](enum.SignalStateEncoding.html). Dark and unavailable share a value, which reads as unavailable.
+    pub fn from_vlog_state(vlog_state: i16) -> Option<Self> {
+        SIGNAL_STATE_TABLE
+            .iter()
+            .find(|(_, _, _, detailed_vlog_state)| *detailed_vlog_state == vlog_state)
+            .map(|(state, _, _, _)| *state)
+    }
 }
 
-impl From<u64> for DetectorState {
+/// A TLC-FI detector state that isn't free, occupied or faulty.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct UnknownDetectorState(pub u64);
+
+impl fmt::Display for UnknownDetectorState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown detector state {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownDetectorState {}
+
+impl TryFrom<u64> for DetectorState {
+    type Error = UnknownDetectorState;
+
     /// Returns the [DetectorState](enum.DetectorState.html) corresponding to the given TLC-FI detector state represented as a number
     ///
     /// # Arguments
     ///
-    /// * `tlc_fi_state` - A u64 that represents a TLC-FI signal state
-    fn from(tlc_fi_state: u64) -> Self {
-        match tlc_fi_state {
-            0 => DetectorState::FREE,
-            1 => DetectorState::OCCUPIED,
-            _ => panic!(
-                "Don't know what DetectorState to transform '{}' into.",
-                tlc_fi_state
-            ),
-        }
+    /// * `tlc_fi_state` - A u64 that represents a TLC-FI detector state
+    fn try_from(tlc_fi_state: u64) -> Result<Self, Self::Error> {
+        DetectorState::from_tlc_fi_state(tlc_fi_state).ok_or(UnknownDetectorState(tlc_fi_state))
     }
 }
 
 impl DetectorState {
+    /// Returns the [DetectorState](enum.DetectorState.html) corresponding to the given TLC-FI detector state, None for unknown states
+    pub fn from_tlc_fi_state(tlc_fi_state: u64) -> Option<Self> {
+        match tlc_fi_state {
+            0 => Some(DetectorState::FREE),
+            1 => Some(DetectorState::OCCUPIED),
+            2 => Some(DetectorState::FAULTY),
+            _ => None,
+        }
+    }
+
     /// Transforms a [DetectorState](enum.DetectorState.html) to the value corresponding to that state in VLog
     pub fn to_vlog_state(&self) -> i16 {
         match self {
             Self::FREE => 0,
             Self::OCCUPIED => 1,
+            Self::FAULTY => 2,
         }
     }
+
+    /// Returns the [DetectorState](enum.DetectorState.html) that is written as the given VLog value, None for unknown values
+    pub fn from_vlog_state(vlog_state: i16) -> Option<Self> {
+        match vlog_state {
+            0 => Some(Self::FREE),
+            1 => Some(Self::OCCUPIED),
+            2 => Some(Self::FAULTY),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn repeated_warnings_should_be_summarised_per_category() {
+        let mut console = Console::new(Verbosity::Quiet);
+
+        for line in 0..3 {
+            console.repeated_warning(
+                "skipped {} lines that did not split on '- '",
+                &line.to_string(),
+            );
+        }
+        console.repeated_warning("skipped {} lines before the first tick", "a line");
+
+        assert_eq!(
+            console.summary_lines(),
+            vec![
+                "skipped 1 lines before the first tick",
+                "skipped 3 lines that did not split on '- '"
+            ]
+        );
+    }
+
+    #[test]
+    fn all_warnings_should_include_single_and_summarised_warnings() {
+        let mut console = Console::new(Verbosity::Quiet);
+
+        console.warning("Warning: something happened");
+        console.repeated_warning("skipped {} lines", "a line");
+
+        assert_eq!(
+            console.all_warnings(),
+            vec!["Warning: something happened", "Warning: skipped 1 lines"]
+        );
+    }
+
+    #[test]
+    fn collected_warnings_should_be_counted_with_their_first_occurrence_as_sample() {
+        let mut warnings = WarningCollector::default();
+        let mut other_warnings = WarningCollector::default();
+
+        warnings.add("skipped {} lines that did not split on '- '", "first line");
+        warnings.add("skipped {} lines that did not split on '- '", "second line");
+        other_warnings.add("skipped {} lines that did not split on '- '", "third line");
+        other_warnings.add("failed to parse {} messages", "a message");
+        warnings.merge(&other_warnings);
+
+        assert_eq!(
+            warnings.get("skipped {} lines that did not split on '- '"),
+            Some(&CollectedWarning {
+                count: 3,
+                sample: "first line".to_string()
+            })
+        );
+        assert_eq!(
+            warnings.summary_lines_with_samples(),
+            vec![
+                ("failed to parse 1 messages".to_string(), "a message"),
+                (
+                    "skipped 3 lines that did not split on '- '".to_string(),
+                    "first line"
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn a_console_without_repeated_warnings_should_have_an_empty_summary() {
+        assert!(Console::default().summary_lines().is_empty());
+    }
+
+    #[test]
+    fn signal_states_should_preserve_the_tlc_fi_codes_but_collapse_them_by_default() {
+        let states: Vec<SignalState> = vec![2, 3, 5, 6, 9]
+            .into_iter()
+            .map(|state| SignalState::try_from(state).unwrap())
+            .collect();
+
+        assert_eq!(
+            states,
+            vec![
+                SignalState::RedStopThenProceed,
+                SignalState::Red,
+                SignalState::GreenPermissive,
+                SignalState::Green,
+                SignalState::AmberFlashing
+            ]
+        );
+        let collapsed: Vec<i16> = states.iter().map(|state| state.to_vlog_state()).collect();
+        assert_eq!(collapsed, vec![0, 0, 1, 1, 5]);
+        let detailed: Vec<i16> = states
+            .iter()
+            .map(|state| state.to_vlog_state_with(SignalStateEncoding::Detailed))
+            .collect();
+        assert_eq!(detailed, vec![6, 0, 7, 1, 5]);
+        assert_eq!(SignalState::try_from(10), Err(UnknownSignalState(10)));
+    }
+
+    #[test]
+    fn detailed_vlog_states_should_read_back_as_the_states_they_were_written_from() {
+        let states = [
+            SignalState::Unavailable,
+            SignalState::Red,
+            SignalState::RedStopThenProceed,
+            SignalState::PreMovement,
+            SignalState::Amber,
+            SignalState::Green,
+            SignalState::GreenPermissive,
+            SignalState::AmberFlashing,
+        ];
+
+        for state in states.iter() {
+            assert_eq!(
+                SignalState::from_vlog_state(
+                    state.to_vlog_state_with(SignalStateEncoding::Detailed)
+                ),
+                Some(*state)
+            );
+        }
+        assert_eq!(SignalState::from_vlog_state(3), None);
+        let detector_states = [DetectorState::FREE, DetectorState::OCCUPIED, DetectorState::FAULTY];
+        for state in detector_states.iter() {
+            assert_eq!(DetectorState::from_vlog_state(state.to_vlog_state()), Some(*state));
+        }
+    }
+
+    #[test]
+    fn tick_units_should_parse_and_scale_ticks_to_milliseconds() {
+        assert_eq!("ms".parse(), Ok(TickUnit::Milliseconds));
+        assert_eq!("100us".parse(), Ok(TickUnit::HundredMicroseconds));
+        assert_eq!("custom:2.5".parse(), Ok(TickUnit::Custom(2.5)));
+        assert!("custom:0".parse::<TickUnit>().is_err());
+        assert!("custom:".parse::<TickUnit>().is_err());
+        assert!("us".parse::<TickUnit>().is_err());
+
+        assert_eq!(TickUnit::Milliseconds.to_ms(12_345), 12_345);
+        assert_eq!(TickUnit::HundredMicroseconds.to_ms(12_345), 1_235);
+        assert_eq!(TickUnit::Custom(2.5).to_ms(10_000), 4_000);
+    }
 }
@@ -0,0 +1,130 @@
+//! The lines of the log that is converted, oldest first. A single uncompressed log with its newest line first is read
+//! from its end for every pass over its lines, instead of keeping the whole log in memory to reverse it.
+
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{self, BufRead, BufReader},
+};
+
+use tlcfi_assimilator::Console;
+
+use crate::reverse_reading;
+
+pub enum LogLines {
+    /// Lines read into memory, oldest first
+    Sorted(Vec<String>),
+    /// A log with its newest line first, which is read again for every pass over its lines
+    FromEnd {
+        file_name: String,
+        line_count: usize,
+        /// The bytes of its lines with their line breaks
+        byte_count: u64,
+    },
+}
+
+impl LogLines {
+    /// Reads a log with its newest line first once, calling `on_line` with the length of every line, to count its
+    /// lines and warn about the ones that can't be read.
+    pub fn from_end(
+        file_name: &str,
+        console: &mut Console,
+        on_line: &mut dyn FnMut(usize),
+    ) -> io::Result<LogLines> {
+        let (mut line_count, mut byte_count) = (0, 0);
+        reverse_reading::for_each_line_oldest_first(
+            File::open(file_name)?,
+            console,
+            &mut |line| {
+                on_line(line.len());
+                line_count += 1;
+                byte_count += line.len() as u64 + 1;
+            },
+        )?;
+        Ok(LogLines::FromEnd {
+            file_name: file_name.to_string(),
+            line_count,
+            byte_count,
+        })
+    }
+
+    /// The lines oldest first.
+    pub fn oldest_first(&self) -> io::Result<Box<dyn Iterator<Item = Cow<'_, str>> + '_>> {
+        match self {
+            LogLines::Sorted(lines) => {
+                Ok(Box::new(lines.iter().map(|line| Cow::from(line.as_str()))))
+            }
+            LogLines::FromEnd { file_name, .. } => Ok(Box::new(
+                reverse_reading::lines_oldest_first(file_name)?.map(Cow::from),
+            )),
+        }
+    }
+
+    /// The lines newest first, which a log read from its end has from its start.
+    pub fn newest_first(&self) -> io::Result<Box<dyn Iterator<Item = Cow<'_, str>> + '_>> {
+        match self {
+            LogLines::Sorted(lines) => Ok(Box::new(
+                lines.iter().rev().map(|line| Cow::from(line.as_str())),
+            )),
+            LogLines::FromEnd { file_name, .. } => Ok(Box::new(
+                BufReader::new(File::open(file_name)?)
+                    .lines()
+                    .map_while(Result::ok)
+                    .map(Cow::from),
+            )),
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        match self {
+            LogLines::Sorted(lines) => lines.len(),
+            LogLines::FromEnd { line_count, .. } => *line_count,
+        }
+    }
+
+    /// The bytes of the lines with their line breaks.
+    pub fn byte_count(&self) -> u64 {
+        match self {
+            LogLines::Sorted(lines) => lines.iter().map(|line| line.len() as u64 + 1).sum(),
+            LogLines::FromEnd { byte_count, .. } => *byte_count,
+        }
+    }
+
+    /// Takes the lines that were read into memory, leaving none. A log read from its end has none.
+    pub fn take_sorted_lines(&mut self) -> Vec<String> {
+        match self {
+            LogLines::Sorted(lines) => std::mem::take(lines),
+            LogLines::FromEnd { .. } => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn a_log_read_from_its_end_should_have_the_lines_of_the_sorted_log() {
+        let file_name = std::env::temp_dir().join("tlcfi_assimilator_log_lines.txt");
+        let file_name = file_name.to_str().unwrap();
+        fs::write(file_name, "third\nsecond\nfirst\n").unwrap();
+        let sorted = LogLines::Sorted(vec!["first".into(), "second".into(), "third".into()]);
+
+        let from_end = LogLines::from_end(file_name, &mut Console::default(), &mut |_| {}).unwrap();
+
+        for log_lines in [&sorted, &from_end] {
+            assert_eq!(
+                log_lines.oldest_first().unwrap().collect::<Vec<_>>(),
+                ["first", "second", "third"]
+            );
+            assert_eq!(
+                log_lines.newest_first().unwrap().collect::<Vec<_>>(),
+                ["third", "second", "first"]
+            );
+            assert_eq!(log_lines.line_count(), 3);
+            assert_eq!(log_lines.byte_count(), 19);
+        }
+        fs::remove_file(file_name).unwrap();
+    }
+}
@@ -0,0 +1,147 @@
+//! Splits log lines into their time, their direction and their TLC-FI message, by the layout of the SmartTraffic logs
+//! or by a pattern given with `--log-pattern` for logs with another layout, like `timestamp | DIRECTION | json`.
+
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use regex::{Captures, Regex};
+use serde::{Serialize, Serializer};
+
+use crate::tlcfi_parsing;
+
+/// The named groups a log pattern must have.
+const REQUIRED_GROUPS: [&str; 3] = ["timestamp", "direction", "json"];
+
+/// How the lines of a log are laid out.
+#[derive(Debug, Default, Clone)]
+pub enum LogPattern {
+    /// `timestamp LEVEL logger - DIRECTION - json`, split on the first two `- `, with the timestamp at the start
+    #[default]
+    SmartTraffic,
+    /// A regex with the named groups `timestamp`, `direction` and `json`, and optionally `level`
+    Custom(Regex),
+}
+
+impl LogPattern {
+    /// Splits a line into the text before its direction, its direction and its TLC-FI message. A line that doesn't
+    /// have this layout gives fewer parts.
+    pub fn split<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        match self {
+            LogPattern::SmartTraffic => tlcfi_parsing::split_log_line(line),
+            LogPattern::Custom(regex) => {
+                let parts = regex.captures(line).and_then(|captures| {
+                    let direction = captures.name("direction")?;
+                    let json = captures.name("json")?;
+                    Some(vec![
+                        &line[..direction.start()],
+                        direction.as_str(),
+                        json.as_str(),
+                    ])
+                });
+                parts.unwrap_or_else(|| vec![line])
+            }
+        }
+    }
+
+    /// The timestamp of a line as it's written, e.g. `2021-12-15 11:00:00,074`.
+    pub fn timestamp<'a>(&self, line: &'a str) -> Option<&'a str> {
+        match self {
+            LogPattern::SmartTraffic => tlcfi_parsing::log_line_timestamp(line),
+            LogPattern::Custom(regex) => group(regex.captures(line)?, "timestamp"),
+        }
+    }
+
+    /// The time of a line, with the milliseconds after a comma or a dot and a space or a `T` before the time.
+    pub fn line_time(&self, line: &str) -> Option<NaiveDateTime> {
+        match self {
+            LogPattern::SmartTraffic => tlcfi_parsing::log_line_time(line),
+            LogPattern::Custom(_) => self
+                .timestamp(line)
+                .and_then(|timestamp| tlcfi_parsing::parse_log_timestamp(timestamp).ok()),
+        }
+    }
+
+    /// The log level of a line, e.g. `INFO`. A custom pattern only has levels when it has a `level` group.
+    pub fn log_level<'a>(&self, line: &'a str) -> Option<&'a str> {
+        match self {
+            LogPattern::SmartTraffic => tlcfi_parsing::log_level(line),
+            LogPattern::Custom(regex) => group(regex.captures(line)?, "level"),
+        }
+    }
+}
+
+/// The text of a named group that took part in the match.
+fn group<'a>(captures: Captures<'a>, name: &str) -> Option<&'a str> {
+    captures.name(name).map(|group| group.as_str())
+}
+
+// Serialized as it's given on the command line, with the SmartTraffic layout as no pattern
+impl Serialize for LogPattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            LogPattern::SmartTraffic => serializer.serialize_none(),
+            LogPattern::Custom(regex) => serializer.serialize_some(regex.as_str()),
+        }
+    }
+}
+
+impl FromStr for LogPattern {
+    type Err = String;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        let regex =
+            Regex::new(arg).map_err(|error| format!("'{}' isn't a valid regex: {}", arg, error))?;
+        let group_names: Vec<&str> = regex.capture_names().flatten().collect();
+        if let Some(missing_group) = REQUIRED_GROUPS
+            .iter()
+            .find(|group| !group_names.contains(group))
+        {
+            return Err(format!(
+                "the log pattern '{}' has no named group '{}', it needs timestamp, direction and json",
+                arg, missing_group
+            ));
+        }
+        Ok(LogPattern::Custom(regex))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::NaiveDate;
+
+    const SMART_TRAFFIC_LINE: &str =
+        "2021-12-15 12:00:00,650 INFO  tlcFiMessages:41 - IN - {\"method\":\"UpdateState\"}";
+
+    const PIPE_LINE: &str = "2021-12-15 12:00:00.650 | IN | {\"method\":\"UpdateState\"}";
+
+    const PIPE_PATTERN: &str = r"^(?P<timestamp>\S+ \S+) \| (?P<direction>\w+) \| (?P<json>.*)$";
+
+    #[test]
+    fn both_layouts_should_split_into_the_same_message_and_time() {
+        let pipe_pattern: LogPattern = PIPE_PATTERN.parse().unwrap();
+        let time = NaiveDate::from_ymd(2021, 12, 15).and_hms_milli(12, 0, 0, 650);
+
+        let smart_traffic_parts = LogPattern::SmartTraffic.split(SMART_TRAFFIC_LINE);
+        let pipe_parts = pipe_pattern.split(PIPE_LINE);
+
+        assert_eq!(smart_traffic_parts[2], pipe_parts[2]);
+        assert_eq!(pipe_parts[..2], ["2021-12-15 12:00:00.650 | ", "IN"]);
+        assert_eq!(
+            LogPattern::SmartTraffic.line_time(SMART_TRAFFIC_LINE),
+            Some(time)
+        );
+        assert_eq!(pipe_pattern.line_time(PIPE_LINE), Some(time));
+        assert_eq!(pipe_pattern.split(SMART_TRAFFIC_LINE).len(), 1);
+        assert_eq!(pipe_pattern.log_level(PIPE_LINE), None);
+    }
+
+    #[test]
+    fn a_pattern_without_the_groups_should_be_refused() {
+        assert!(r"^(?P<timestamp>\S+ \S+) \| (?P<json>.*)$"
+            .parse::<LogPattern>()
+            .unwrap_err()
+            .contains("has no named group 'direction'"));
+        assert!("(?P<json>".parse::<LogPattern>().is_err());
+    }
+}
@@ -0,0 +1,525 @@
+//! Analyses a TLC-FI log without producing VLog, to answer what is in it before a mapping file exists.
+//!
+//! Per signal group and detector a CSV line is written: `name,type,change_count,first_seen_ms,last_seen_ms`.
+//! The `--list-objects` mode of the conversion prints the same objects as a table, with the times they were seen.
+
+use std::{collections::BTreeMap, fs, str::FromStr};
+
+use chrono::{Duration, NaiveDateTime};
+use serde::Serialize;
+use tlcfi_assimilator::{AssimilationData, Console, ConversionStats, TimestampedChanges};
+
+use crate::{
+    schema::{FieldSchema, FieldType},
+    AppArgs, ConversionFailure, EXIT_CODE_ARGUMENT_ERROR, EXIT_CODE_INPUT_ERROR,
+    EXIT_CODE_OUTPUT_ERROR,
+};
+
+const STATS_HELP: &str = "\
+TLC-FI Assimilator stats
+
+USAGE:
+  tlcfi_assimilator stats [OPTIONS]
+
+FLAGS:
+  -h, --help                Prints help information
+  -q, --quiet               Only prints errors
+  -v, --verbose             Also prints per line diagnostics, tick overflows and resets
+
+OPTIONS:
+  --chronological           Reads the logs as chronological (newest last), --chronological false still works
+  --reverse                 Reads the logs as newest first, which is the default
+  --invert-direction        Treat OUT messages as coming from the TLC and IN messages as going to it
+  --output STRING           Writes the CSV to this file instead of stdout
+  --tlcfi-log-file STRING   Sets the name of the file to load [default: tlcfi.txt]
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ObjectType {
+    Signal,
+    Detector,
+}
+
+impl ObjectType {
+    const ALL: [ObjectType; 2] = [ObjectType::Signal, ObjectType::Detector];
+
+    fn name(&self) -> &'static str {
+        match self {
+            ObjectType::Signal => "signal",
+            ObjectType::Detector => "detector",
+        }
+    }
+}
+
+/// How `--list-objects` prints the objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum ListFormat {
+    /// Aligned columns with the times of the log
+    #[default]
+    Table,
+    /// The CSV of the `stats` subcommand
+    Csv,
+}
+
+impl FromStr for ListFormat {
+    type Err = String;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        match arg {
+            "table" => Ok(ListFormat::Table),
+            "csv" => Ok(ListFormat::Csv),
+            _ => Err(format!("Unknown format '{}', use table or csv", arg)),
+        }
+    }
+}
+
+/// How often and when a single signal group or detector changed.
+#[derive(Debug, PartialEq)]
+pub struct ObjectStats {
+    name: String,
+    object_type: ObjectType,
+    change_count: usize,
+    first_seen_ms: u64,
+    last_seen_ms: u64,
+}
+
+/// Parses the `stats` arguments, analyses the log and writes the CSV. Returns the exit code.
+pub fn run_stats() -> i32 {
+    let mut pargs = pico_args::Arguments::from_env();
+    // Drop the "stats" subcommand itself
+    let _ = pargs.subcommand();
+
+    if pargs.contains(["-h", "--help"]) {
+        println!("{}", STATS_HELP);
+        return 0;
+    }
+
+    let (app_args, output_file) = match parse_stats_args(&mut pargs) {
+        Ok(args) => args,
+        Err(error) => {
+            error.print(STATS_HELP);
+            return EXIT_CODE_ARGUMENT_ERROR;
+        }
+    };
+
+    let mut console = Console::new(app_args.verbosity);
+    let sorted_lines = match crate::sort_lines(
+        &app_args.tlcfi_log_file,
+        &app_args.is_chronological,
+        &mut console,
+    ) {
+        Ok(sorted_lines) => sorted_lines,
+        Err(error) => {
+            console.error(&format!(
+                "Error: failed to read the log '{}': {}",
+                app_args.tlcfi_log_file, error
+            ));
+            return EXIT_CODE_INPUT_ERROR;
+        }
+    };
+    let mut data = AssimilationData {
+        console,
+        sorted_lines,
+        ..Default::default()
+    };
+    if let Err(problem) = crate::read_lines_and_save_changes(&mut data, &app_args) {
+        data.console.error(&format!(
+            "Error: {}",
+            crate::describe_line_problem(
+                &problem,
+                &data,
+                crate::sorted_log_line_count(&data),
+                &app_args
+            )
+        ));
+        return EXIT_CODE_INPUT_ERROR;
+    }
+
+    let csv = to_csv(&collect_object_stats(&data.changes));
+    match output_file {
+        Some(output_file) => {
+            if let Err(error) = fs::write(&output_file, csv) {
+                data.console.error(&format!(
+                    "Error: failed to write the stats to '{}': {}",
+                    output_file, error
+                ));
+                return EXIT_CODE_OUTPUT_ERROR;
+            }
+            data.console
+                .info(&format!("Wrote the stats to: {}", output_file));
+        }
+        None => print!("{}", csv),
+    }
+
+    data.console.print_summary();
+    for line in log_summary_lines(&data.stats) {
+        data.console.report(&line);
+    }
+    if data.changes.is_empty() {
+        return EXIT_CODE_INPUT_ERROR;
+    }
+    0
+}
+
+/// Prints every signal group and detector in the log for `--list-objects`, without a mapping file. Returns the exit
+/// code.
+pub fn run_object_listing(app_args: &AppArgs) -> i32 {
+    match list_objects(app_args) {
+        Ok(listing) => {
+            print!("{}", listing);
+            0
+        }
+        Err(failure) => {
+            eprintln!("{}", failure.message);
+            failure.exit_code
+        }
+    }
+}
+
+/// Parses the log like a conversion does and lists its objects in the format of the arguments.
+fn list_objects(app_args: &AppArgs) -> Result<String, ConversionFailure> {
+    let mut console = Console::new(app_args.verbosity);
+    let sorted_lines = crate::sort_lines(
+        &app_args.tlcfi_log_file,
+        &app_args.is_chronological,
+        &mut console,
+    )
+    .map_err(|error| ConversionFailure {
+        exit_code: EXIT_CODE_INPUT_ERROR,
+        message: format!(
+            "Error: failed to read the log '{}': {}",
+            app_args.tlcfi_log_file, error
+        ),
+    })?;
+    let start_time = match app_args.start_date_time {
+        Some(start_date_time) => start_date_time,
+        None => crate::get_start_date_time_from_file(&sorted_lines, &app_args.log_pattern)
+            .map_err(|error| ConversionFailure {
+                exit_code: EXIT_CODE_INPUT_ERROR,
+                message: format!("Error: failed to get start date time from logs. {}", error),
+            })?,
+    };
+    let mut data = AssimilationData {
+        start_time,
+        time_source: app_args.time_source,
+        tick_unit: app_args.tick_unit,
+        log_pattern: app_args.log_pattern.clone(),
+        log_levels: app_args.log_levels.clone(),
+        logger_name: app_args.logger_name.clone(),
+        update_methods: app_args.update_methods.clone(),
+        console,
+        sorted_lines,
+        ..Default::default()
+    };
+    crate::read_lines_and_save_changes(&mut data, app_args).map_err(|problem| {
+        ConversionFailure {
+            exit_code: EXIT_CODE_INPUT_ERROR,
+            message: format!(
+                "Error: {}",
+                crate::describe_line_problem(
+                    &problem,
+                    &data,
+                    crate::sorted_log_line_count(&data),
+                    app_args
+                )
+            ),
+        }
+    })?;
+    if data.changes.is_empty() {
+        return Err(crate::no_messages_failure(app_args, &data.stats));
+    }
+    data.console.print_summary();
+
+    let object_stats = collect_object_stats(&data.changes);
+    Ok(match app_args.list_format {
+        ListFormat::Table => to_table(&object_stats, &start_time),
+        ListFormat::Csv => to_csv(&object_stats),
+    })
+}
+
+/// Reads the options of the `stats` subcommand into [AppArgs](../struct.AppArgs.html) and an optional output file.
+fn parse_stats_args(
+    pargs: &mut pico_args::Arguments,
+) -> Result<(AppArgs, Option<String>), crate::ArgsError> {
+    let app_args = AppArgs {
+        is_chronological: crate::parse_is_chronological(pargs)?,
+        invert_direction: pargs.contains("--invert-direction"),
+        tlcfi_log_file: pargs
+            .opt_value_from_fn("--tlcfi-log-file", crate::check_file_existence)?
+            .unwrap_or("tlcfi.txt".to_string()),
+        verbosity: crate::parse_verbosity(pargs)?,
+        ..Default::default()
+    };
+    let output_file = pargs.opt_value_from_str("--output")?;
+    Ok((app_args, output_file))
+}
+
+/// The columns of the CSV, also printed by the `schema` subcommand.
+pub fn csv_schema() -> Vec<FieldSchema> {
+    let field = |name, field_type| FieldSchema {
+        name,
+        field_type,
+        nullable: false,
+        enum_values: Vec::new(),
+    };
+    vec![
+        field("name", FieldType::String),
+        FieldSchema {
+            enum_values: ObjectType::ALL.iter().map(ObjectType::name).collect(),
+            ..field("type", FieldType::String)
+        },
+        field("change_count", FieldType::UnsignedInteger),
+        field("first_seen_ms", FieldType::UnsignedInteger),
+        field("last_seen_ms", FieldType::UnsignedInteger),
+    ]
+}
+
+/// Counts the changes per signal group and detector, sorted by type and name.
+pub fn collect_object_stats(changes: &[TimestampedChanges]) -> Vec<ObjectStats> {
+    let mut object_stats: BTreeMap<(ObjectType, String), ObjectStats> = BTreeMap::new();
+    for change in changes {
+        let names = change
+            .signal_names
+            .iter()
+            .map(|name| (ObjectType::Signal, name))
+            .chain(
+                change
+                    .detector_names
+                    .iter()
+                    .map(|name| (ObjectType::Detector, name)),
+            );
+        for (object_type, name) in names {
+            let stats = object_stats
+                .entry((object_type, name.clone()))
+                .or_insert(ObjectStats {
+                    name: name.clone(),
+                    object_type,
+                    change_count: 0,
+                    first_seen_ms: change.ms_from_beginning,
+                    last_seen_ms: change.ms_from_beginning,
+                });
+            stats.change_count += 1;
+            stats.first_seen_ms = stats.first_seen_ms.min(change.ms_from_beginning);
+            stats.last_seen_ms = stats.last_seen_ms.max(change.ms_from_beginning);
+        }
+    }
+    object_stats.into_values().collect()
+}
+
+pub fn to_csv(object_stats: &[ObjectStats]) -> String {
+    let header: Vec<&str> = csv_schema().iter().map(|field| field.name).collect();
+    let mut csv = format!("{}\n", header.join(","));
+    for stats in object_stats {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            stats.name,
+            stats.object_type.name(),
+            stats.change_count,
+            stats.first_seen_ms,
+            stats.last_seen_ms
+        ));
+    }
+    csv
+}
+
+/// Formats the object stats as aligned columns, with the times they were first and last seen after the start time.
+pub fn to_table(object_stats: &[ObjectStats], start_time: &NaiveDateTime) -> String {
+    let format_time = |ms: u64| {
+        (*start_time + Duration::milliseconds(ms as i64))
+            .format("%Y-%m-%d %H:%M:%S%.3f")
+            .to_string()
+    };
+    let header = [
+        "name".to_string(),
+        "type".to_string(),
+        "changes".to_string(),
+        "first seen".to_string(),
+        "last seen".to_string(),
+    ];
+    let rows: Vec<[String; 5]> = object_stats
+        .iter()
+        .map(|stats| {
+            [
+                stats.name.clone(),
+                stats.object_type.name().to_string(),
+                stats.change_count.to_string(),
+                format_time(stats.first_seen_ms),
+                format_time(stats.last_seen_ms),
+            ]
+        })
+        .collect();
+    let mut widths = [0; 5];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let mut table = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+fn log_summary_lines(stats: &ConversionStats) -> Vec<String> {
+    let tick_or_none = |tick: Option<u64>| {
+        tick.map(|tick| tick.to_string())
+            .unwrap_or_else(|| "none".to_string())
+    };
+    vec![
+        "Summary:".to_string(),
+        format!(
+            "  log lines read: {} ({} skipped, {} IN, {} OUT)",
+            stats.lines_read, stats.lines_skipped, stats.in_lines, stats.out_lines
+        ),
+        format!(
+            "  ticks: first {}, last {}",
+            tick_or_none(stats.first_tick),
+            tick_or_none(stats.last_tick)
+        ),
+        format!("  unsupported messages: {}", stats.unsupported_messages),
+        format!(
+            "  tick events: {} overflow(s), {} reset(s)",
+            stats.tick_overflows,
+            stats.controller_resets.len()
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tlcfi_assimilator::{DetectorState, SignalState, Verbosity};
+
+    #[test]
+    fn collecting_object_stats_should_count_changes_per_name_and_type() {
+        let changes = vec![
+            TimestampedChanges {
+                ms_from_beginning: 100,
+                signal_names: vec!["02".to_string(), "03".to_string()],
+                signal_states: vec![SignalState::Green, SignalState::Red],
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 200,
+                detector_names: vec!["02".to_string()],
+                detector_states: vec![DetectorState::OCCUPIED],
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 300,
+                signal_names: vec!["02".to_string()],
+                signal_states: vec![SignalState::Amber],
+                ..Default::default()
+            },
+        ];
+
+        let object_stats = collect_object_stats(&changes);
+
+        assert_eq!(
+            to_csv(&object_stats),
+            "name,type,change_count,first_seen_ms,last_seen_ms\n\
+             02,signal,2,100,300\n\
+             03,signal,1,100,100\n\
+             02,detector,1,200,200\n"
+        );
+    }
+
+    #[test]
+    fn the_object_table_should_align_its_columns_and_show_the_times_seen() {
+        let object_stats = vec![
+            ObjectStats {
+                name: "02".to_string(),
+                object_type: ObjectType::Signal,
+                change_count: 12,
+                first_seen_ms: 0,
+                last_seen_ms: 61_500,
+            },
+            ObjectStats {
+                name: "D611".to_string(),
+                object_type: ObjectType::Detector,
+                change_count: 3,
+                first_seen_ms: 250,
+                last_seen_ms: 250,
+            },
+        ];
+        let start_time = chrono::NaiveDate::from_ymd(2021, 12, 15).and_hms(12, 0, 0);
+
+        assert_eq!(
+            to_table(&object_stats, &start_time),
+            "name  type      changes  first seen               last seen\n\
+             02    signal    12       2021-12-15 12:00:00.000  2021-12-15 12:01:01.500\n\
+             D611  detector  3        2021-12-15 12:00:00.250  2021-12-15 12:00:00.250\n"
+        );
+    }
+
+    #[test]
+    fn listing_the_objects_of_the_test_log_should_not_need_a_mapping_file() {
+        let app_args = AppArgs {
+            tlcfi_log_file: "./tlcfi.txt".to_string(),
+            vlog_tlcfi_mapping_file: String::new(),
+            list_objects: true,
+            verbosity: Verbosity::Quiet,
+            ..Default::default()
+        };
+
+        let table = list_objects(&app_args).unwrap();
+        let csv = list_objects(&AppArgs {
+            list_format: ListFormat::Csv,
+            ..app_args
+        })
+        .unwrap();
+
+        let table_lines: Vec<&str> = table.lines().collect();
+        let csv_lines: Vec<&str> = csv.lines().collect();
+        assert!(table_lines[0].starts_with("name  type"));
+        assert_eq!(
+            csv_lines[0],
+            "name,type,change_count,first_seen_ms,last_seen_ms"
+        );
+        assert_eq!(table_lines.len(), csv_lines.len());
+        assert!(table_lines[1..]
+            .iter()
+            .all(|line| line.contains("  2021-12-15 1")));
+        assert!(csv_lines.iter().any(|line| line.starts_with("61,signal,")));
+        assert!(csv_lines
+            .iter()
+            .any(|line| line.starts_with("D611,detector,")));
+    }
+
+    #[test]
+    fn analysing_the_test_log_should_not_need_a_mapping_file() {
+        let mut data = AssimilationData {
+            console: Console::new(Verbosity::Quiet),
+            sorted_lines: crate::sort_lines(
+                "./tlcfi.txt",
+                &false,
+                &mut Console::new(Verbosity::Quiet),
+            )
+            .unwrap(),
+            ..Default::default()
+        };
+        let app_args = AppArgs {
+            verbosity: Verbosity::Quiet,
+            ..Default::default()
+        };
+
+        crate::read_lines_and_save_changes(&mut data, &app_args).unwrap();
+        let object_stats = collect_object_stats(&data.changes);
+
+        assert!(object_stats
+            .iter()
+            .any(|stats| stats.object_type == ObjectType::Signal));
+        assert!(object_stats
+            .iter()
+            .any(|stats| stats.object_type == ObjectType::Detector));
+        assert!(data.stats.first_tick.is_some());
+        assert!(data.stats.last_tick.is_some());
+    }
+}
@@ -1,30 +1,240 @@
 use std::{
-    fs::File,
-    io::{BufRead, BufReader, Write},
+    borrow::Cow,
+    cell::RefCell,
+    collections::HashMap,
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, Write},
 };
 
-mod tlcfi_parsing;
-mod vlog_transformer;
+mod amber_policy;
+mod appending;
+mod batch;
+mod change_filtering;
+mod checkpointing;
+mod clock_steps;
+mod compare;
+mod compressed_logs;
+mod config;
+mod controller_resets;
+mod conversion_cache;
+mod dated_logs;
+mod decode;
+mod deduplication;
+mod file_name_template;
+mod following;
+mod job;
+mod log_lines;
+mod log_stats;
+mod mapping_check;
+mod mapping_from_log;
+mod mapping_generation;
+mod mapping_selection;
+mod mapping_validation;
+mod output_size;
+mod output_splitting;
+mod output_verification;
+mod pacing;
+mod predictions;
+mod progress;
+mod reverse_reading;
+mod rotated_logs;
+mod schema;
+mod tee;
+mod vlog_decoding;
+mod watching;
+mod zip_archive;
 
-use chrono::NaiveDateTime;
-use tlcfi_assimilator::AssimilationData;
+use amber_policy::AmberPolicy;
+use change_filtering::NameFilter;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use deduplication::Deduplicator;
+use file_name_template::FileNameTemplate;
+use flate2::{write::GzEncoder, Compression};
+use log_lines::LogLines;
+use output_size::SizePolicy;
+use output_splitting::SplitMode;
+use output_verification::OutputVerifier;
+use progress::Progress;
+use serde::Serialize;
+use tlcfi_assimilator::{
+    conversion_summary::ConversionSummary, log_pattern::LogPattern, time_zone::TimeZone,
+    tlcfi_parsing, vlog_transformer, AssimilationData, ClockStep, Console, ConversionMode,
+    ConversionStats, LineErrors, MessageDirection, RejectedLine, SignalStateEncoding, TickUnit,
+    TimeSource, TimestampedChanges, TlcMeta, Verbosity,
+};
+
+/// The exit code used when the arguments can't be parsed.
+const EXIT_CODE_ARGUMENT_ERROR: i32 = 1;
+/// The exit code used when the inputs have problems, e.g. found by a dry run.
+const EXIT_CODE_INPUT_ERROR: i32 = 2;
+/// The exit code used when writing the VLog output failed.
+const EXIT_CODE_OUTPUT_ERROR: i32 = 3;
+/// The exit code used when the mapping file lacks the TLC name or names that are in the log.
+const EXIT_CODE_MAPPING_ERROR: i32 = 4;
+/// The exit code used when the log has no messages to convert.
+const EXIT_CODE_NO_MESSAGES: i32 = 5;
+/// The exit code used when compared VLog files differ.
+const EXIT_CODE_DIFFERENCES: i32 = 6;
 
 const ARGS_HELP: &str = "\
 TLC-FI Assimilator
 
 USAGE:
-  tlcfi_assimilator [OPTIONS] --start-date-time STRING [VLOG_TLCFI_MAPPING_FILE]
+  tlcfi_assimilator [OPTIONS] --start-date-time STRING --mapping-file STRING
 
 FLAGS:
   -h, --help                Prints help information
+  -q, --quiet               Only prints errors
+  -v, --verbose             Also prints per line diagnostics, tick overflows and resets and mappings
+  -V, --version             Prints version information and the supported VLog messages
 
 OPTIONS:
-  --chronological BOOL      Sets whether the logs are in chronological order (newest last) [default: false]
-  --start-date-time STRING  ISO 8601 timestamp for the start moment of tlcfi logs (e.g. 2021-12-15T11:00:00.000)
-  --tlcfi-log-file STRING   Sets the name of the file to load [default: tlcfi.txt]
+  --cache-dir STRING        Reuses the VLog file of an earlier conversion of the same inputs and options from this
+                            directory, and stores new conversions in it
+  --config STRING           Reads options from this TOML file, with the long option names as keys. Options given on
+                            the command line override the ones in the file
+  --dry-run                 Checks the log and mapping file and reports problems, without writing a VLog file
+  --generate-mapping STRING Writes a mapping file with every signal and detector in the log to this file, with
+                            sequential VLog ids and a placeholder TLC name to edit, no mapping file needed
+  --list-objects            Prints every signal and detector in the log with how often it changed and when it was
+                            first and last seen, to write a mapping file from, no mapping file needed
+  --format STRING           How --list-objects prints the objects: table or csv [default: table]
+  --validate-mapping        Checks the whole mapping file for missing sections, malformed rows and duplicate names
+                            and ids, and reports every problem with its line without reading the log
+  --reset-snapshot          Makes all signal groups and detectors unavailable when the controller resets
+  --tee                     Also writes every VLog message to stdout while writing the VLog file, the other output
+                            of the conversion goes to stderr
+  --watch STRING            Keeps converting the logs that appear in this directory once their size stops changing,
+                            and moves each of them to its processed or failed directory afterwards
+  --watch-pattern STRING    The names of the logs to convert when watching, with * and ? wildcards [default: *.txt]
+  --log-dir STRING          A directory of logs to pick the logs of --date from, by the date in their name or of their
+                            oldest line. They are read as one log, from the first message of the date
+  --date STRING             The date of the logs to convert from --log-dir (e.g. 2021-12-15)
+  --output-dir STRING       Writes the VLog files to this directory instead of the working directory
+  --mapping-dir STRING      A directory of mapping files to pick the one with the TLC name of --tlc-name or the path
+                            of the log from, like --mapping-file with a directory
+  --follow                  Keeps appending the VLog messages of lines added to the log until Ctrl-C is pressed,
+                            the log is read as chronological. With --pace the messages are written once they're due
+  --split STRING            Starts a new VLog file at every hour boundary with hourly [default: none]
+  --max-messages NUMBER     Continues in a new VLog file, named with _part2 etc., after this many messages
+  --max-output-size SIZE    The largest VLog file in bytes, or with K, M or G (e.g. 500M). Larger files are handled
+                            according to --size-policy
+  --size-policy STRING      Aborts before writing, compresses or rotates files that are too large: abort, compress
+                            or rotate [default: abort]
+  --checkpoint STRING       Saves how far the conversion got to this file every 100000 lines, and continues from it
+                            when it exists after an interruption. Needs a single uncompressed chronological log
+  --append STRING           Appends the VLog messages to this existing VLog file, continuing from its last time
+                            reference. The log has to start after the file ends
+  --output-template STRING  Names the VLog files with the placeholders {tlc}, {date}, {time}, {start_iso} and {ext},
+                            a / places them in directories [default: {tlc}_{date}_{time}.{ext}]
+  --line-ending STRING      Ends every VLog message with crlf or lf [default: crlf]
+  --name-date STRING        Which date time names the VLog file: window-start, first-change or majority-day
+                            [default: window-start]
+  --chronological           Reads the logs as chronological (newest last), --chronological false still works
+  --reverse                 Reads the logs as newest first, which is the default
+  --filter-signals STRING   Only convert the signals with these comma separated names (e.g. 03,04,71)
+  --filter-detectors STRING Only convert the detectors with these comma separated names (e.g. D712,D713)
+  --exclude-detectors STRING
+                            Convert every detector except the ones with these comma separated names
+                            The filters also take regular expressions that start with re: (e.g. re:^D7)
+  --duration STRING         Only convert this much time from the first decoded message, in seconds or
+                            with units (e.g. 3600 or 1h30m)
+  --limit NUMBER            Only convert the first this many changes, after filtering, e.g. to check a mapping
+                            quickly. Together with --duration the first limit that is reached counts
+  --pace                    Write every VLog message only once its time (relative to now) has passed
+  --pace-speed NUMBER       Replay this many times faster than real time when pacing [default: 1]
+  --strict                  Fails at the first log line that doesn't split or has a TLC-FI message that can't be
+                            parsed, when the VLog output has duplicate lines, a second information message or
+                            time references going back in time, and when the mapping file probably belongs to
+                            another intersection
+  --strict-rpc              Fails without writing a VLog file when the TLC sent a JSON-RPC error response, which
+                            usually means the session was unhealthy during the measurement
+  --max-errors NUMBER       Gives up without writing a VLog file when more than this many log lines can't be parsed
+  --summary-json STRING     Writes a JSON summary of the conversion to this file, with the input and output files,
+                            start time, TLC name, messages per VLog type, skipped lines per reason, tick events
+                            and runtime
+  --reject-file STRING      Writes every skipped log line to this file, after the reason (no-split, json-error,
+                            unknown-state, malformed-message or no-first-tick) and its line number
+  --predictions-csv STRING  Writes the predicted ends of the future signal states to this CSV file, in ms from the
+                            start like the times of the changes
+  --overwrite               Replaces VLog files that already exist, instead of stopping before the log is parsed
+  --no-progress             Doesn't print the progress of reading and converting the log, e.g. for cron jobs
+  --detailed-signal-states  Writes stop then proceed reds, pre-movement red-ambers and permissive greens with their own
+                            VLog values (6, 8 and 7)
+  --dedupe                  Drops the states that repeat the last state of their signal group or detector, for TLCs
+                            that resend their full state even when nothing changed
+  --skip-until-first-signal Drops the detector changes before the first signal change and starts the VLog file at
+                            that change, for logs that start before the signal plan is active
+  --amber-policy STRING     Writes amber as green or red for consumers that only know drivable or not: as-green,
+                            as-red or keep [default: keep]
+  --signals-only            Only convert signal changes, no detector mappings are needed
+  --detectors-only          Only convert detector changes, no signal mappings are needed
+  --max-id-gap NUMBER       Warn when the VLog ids in the mapping file skip more than this many ids [default: 10]
+  --renumber-ids            Numbers the VLog ids densely and writes the renumbered mapping next to the VLog file
+  --min-mapped-fraction NUMBER
+                            Warn when less than this fraction of the names in the log is mapped [default: 0.9]
+  --mapping-file STRING     The VLog TLC-FI mapping file with the TLC name and the Signals and Detectors sections,
+                            or a directory of mapping files to pick the one with the TLC name of --tlc-name or the
+                            path of the log from
+  --mapping-from-log        Uses the indices in the ReadMeta responses in the log as VLog ids and the intersection
+                            as TLC name, and writes that mapping next to the VLog file. A mapping file is then
+                            optional and only checked against the meta
+  --tlc-name STRING         Writes and names the VLog files with this TLC name instead of the one in the mapping
+                            file, at most 20 characters
+  --direction STRING        Which messages are converted: in (from the TLC), out (to the TLC) or both, merged by
+                            their time [default: in]
+  --invert-direction        Treat OUT messages as coming from the TLC and IN messages as going to it
+  --time-source STRING      Takes the times of the messages from their ticks or from the times at the start of the
+                            log lines, which don't jump when the TLC restarts: ticks or log [default: ticks]
+  --log-levels STRING       Only reads the log lines with these comma separated log levels (e.g. INFO,WARN), the
+                            word after the timestamp. The other lines are skipped before they're parsed
+  --logger-name STRING      Only reads the log lines with this logger name before the direction (e.g.
+                            tlcFiMessages), for logs other components also log JSON messages into
+  --log-pattern STRING      Splits the log lines with this regex with the named groups timestamp, direction and
+                            json (and optionally level), for logs that aren't laid out like the SmartTraffic logs:
+                            timestamp LEVEL logger - DIRECTION - json
+  --update-methods STRING   Also reads the messages with these comma separated methods as state updates, for vendors
+                            that don't use UpdateState (e.g. UpdateStates). Other methods are skipped and counted
+  --tick-unit STRING        How long a tick of the TLC is: ms, 100us or custom: with the number of ticks per
+                            millisecond (e.g. custom:10) [default: ms]
+  --first-tick NUMBER       The tick of the start date time, for a slice of a log whose first message isn't at the
+                            start, so its times line up with the conversion of the whole log
+  --repair-clock-steps      Corrects the line times after a step of the logger's clock (e.g. by NTP), which is
+                            found by the line times moving differently from the ticks
+  --start-date-time STRING  ISO 8601 timestamp for the start moment of tlcfi logs (e.g. 2021-12-15T11:00:00.000),
+                            with an offset like +01:00 or Z to have it converted to the --timezone of the log,
+                            or epoch milliseconds when it's only digits. Lines logged before it are skipped
+  --start-tolerance STRING  How far the start date time may be before the oldest or after the newest line of the log
+                            before it's warned about, or with --strict fails, in seconds or with units [default: 10m]
+  --epoch-ms NUMBER         The start moment as milliseconds since 1970-01-01 UTC (e.g. 1639566000000), between
+                            2000 and 2100
+  --timezone STRING         The time zone the log is stamped in and the start date time is given in, e.g.
+                            Europe/Amsterdam or +01:00. Time references follow changes to and from summer time
+  --output-utc              Writes the time references and names the VLog file in UTC instead of the time zone
+  --tlcfi-log-file STRING   Sets the name of the file to load, which may be gzip compressed or a ZIP
+                            archive of logs [default: tlcfi.txt]
 
 ARGS:
-  <VLOG_TLCFI_MAPPING_FILE>
+  <VLOG_TLCFI_MAPPING_FILE> Deprecated, use --mapping-file
+
+SUBCOMMANDS:
+  batch                     Converts every *.txt log in a directory on its own and prints a result per log
+  decode                    Prints a VLog file as text, one line per message with its time, type and contents
+  compare                   Prints the differences in the preambles and states of two VLog files
+  job                       Reads a JSON job description from stdin and writes a JSON result to stdout
+  stats                     Writes a CSV with the changes per signal group and detector, no mapping file needed
+  schema                    Prints the schema of the stats CSV as JSON
+
+EXIT CODES:
+  0                         The conversion succeeded
+  1                         The arguments are invalid
+  2                         The inputs have problems, e.g. the log can't be read or a dry run found problems
+  3                         The VLog output couldn't be written
+  4                         The mapping file lacks the TLC name or signals or detectors in the log, or
+                            --validate-mapping found problems
+  5                         The log has no signal or detector messages to convert
+  6                         The VLog files given to compare differ
 ";
 
 /// The entry point for this program
@@ -37,340 +247,6669 @@ ARGS:
 ///
 /// The line is split in three parts using `- ` as a delimiter, and we assume the tlcfi json is the 3rd element. The second element is used to see whether a message is incoming or outgoing of ST.
 fn main() {
-    let app_args = match parse_args() {
+    match std::env::args().nth(1).as_deref() {
+        Some("batch") => std::process::exit(batch::run_batch()),
+        Some("decode") => std::process::exit(decode::run_decode()),
+        Some("compare") => std::process::exit(compare::run_compare()),
+        Some("job") => std::process::exit(job::run_job()),
+        Some("stats") => std::process::exit(log_stats::run_stats()),
+        Some("schema") => std::process::exit(schema::run_schema()),
+        _ => {}
+    }
+
+    let app_args = match parse_args(std::env::args_os().skip(1).collect()) {
         Ok(v) => v,
         Err(e) => {
-            eprintln!("Error: {}.", e);
-            println!("{}", ARGS_HELP);
-            std::process::exit(1);
+            e.print(ARGS_HELP);
+            std::process::exit(EXIT_CODE_ARGUMENT_ERROR);
         }
     };
 
-    run_with_args(app_args)
+    std::process::exit(run_with_args(app_args))
 }
 
-fn run_with_args(app_args: AppArgs) {
-    let time_sorted_lines = sort_lines(&app_args.tlcfi_log_file, &app_args.is_chronological);
+/// What a conversion that didn't fail resulted in.
+#[derive(Debug, Default)]
+struct ConversionReport {
+    /// The written VLog files, none for a dry run
+    file_names: Vec<String>,
+    change_count: usize,
+    vlog_message_count: usize,
+    /// Problems found by a dry run
+    problems: Vec<String>,
+    warnings: Vec<String>,
+    stats: ConversionStats,
+    /// The moment the output starts at
+    start_time: Option<NaiveDateTime>,
+    /// The TLC name the output was written for, None when no file was written
+    tlc_name: Option<String>,
+}
 
-    let start_time = &app_args.start_date_time.unwrap_or_else(||
-        get_start_date_time_from_file(&time_sorted_lines).expect("Failed to get start date time from logs, set it in application arguments instead or filter the logs."));
+/// Why a conversion failed, and the exit code to stop the program with.
+#[derive(Debug)]
+struct ConversionFailure {
+    exit_code: i32,
+    message: String,
+}
 
-    let mut data = AssimilationData {
-        start_time: *start_time,
-        sorted_lines: time_sorted_lines,
-        first_tick: Option::None,
-        previous_tick: Option::None,
-        bonus_ms: Option::None,
-        changes: Vec::new(),
+/// Converts and returns the exit code to stop the program with.
+fn run_with_args(app_args: AppArgs) -> i32 {
+    if app_args.validate_mapping {
+        return run_mapping_validation(&app_args);
+    }
+    if let Some(output_file) = &app_args.generate_mapping {
+        return run_mapping_generation(&app_args, output_file);
+    }
+    if app_args.list_objects {
+        return log_stats::run_object_listing(&app_args);
+    }
+    if let Some(watch_dir) = &app_args.watch_dir {
+        return watching::run_watch(&app_args, watch_dir);
+    }
+    let started = std::time::Instant::now();
+    let conversion = convert(&app_args);
+    if let (Some(summary_file), Ok(report)) = (&app_args.summary_json, &conversion) {
+        if let Err(failure) = write_summary_json(summary_file, report, &app_args, started.elapsed())
+        {
+            eprintln!("{}", failure.message);
+            return failure.exit_code;
+        }
+    }
+    match conversion {
+        Ok(report) if report.problems.is_empty() => 0,
+        Ok(_) => EXIT_CODE_INPUT_ERROR,
+        Err(failure) => {
+            eprintln!("{}", failure.message);
+            failure.exit_code
+        }
+    }
+}
+
+/// Writes the [ConversionSummary](../tlcfi_assimilator/conversion_summary/struct.ConversionSummary.html) of a
+/// conversion that didn't fail as JSON.
+fn write_summary_json(
+    summary_file: &str,
+    report: &ConversionReport,
+    app_args: &AppArgs,
+    runtime: std::time::Duration,
+) -> Result<(), ConversionFailure> {
+    let stats = &report.stats;
+    // Every written file starts with an information message, the stats count the other types
+    let vlog_information_messages = report.vlog_message_count.saturating_sub(
+        stats.time_references
+            + stats.signal_change_messages
+            + stats.detector_change_messages
+            + stats.output_change_messages,
+    );
+    let summary = ConversionSummary {
+        input_files: log_files(app_args),
+        start_time: report.start_time,
+        tlc_name: report.tlc_name.clone(),
+        output_files: report.file_names.clone(),
+        runtime_ms: runtime.as_millis() as u64,
+        ..ConversionSummary::from_stats(stats, vlog_information_messages)
     };
+    create_parent_dirs(summary_file)
+        .and_then(|_| {
+            let json = serde_json::to_string_pretty(&summary).map_err(io::Error::from)?;
+            fs::write(summary_file, json)
+        })
+        .map_err(|error| ConversionFailure {
+            exit_code: EXIT_CODE_OUTPUT_ERROR,
+            message: format!(
+                "Error: failed to write the summary to '{}': {}",
+                summary_file, error
+            ),
+        })
+}
 
-    read_lines_and_save_changes(&mut data);
-    let tlc_name =
-        vlog_transformer::load_tlc_name(&app_args.vlog_tlcfi_mapping_file).expect(&format!(
-            "Couldn't find a TLC name in the given VLog TLC FI mapping file: {:?}",
-            &app_args.vlog_tlcfi_mapping_file
+/// Reports every structural problem of the mapping file and returns the exit code to stop the program with.
+fn run_mapping_validation(app_args: &AppArgs) -> i32 {
+    let console = Console::new(app_args.verbosity);
+    let problems = match fs::read_to_string(&app_args.vlog_tlcfi_mapping_file)
+        .and_then(|content| mapping_validation::validate_mapping(&content))
+    {
+        Ok(problems) => problems,
+        Err(error) => {
+            eprintln!(
+                "Error: failed to read the VLog TLC FI mapping file {:?}: {}",
+                &app_args.vlog_tlcfi_mapping_file, error
+            );
+            return EXIT_CODE_INPUT_ERROR;
+        }
+    };
+    for problem in &problems {
+        console.error(&format!("Problem: {}.", problem));
+    }
+    if problems.is_empty() {
+        console.info(&format!(
+            "The mapping file '{}' has no problems.",
+            app_args.vlog_tlcfi_mapping_file
         ));
+        0
+    } else {
+        EXIT_CODE_MAPPING_ERROR
+    }
+}
 
-    let vlog_messages = vlog_transformer::to_vlog(
-        data.changes,
-        start_time,
-        &app_args.vlog_tlcfi_mapping_file,
-        &tlc_name,
-    );
+/// Writes a mapping file skeleton with the names in the log and returns the exit code to stop the program with.
+fn run_mapping_generation(app_args: &AppArgs, output_file: &str) -> i32 {
+    match generate_mapping(app_args, output_file) {
+        Ok(()) => 0,
+        Err(failure) => {
+            eprintln!("{}", failure.message);
+            failure.exit_code
+        }
+    }
+}
 
-    let file_name = create_file_name(&tlc_name, start_time);
+/// Writes the signals and detectors of the log to a mapping file, in the order they first appear.
+fn generate_mapping(app_args: &AppArgs, output_file: &str) -> Result<(), ConversionFailure> {
+    check_not_overwriting(output_file, app_args)?;
+    let mut console = Console::new(app_args.verbosity);
+    let sorted_lines = sort_lines(
+        &app_args.tlcfi_log_file,
+        &app_args.is_chronological,
+        &mut console,
+    )
+    .map_err(|error| ConversionFailure {
+        exit_code: EXIT_CODE_INPUT_ERROR,
+        message: format!(
+            "Error: failed to read the log '{}': {}",
+            app_args.tlcfi_log_file, error
+        ),
+    })?;
+    let mut data = AssimilationData {
+        console,
+        sorted_lines,
+        ..Default::default()
+    };
+    read_lines_and_save_changes(&mut data, app_args).map_err(|problem| ConversionFailure {
+        exit_code: EXIT_CODE_INPUT_ERROR,
+        message: format!(
+            "Error: {}",
+            describe_line_problem(&problem, &data, sorted_log_line_count(&data), app_args)
+        ),
+    })?;
+    if data.changes.is_empty() {
+        return Err(no_messages_failure(app_args, &data.stats));
+    }
 
-    let mut file = File::create(&file_name).expect(&format!(
-        "Failed to create the file '{}' for saving the VLog output.",
-        &file_name
+    let names_in_order = mapping_generation::collect_names_in_order(&data.changes);
+    fs::write(
+        output_file,
+        mapping_generation::generate_mapping_file(&names_in_order),
+    )
+    .map_err(|error| ConversionFailure {
+        exit_code: EXIT_CODE_OUTPUT_ERROR,
+        message: format!(
+            "Error: failed to write the mapping to '{}': {}",
+            output_file, error
+        ),
+    })?;
+    data.console.print_summary();
+    data.console.info(&format!(
+        "Wrote a mapping with {} signals and {} detectors to: {}. Replace its TLC name {} with the real one.",
+        names_in_order.signals.len(),
+        names_in_order.detectors.len(),
+        output_file,
+        mapping_generation::PLACEHOLDER_TLC_NAME
     ));
-    println!("Created file: {}", &file_name);
+    Ok(())
+}
 
-    for msg in vlog_messages {
-        write!(file, "{}\r\n", msg).expect(&format!(
-            "Failed to write line {:?} to the VLog output file.",
-            msg
-        ));
+/// Converts the TLC-FI log into a VLog file as described by the given arguments.
+///
+/// The conversion is a pipeline of steps: reading the log, finding its start, parsing it into changes, preparing the
+/// changes for the output and writing them, to a new file or by appending or following.
+fn convert(app_args: &AppArgs) -> Result<ConversionReport, ConversionFailure> {
+    if let Some(checkpoint_file) = &app_args.checkpoint {
+        return convert_checkpointed(
+            app_args,
+            checkpoint_file,
+            checkpointing::LINES_PER_CHECKPOINT,
+            &mut |_| true,
+        );
+    }
+    let mut console = Console::new(app_args.verbosity);
+    let cache_key = conversion_cache_key(app_args)?;
+    if let Some(report) = restore_cached_conversion(app_args, &cache_key, &mut console)? {
+        return Ok(report);
     }
-}
 
-fn sort_lines(tlcfi_log_file: &str, is_chronological: &bool) -> Vec<String> {
-    let tlcfi_log_file =
-        File::open(tlcfi_log_file).expect("Couldn't open the given file path for the TLC FI logs");
-    let reader = BufReader::new(tlcfi_log_file);
-    let mut time_sorted_lines = Vec::new();
-    for line_res in reader.lines() {
-        if let Ok(line) = line_res {
-            if *is_chronological {
-                time_sorted_lines.push(line);
-            } else {
-                time_sorted_lines.insert(0, line);
-            }
-        } else {
-            eprintln!("Failed to read line {:?}", line_res)
+    let (mut log_lines, log_tail) = read_log(app_args, &mut console)?;
+    let clock_steps = find_clock_steps_reporting(&mut log_lines, app_args, &mut console)?;
+    let log_start_time = find_start_time(&log_lines, app_args, &mut console)?;
+    let mut data = AssimilationData {
+        stats: ConversionStats {
+            clock_steps,
+            ..Default::default()
+        },
+        rejected_lines: app_args.reject_file.as_ref().map(|_| Vec::new()),
+        predictions: app_args.predictions_csv.as_ref().map(|_| Vec::new()),
+        ..assimilation_data(app_args, log_start_time, console)
+    };
+    // The log is parsed in its own time, the output is named and timed in the output time zone
+    let start_time = &to_output_time(&log_start_time, app_args);
+    report_directions(app_args, &mut data.console);
+
+    // The TLC name is loaded before parsing, so an existing VLog file can be refused without waiting for it.
+    // A dry run doesn't write a file, it reports a missing TLC name as a problem. The TLC name of the mapping from
+    // the log is only known after parsing it.
+    let is_tlc_name_from_log = app_args.mapping_from_log && app_args.tlc_name.is_none();
+    let tlc_name = if app_args.dry_run || is_tlc_name_from_log {
+        String::new()
+    } else {
+        load_tlc_name(app_args)?
+    };
+    if !app_args.dry_run
+        && !is_tlc_name_from_log
+        && app_args.append.is_none()
+        && app_args.split == SplitMode::None
+        && app_args.name_date == NameDate::WindowStart
+        && !app_args.skip_until_first_signal
+    {
+        check_not_overwriting(&create_file_name(&tlc_name, start_time, app_args), app_args)?;
+    }
+    let append_target = read_append_target(app_args, start_time)?;
+
+    parse_log(&mut data, log_lines, app_args)?;
+    let mapping_from_log_file = use_mapping_from_log(&mut data, app_args, start_time)?;
+    // The rest of the conversion uses the mapping from the log like a mapping file
+    let mapping_from_log_args;
+    let app_args = match &mapping_from_log_file {
+        Some(mapping_file) => {
+            mapping_from_log_args = AppArgs {
+                vlog_tlcfi_mapping_file: mapping_file.clone(),
+                ..app_args.clone()
+            };
+            &mapping_from_log_args
+        }
+        None => app_args,
+    };
+    let tlc_name = if is_tlc_name_from_log && !app_args.dry_run {
+        load_tlc_name(app_args)?
+    } else {
+        tlc_name
+    };
+    let prepared = prepare_changes(&mut data, app_args, start_time);
+    let start_time = &prepared.start_time;
+
+    if app_args.dry_run {
+        if let Some(mapping_file) = &mapping_from_log_file {
+            let _ = fs::remove_file(mapping_file);
         }
+        return Ok(finish_dry_run(data, app_args, start_time));
     }
-    time_sorted_lines
-}
+    check_changes_are_convertible(&mut data, app_args)?;
+    if let Some(append_target) = append_target {
+        return convert_appending(data, app_args, append_target).map(|report| ConversionReport {
+            start_time: Some(*start_time),
+            tlc_name: Some(tlc_name),
+            ..report
+        });
+    }
+    let file_name_date_time =
+        choose_file_name_date_time(app_args.name_date, start_time, &data.changes);
+    if app_args.split == SplitMode::None {
+        data.console.info(&format!(
+            "Naming the VLog file after {} ({}).",
+            file_name_date_time.date(),
+            app_args.name_date.name()
+        ));
+    }
+    let mapping_file = output_mapping_file(app_args, &tlc_name, &file_name_date_time, &mut data)?;
 
-fn read_lines_and_save_changes(data: &mut AssimilationData) {
-    for line in data.sorted_lines.clone() {
-        let filtered_line = line.replace("\"\"", "\"");
-        let split_line: Vec<&str> = filtered_line.split("- ").collect();
+    if let Some(log_tail) = log_tail {
+        return convert_following(
+            prepared.deduplicator,
+            log_tail,
+            data,
+            app_args,
+            &mapping_file,
+            &tlc_name,
+            &file_name_date_time,
+        )
+        .map(|report| ConversionReport {
+            start_time: Some(*start_time),
+            tlc_name: Some(tlc_name.clone()),
+            ..report
+        });
+    }
 
-        if split_line.len() != 3 {
-            // This program is only familiar with lines that split into three parts with "- "
-            continue;
+    let change_count = data.changes.len();
+    let (file_names, vlog_message_count) = write_vlog_files(
+        &mut data,
+        app_args,
+        start_time,
+        &file_name_date_time,
+        &mapping_file,
+        &tlc_name,
+    )?;
+    warn_about_unseen_names(&prepared, &mut data.console);
+    data.console
+        .add_repeated_warnings(&data.stats.transform_warnings);
+    data.console.print_summary();
+    let summary = conversion_summary_lines(&data.stats, start_time, app_args);
+    for line in &summary {
+        data.console.report(line);
+    }
+    if let (Some(cache_dir), Some(cache_key)) = (&app_args.cache_dir, &cache_key) {
+        let conversion = conversion_cache::CachedConversion {
+            file_names: file_names.clone(),
+            change_count,
+            vlog_message_count,
+            warnings: data.console.all_warnings(),
+            summary,
+            stats: data.stats.clone(),
+            start_time: *start_time,
+            tlc_name: tlc_name.clone(),
+        };
+        match conversion_cache::store(cache_dir, cache_key, &conversion) {
+            Ok(_) => data.console.report("  cache: miss, stored the conversion"),
+            Err(error) => data.console.warning(&format!("Warning: {}", error)),
         }
+    }
 
-        // Only consider message from the TLC.
-        if split_line[1].contains("IN") {
-            if data.first_tick == Option::None {
-                data.first_tick = tlcfi_parsing::find_first_tick(&split_line[2]);
-            }
-            if data.first_tick.is_some() {
-                if let Ok(timestamped_changes_res) =
-                    tlcfi_parsing::parse_string(split_line[2], data)
-                {
-                    data.changes.extend(timestamped_changes_res)
-                }
-            } else {
-                eprintln!("Didn't find a first tick yet!")
-            }
+    Ok(ConversionReport {
+        file_names,
+        change_count,
+        vlog_message_count,
+        problems: Vec::new(),
+        warnings: data.console.all_warnings(),
+        stats: data.stats,
+        start_time: Some(*start_time),
+        tlc_name: Some(tlc_name),
+    })
+}
+
+/// The key of the conversion in the cache, None when it isn't cached.
+///
+/// Pacing, following and teeing are about when and where the messages are written, so they can't be served from
+/// the cache. It only keeps the VLog files, so a conversion that writes a renumbered mapping, the mapping from the log,
+/// a reject file or a predictions CSV isn't cached.
+fn conversion_cache_key(app_args: &AppArgs) -> Result<Option<String>, ConversionFailure> {
+    match &app_args.cache_dir {
+        Some(_)
+            if !app_args.dry_run
+                && !app_args.pace
+                && !app_args.follow
+                && !app_args.tee
+                && !app_args.renumber_ids
+                && !app_args.mapping_from_log
+                && app_args.reject_file.is_none()
+                && app_args.predictions_csv.is_none() =>
+        {
+            conversion_cache::cache_key(app_args)
+                .map(Some)
+                .map_err(|error| ConversionFailure {
+                    exit_code: EXIT_CODE_INPUT_ERROR,
+                    message: format!("Error: {}", error),
+                })
         }
+        _ => Ok(None),
     }
 }
 
-fn create_file_name(tlc_name: &str, start_date_time: &NaiveDateTime) -> String {
-    let date_part = start_date_time.date().to_string().replace("-", "");
-    let time_part = &start_date_time.time().to_string().replace(":", "")[0..6];
-    String::from(tlc_name) + "_" + &date_part + "_" + &time_part + ".vlg"
+/// Restores the VLog files of an earlier conversion of the same inputs from the cache, if it has them.
+/// Existing files are only replaced with `--overwrite`, like converting would.
+fn restore_cached_conversion(
+    app_args: &AppArgs,
+    cache_key: &Option<String>,
+    console: &mut Console,
+) -> Result<Option<ConversionReport>, ConversionFailure> {
+    let (cache_dir, cache_key) = match (&app_args.cache_dir, cache_key) {
+        (Some(cache_dir), Some(cache_key)) => (cache_dir, cache_key),
+        _ => return Ok(None),
+    };
+    let cached = match conversion_cache::lookup(cache_dir, cache_key) {
+        Some(cached) => cached,
+        None => return Ok(None),
+    };
+    for file_name in &cached.file_names {
+        check_not_overwriting(file_name, app_args)?;
+    }
+    if conversion_cache::restore(cache_dir, cache_key, &cached).is_none() {
+        return Ok(None);
+    }
+    for file_name in &cached.file_names {
+        console.info(&format!("Restored file from the cache: {}", file_name));
+    }
+    for line in cached.warnings.iter().chain(cached.summary.iter()) {
+        console.report(line);
+    }
+    console.report("  cache: hit");
+    Ok(Some(ConversionReport {
+        file_names: cached.file_names,
+        change_count: cached.change_count,
+        vlog_message_count: cached.vlog_message_count,
+        problems: Vec::new(),
+        warnings: cached.warnings,
+        stats: cached.stats,
+        start_time: Some(cached.start_time),
+        tlc_name: Some(cached.tlc_name),
+    }))
 }
 
-fn parse_args() -> Result<AppArgs, pico_args::Error> {
-    let mut pargs = pico_args::Arguments::from_env();
+/// Reads the lines of the log, and for following the tail of the log that continues after them.
+/// A log without a single log line has nothing to convert.
+fn read_log(
+    app_args: &AppArgs,
+    console: &mut Console,
+) -> Result<(LogLines, Option<following::LogTail>), ConversionFailure> {
+    let read_failure = |error| log_read_failure(app_args, error);
+    // A log that is still being written grows at the end, so it's read from its start to where following continues
+    if app_args.follow {
+        let mut log_tail =
+            following::LogTail::open(&app_args.tlcfi_log_file).map_err(read_failure)?;
+        let lines = log_tail.read_new_lines().map_err(read_failure)?;
+        return Ok((LogLines::Sorted(lines), Some(log_tail)));
+    }
 
-    if pargs.contains(["-h", "--help"]) {
-        println!("{}", ARGS_HELP);
-        std::process::exit(0);
+    let is_compressed = compressed_logs::is_gzip_file_name(&app_args.tlcfi_log_file)
+        || zip_archive::is_zip_file(&app_args.tlcfi_log_file);
+    let log_files = log_files(app_args);
+    let mut progress = Progress::new(
+        "Reading the log",
+        log_files
+            .iter()
+            .map(|file_name| progress::log_file_size(file_name, is_compressed))
+            .sum(),
+        app_args.show_progress && app_args.verbosity >= Verbosity::Normal,
+    );
+    let on_line = &mut |line_length| progress.line_done(line_length);
+    // Repairing the clock steps changes the lines, so they have to be in memory for it
+    let log_lines = if is_newest_first_log(&log_files, app_args.is_chronological)
+        && !app_args.repair_clock_steps
+    {
+        LogLines::from_end(&log_files[0], console, on_line)
+    } else {
+        sort_lines_reporting(&log_files, &app_args.is_chronological, console, on_line)
+            .map(LogLines::Sorted)
     }
+    .map_err(read_failure)?;
+    progress.finish();
 
-    let args = AppArgs {
-        is_chronological: pargs
-            .opt_value_from_str("--chronological")?
-            .unwrap_or(false),
-        start_date_time: pargs.opt_value_from_fn("--start-date-time", parse_date_time)?,
-        tlcfi_log_file: pargs
-            .opt_value_from_fn("--tlcfi-log-file", check_file_existence)?
-            .unwrap_or("tlcfi.txt".to_string()),
-        vlog_tlcfi_mapping_file: pargs.free_from_fn(check_file_existence)?,
-    };
-    Ok(args)
+    if !log_lines
+        .oldest_first()
+        .map_err(read_failure)?
+        .any(|line| is_log_line(&line, &app_args.log_pattern))
+    {
+        let stats = ConversionStats {
+            lines_read: log_lines.line_count(),
+            ..Default::default()
+        };
+        return Err(no_messages_failure(app_args, &stats));
+    }
+    Ok((log_lines, None))
 }
 
-fn parse_date_time(arg: &str) -> Result<NaiveDateTime, String> {
-    // <Year-month-day format (ISO 8601). Same to %Y-%m-%d><T><Hour-minute-second format. Same to %H:%M:%S><Similar to .%f but left-aligned. These all consume the leading dot.>
-    match NaiveDateTime::parse_from_str(arg, "%FT%T%.3f") {
-        Ok(date_time) => Ok(date_time),
-        Err(error) => Err(format!(
-            "Failed to transform argument {} into a date time:\n{}",
-            arg, error
-        )),
+/// Finds the steps of the logger's clock and warns about them, repairing the line times with `--repair-clock-steps`.
+/// The start time is taken from the line times, so they are repaired before it's found.
+fn find_clock_steps_reporting(
+    log_lines: &mut LogLines,
+    app_args: &AppArgs,
+    console: &mut Console,
+) -> Result<Vec<ClockStep>, ConversionFailure> {
+    let mut clock_steps = clock_steps::find_clock_steps(
+        log_lines
+            .oldest_first()
+            .map_err(|error| log_read_failure(app_args, error))?,
+        &app_args.log_pattern,
+        app_args.invert_direction,
+        app_args.tick_unit,
+        clock_steps::CLOCK_STEP_THRESHOLD_MS,
+    );
+    // The local clock of a log in a time zone moves an hour when summer time starts or ends, the ticks don't
+    if let Some(time_zone) = app_args.time_zone {
+        clock_steps.retain(|clock_step| {
+            !time_zone.is_summer_time_change(&clock_step.before, clock_step.step_ms)
+        });
+    }
+    if let (true, LogLines::Sorted(lines)) = (app_args.repair_clock_steps, log_lines) {
+        clock_steps::repair_line_times(lines, &mut clock_steps);
+    }
+    for clock_step in &clock_steps {
+        console.warning(&format!(
+            "Warning: {}{}",
+            clock_steps::describe(clock_step),
+            if clock_step.repaired {
+                ""
+            } else {
+                ", use --repair-clock-steps to correct them"
+            }
+        ));
     }
+    Ok(clock_steps)
 }
 
-fn get_start_date_time_from_file(
-    sorted_lines: &Vec<String>,
-) -> Result<NaiveDateTime, pico_args::Error> {
-    let mut date_time_bit = String::new();
-    for line in sorted_lines {
-        let split_line: Vec<&str> = line.split("- ").collect();
-
-        // if it is a logline
-        if line.len() >= 23 && split_line.len() == 3 {
-            date_time_bit = line[0..23].to_string();
-            break;
+/// The start of the conversion in the time of the log: the given start date time, which is checked against the log,
+/// or the time of the first log line.
+fn find_start_time(
+    log_lines: &LogLines,
+    app_args: &AppArgs,
+    console: &mut Console,
+) -> Result<NaiveDateTime, ConversionFailure> {
+    let read_failure = |error| log_read_failure(app_args, error);
+    let start_date_time = match app_args.start_date_time {
+        Some(start_date_time) => start_date_time,
+        None => {
+            return get_start_date_time_from_file(
+                log_lines.oldest_first().map_err(read_failure)?,
+                &app_args.log_pattern,
+            )
+            .map_err(|error| ConversionFailure {
+                exit_code: EXIT_CODE_INPUT_ERROR,
+                message: format!("Error: failed to get start date time from logs, set it in application arguments instead or filter the logs. {}", error),
+            })
+        }
+    };
+    if let Some(mismatch) = describe_start_mismatch(
+        &start_date_time,
+        log_lines.oldest_first().map_err(read_failure)?,
+        log_lines.newest_first().map_err(read_failure)?,
+        &app_args.log_pattern,
+        app_args.start_tolerance_s,
+    ) {
+        if app_args.strict {
+            return Err(ConversionFailure {
+                exit_code: EXIT_CODE_INPUT_ERROR,
+                message: format!("Error: {}.", mismatch),
+            });
         }
+        console.warning(&format!(
+            "Warning: {}. Check its date and --timezone, --strict stops at this.",
+            mismatch
+        ));
     }
+    Ok(start_date_time)
+}
 
-    date_time_bit = date_time_bit.replace(",", ".").replace(" ", "T");
+/// The parsing state of a log that starts at the given time in the time of the log, set up by the options.
+fn assimilation_data(
+    app_args: &AppArgs,
+    start_time: NaiveDateTime,
+    console: Console,
+) -> AssimilationData {
+    AssimilationData {
+        start_time,
+        conversion_mode: app_args.conversion_mode,
+        time_source: app_args.time_source,
+        tick_unit: app_args.tick_unit,
+        log_pattern: app_args.log_pattern.clone(),
+        log_levels: app_args.log_levels.clone(),
+        logger_name: app_args.logger_name.clone(),
+        update_methods: app_args.update_methods.clone(),
+        console,
+        first_tick: app_args.first_tick,
+        ..Default::default()
+    }
+}
 
-    match parse_date_time(&date_time_bit) {
-        Ok(date_time) => Ok(date_time),
-        Err(error) => Err(pico_args::Error::ArgumentParsingFailed {
-            cause: format!("--start-date-time wasn't given and we couldn't extract it from the log file. Failed with error: {}", error),
-        }),
+/// The options of the VLog transformer that come from the arguments.
+fn transform_options(app_args: &AppArgs) -> vlog_transformer::TransformOptions {
+    vlog_transformer::TransformOptions {
+        conversion_mode: app_args.conversion_mode,
+        verbosity: app_args.verbosity,
+        signal_state_encoding: app_args.signal_state_encoding,
+        time_zone: output_time_zone(app_args),
     }
 }
 
-fn check_file_existence(file_name: &str) -> Result<String, String> {
-    match File::open(file_name) {
-        Ok(_) => Ok(file_name.to_string()),
-        Err(error) => Err(format!(
-            "File name passed as argument '{}' could not be opened. Did you make a typo?\n{}",
-            file_name, error
-        )),
+/// Tells which messages are converted when it isn't the default of the ones coming from the TLC.
+fn report_directions(app_args: &AppArgs, console: &mut Console) {
+    if app_args.invert_direction {
+        console.info(
+            "Direction inversion is active: OUT messages are treated as coming from the TLC.",
+        );
+    }
+    match app_args.direction {
+        MessageDirection::In => {}
+        MessageDirection::Out => console
+            .info("Converting the messages going to the TLC instead of the ones coming from it."),
+        MessageDirection::Both => {
+            console.info("Converting the messages of both directions, merged by their time.")
+        }
     }
 }
 
-#[derive(Debug)]
-struct AppArgs {
-    is_chronological: bool,
-    start_date_time: Option<NaiveDateTime>,
-    tlcfi_log_file: String,
-    vlog_tlcfi_mapping_file: String,
+/// Reads the VLog file to append to, refusing a log that starts before its end before the log is parsed.
+fn read_append_target(
+    app_args: &AppArgs,
+    start_time: &NaiveDateTime,
+) -> Result<Option<appending::AppendTarget>, ConversionFailure> {
+    let file_name = match &app_args.append {
+        Some(file_name) => file_name,
+        None => return Ok(None),
+    };
+    let target = appending::read_append_target(file_name).map_err(|error| ConversionFailure {
+        exit_code: EXIT_CODE_OUTPUT_ERROR,
+        message: format!("Error: {}", error),
+    })?;
+    appending::check_start(&target, start_time).map_err(|error| ConversionFailure {
+        exit_code: EXIT_CODE_INPUT_ERROR,
+        message: format!("Error: {}, it can't be appended", error),
+    })?;
+    Ok(Some(target))
 }
 
-#[cfg(test)]
-mod test {
+/// Parses the lines of the log into changes, writing the reject file and the predictions CSV on the way.
+/// Stops at a line strict mode can't parse, too many lines that can't be parsed and, with `--strict-rpc`,
+/// error responses of the TLC.
+fn parse_log(
+    data: &mut AssimilationData,
+    mut log_lines: LogLines,
+    app_args: &AppArgs,
+) -> Result<(), ConversionFailure> {
+    let (line_count, byte_count) = (log_lines.line_count(), log_lines.byte_count());
+    data.sorted_lines = log_lines.take_sorted_lines();
+    let show_progress = app_args.show_progress && app_args.verbosity >= Verbosity::Normal;
+    let mut progress = Progress::new("Converting", Some(byte_count), show_progress);
+    let on_line = &mut |line_length| progress.line_done(line_length);
+    let parsed = match &log_lines {
+        LogLines::FromEnd { .. } => parse_line_stream_reporting(
+            data,
+            log_lines
+                .oldest_first()
+                .map_err(|error| log_read_failure(app_args, error))?,
+            app_args,
+            on_line,
+        ),
+        LogLines::Sorted(_) => read_lines_and_save_changes_reporting(data, app_args, on_line),
+    };
+    progress.finish();
+    if let Some(reject_file) = &app_args.reject_file {
+        write_reject_file(reject_file, data, line_count, app_args)?;
+    }
+    if let Some(predictions_csv) = &app_args.predictions_csv {
+        write_predictions_csv(predictions_csv, data)?;
+    }
+    if let Err(problem) = parsed {
+        return Err(ConversionFailure {
+            exit_code: EXIT_CODE_INPUT_ERROR,
+            message: format!(
+                "Error: {}",
+                describe_line_problem(&problem, data, line_count, app_args)
+            ),
+        });
+    }
+    if let Some(message) = too_many_line_errors(&data.stats.line_errors, app_args.max_errors) {
+        return Err(ConversionFailure {
+            exit_code: EXIT_CODE_INPUT_ERROR,
+            message: format!("Error: {}", message),
+        });
+    }
+    if let (Some(rpc_error), true) = (data.stats.rpc_errors.first(), app_args.strict_rpc) {
+        return Err(ConversionFailure {
+            exit_code: EXIT_CODE_INPUT_ERROR,
+            message: format!(
+                "Error: the TLC sent {} JSON-RPC error response(s), the first is {}",
+                data.stats.rpc_errors.len(),
+                rpc_error.describe()
+            ),
+        });
+    }
+    Ok(())
+}
 
-    use super::*;
-    use chrono::{NaiveDate, NaiveDateTime};
-    use std::fs::read_to_string;
-    use tlcfi_assimilator::{self, DetectorState};
+/// Compares the meta in the log with the mapping file and, with `--mapping-from-log`, writes the mapping from the log
+/// next to the VLog file. Returns that mapping file, None when the mapping file is used.
+fn use_mapping_from_log(
+    data: &mut AssimilationData,
+    app_args: &AppArgs,
+    start_time: &NaiveDateTime,
+) -> Result<Option<String>, ConversionFailure> {
+    let has_mapping_file = !app_args.vlog_tlcfi_mapping_file.is_empty();
+    if !data.meta.is_empty() && has_mapping_file {
+        warn_about_meta_mismatch(&data.meta, app_args, &mut data.console);
+    }
+    if !app_args.mapping_from_log {
+        return Ok(None);
+    }
+    if data.meta.is_empty() && has_mapping_file {
+        data.console.warning(
+            "Warning: the log has no ReadMeta responses to take the mapping from, the mapping file is used instead.",
+        );
+        return Ok(None);
+    }
+    let mapping_file = write_mapping_from_log(&data.meta, app_args, start_time)?;
+    data.console.info(&format!(
+        "Wrote the mapping from the log to: {}",
+        mapping_file
+    ));
+    Ok(Some(mapping_file))
+}
 
-    const RELATIVE_TLCFI_FILE_PATH: &str = "./tlcfi.txt";
-    const RELATIVE_VLOG_MAPPING_FILE_PATH: &str = "./vlog_tlcfi_mapping.txt";
+/// The changes after preparing them for the output.
+struct PreparedChanges {
+    /// The start of the output, after the changes skipped by `--skip-until-first-signal`
+    start_time: NaiveDateTime,
+    /// The names of the filters that no change had
+    unseen_signal_names: Vec<String>,
+    unseen_detector_names: Vec<String>,
+    /// The last states of the written changes, which following continues deduplicating from
+    deduplicator: Deduplicator,
+}
 
-    fn get_test_start_time() -> NaiveDateTime {
-        NaiveDate::from_ymd(2021, 12, 15).and_hms(11, 00, 00)
+/// Shapes the parsed changes into the ones that are written: inserts the reset snapshots, skips the warm-up, limits
+/// the duration, applies the filters and the amber policy, leaves out repeated states and limits the number of changes.
+fn prepare_changes(
+    data: &mut AssimilationData,
+    app_args: &AppArgs,
+    start_time: &NaiveDateTime,
+) -> PreparedChanges {
+    if app_args.reset_snapshot {
+        controller_resets::insert_reset_snapshots(&mut data.changes, &data.stats.controller_resets);
+    }
+    // The output starts at the first signal change, so the start moves forward with the skipped changes
+    let start_time = if app_args.skip_until_first_signal {
+        skip_warm_up(data, start_time)
+    } else {
+        *start_time
+    };
+    if let Some(duration_in_s) = app_args.duration_in_s {
+        limit_changes_to_duration(&mut data.changes, duration_in_s);
+    }
+    let unseen_signal_names = match &app_args.signal_filter {
+        Some(filter) => change_filtering::filter_signals(&mut data.changes, filter),
+        None => Vec::new(),
+    };
+    let unseen_detector_names = match &app_args.detector_filter {
+        Some(filter) => change_filtering::filter_detectors(&mut data.changes, filter),
+        None => Vec::new(),
+    };
+    data.stats.collapsed_amber_states +=
+        amber_policy::collapse_amber(&mut data.changes, app_args.amber_policy);
+    let mut deduplicator = Deduplicator::default();
+    if app_args.dedupe {
+        let suppressed = deduplicator.dedupe(&mut data.changes);
+        data.stats.suppressed_signal_states += suppressed.signals;
+        data.stats.suppressed_detector_states += suppressed.detectors;
+    }
+    if let Some(limit) = app_args.limit {
+        data.stats.truncated_changes = limit_changes(&mut data.changes, limit);
+        if data.stats.truncated_changes > 0 {
+            data.console.warning(&format!(
+                "Warning: the output is truncated after the first {} change(s) because of --limit, {} change(s) are left out",
+                limit, data.stats.truncated_changes
+            ));
+        }
     }
+    PreparedChanges {
+        start_time,
+        unseen_signal_names,
+        unseen_detector_names,
+        deduplicator,
+    }
+}
 
-    #[test]
-    fn checking_file_existence_of_valid_file_should_return_ok() {
-        let file_existence = check_file_existence(RELATIVE_TLCFI_FILE_PATH);
-        assert!(file_existence.is_ok());
+/// Reports what a conversion would write, with the problems that would stop it.
+fn finish_dry_run(
+    data: AssimilationData,
+    app_args: &AppArgs,
+    start_time: &NaiveDateTime,
+) -> ConversionReport {
+    let problems = find_dry_run_problems(&data.changes, app_args);
+    report_dry_run(&data.changes, start_time, &problems, &data.console);
+    data.console.print_summary();
+    ConversionReport {
+        change_count: data.changes.len(),
+        problems,
+        warnings: data.console.all_warnings(),
+        stats: data.stats,
+        start_time: Some(*start_time),
+        ..Default::default()
     }
+}
 
-    #[test]
-    fn checking_file_existence_of_invalid_file_should_return_err() {
-        let file_existence = check_file_existence("./thisfiledoesnot.exist");
-        assert!(file_existence.is_err());
+/// Refuses changes that can't be converted: none at all, unless following where they can still come in, or names
+/// the mapping file doesn't have. Checked up front, so an incomplete mapping doesn't stop the conversion halfway.
+fn check_changes_are_convertible(
+    data: &mut AssimilationData,
+    app_args: &AppArgs,
+) -> Result<(), ConversionFailure> {
+    if data.changes.is_empty() {
+        if !app_args.follow {
+            return Err(no_messages_failure(app_args, &data.stats));
+        }
+        let description = describe_examined_lines(&data.stats, app_args);
+        data.console.warning(&format!(
+            "Warning: no signal or detector messages to convert yet, {}",
+            description
+        ));
     }
+    let mapping_problems = find_mapping_problems(&data.changes, app_args);
+    if !mapping_problems.is_empty() {
+        return Err(ConversionFailure {
+            exit_code: EXIT_CODE_MAPPING_ERROR,
+            message: format!(
+                "Error: the VLog TLC FI mapping file {:?} can't be used: {}.",
+                &app_args.vlog_tlcfi_mapping_file,
+                mapping_problems.join("; ")
+            ),
+        });
+    }
+    warn_about_mapping_mismatch(&data.changes, app_args, &mut data.console)
+}
 
-    #[test]
-    fn parse_date_time_of_valid_iso_8601_str_should_return_ok() {
-        let valid_time_stamp = "2021-12-15T11:00:00.000";
-        let parsed_date_time = parse_date_time(valid_time_stamp);
-        assert_eq!(parsed_date_time, Ok(get_test_start_time()));
+/// The mapping file the VLog messages are made with, which is a renumbered copy with `--renumber-ids`.
+fn output_mapping_file(
+    app_args: &AppArgs,
+    tlc_name: &str,
+    file_name_date_time: &NaiveDateTime,
+    data: &mut AssimilationData,
+) -> Result<String, ConversionFailure> {
+    if !app_args.renumber_ids {
+        return Ok(app_args.vlog_tlcfi_mapping_file.clone());
     }
+    let renumbered_mapping_file =
+        write_renumbered_mapping(app_args, tlc_name, file_name_date_time)?;
+    data.console.info(&format!(
+        "Wrote the renumbered mapping to: {}",
+        renumbered_mapping_file
+    ));
+    Ok(renumbered_mapping_file)
+}
 
-    #[test]
-    fn parse_date_time_of_invalid_iso_8601_str_should_return_err() {
-        assert!(parse_date_time("2021-12-15T11:00:00,000").is_err());
-        assert!(parse_date_time("2021-12-15 11:00:00.000").is_err());
+/// Writes the changes to VLog files, one per hour with `--split hourly` and rotated or compressed as the size limits
+/// need. Returns the names of the written files and the number of messages in them.
+fn write_vlog_files(
+    data: &mut AssimilationData,
+    app_args: &AppArgs,
+    start_time: &NaiveDateTime,
+    file_name_date_time: &NaiveDateTime,
+    mapping_file: &str,
+    tlc_name: &str,
+) -> Result<(Vec<String>, usize), ConversionFailure> {
+    let changes = std::mem::take(&mut data.changes);
+    let parts = output_splitting::split_changes(changes, start_time, app_args.split);
+    let size_estimates: Vec<u64> = parts
+        .iter()
+        .map(|part| vlog_transformer::estimate_vlog_size(&part.changes, tlc_name))
+        .collect();
+    if let Some(max_output_size) = app_args.max_output_size {
+        let largest_estimate = size_estimates.iter().max().copied().unwrap_or(0);
+        if largest_estimate > max_output_size && app_args.size_policy == SizePolicy::Abort {
+            return Err(ConversionFailure {
+                exit_code: EXIT_CODE_OUTPUT_ERROR,
+                message: format!(
+                    "Error: the VLog file is estimated at {} bytes, more than the --max-output-size of {} bytes. Use --size-policy compress or rotate to write it anyway.",
+                    largest_estimate, max_output_size
+                ),
+            });
+        }
     }
+    let mut pacer = if app_args.pace {
+        Some(pacing::Pacer::new(app_args.pace_speed))
+    } else {
+        None
+    };
+    let mut file_names = Vec::new();
+    let mut vlog_message_count = 0;
+    for (part, size_estimate) in parts.into_iter().zip(size_estimates) {
+        let is_over_budget = matches!(
+            app_args.max_output_size,
+            Some(max_output_size) if size_estimate > max_output_size
+        );
+        let mut part_stats = ConversionStats::default();
+        let offset_ms = part.offset_ms(start_time);
+        let vlog_messages = vlog_transformer::to_vlog(
+            part.changes,
+            &part.start_date_time,
+            mapping_file,
+            tlc_name,
+            transform_options(app_args),
+            &mut part_stats,
+        );
+        data.stats.add_written_part(&part_stats, offset_ms);
 
-    #[test]
-    fn reading_an_empty_line_should_not_result_in_any_changes_added() {
-        let mut data = AssimilationData {
-            start_time: get_test_start_time(),
-            sorted_lines: vec![String::from("")],
-            first_tick: Option::Some(0),
-            ..Default::default()
+        // Split files are named after the moment they start, --name-date only applies to a single file
+        let file_name = match app_args.split {
+            SplitMode::None => create_file_name(tlc_name, file_name_date_time, app_args),
+            SplitMode::Hourly => create_file_name(tlc_name, &part.start_date_time, app_args),
         };
+        data.stats.estimated_output_bytes += size_estimate;
+        data.stats.output_bytes += vlog_messages
+            .iter()
+            .map(|message| (message.len() + app_args.line_ending.as_str().len()) as u64)
+            .sum::<u64>();
 
-        read_lines_and_save_changes(&mut data);
+        let max_messages = match (app_args.max_output_size, app_args.size_policy) {
+            (Some(max_output_size), SizePolicy::Rotate) if is_over_budget => {
+                let max_messages_within_size =
+                    output_splitting::max_messages_within_size(&vlog_messages, max_output_size);
+                Some(
+                    app_args
+                        .max_messages
+                        .map_or(max_messages_within_size, |max_messages| {
+                            max_messages.min(max_messages_within_size)
+                        }),
+                )
+            }
+            _ => app_args.max_messages,
+        };
+        let message_files = match max_messages {
+            Some(max_messages) => output_splitting::split_messages(vlog_messages, max_messages),
+            None => vec![vlog_messages],
+        };
+        for (part_index, vlog_messages) in message_files.iter().enumerate() {
+            let file_name = output_splitting::part_file_name(&file_name, part_index);
+            let is_compressed = is_over_budget && app_args.size_policy == SizePolicy::Compress;
+            check_not_overwriting(&file_name, app_args)?;
+            if is_compressed {
+                check_not_overwriting(&format!("{}.gz", file_name), app_args)?;
+            }
+            let mut verifier = OutputVerifier::default();
+            vlog_messages
+                .iter()
+                .for_each(|message| verifier.check(message));
+            report_output_defects(&verifier, &file_name, app_args.strict, &mut data.console)?;
+            write_vlog_file(
+                &file_name,
+                vlog_messages,
+                &part.start_date_time,
+                app_args.line_ending,
+                pacer.as_mut(),
+                app_args.tee,
+                &data.console,
+            )?;
+            vlog_message_count += vlog_messages.len();
+            if is_compressed {
+                file_names.push(compress_vlog_file(&file_name, app_args, &mut data.console)?);
+            } else {
+                file_names.push(file_name);
+            }
+        }
+    }
+    Ok((file_names, vlog_message_count))
+}
 
-        assert!(data.changes.is_empty());
+/// Warns about the names of the filters that never appeared in the log, which are likely typos.
+fn warn_about_unseen_names(prepared: &PreparedChanges, console: &mut Console) {
+    for signal_name in &prepared.unseen_signal_names {
+        console.warning(&format!(
+            "Warning: signal '{}' given in --filter-signals never appeared in the log. Did you make a typo?",
+            signal_name
+        ));
+    }
+    for detector_name in &prepared.unseen_detector_names {
+        console.warning(&format!(
+            "Warning: detector '{}' given in the detector filter never appeared in the log. Did you make a typo?",
+            detector_name
+        ));
     }
+}
 
-    #[test]
-    fn reading_an_out_line_should_not_result_in_any_changes_added() {
-        let mut data = AssimilationData {
-            start_time: get_test_start_time(),
-            sorted_lines: vec![String::from("2021-12-15 12:59:59,794 INFO  tlcFiMessages:41 - OUT - {\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087974612,\"update\":[{\"objects\":{\"ids\":[\"D681\"],\"type\":4},\"states\":[{\"state\":0}]}]}}")],
-            first_tick: Option::Some(0),
-            ..Default::default()
-        };
+/// Appends the VLog messages of the changes to an existing VLog file, continuing from its last time reference.
+fn convert_appending(
+    mut data: AssimilationData,
+    app_args: &AppArgs,
+    target: appending::AppendTarget,
+) -> Result<ConversionReport, ConversionFailure> {
+    let start_time = to_output_time(&data.start_time, app_args);
+    let offset_ms = appending::offset_ms(&target, &start_time);
+    let mut changes = std::mem::take(&mut data.changes);
+    let change_count = changes.len();
+    appending::shift_changes(&mut changes, offset_ms);
+    let mut transformer = vlog_transformer::VLogTransformer::new(
+        &target.last_reference,
+        &app_args.vlog_tlcfi_mapping_file,
+        transform_options(app_args),
+    );
+    let mut appended_stats = ConversionStats::default();
+    let vlog_messages = transformer.transform(changes, &mut appended_stats);
+    // The written changes are relative to the time reference, the summary expects them relative to the log's start
+    appended_stats.first_written_change_ms = appended_stats
+        .first_written_change_ms
+        .map(|ms| ms - offset_ms);
+    appended_stats.last_written_change_ms = appended_stats
+        .last_written_change_ms
+        .map(|ms| ms - offset_ms);
+    data.stats.add_written_part(&appended_stats, 0);
+    data.stats.output_bytes += vlog_messages
+        .iter()
+        .map(|message| (message.len() + target.line_ending.as_str().len()) as u64)
+        .sum::<u64>();
 
-        read_lines_and_save_changes(&mut data);
+    let mut verifier = OutputVerifier::default();
+    target
+        .messages
+        .iter()
+        .chain(vlog_messages.iter())
+        .for_each(|message| verifier.check(message));
+    report_output_defects(
+        &verifier,
+        &target.file_name,
+        app_args.strict,
+        &mut data.console,
+    )?;
 
-        assert!(data.changes.is_empty());
+    let mut pacer = if app_args.pace {
+        Some(pacing::Pacer::new(app_args.pace_speed))
+    } else {
+        None
+    };
+    let append_failure = |error: std::io::Error| ConversionFailure {
+        exit_code: EXIT_CODE_OUTPUT_ERROR,
+        message: format!(
+            "Error: failed to append the VLog output to '{}': {}",
+            target.file_name, error
+        ),
+    };
+    let file = OpenOptions::new()
+        .append(true)
+        .open(&target.file_name)
+        .map_err(append_failure)?;
+    let mut writer = tee::tee_to_stdout(file, app_args.tee);
+    write_vlog_messages(&mut writer, &vlog_messages, target.line_ending, pacer.as_mut()).map_err(
+        |failure| ConversionFailure {
+            exit_code: EXIT_CODE_OUTPUT_ERROR,
+            message: format!(
+                "Error: failed to append the VLog output to '{}': {}. Only {} of {} messages were appended.",
+                target.file_name,
+                failure.error,
+                failure.written_messages,
+                vlog_messages.len()
+            ),
+        },
+    )?;
+    data.console.info(&format!(
+        "Appended {} messages to: {}",
+        vlog_messages.len(),
+        target.file_name
+    ));
+
+    data.console
+        .add_repeated_warnings(&data.stats.transform_warnings);
+    data.console.print_summary();
+    for line in conversion_summary_lines(&data.stats, &start_time, app_args) {
+        data.console.report(&line);
     }
+    Ok(ConversionReport {
+        file_names: vec![target.file_name],
+        change_count,
+        vlog_message_count: vlog_messages.len(),
+        problems: Vec::new(),
+        warnings: data.console.all_warnings(),
+        stats: data.stats,
+        ..Default::default()
+    })
+}
 
-    #[test]
-    fn reading_a_valid_line_should_mutate_changes() {
-        let mut data = AssimilationData {
-            start_time: get_test_start_time(),
-            sorted_lines: vec![String::from("2021-12-15 12:59:59,794 INFO  tlcFiMessages:41 - IN - {\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087,\"update\":[{\"objects\":{\"ids\":[\"D681\"],\"type\":4},\"states\":[{\"state\":0}]}]}}")],
-            first_tick: Option::Some(4000),
-            ..Default::default()
-        };
+/// Writes the changes found so far to the VLog file, and keeps appending the ones of new log lines until Ctrl-C is pressed.
+fn convert_following(
+    deduplicator: Deduplicator,
+    log_tail: following::LogTail,
+    mut data: AssimilationData,
+    app_args: &AppArgs,
+    mapping_file: &str,
+    tlc_name: &str,
+    file_name_date_time: &NaiveDateTime,
+) -> Result<ConversionReport, ConversionFailure> {
+    let start_time = to_output_time(&data.start_time, app_args);
+    let mut transformer = vlog_transformer::VLogTransformer::new(
+        &start_time,
+        mapping_file,
+        transform_options(app_args),
+    );
+    let changes = std::mem::take(&mut data.changes);
+    let change_count = changes.len();
+    let mut vlog_messages = transformer.start(tlc_name, &mut data.stats);
+    vlog_messages.extend(transformer.transform(changes, &mut data.stats));
 
-        read_lines_and_save_changes(&mut data);
+    let file_name = create_file_name(tlc_name, file_name_date_time, app_args);
+    check_not_overwriting(&file_name, app_args)?;
+    let mut verifier = OutputVerifier::default();
+    vlog_messages
+        .iter()
+        .for_each(|message| verifier.check(message));
+    report_output_defects(&verifier, &file_name, app_args.strict, &mut data.console)?;
+    // Ctrl-C also has to stop pacing the messages of the existing lines cleanly
+    let mut pacer = if app_args.pace {
+        following::stop_on_interrupt();
+        Some(pacing::Pacer::new(app_args.pace_speed).stopped_by(&following::STOP_REQUESTED))
+    } else {
+        None
+    };
+    write_vlog_file(
+        &file_name,
+        &vlog_messages,
+        &start_time,
+        app_args.line_ending,
+        pacer.as_mut(),
+        app_args.tee,
+        &data.console,
+    )?;
+    let follow_error = |error: std::io::Error| ConversionFailure {
+        exit_code: EXIT_CODE_OUTPUT_ERROR,
+        message: format!(
+            "Error: failed to follow the log into the file '{}': {}",
+            file_name, error
+        ),
+    };
+    let file = OpenOptions::new()
+        .append(true)
+        .open(&file_name)
+        .map_err(follow_error)?;
+    let mut writer = tee::tee_to_stdout(file, app_args.tee);
+    data.console
+        .info("Following the log for new lines, press Ctrl-C to stop.");
 
-        assert!(!data.changes.is_empty());
-        let change = &data.changes[0];
-        assert_eq!(change.ms_from_beginning, 4087 - data.first_tick.unwrap());
-        assert_eq!(change.detector_names[0], "D681");
-        assert_eq!(change.detector_states[0], DetectorState::FREE);
+    let mut follower =
+        following::Follower::new(log_tail, data, transformer, deduplicator, app_args);
+    let followed = following::follow(&mut follower, &mut writer, &mut verifier, pacer.as_mut());
+    drop(writer);
+    if let Err(error) = &followed {
+        // A log that turned out not to be convertible leaves no partial VLog file behind
+        if error.kind() == std::io::ErrorKind::InvalidData {
+            let _ = fs::remove_file(&file_name);
+        }
     }
+    let appended_messages = followed.map_err(follow_error)?;
+    let change_count = change_count + follower.change_count();
+    let mut data = follower.into_data();
+    report_output_defects(&verifier, &file_name, false, &mut data.console)?;
 
-    #[test]
-    fn reading_a_valid_line_without_a_first_tick_should_set_the_first_tick_to_the_one_of_the_message(
-    ) {
-        let mut data = AssimilationData {
-            start_time: get_test_start_time(),
-            sorted_lines: vec![String::from("2021-12-15 12:59:59,794 INFO  tlcFiMessages:41 - IN - {\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087974612,\"update\":[{\"objects\":{\"ids\":[\"D681\"],\"type\":4},\"states\":[{\"state\":0}]}]}}")],
-            ..Default::default()
-        };
+    data.console
+        .add_repeated_warnings(&data.stats.transform_warnings);
+    data.console.print_summary();
+    for line in conversion_summary_lines(&data.stats, &start_time, app_args) {
+        data.console.report(&line);
+    }
+    Ok(ConversionReport {
+        file_names: vec![file_name],
+        change_count,
+        vlog_message_count: vlog_messages.len() + appended_messages,
+        problems: Vec::new(),
+        warnings: data.console.all_warnings(),
+        stats: data.stats,
+        ..Default::default()
+    })
+}
+
+/// Converts the log in batches of lines, saving a checkpoint after writing each batch. When the checkpoint file exists,
+/// the conversion continues where it stopped, with the VLog file cut back to the size it had at the checkpoint.
+/// `on_checkpoint` is called after saving a checkpoint, returning false stops the conversion as if it was interrupted.
+/// The summary continues from the stats in the checkpoint, so it covers the whole log.
+fn convert_checkpointed(
+    app_args: &AppArgs,
+    checkpoint_file: &str,
+    lines_per_checkpoint: usize,
+    on_checkpoint: &mut dyn FnMut(&checkpointing::Checkpoint) -> bool,
+) -> Result<ConversionReport, ConversionFailure> {
+    let input_failure = |message: String| ConversionFailure {
+        exit_code: EXIT_CODE_INPUT_ERROR,
+        message: format!("Error: {}", message),
+    };
+    let resumed = checkpointing::load(checkpoint_file).map_err(input_failure)?;
+    let options_fingerprint =
+        conversion_cache::options_fingerprint(app_args).map_err(input_failure)?;
+    if let Some(checkpoint) = &resumed {
+        if checkpoint.tlcfi_log_file != app_args.tlcfi_log_file {
+            return Err(input_failure(format!(
+                "the checkpoint '{}' is of the log '{}', not of '{}'",
+                checkpoint_file, checkpoint.tlcfi_log_file, app_args.tlcfi_log_file
+            )));
+        }
+        if checkpoint.options_fingerprint != options_fingerprint {
+            return Err(input_failure(format!(
+                "the checkpoint '{}' is of a conversion with other options or another mapping file, continue it with the same ones or remove it to start over",
+                checkpoint_file
+            )));
+        }
+    }
+    if log_files(app_args).len() > 1 {
+        return Err(input_failure(format!(
+            "the log '{}' has rotated parts or other logs of its date, --checkpoint needs a single log",
+            app_args.tlcfi_log_file
+        )));
+    }
+    let tlc_name = load_tlc_name(app_args)?;
+    let log_start_time = match (&resumed, app_args.start_date_time) {
+        (Some(checkpoint), _) => checkpoint.start_date_time,
+        (None, Some(start_date_time)) => start_date_time,
+        (None, None) => read_start_date_time(&app_args.tlcfi_log_file, &app_args.log_pattern)?,
+    };
+    let start_time = to_output_time(&log_start_time, app_args);
+    let mut transformer = vlog_transformer::VLogTransformer::new(
+        &start_time,
+        &app_args.vlog_tlcfi_mapping_file,
+        transform_options(app_args),
+    );
+    let mut data = assimilation_data(app_args, log_start_time, Console::new(app_args.verbosity));
+
+    let output_failure = |file_name: &str, error: std::io::Error| ConversionFailure {
+        exit_code: EXIT_CODE_OUTPUT_ERROR,
+        message: format!(
+            "Error: failed to write the VLog output to '{}': {}",
+            file_name, error
+        ),
+    };
+    let (file_name, log_offset, resumed_change_count, mut vlog_message_count) = match &resumed {
+        Some(checkpoint) => {
+            data.first_tick = checkpoint.first_tick;
+            data.first_line_time = checkpoint.first_line_time;
+            data.previous_tick = checkpoint.previous_tick;
+            data.bonus_ms = checkpoint.bonus_ms;
+            data.stats = checkpoint.stats.clone();
+            transformer.resume_at(checkpoint.ms_of_last_time_reference);
+            data.console.info(&format!(
+                "Continuing the conversion from the checkpoint at byte {} of the log.",
+                checkpoint.log_offset
+            ));
+            (
+                checkpoint.vlog_file.clone(),
+                checkpoint.log_offset,
+                checkpoint.change_count,
+                checkpoint.vlog_message_count,
+            )
+        }
+        None => {
+            let file_name = create_file_name(&tlc_name, &start_time, app_args);
+            check_not_overwriting(&file_name, app_args)?;
+            let vlog_messages = transformer.start(&tlc_name, &mut data.stats);
+            write_vlog_file(
+                &file_name,
+                &vlog_messages,
+                &start_time,
+                app_args.line_ending,
+                None,
+                app_args.tee,
+                &data.console,
+            )?;
+            (file_name, 0, 0, vlog_messages.len())
+        }
+    };
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(&file_name)
+        .map_err(|error| output_failure(&file_name, error))?;
+    // Messages written after the last checkpoint are written again
+    if let Some(checkpoint) = &resumed {
+        let file_size = file
+            .metadata()
+            .map_err(|error| output_failure(&file_name, error))?
+            .len();
+        if file_size < checkpoint.vlog_offset {
+            return Err(ConversionFailure {
+                exit_code: EXIT_CODE_OUTPUT_ERROR,
+                message: format!(
+                    "Error: the VLog file '{}' is smaller than at the checkpoint '{}', it can't be continued",
+                    file_name, checkpoint_file
+                ),
+            });
+        }
+        file.set_len(checkpoint.vlog_offset)
+            .map_err(|error| output_failure(&file_name, error))?;
+    }
+
+    let log_tail = following::LogTail::open_at(
+        &app_args.tlcfi_log_file,
+        log_offset,
+        Some(lines_per_checkpoint),
+    )
+    .map_err(|error| {
+        input_failure(format!(
+            "failed to read the log '{}': {}",
+            app_args.tlcfi_log_file, error
+        ))
+    })?;
+    let mut follower = following::Follower::new(
+        log_tail,
+        data,
+        transformer,
+        Deduplicator::default(),
+        app_args,
+    );
+    let mut verifier = OutputVerifier::default();
+    let mut is_complete = false;
+    while !is_complete {
+        let previous_offset = follower.log_offset();
+        let mut batch = follower.next_batch();
+        if follower.log_offset() == previous_offset {
+            batch = follower.finish();
+            is_complete = true;
+        }
+        let vlog_messages = batch.map_err(|error| {
+            input_failure(format!(
+                "failed to convert the log '{}': {}",
+                app_args.tlcfi_log_file, error
+            ))
+        })?;
+        let mut writer = tee::tee_to_stdout(&mut file, app_args.tee);
+        vlog_message_count +=
+            following::write_batch(&mut writer, &vlog_messages, &mut verifier, app_args, None)
+                .map_err(|error| output_failure(&file_name, error))?;
+        drop(writer);
+
+        let data = follower.data();
+        let checkpoint = checkpointing::Checkpoint {
+            tlcfi_log_file: app_args.tlcfi_log_file.clone(),
+            vlog_file: file_name.clone(),
+            options_fingerprint: options_fingerprint.clone(),
+            start_date_time: log_start_time,
+            log_offset: follower.log_offset(),
+            vlog_offset: file
+                .metadata()
+                .map_err(|error| output_failure(&file_name, error))?
+                .len(),
+            first_tick: data.first_tick,
+            first_line_time: data.first_line_time,
+            previous_tick: data.previous_tick,
+            bonus_ms: data.bonus_ms,
+            ms_of_last_time_reference: follower.ms_of_last_time_reference(),
+            change_count: resumed_change_count + follower.change_count(),
+            vlog_message_count,
+            stats: data.stats.clone(),
+        };
+        checkpointing::save(checkpoint_file, &checkpoint).map_err(|error| ConversionFailure {
+            exit_code: EXIT_CODE_OUTPUT_ERROR,
+            message: format!(
+                "Error: failed to save the checkpoint '{}': {}",
+                checkpoint_file, error
+            ),
+        })?;
+        if !on_checkpoint(&checkpoint) {
+            return Ok(ConversionReport {
+                file_names: vec![file_name],
+                change_count: checkpoint.change_count,
+                vlog_message_count,
+                ..Default::default()
+            });
+        }
+    }
+    drop(file);
+    let change_count = resumed_change_count + follower.change_count();
+    let mut data = follower.into_data();
+    if change_count == 0 {
+        let _ = fs::remove_file(&file_name);
+        let _ = checkpointing::remove(checkpoint_file);
+        return Err(no_messages_failure(app_args, &data.stats));
+    }
+    if let Err(error) = checkpointing::remove(checkpoint_file) {
+        data.console.warning(&format!(
+            "Warning: failed to remove the checkpoint '{}' of the finished conversion: {}",
+            checkpoint_file, error
+        ));
+    }
+    report_output_defects(&verifier, &file_name, false, &mut data.console)?;
+
+    data.console
+        .add_repeated_warnings(&data.stats.transform_warnings);
+    data.console.print_summary();
+    for line in conversion_summary_lines(&data.stats, &start_time, app_args) {
+        data.console.report(&line);
+    }
+    Ok(ConversionReport {
+        file_names: vec![file_name],
+        change_count,
+        vlog_message_count,
+        problems: Vec::new(),
+        warnings: data.console.all_warnings(),
+        stats: data.stats,
+        start_time: Some(start_time),
+        tlc_name: Some(tlc_name),
+    })
+}
+
+/// Takes the start date time from the first log line, without reading the rest of the log.
+fn read_start_date_time(
+    tlcfi_log_file: &str,
+    log_pattern: &LogPattern,
+) -> Result<NaiveDateTime, ConversionFailure> {
+    let first_log_line = compressed_logs::open_log(tlcfi_log_file)
+        .map(|reader| {
+            reader
+                .lines()
+                .map_while(Result::ok)
+                .find(|line| is_log_line(line, log_pattern))
+        })
+        .map_err(|error| ConversionFailure {
+            exit_code: EXIT_CODE_INPUT_ERROR,
+            message: format!(
+                "Error: failed to read the log '{}': {}",
+                tlcfi_log_file, error
+            ),
+        })?;
+    let first_log_lines: Vec<String> = first_log_line.into_iter().collect();
+    get_start_date_time_from_file(&first_log_lines, log_pattern).map_err(|error| {
+        ConversionFailure {
+            exit_code: EXIT_CODE_INPUT_ERROR,
+            message: format!("Error: failed to get start date time from logs, set it in application arguments instead or filter the logs. {}", error),
+        }
+    })
+}
+
+/// Describes what happened during the conversion, so the user can judge whether the output is plausible.
+fn conversion_summary_lines(
+    stats: &ConversionStats,
+    start_time: &NaiveDateTime,
+    app_args: &AppArgs,
+) -> Vec<String> {
+    let mut lines = vec![
+        "Summary:".to_string(),
+        format!(
+            "  log lines read: {} ({} skipped, {} IN, {} OUT)",
+            stats.lines_read, stats.lines_skipped, stats.in_lines, stats.out_lines
+        ),
+        format!(
+            "  VLog messages written: {} signal changes, {} detector changes, {} time references",
+            stats.signal_change_messages, stats.detector_change_messages, stats.time_references
+        ),
+    ];
+    if let Some(mapping_dir) = &app_args.mapping_dir {
+        lines.push(format!(
+            "  mapping file: {} (picked from {})",
+            app_args.vlog_tlcfi_mapping_file, mapping_dir
+        ));
+    }
+    if stats.line_errors.total() > 0 {
+        lines.push(format!(
+            "  log lines that can't be parsed: {} ({})",
+            stats.line_errors.total(),
+            stats.line_errors.describe()
+        ));
+    }
+    if let Some(predictions_csv) = &app_args.predictions_csv {
+        lines.push(format!(
+            "  signal predictions: {} (written to {})",
+            stats.signal_predictions, predictions_csv
+        ));
+    }
+    if let (Some(reject_file), true) = (&app_args.reject_file, stats.rejected_lines > 0) {
+        lines.push(format!(
+            "  rejected log lines: {} (written to {})",
+            stats.rejected_lines, reject_file
+        ));
+    }
+    if stats.lines_before_start > 0 {
+        lines.push(format!(
+            "  log lines before the start time {}: {} (not converted)",
+            start_time, stats.lines_before_start
+        ));
+    }
+    if let (Some(log_levels), true) = (&app_args.log_levels, stats.lines_of_other_levels > 0) {
+        lines.push(format!(
+            "  log lines of other levels than {}: {} (not converted)",
+            log_levels.join(", "),
+            stats.lines_of_other_levels
+        ));
+    }
+    if let (Some(logger_name), true) = (&app_args.logger_name, stats.lines_of_other_loggers > 0) {
+        lines.push(format!(
+            "  log lines of other loggers than {}: {} (not converted)",
+            logger_name, stats.lines_of_other_loggers
+        ));
+    }
+    if stats.output_change_messages > 0 {
+        lines.push(format!(
+            "  VLog output change messages written: {}",
+            stats.output_change_messages
+        ));
+    }
+    if stats.unmapped_output_states > 0 {
+        lines.push(format!(
+            "  output states without an Outputs mapping: {} (not written)",
+            stats.unmapped_output_states
+        ));
+    }
+    if stats.intersection_state_changes > 0 {
+        lines.push(format!(
+            "  intersection state changes: {} (not written, VLog has no message for them)",
+            stats.intersection_state_changes
+        ));
+    }
+    if stats.unknown_intersection_states > 0 {
+        lines.push(format!(
+            "  unknown intersection states: {} (skipped)",
+            stats.unknown_intersection_states
+        ));
+    }
+    if !stats.other_methods.is_empty() {
+        let other_methods: Vec<String> = stats
+            .other_methods
+            .iter()
+            .map(|(method, count)| format!("{} {}", method, count))
+            .collect();
+        lines.push(format!(
+            "  messages that aren't state updates: {} (skipped)",
+            other_methods.join(", ")
+        ));
+    }
+    if stats.null_signal_states + stats.null_detector_states + stats.null_output_states > 0 {
+        lines.push(format!(
+            "  entries without a state: {} signal, {} detector, {} output (no change)",
+            stats.null_signal_states, stats.null_detector_states, stats.null_output_states
+        ));
+    }
+    if let (Some(first_ms), Some(last_ms)) =
+        (stats.first_written_change_ms, stats.last_written_change_ms)
+    {
+        lines.push(format!(
+            "  covered time span: {} until {}",
+            *start_time + chrono::Duration::milliseconds(first_ms as i64),
+            *start_time + chrono::Duration::milliseconds(last_ms as i64)
+        ));
+    }
+    if stats.tick_overflows > 0 || !stats.controller_resets.is_empty() {
+        lines.push(format!(
+            "  tick events: {} overflow(s), {} reset(s)",
+            stats.tick_overflows,
+            stats.controller_resets.len()
+        ));
+    }
+    for reset in &stats.controller_resets {
+        lines.push(format!(
+            "  controller reset at {}: the tick jumped back {} from {} to {}",
+            *start_time + chrono::Duration::milliseconds(reset.ms_from_beginning as i64),
+            reset.tick_discontinuity(),
+            reset.previous_tick,
+            reset.tick
+        ));
+    }
+    if stats.output_bytes > 0 {
+        lines.push(format!(
+            "  VLog size: {} bytes written, {} bytes estimated",
+            stats.output_bytes, stats.estimated_output_bytes
+        ));
+    }
+    for retired_object in &stats.retired_objects {
+        lines.push(format!(
+            "  {} removed from the session at {}",
+            retired_object.describe(),
+            *start_time + chrono::Duration::milliseconds(retired_object.ms_from_beginning as i64)
+        ));
+    }
+    if !stats.special_vehicle_events.is_empty() {
+        lines.push(format!(
+            "  special vehicle events: {}",
+            stats.special_vehicle_events.len()
+        ));
+    }
+    // There can be an event for every bus, so they are only listed when asked for
+    if app_args.verbosity >= Verbosity::Verbose {
+        for event in &stats.special_vehicle_events {
+            lines.push(format!(
+                "  {} at {}",
+                event.describe(),
+                *start_time + chrono::Duration::milliseconds(event.ms_from_beginning as i64)
+            ));
+        }
+    }
+    for session in &stats.sessions {
+        lines.push(format!("  {}", session.describe()));
+    }
+    for rpc_error in &stats.rpc_errors {
+        lines.push(format!("  JSON-RPC {}", rpc_error.describe()));
+    }
+    for clock_step in &stats.clock_steps {
+        lines.push(format!(
+            "  clock step: {}",
+            clock_steps::describe(clock_step)
+        ));
+    }
+    if app_args.invert_direction {
+        lines.push("  direction inversion was active".to_string());
+    }
+    if app_args.time_source != TimeSource::Ticks {
+        lines.push(format!(
+            "  times taken from the {} instead of the ticks",
+            app_args.time_source.name()
+        ));
+    }
+    if app_args.direction != MessageDirection::In {
+        lines.push(format!(
+            "  converted direction: {}",
+            app_args.direction.name()
+        ));
+    }
+    if app_args.amber_policy != AmberPolicy::Keep {
+        lines.push(format!(
+            "  amber policy {}: {} amber state(s) collapsed",
+            app_args.amber_policy.name(),
+            stats.collapsed_amber_states
+        ));
+    }
+    if app_args.dedupe {
+        lines.push(format!(
+            "  repeated states suppressed: {} signal, {} detector",
+            stats.suppressed_signal_states, stats.suppressed_detector_states
+        ));
+    }
+    if stats.truncated_changes > 0 {
+        lines.push(format!(
+            "  output truncated: {} change(s) left out after the --limit of {}",
+            stats.truncated_changes,
+            app_args.limit.unwrap_or_default()
+        ));
+    }
+    lines
+}
+
+/// The TLC name given with `--tlc-name`, or otherwise the one of the mapping file.
+fn load_tlc_name(app_args: &AppArgs) -> Result<String, ConversionFailure> {
+    if let Some(tlc_name) = &app_args.tlc_name {
+        return Ok(tlc_name.clone());
+    }
+    match vlog_transformer::load_tlc_name(&app_args.vlog_tlcfi_mapping_file) {
+        Ok(Some(tlc_name)) => Ok(tlc_name),
+        Ok(None) => Err(ConversionFailure {
+            exit_code: EXIT_CODE_MAPPING_ERROR,
+            message: format!(
+                "Error: couldn't find a TLC name in the given VLog TLC FI mapping file: {:?}",
+                &app_args.vlog_tlcfi_mapping_file
+            ),
+        }),
+        Err(error) => Err(ConversionFailure {
+            exit_code: EXIT_CODE_INPUT_ERROR,
+            message: format!(
+                "Error: failed to read the VLog TLC FI mapping file {:?}: {}",
+                &app_args.vlog_tlcfi_mapping_file, error
+            ),
+        }),
+    }
+}
+
+/// Fails when the VLog file already exists and `--overwrite` wasn't given,
+/// since the file name only depends on the TLC name and a date time and conversions of overlapping logs share it.
+fn check_not_overwriting(file_name: &str, app_args: &AppArgs) -> Result<(), ConversionFailure> {
+    if app_args.overwrite || !std::path::Path::new(file_name).exists() {
+        return Ok(());
+    }
+    Err(ConversionFailure {
+        exit_code: EXIT_CODE_OUTPUT_ERROR,
+        message: format!(
+            "Error: the file '{}' already exists. Use --overwrite to replace it.",
+            file_name
+        ),
+    })
+}
+
+/// Creates the VLog file and writes the messages to it.
+/// When writing fails, the partial output is moved aside so it can't be mistaken for a complete file.
+fn write_vlog_file(
+    file_name: &str,
+    vlog_messages: &[String],
+    start_date_time: &NaiveDateTime,
+    line_ending: LineEnding,
+    pacer: Option<&mut pacing::Pacer>,
+    is_teed: bool,
+    console: &Console,
+) -> Result<(), ConversionFailure> {
+    let file = create_parent_dirs(file_name)
+        .and_then(|_| File::create(file_name))
+        .map_err(|error| ConversionFailure {
+            exit_code: EXIT_CODE_OUTPUT_ERROR,
+            message: format!(
+                "Error: failed to create the file '{}' for saving the VLog output: {}",
+                file_name, error
+            ),
+        })?;
+    console.info(&format!("Created file: {}", file_name));
+
+    let mut writer = tee::tee_to_stdout(file, is_teed);
+    if let Err(failure) = write_vlog_messages(&mut writer, vlog_messages, line_ending, pacer) {
+        drop(writer);
+        let partial_file_name = move_to_partial_file(file_name);
+        return Err(ConversionFailure {
+            exit_code: EXIT_CODE_OUTPUT_ERROR,
+            message: format!(
+            "Error: failed to write the VLog output: {}. Only {} of {} messages were written{}, the partial output was moved to '{}'.",
+            failure.error,
+            failure.written_messages,
+            vlog_messages.len(),
+            failure
+                .written_until
+                .map(|until| format!(" covering {} until {}", start_date_time, until))
+                .unwrap_or_default(),
+            partial_file_name
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Warns about the defects found in the output for a file, or fails in strict mode.
+fn report_output_defects(
+    verifier: &OutputVerifier,
+    file_name: &str,
+    is_strict: bool,
+    console: &mut Console,
+) -> Result<(), ConversionFailure> {
+    if verifier.defect_count() == 0 {
+        return Ok(());
+    }
+    let descriptions: Vec<String> = verifier
+        .defects()
+        .iter()
+        .map(|defect| defect.describe())
+        .collect();
+    if is_strict {
+        return Err(ConversionFailure {
+            exit_code: EXIT_CODE_OUTPUT_ERROR,
+            message: format!(
+                "Error: the VLog output for '{}' failed the integrity check with {} defect(s), nothing was written: {}",
+                file_name,
+                verifier.defect_count(),
+                descriptions.join("; ")
+            ),
+        });
+    }
+    for description in descriptions {
+        console.warning(&format!(
+            "Warning: the VLog output for '{}' looks wrong, {}.",
+            file_name, description
+        ));
+    }
+    if verifier.defect_count() > verifier.defects().len() {
+        console.warning(&format!(
+            "Warning: the VLog output for '{}' has {} more defect(s).",
+            file_name,
+            verifier.defect_count() - verifier.defects().len()
+        ));
+    }
+    Ok(())
+}
+
+/// Replaces a written VLog file by its gzip compressed version `<name>.gz` and returns the new file name.
+/// Warns when even the compressed file is larger than `--max-output-size`.
+fn compress_vlog_file(
+    file_name: &str,
+    app_args: &AppArgs,
+    console: &mut Console,
+) -> Result<String, ConversionFailure> {
+    let compressed_file_name = format!("{}.gz", file_name);
+    let compress_error = |error: std::io::Error| ConversionFailure {
+        exit_code: EXIT_CODE_OUTPUT_ERROR,
+        message: format!(
+            "Error: failed to compress the VLog file '{}': {}",
+            file_name, error
+        ),
+    };
+    let compressed_size = File::open(file_name)
+        .and_then(|mut vlog_file| {
+            let mut encoder =
+                GzEncoder::new(File::create(&compressed_file_name)?, Compression::default());
+            io::copy(&mut vlog_file, &mut encoder)?;
+            encoder.finish()?.metadata()
+        })
+        .map_err(compress_error)?
+        .len();
+    fs::remove_file(file_name).map_err(compress_error)?;
+    console.info(&format!(
+        "Compressed the VLog file to: {} ({} bytes)",
+        compressed_file_name, compressed_size
+    ));
+    if let Some(max_output_size) = app_args.max_output_size {
+        if compressed_size > max_output_size {
+            console.warning(&format!(
+                "Warning: the compressed VLog file '{}' is still larger than the --max-output-size of {} bytes.",
+                compressed_file_name, max_output_size
+            ));
+        }
+    }
+    Ok(compressed_file_name)
+}
+
+/// Describes how far writing VLog messages got before it failed.
+#[derive(Debug)]
+struct WriteFailure {
+    written_messages: usize,
+    written_until: Option<NaiveDateTime>,
+    error: std::io::Error,
+}
+
+/// Writes the VLog messages line by line, pacing them if a [Pacer](pacing/struct.Pacer.html) is given.
+/// Stops at the first write error, or without an error when the pacer was stopped.
+fn write_vlog_messages<W: Write>(
+    writer: &mut W,
+    vlog_messages: &[String],
+    line_ending: LineEnding,
+    mut pacer: Option<&mut pacing::Pacer>,
+) -> Result<(), WriteFailure> {
+    let mut clock = vlog_decoding::MessageClock::default();
+    let mut written_until = None;
+    for (written_messages, msg) in vlog_messages.iter().enumerate() {
+        if let Some(pacer) = pacer.as_mut() {
+            if !pacer.wait_for(msg) {
+                break;
+            }
+        }
+        if let Err(error) = write_vlog_line(writer, msg, line_ending) {
+            return Err(WriteFailure {
+                written_messages,
+                written_until,
+                error,
+            });
+        }
+        if let Some(message_time) = clock.time_of(msg) {
+            written_until = Some(message_time);
+        }
+    }
+    Ok(())
+}
+
+/// Writes a single VLog message with its line ending.
+fn write_vlog_line<W: Write>(
+    writer: &mut W,
+    message: &str,
+    line_ending: LineEnding,
+) -> std::io::Result<()> {
+    write!(writer, "{}{}", message, line_ending.as_str())
+}
+
+/// Renames an incompletely written output file to `<name>.partial` so it isn't mistaken for a complete one.
+/// Returns the new file name, or the old one if renaming failed.
+fn move_to_partial_file(file_name: &str) -> String {
+    let partial_file_name = format!("{}.partial", file_name);
+    match fs::rename(file_name, &partial_file_name) {
+        Ok(_) => partial_file_name,
+        Err(error) => {
+            eprintln!("Failed to rename '{}': {}", file_name, error);
+            file_name.to_string()
+        }
+    }
+}
+
+/// Checks everything a conversion needs without converting: changes were found, and the mapping file has a TLC name
+/// and every signal and detector that will be converted.
+fn find_dry_run_problems(changes: &[TimestampedChanges], app_args: &AppArgs) -> Vec<String> {
+    let mut problems = Vec::new();
+    if changes.is_empty() {
+        problems.push("no signal or detector changes were found in the log".to_string());
+    }
+    problems.extend(find_mapping_problems(changes, app_args));
+    problems
+}
+
+/// Checks that the mapping file has a TLC name, unless one is given with `--tlc-name`,
+/// and every signal and detector that will be converted.
+fn find_mapping_problems(changes: &[TimestampedChanges], app_args: &AppArgs) -> Vec<String> {
+    let mut problems = Vec::new();
+    match vlog_transformer::load_tlc_name(&app_args.vlog_tlcfi_mapping_file) {
+        Ok(Some(_)) => {}
+        Ok(None) if app_args.tlc_name.is_some() => {}
+        Ok(None) => problems.push("no TLC name was found in the mapping file".to_string()),
+        Err(error) => problems.push(format!("failed to read the mapping file: {}", error)),
+    }
+
+    let load_mappings_for = |mapping_type: &str, is_converted: bool, problems: &mut Vec<String>| {
+        if !is_converted {
+            return HashMap::new();
+        }
+        vlog_transformer::load_mappings(&app_args.vlog_tlcfi_mapping_file, mapping_type)
+            .unwrap_or_else(|error| {
+                problems.push(error.to_string());
+                HashMap::new()
+            })
+    };
+    let signal_mapping = load_mappings_for(
+        "Signals",
+        app_args.conversion_mode.converts_signals(),
+        &mut problems,
+    );
+    let detector_mapping = load_mappings_for(
+        "Detectors",
+        app_args.conversion_mode.converts_detectors(),
+        &mut problems,
+    );
+
+    let unmapped_names = mapping_check::find_unmapped_names(
+        &mapping_check::collect_observed_names(changes),
+        &signal_mapping,
+        &detector_mapping,
+    );
+    if !unmapped_names.signals.is_empty() {
+        problems.push(format!(
+            "signals missing from the mapping file: {}",
+            unmapped_names.signals.join(", ")
+        ));
+    }
+    if !unmapped_names.detectors.is_empty() {
+        problems.push(format!(
+            "detectors missing from the mapping file: {}",
+            unmapped_names.detectors.join(", ")
+        ));
+    }
+    problems
+}
+
+fn report_dry_run(
+    changes: &[TimestampedChanges],
+    start_time: &NaiveDateTime,
+    problems: &[String],
+    console: &Console,
+) {
+    console.info(&format!("Dry run: found {} changes.", changes.len()));
+    let first_ms = changes.iter().map(|change| change.ms_from_beginning).min();
+    let last_ms = changes.iter().map(|change| change.ms_from_beginning).max();
+    if let (Some(first_ms), Some(last_ms)) = (first_ms, last_ms) {
+        console.info(&format!(
+            "Dry run: the changes cover {} until {}.",
+            *start_time + chrono::Duration::milliseconds(first_ms as i64),
+            *start_time + chrono::Duration::milliseconds(last_ms as i64)
+        ));
+    }
+    for problem in problems {
+        console.error(&format!("Problem: {}.", problem));
+    }
+}
+
+/// Warns prominently when the mapping file seems to belong to another intersection than the log file, or fails in
+/// strict mode.
+fn warn_about_mapping_mismatch(
+    changes: &[TimestampedChanges],
+    app_args: &AppArgs,
+    console: &mut Console,
+) -> Result<(), ConversionFailure> {
+    let observed_names = mapping_check::collect_observed_names(changes);
+    let load_mappings_for = |mapping_type: &str, is_converted: bool| {
+        if is_converted {
+            vlog_transformer::load_mappings(&app_args.vlog_tlcfi_mapping_file, mapping_type)
+                .unwrap_or_default()
+        } else {
+            HashMap::new()
+        }
+    };
+    let signal_mapping = load_mappings_for("Signals", app_args.conversion_mode.converts_signals());
+    let detector_mapping =
+        load_mappings_for("Detectors", app_args.conversion_mode.converts_detectors());
+
+    if let Some(reason) = mapping_check::find_mapping_mismatch(
+        &observed_names,
+        &signal_mapping,
+        &detector_mapping,
+        app_args.min_mapped_fraction,
+    ) {
+        if app_args.strict {
+            return Err(ConversionFailure {
+                exit_code: EXIT_CODE_MAPPING_ERROR,
+                message: format!(
+                    "Error: the mapping file '{}' probably doesn't belong to the log file '{}': {}.",
+                    app_args.vlog_tlcfi_mapping_file, app_args.tlcfi_log_file, reason
+                ),
+            });
+        }
+        console.warning(&format!(
+            "WARNING: the mapping file '{}' probably doesn't belong to the log file '{}': {}.",
+            app_args.vlog_tlcfi_mapping_file, app_args.tlcfi_log_file, reason
+        ));
+    }
+
+    let ambiguous_names = mapping_check::find_ambiguous_names(&signal_mapping, &detector_mapping);
+    if !ambiguous_names.is_empty() {
+        console.warning(&format!(
+            "Warning: the mapping file '{}' has both signals and detectors named {}, the object type of each message decides which is used.",
+            app_args.vlog_tlcfi_mapping_file,
+            ambiguous_names.join(", ")
+        ));
+    }
+
+    // Renumbered ids don't have gaps
+    if app_args.renumber_ids {
+        return Ok(());
+    }
+    for (category, mapping) in [("signal", &signal_mapping), ("detector", &detector_mapping)].iter()
+    {
+        for gap in mapping_check::find_id_gaps(mapping, app_args.max_id_gap) {
+            let implied_count = mapping.values().max().map_or(0, |max_id| max_id + 1);
+            console.warning(&format!(
+                "Warning: the VLog {} ids in the mapping file {}, which implies {} {}s for {} mapped ones. Is the mapping stale? Use --renumber-ids to number them densely.",
+                category, gap.describe(), implied_count, category, mapping.len()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Writes the mappings with densely numbered VLog ids next to the VLog file and returns its name.
+/// Warns about the differences between the mapping file and the meta in the log.
+fn warn_about_meta_mismatch(meta: &TlcMeta, app_args: &AppArgs, console: &mut Console) {
+    let load_mappings_for = |mapping_type: &str| {
+        vlog_transformer::load_mappings(&app_args.vlog_tlcfi_mapping_file, mapping_type)
+            .unwrap_or_default()
+    };
+    let mapping = TlcMeta {
+        tlc_name: vlog_transformer::load_tlc_name(&app_args.vlog_tlcfi_mapping_file)
+            .ok()
+            .flatten(),
+        signals: load_mappings_for("Signals"),
+        detectors: load_mappings_for("Detectors"),
+        outputs: load_mappings_for("Outputs"),
+    };
+    let mismatches = mapping_from_log::find_meta_mismatches(meta, &mapping);
+    if !mismatches.is_empty() {
+        console.warning(&format!(
+            "Warning: the mapping file '{}' doesn't match the meta in the log: {}.",
+            app_args.vlog_tlcfi_mapping_file,
+            mismatches.join("; ")
+        ));
+    }
+}
+
+/// Writes the mapping from the meta in the log next to the VLog file, or to the temporary directory for a dry run,
+/// and returns its file name. Fails when the meta doesn't have the objects that are converted or a TLC name.
+fn write_mapping_from_log(
+    meta: &TlcMeta,
+    app_args: &AppArgs,
+    start_time: &NaiveDateTime,
+) -> Result<String, ConversionFailure> {
+    let mapping_failure = |message: String| ConversionFailure {
+        exit_code: EXIT_CODE_MAPPING_ERROR,
+        message: format!("Error: {}", message),
+    };
+    if meta.is_empty() {
+        return Err(mapping_failure(
+            "the log has no ReadMeta responses to take the mapping from, give a mapping file with --mapping-file instead"
+                .to_string(),
+        ));
+    }
+    let converted_objects = [
+        (
+            "signal groups",
+            app_args.conversion_mode.converts_signals(),
+            &meta.signals,
+        ),
+        (
+            "detectors",
+            app_args.conversion_mode.converts_detectors(),
+            &meta.detectors,
+        ),
+    ];
+    for (objects, is_converted, indices) in converted_objects.iter() {
+        if *is_converted && indices.is_empty() {
+            return Err(mapping_failure(format!(
+                "the meta in the log has no {}, which the conversion mode converts",
+                objects
+            )));
+        }
+    }
+    let tlc_name = app_args
+        .tlc_name
+        .as_ref()
+        .or(meta.tlc_name.as_ref())
+        .ok_or_else(|| {
+            mapping_failure(
+                "the meta in the log has no intersection to name the TLC after, give the name with --tlc-name"
+                    .to_string(),
+            )
+        })?;
+    let contents = mapping_from_log::format_mapping_file(meta, tlc_name);
+    let file_name = if app_args.dry_run {
+        std::env::temp_dir()
+            .join(format!(
+                "tlcfi_assimilator_{}_mapping_from_log.txt",
+                std::process::id()
+            ))
+            .to_string_lossy()
+            .to_string()
+    } else {
+        let vlog_file_name = create_file_name(tlc_name, start_time, app_args);
+        format!(
+            "{}_mapping_from_log.txt",
+            vlog_file_name
+                .strip_suffix(".vlg")
+                .unwrap_or(&vlog_file_name)
+        )
+    };
+    create_parent_dirs(&file_name)
+        .and_then(|_| fs::write(&file_name, contents))
+        .map_err(|error| ConversionFailure {
+            exit_code: EXIT_CODE_OUTPUT_ERROR,
+            message: format!(
+                "Error: failed to write the mapping from the log to '{}': {}",
+                file_name, error
+            ),
+        })?;
+    Ok(file_name)
+}
+
+fn write_renumbered_mapping(
+    app_args: &AppArgs,
+    tlc_name: &str,
+    file_name_date_time: &NaiveDateTime,
+) -> Result<String, ConversionFailure> {
+    let load_renumbered = |mapping_type: &str| {
+        vlog_transformer::load_mappings(&app_args.vlog_tlcfi_mapping_file, mapping_type)
+            .map(|mapping| mapping_check::renumber_densely(&mapping))
+            .unwrap_or_default()
+    };
+    let contents = mapping_check::format_mapping_file(
+        tlc_name,
+        &load_renumbered("Signals"),
+        &load_renumbered("Detectors"),
+    );
+    let vlog_file_name = create_file_name(tlc_name, file_name_date_time, app_args);
+    let file_name = format!(
+        "{}_mapping.txt",
+        vlog_file_name
+            .strip_suffix(".vlg")
+            .unwrap_or(&vlog_file_name)
+    );
+    create_parent_dirs(&file_name)
+        .and_then(|_| fs::write(&file_name, contents))
+        .map_err(|error| ConversionFailure {
+            exit_code: EXIT_CODE_OUTPUT_ERROR,
+            message: format!(
+                "Error: failed to write the renumbered mapping to '{}': {}",
+                file_name, error
+            ),
+        })?;
+    Ok(file_name)
+}
+
+fn sort_lines(
+    tlcfi_log_file: &str,
+    is_chronological: &bool,
+    console: &mut Console,
+) -> io::Result<Vec<String>> {
+    sort_lines_reporting(
+        &rotated_logs::find_rotated_logs(tlcfi_log_file),
+        is_chronological,
+        console,
+        &mut |_| {},
+    )
+}
+
+/// The log files that are read as one log: the logs of `--date`, or the log with its rotated logs.
+fn log_files(app_args: &AppArgs) -> Vec<String> {
+    if app_args.dated_log_files.is_empty() {
+        rotated_logs::find_rotated_logs(&app_args.tlcfi_log_file)
+    } else {
+        app_args.dated_log_files.clone()
+    }
+}
+
+/// Reads the lines of the log files like [sort_lines](fn.sort_lines.html), calling `on_line` with the length of every
+/// line that was read. The first file is the log itself, which may be a ZIP archive, the others are read along with it.
+fn sort_lines_reporting(
+    log_files: &[String],
+    is_chronological: &bool,
+    console: &mut Console,
+    on_line: &mut dyn FnMut(usize),
+) -> io::Result<Vec<String>> {
+    let tlcfi_log_file = &log_files[0];
+    if is_newest_first_log(log_files, *is_chronological) {
+        return reverse_reading::read_lines_oldest_first(
+            File::open(tlcfi_log_file)?,
+            console,
+            on_line,
+        );
+    }
+    if zip_archive::is_zip_file(tlcfi_log_file) {
+        return zip_archive::read_logs(tlcfi_log_file, *is_chronological, console, on_line);
+    }
+    let reader = if log_files.len() > 1 {
+        console.info(&format!(
+            "Reading the log along with: {}",
+            log_files[1..].join(", ")
+        ));
+        rotated_logs::open_logs(log_files, *is_chronological, console)
+    } else {
+        compressed_logs::open_log(tlcfi_log_file)
+    }?;
+    tlcfi_parsing::read_lines(reader, *is_chronological, console, on_line)
+}
+
+/// Whether the log is a single uncompressed file with its newest line first, which is read from its end instead of
+/// reversing all its lines.
+fn is_newest_first_log(log_files: &[String], is_chronological: bool) -> bool {
+    !is_chronological
+        && log_files.len() == 1
+        && !zip_archive::is_zip_file(&log_files[0])
+        && !compressed_logs::is_gzip_file(&log_files[0])
+}
+
+/// Fails a conversion whose log can't be read.
+fn log_read_failure(app_args: &AppArgs, error: io::Error) -> ConversionFailure {
+    ConversionFailure {
+        exit_code: EXIT_CODE_INPUT_ERROR,
+        message: format!(
+            "Error: failed to read the log '{}': {}",
+            app_args.tlcfi_log_file, error
+        ),
+    }
+}
+
+/// Describes how far the given start date time is from the log when it's more than the tolerance before its oldest
+/// line or after its newest line, which a wrong date or time zone would do. A start within the log cuts off its
+/// beginning, which is fine.
+fn describe_start_mismatch<S: AsRef<str>>(
+    start_date_time: &NaiveDateTime,
+    oldest_first_lines: impl IntoIterator<Item = S>,
+    newest_first_lines: impl IntoIterator<Item = S>,
+    log_pattern: &LogPattern,
+    tolerance_s: u64,
+) -> Option<String> {
+    let oldest_line_time = oldest_first_lines
+        .into_iter()
+        .find_map(|line| log_pattern.line_time(line.as_ref()))?;
+    let newest_line_time = newest_first_lines
+        .into_iter()
+        .find_map(|line| log_pattern.line_time(line.as_ref()))?;
+    let tolerance = chrono::Duration::seconds(tolerance_s as i64);
+    if *start_date_time < oldest_line_time - tolerance {
+        Some(format!(
+            "the start date time {} is {} before the oldest line of the log at {}, so the times in the VLog file are off",
+            start_date_time,
+            format_duration((oldest_line_time - *start_date_time).num_seconds() as u64),
+            oldest_line_time
+        ))
+    } else if *start_date_time > newest_line_time + tolerance {
+        Some(format!(
+            "the start date time {} is {} after the newest line of the log at {}",
+            start_date_time,
+            format_duration((*start_date_time - newest_line_time).num_seconds() as u64),
+            newest_line_time
+        ))
+    } else {
+        None
+    }
+}
+
+/// Whether the line splits like a TLC-FI log line, in a time, a direction and a message.
+fn is_log_line(line: &str, log_pattern: &LogPattern) -> bool {
+    log_pattern.split(line).len() == 3
+}
+
+/// Fails a conversion that found nothing to write, before any file is created.
+fn no_messages_failure(app_args: &AppArgs, stats: &ConversionStats) -> ConversionFailure {
+    ConversionFailure {
+        exit_code: EXIT_CODE_NO_MESSAGES,
+        message: format!(
+            "Error: the log '{}' contains no signal or detector messages that can be converted, {}.",
+            app_args.tlcfi_log_file,
+            describe_examined_lines(stats, app_args)
+        ),
+    }
+}
+
+/// Describes what the lines of a log without convertible messages were, to tell an empty log from one with only
+/// messages in the other direction.
+fn describe_examined_lines(stats: &ConversionStats, app_args: &AppArgs) -> String {
+    let mut description = format!(
+        "examined {} line(s): {} IN, {} OUT",
+        stats.lines_read, stats.in_lines, stats.out_lines
+    );
+    if stats.first_tick.is_none()
+        && stats.lines_read > 0
+        && app_args.time_source == TimeSource::Ticks
+    {
+        description.push_str(&format!(
+            ", no first tick found in the {} direction",
+            app_args.direction.name()
+        ));
+    }
+    description
+}
+
+fn read_lines_and_save_changes(
+    data: &mut AssimilationData,
+    app_args: &AppArgs,
+) -> Result<(), tlcfi_parsing::LineProblem> {
+    read_lines_and_save_changes_reporting(data, app_args, &mut |_| {})
+}
+
+/// Parses the lines like [read_lines_and_save_changes](fn.read_lines_and_save_changes.html),
+/// calling `on_line` with the length of every line before it's parsed.
+fn read_lines_and_save_changes_reporting(
+    data: &mut AssimilationData,
+    app_args: &AppArgs,
+    on_line: &mut dyn FnMut(usize),
+) -> Result<(), tlcfi_parsing::LineProblem> {
+    if let Some(start_date_time) = app_args.start_date_time {
+        skip_lines_before(data, &start_date_time, on_line);
+    }
+    tlcfi_parsing::parse_lines(
+        data,
+        app_args.direction,
+        app_args.invert_direction,
+        app_args.strict,
+        on_line,
+    )
+}
+
+/// Parses lines that are read while they're parsed like [read_lines_and_save_changes_reporting](fn.read_lines_and_save_changes_reporting.html),
+/// dropping the lines before the start time as they come by.
+fn parse_line_stream_reporting<'a>(
+    data: &mut AssimilationData,
+    lines: impl Iterator<Item = Cow<'a, str>>,
+    app_args: &AppArgs,
+    on_line: &mut dyn FnMut(usize),
+) -> Result<(), tlcfi_parsing::LineProblem> {
+    // Both the dropped and the parsed lines are reported
+    let on_line = RefCell::new(on_line);
+    let log_pattern = data.log_pattern.clone();
+    let mut lines_before_start = 0;
+    let lines = lines.map(Cow::into_owned).filter(|line| {
+        let is_before_start = matches!(&app_args.start_date_time,
+            Some(start_date_time) if is_before(line, start_date_time, &log_pattern));
+        if is_before_start {
+            (on_line.borrow_mut())(line.len());
+            lines_before_start += 1;
+        }
+        !is_before_start
+    });
+    let parsed = tlcfi_parsing::parse_line_stream(
+        data,
+        lines,
+        app_args.direction,
+        app_args.invert_direction,
+        app_args.strict,
+        &mut |line_length| (on_line.borrow_mut())(line_length),
+    );
+    data.stats.lines_before_start += lines_before_start;
+    parsed
+}
+
+/// The zone the VLog time references are written in: UTC with --output-utc, otherwise the zone of the log.
+fn output_time_zone(app_args: &AppArgs) -> TimeZone {
+    if app_args.output_utc {
+        TimeZone::default()
+    } else {
+        app_args.time_zone.unwrap_or_default()
+    }
+}
+
+/// The date time of the log in the output time zone.
+fn to_output_time(log_date_time: &NaiveDateTime, app_args: &AppArgs) -> NaiveDateTime {
+    match app_args.time_zone {
+        Some(time_zone) if app_args.output_utc => time_zone.to_utc(log_date_time),
+        _ => *log_date_time,
+    }
+}
+
+/// Describes the log lines that couldn't be parsed when there are more than `max_errors` of them.
+fn too_many_line_errors(line_errors: &LineErrors, max_errors: Option<usize>) -> Option<String> {
+    let max_errors = max_errors?;
+    if line_errors.total() <= max_errors {
+        return None;
+    }
+    Some(format!(
+        "{} log line(s) can't be parsed, more than the {} allowed with --max-errors ({}). The first of them:\n{}",
+        line_errors.total(),
+        max_errors,
+        line_errors.describe(),
+        line_errors.first_lines.join("\n")
+    ))
+}
+
+/// Describes the line strict parsing stopped at, with its line number in the log file of `line_count` lines.
+fn describe_line_problem(
+    problem: &tlcfi_parsing::LineProblem,
+    data: &AssimilationData,
+    line_count: usize,
+    app_args: &AppArgs,
+) -> String {
+    format!(
+        "line {} of the log can't be parsed: {}\n{}",
+        log_line_number(problem.line_index, data, line_count, app_args),
+        problem.reason,
+        problem.line
+    )
+}
+
+/// The number of lines of the log whose lines were parsed from the sorted lines of the data.
+fn sorted_log_line_count(data: &AssimilationData) -> usize {
+    data.stats.lines_before_start + data.sorted_lines.len()
+}
+
+/// The line number in the log file of `line_count` lines of the parsed line at the given index.
+/// Lines skipped for being before the start time are assumed to be the oldest ones.
+fn log_line_number(
+    line_index: usize,
+    data: &AssimilationData,
+    line_count: usize,
+    app_args: &AppArgs,
+) -> usize {
+    let oldest_first_index = data.stats.lines_before_start + line_index;
+    if app_args.is_chronological {
+        oldest_first_index + 1
+    } else {
+        line_count - oldest_first_index
+    }
+}
+
+/// Writes the skipped lines to the reject file in the order of the log file, each prefixed with the reason it was
+/// skipped and its line number, e.g. `no-split 12 <line>`. The file is only created when a line was skipped.
+fn write_reject_file(
+    reject_file: &str,
+    data: &mut AssimilationData,
+    line_count: usize,
+    app_args: &AppArgs,
+) -> Result<(), ConversionFailure> {
+    let rejected_lines = data.rejected_lines.take().unwrap_or_default();
+    if rejected_lines.is_empty() {
+        return Ok(());
+    }
+    let mut numbered_lines: Vec<(usize, &RejectedLine)> = rejected_lines
+        .iter()
+        .map(|rejected| {
+            (
+                log_line_number(rejected.line_index, data, line_count, app_args),
+                rejected,
+            )
+        })
+        .collect();
+    numbered_lines.sort_by_key(|(line_number, _)| *line_number);
+    let contents: String = numbered_lines
+        .iter()
+        .map(|(line_number, rejected)| {
+            format!("{} {} {}\n", rejected.reason, line_number, rejected.line)
+        })
+        .collect();
+    create_parent_dirs(reject_file)
+        .and_then(|_| fs::write(reject_file, contents))
+        .map_err(|error| ConversionFailure {
+            exit_code: EXIT_CODE_OUTPUT_ERROR,
+            message: format!(
+                "Error: failed to write the rejected lines to '{}': {}",
+                reject_file, error
+            ),
+        })?;
+    data.stats.rejected_lines = rejected_lines.len();
+    Ok(())
+}
+
+/// Writes the predictions of the signal states to the CSV file, which only has its header when there were none.
+fn write_predictions_csv(
+    predictions_csv: &str,
+    data: &mut AssimilationData,
+) -> Result<(), ConversionFailure> {
+    let signal_predictions = data.predictions.take().unwrap_or_default();
+    create_parent_dirs(predictions_csv)
+        .and_then(|_| fs::write(predictions_csv, predictions::to_csv(&signal_predictions)))
+        .map_err(|error| ConversionFailure {
+            exit_code: EXIT_CODE_OUTPUT_ERROR,
+            message: format!(
+                "Error: failed to write the predictions to '{}': {}",
+                predictions_csv, error
+            ),
+        })?;
+    data.stats.signal_predictions = signal_predictions.len();
+    Ok(())
+}
+
+/// Drops the lines logged before the given start time, so the first tick is taken from a message at or after it.
+/// Lines without a time are kept, the parser decides what to do with them.
+fn skip_lines_before(
+    data: &mut AssimilationData,
+    start_date_time: &NaiveDateTime,
+    on_line: &mut dyn FnMut(usize),
+) {
+    let line_count = data.sorted_lines.len();
+    let log_pattern = &data.log_pattern;
+    data.sorted_lines.retain(|line| {
+        let is_before_start = is_before(line, start_date_time, log_pattern);
+        if is_before_start {
+            on_line(line.len());
+        }
+        !is_before_start
+    });
+    data.stats.lines_before_start += line_count - data.sorted_lines.len();
+}
+
+/// Whether the line was logged before the given time. A line without a time isn't.
+fn is_before(line: &str, date_time: &NaiveDateTime, log_pattern: &LogPattern) -> bool {
+    matches!(log_pattern.line_time(line), Some(time) if time < *date_time)
+}
+
+/// Drops all changes that happened more than `duration_in_s` seconds after the first decoded message.
+/// Changes exactly at the boundary are kept.
+fn limit_changes_to_duration(changes: &mut Vec<TimestampedChanges>, duration_in_s: u64) {
+    // A duration that doesn't fit in milliseconds is longer than any log
+    let duration_in_ms = match duration_in_s.checked_mul(1000) {
+        Some(duration_in_ms) => duration_in_ms,
+        None => return,
+    };
+    changes.retain(|change| change.ms_from_beginning <= duration_in_ms);
+}
+
+/// Drops the changes before the first signal change and makes that change the start of the conversion. Returns
+/// the start time moved forward by the skipped time.
+fn skip_warm_up(data: &mut AssimilationData, start_time: &NaiveDateTime) -> NaiveDateTime {
+    let change_count = data.changes.len();
+    let skipped_ms = skip_until_first_signal(&mut data.changes).unwrap_or(0);
+    let skipped_duration = chrono::Duration::milliseconds(skipped_ms as i64);
+    data.start_time += skipped_duration;
+    // The events of the summary are timed from the start as well
+    for controller_reset in &mut data.stats.controller_resets {
+        controller_reset.ms_from_beginning = controller_reset
+            .ms_from_beginning
+            .saturating_sub(skipped_ms);
+    }
+    for retired_object in &mut data.stats.retired_objects {
+        retired_object.ms_from_beginning =
+            retired_object.ms_from_beginning.saturating_sub(skipped_ms);
+    }
+    for event in &mut data.stats.special_vehicle_events {
+        event.ms_from_beginning = event.ms_from_beginning.saturating_sub(skipped_ms);
+    }
+    data.console.info(&format!(
+        "Skipped {} change(s) in the first {} before the first signal change.",
+        change_count - data.changes.len(),
+        format_duration(skipped_ms / 1000)
+    ));
+    *start_time + skipped_duration
+}
+
+/// Drops the changes before the first change with signal states and makes the times relative to it. Returns the
+/// ms it was after the start, None when there are no signal changes and all changes are dropped.
+fn skip_until_first_signal(changes: &mut Vec<TimestampedChanges>) -> Option<u64> {
+    let first_signal_index = changes
+        .iter()
+        .position(|change| !change.signal_names.is_empty());
+    let first_signal_index = match first_signal_index {
+        Some(first_signal_index) => first_signal_index,
+        None => {
+            changes.clear();
+            return None;
+        }
+    };
+    changes.drain(..first_signal_index);
+    let skipped_ms = changes[0].ms_from_beginning;
+    for change in changes.iter_mut() {
+        change.ms_from_beginning = change.ms_from_beginning.saturating_sub(skipped_ms);
+    }
+    Some(skipped_ms)
+}
+
+/// Parses comma separated log levels like `INFO,WARN` to upper case.
+fn parse_log_levels(arg: &str) -> Result<Vec<String>, String> {
+    let log_levels: Vec<String> = arg
+        .split(',')
+        .map(|log_level| log_level.trim().to_ascii_uppercase())
+        .collect();
+    if log_levels.iter().any(|log_level| log_level.is_empty()) {
+        return Err(format!(
+            "Failed to transform argument {} into log levels, expected comma separated levels like INFO,WARN",
+            arg
+        ));
+    }
+    Ok(log_levels)
+}
+
+/// Parses comma separated JSON-RPC methods like `UpdateStates,StateChanged`.
+fn parse_update_methods(arg: &str) -> Result<Vec<String>, String> {
+    let update_methods: Vec<String> = arg
+        .split(',')
+        .map(|method| method.trim().to_string())
+        .collect();
+    if update_methods.iter().any(|method| method.is_empty()) {
+        return Err(format!(
+            "Failed to transform argument {} into methods, expected comma separated methods like UpdateStates,StateChanged",
+            arg
+        ));
+    }
+    Ok(update_methods)
+}
+
+fn parse_first_tick(arg: &str) -> Result<u64, String> {
+    match arg.parse::<u64>() {
+        Ok(tick) if tick <= tlcfi_parsing::MAX_TICKS => Ok(tick),
+        _ => Err(format!(
+            "Failed to transform argument {} into a tick, expected a number up to {}",
+            arg,
+            tlcfi_parsing::MAX_TICKS
+        )),
+    }
+}
+
+/// Keeps the first `limit` changes and returns how many were dropped.
+fn limit_changes(changes: &mut Vec<TimestampedChanges>, limit: usize) -> usize {
+    let dropped = changes.len().saturating_sub(limit);
+    changes.truncate(limit);
+    dropped
+}
+
+fn parse_limit(arg: &str) -> Result<usize, String> {
+    match arg.parse::<usize>() {
+        Ok(limit) if limit > 0 => Ok(limit),
+        _ => Err(format!(
+            "Failed to transform argument {} into a number of changes, expected a number above 0",
+            arg
+        )),
+    }
+}
+
+/// Which date time is used in the name of the VLog file.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize)]
+enum NameDate {
+    /// The start of the converted window, i.e. the start date time
+    #[default]
+    WindowStart,
+    /// The moment of the first change
+    FirstChange,
+    /// The first change on the day that has the most changes
+    MajorityDay,
+}
+
+impl NameDate {
+    fn name(&self) -> &'static str {
+        match self {
+            NameDate::WindowStart => "window-start",
+            NameDate::FirstChange => "first-change",
+            NameDate::MajorityDay => "majority-day",
+        }
+    }
+}
+
+impl std::str::FromStr for NameDate {
+    type Err = String;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        match arg {
+            "window-start" => Ok(Self::WindowStart),
+            "first-change" => Ok(Self::FirstChange),
+            "majority-day" => Ok(Self::MajorityDay),
+            _ => Err(format!(
+                "Unknown name date '{}', use first-change, window-start or majority-day",
+                arg
+            )),
+        }
+    }
+}
+
+/// What ends every message in the VLog file.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize)]
+enum LineEnding {
+    /// `\r\n`, as VLog files are written by the controllers themselves
+    #[default]
+    Crlf,
+    /// `\n`, for tools that don't accept carriage returns
+    Lf,
+}
+
+impl LineEnding {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Lf => "\n",
+        }
+    }
+}
+
+impl std::str::FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        match arg {
+            "crlf" => Ok(Self::Crlf),
+            "lf" => Ok(Self::Lf),
+            _ => Err(format!("Unknown line ending '{}', use crlf or lf", arg)),
+        }
+    }
+}
+
+/// Chooses the date time to name the VLog file after, which matters when the changes span midnight.
+/// Falls back to the start date time when there are no changes.
+fn choose_file_name_date_time(
+    name_date: NameDate,
+    start_date_time: &NaiveDateTime,
+    changes: &[TimestampedChanges],
+) -> NaiveDateTime {
+    let change_date_times = changes.iter().map(|change| {
+        *start_date_time + chrono::Duration::milliseconds(change.ms_from_beginning as i64)
+    });
+    match name_date {
+        NameDate::WindowStart => *start_date_time,
+        NameDate::FirstChange => change_date_times.min().unwrap_or(*start_date_time),
+        NameDate::MajorityDay => {
+            let mut first_change_per_day: HashMap<chrono::NaiveDate, (usize, NaiveDateTime)> =
+                HashMap::new();
+            for date_time in change_date_times {
+                let (count, first) = first_change_per_day
+                    .entry(date_time.date())
+                    .or_insert((0, date_time));
+                *count += 1;
+                *first = (*first).min(date_time);
+            }
+            first_change_per_day
+                .values()
+                .max_by_key(|(count, first)| (*count, std::cmp::Reverse(*first)))
+                .map(|(_, first)| *first)
+                .unwrap_or(*start_date_time)
+        }
+    }
+}
+
+/// Names the VLog file that starts at the given date time with the output template, in the output directory if one is set.
+fn create_file_name(tlc_name: &str, start_date_time: &NaiveDateTime, app_args: &AppArgs) -> String {
+    let file_name = app_args.output_template.expand(tlc_name, start_date_time);
+    match &app_args.output_dir {
+        Some(output_dir) => std::path::Path::new(output_dir)
+            .join(file_name)
+            .to_string_lossy()
+            .to_string(),
+        None => file_name,
+    }
+}
+
+/// Creates the directories of an output file name, which the output template can contain.
+fn create_parent_dirs(file_name: &str) -> std::io::Result<()> {
+    match std::path::Path::new(file_name).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
+
+/// Why the command line arguments can't be converted with.
+#[derive(Debug)]
+enum ArgsError {
+    /// An argument that pico-args couldn't parse
+    Parsing(pico_args::Error),
+    /// Arguments that parse, but can't be used, e.g. a mapping file without detectors or a config file with an
+    /// invalid value. The message says what's wrong with them.
+    Invalid(String),
+}
+
+impl ArgsError {
+    /// Prints the error, followed by the help when an argument couldn't be parsed and by a hint to it otherwise.
+    fn print(&self, help: &str) {
+        eprintln!("Error: {}.", self);
+        match self {
+            ArgsError::Parsing(_) => println!("{}", help),
+            ArgsError::Invalid(_) => eprintln!("Use --help to see the options."),
+        }
+    }
+
+    /// What's wrong with the arguments, without the wording of pico-args around it.
+    fn cause(self) -> String {
+        match self {
+            ArgsError::Invalid(cause)
+            | ArgsError::Parsing(pico_args::Error::ArgumentParsingFailed { cause })
+            | ArgsError::Parsing(pico_args::Error::Utf8ArgumentParsingFailed { cause, .. }) => {
+                cause
+            }
+            ArgsError::Parsing(error) => error.to_string(),
+        }
+    }
+}
+
+impl From<pico_args::Error> for ArgsError {
+    fn from(error: pico_args::Error) -> Self {
+        ArgsError::Parsing(error)
+    }
+}
+
+impl std::fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ArgsError::Parsing(error) => write!(f, "{}", error),
+            ArgsError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+fn parse_args(args: Vec<OsString>) -> Result<AppArgs, ArgsError> {
+    let mut pargs = pico_args::Arguments::from_vec(args);
+
+    if pargs.contains(["-h", "--help"]) {
+        println!("{}", ARGS_HELP);
+        std::process::exit(0);
+    }
+
+    if pargs.contains(["-V", "--version"]) {
+        println!("{}", version_info());
+        std::process::exit(0);
+    }
+
+    let config = match pargs.opt_value_from_str::<_, String>("--config")? {
+        Some(config_file) => config::Config::load(&config_file).map_err(ArgsError::Invalid)?,
+        None => config::Config::default(),
+    };
+    let mut args = pargs.finish();
+    let config_mapping_file = config.mapping_file_not_in(&args);
+    args.extend(config.args_not_in(&args));
+    let mut pargs = pico_args::Arguments::from_vec(args);
+
+    let mut app_args = parse_options(&mut pargs)?;
+    let mapping_file = pargs.opt_value_from_str::<_, String>("--mapping-file")?;
+    let mapping_dir = pargs.opt_value_from_str::<_, String>("--mapping-dir")?;
+    // The options have to be taken out first, the free standing argument would take an option name as file name
+    let positional_mapping_file = pargs.opt_free_from_str::<String>()?;
+    let mapping_file = match (mapping_file, positional_mapping_file) {
+        (Some(_), Some(_)) => {
+            return Err(ArgsError::Invalid(
+                "the mapping file is given both with --mapping-file and as argument".to_string(),
+            ))
+        }
+        (Some(mapping_file), None) => Some(mapping_file),
+        (None, Some(mapping_file)) => {
+            eprintln!(
+                "Warning: giving the mapping file as argument is deprecated, use --mapping-file {}",
+                mapping_file
+            );
+            Some(mapping_file)
+        }
+        (None, None) => config_mapping_file,
+    };
+    let mapping_file = match (mapping_dir, mapping_file) {
+        (Some(mapping_dir), None) => Some(mapping_dir),
+        (Some(_), Some(_)) => {
+            return Err(ArgsError::Invalid(
+                "give either --mapping-dir or --mapping-file".to_string(),
+            ))
+        }
+        (None, mapping_file) => mapping_file,
+    };
+    // Generating a mapping and listing the objects are done before there is one
+    if app_args.generate_mapping.is_some() || app_args.list_objects {
+        return Ok(app_args);
+    }
+    // The log has the mapping itself
+    if app_args.mapping_from_log && mapping_file.is_none() {
+        return Ok(app_args);
+    }
+    let mapping_file = mapping_file.ok_or_else(|| {
+        ArgsError::Invalid("no mapping file was given, use --mapping-file PATH".to_string())
+    })?;
+    // A directory has a mapping file per TLC, of which the one of the log is used
+    if app_args.watch_dir.is_some() && std::path::Path::new(&mapping_file).is_dir() {
+        // The logs of a watched directory each get their own mapping file when they appear
+        app_args.mapping_dir = Some(mapping_file);
+        return Ok(app_args);
+    }
+    let mapping_file = if std::path::Path::new(&mapping_file).is_dir() {
+        let candidates = mapping_selection::tlc_name_candidates(
+            app_args.tlc_name.as_deref(),
+            &app_args.tlcfi_log_file,
+        );
+        let selected_file = mapping_selection::select_mapping_file(&mapping_file, &candidates)
+            .map_err(ArgsError::Invalid)?;
+        app_args.mapping_dir = Some(mapping_file);
+        selected_file
+    } else {
+        mapping_file
+    };
+    // Validating the mapping reports all of its problems itself
+    app_args.vlog_tlcfi_mapping_file = if app_args.validate_mapping {
+        check_file_existence(&mapping_file)
+    } else {
+        validate_mapping_file(
+            &mapping_file,
+            app_args.conversion_mode,
+            app_args.tlc_name.is_none() && !app_args.mapping_from_log,
+        )
+    }
+    .map_err(ArgsError::Invalid)?;
+    Ok(app_args)
+}
+
+/// Parses all options, but not the free standing mapping file argument.
+fn parse_options(pargs: &mut pico_args::Arguments) -> Result<AppArgs, ArgsError> {
+    let mut args = AppArgs {
+        dry_run: pargs.contains("--dry-run"),
+        validate_mapping: pargs.contains("--validate-mapping"),
+        generate_mapping: pargs.opt_value_from_str("--generate-mapping")?,
+        list_objects: pargs.contains("--list-objects"),
+        follow: pargs.contains("--follow"),
+        reset_snapshot: pargs.contains("--reset-snapshot"),
+        name_date: pargs.opt_value_from_str("--name-date")?.unwrap_or_default(),
+        is_chronological: parse_is_chronological(pargs)?,
+        conversion_mode: parse_conversion_mode(pargs)?,
+        direction: pargs.opt_value_from_str("--direction")?.unwrap_or_default(),
+        invert_direction: pargs.contains("--invert-direction"),
+        time_source: pargs
+            .opt_value_from_str("--time-source")?
+            .unwrap_or_default(),
+        log_pattern: pargs
+            .opt_value_from_str("--log-pattern")?
+            .unwrap_or_default(),
+        repair_clock_steps: pargs.contains("--repair-clock-steps"),
+        signal_state_encoding: parse_signal_state_encoding(pargs),
+        dedupe: pargs.contains("--dedupe"),
+        skip_until_first_signal: pargs.contains("--skip-until-first-signal"),
+        amber_policy: pargs
+            .opt_value_from_str("--amber-policy")?
+            .unwrap_or_default(),
+        min_mapped_fraction: pargs
+            .opt_value_from_fn("--min-mapped-fraction", mapping_check::parse_fraction)?
+            .unwrap_or(0.9),
+        max_id_gap: pargs.opt_value_from_str("--max-id-gap")?.unwrap_or(10),
+        renumber_ids: pargs.contains("--renumber-ids"),
+        mapping_from_log: pargs.contains("--mapping-from-log"),
+        pace: pargs.contains("--pace"),
+        show_progress: !pargs.contains("--no-progress"),
+        strict: pargs.contains("--strict"),
+        strict_rpc: pargs.contains("--strict-rpc"),
+        max_errors: pargs.opt_value_from_str("--max-errors")?,
+        reject_file: pargs.opt_value_from_str("--reject-file")?,
+        predictions_csv: pargs.opt_value_from_str("--predictions-csv")?,
+        summary_json: pargs.opt_value_from_str("--summary-json")?,
+        tlc_name: pargs.opt_value_from_fn("--tlc-name", parse_tlc_name)?,
+        append: pargs.opt_value_from_fn("--append", check_file_existence)?,
+        checkpoint: pargs.opt_value_from_str("--checkpoint")?,
+        tee: pargs.contains("--tee"),
+        watch_dir: pargs.opt_value_from_fn("--watch", batch::check_dir_existence)?,
+        watch_pattern: pargs
+            .opt_value_from_str("--watch-pattern")?
+            .unwrap_or_else(|| "*.txt".to_string()),
+        output_dir: pargs.opt_value_from_str("--output-dir")?,
+        overwrite: pargs.contains("--overwrite"),
+        pace_speed: pargs
+            .opt_value_from_fn("--pace-speed", pacing::parse_pace_speed)?
+            .unwrap_or(1.0),
+        duration_in_s: pargs.opt_value_from_fn("--duration", parse_duration)?,
+        limit: pargs.opt_value_from_fn("--limit", parse_limit)?,
+        first_tick: pargs.opt_value_from_fn("--first-tick", parse_first_tick)?,
+        tick_unit: pargs.opt_value_from_str("--tick-unit")?.unwrap_or_default(),
+        log_levels: pargs.opt_value_from_fn("--log-levels", parse_log_levels)?,
+        logger_name: pargs.opt_value_from_str("--logger-name")?,
+        update_methods: pargs
+            .opt_value_from_fn("--update-methods", parse_update_methods)?
+            .unwrap_or_default(),
+        start_tolerance_s: pargs
+            .opt_value_from_fn("--start-tolerance", parse_duration)?
+            .unwrap_or(DEFAULT_START_TOLERANCE_S),
+        signal_filter: pargs
+            .opt_value_from_fn("--filter-signals", change_filtering::parse_name_patterns)?
+            .map(NameFilter::Include),
+        detector_filter: parse_detector_filter(pargs)?,
+        time_zone: pargs.opt_value_from_str("--timezone")?,
+        output_utc: pargs.contains("--output-utc"),
+        tlcfi_log_file: pargs
+            .opt_value_from_fn("--tlcfi-log-file", check_file_existence)?
+            .unwrap_or("tlcfi.txt".to_string()),
+        verbosity: parse_verbosity(pargs)?,
+        cache_dir: pargs.opt_value_from_str("--cache-dir")?,
+        output_template: pargs
+            .opt_value_from_str("--output-template")?
+            .unwrap_or_default(),
+        split: pargs.opt_value_from_str("--split")?.unwrap_or_default(),
+        max_messages: pargs
+            .opt_value_from_fn("--max-messages", output_splitting::parse_max_messages)?,
+        max_output_size: pargs.opt_value_from_fn("--max-output-size", output_size::parse_size)?,
+        line_ending: pargs
+            .opt_value_from_str("--line-ending")?
+            .unwrap_or_default(),
+        size_policy: pargs
+            .opt_value_from_str("--size-policy")?
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+    args.start_date_time = parse_start(pargs, args.time_zone)?;
+    if let Some(list_format) = pargs.opt_value_from_str("--format")? {
+        if !args.list_objects {
+            return Err(ArgsError::Invalid(
+                "--format only applies to --list-objects".to_string(),
+            ));
+        }
+        args.list_format = list_format;
+    }
+    let log_dir = pargs.opt_value_from_fn("--log-dir", batch::check_dir_existence)?;
+    let date = pargs.opt_value_from_fn("--date", dated_logs::parse_date)?;
+    match (log_dir, date) {
+        (Some(log_dir), Some(date)) => select_logs_of_date(&mut args, &log_dir, &date)?,
+        (None, None) => {}
+        _ => {
+            return Err(ArgsError::Invalid(
+                "--log-dir and --date need each other".to_string(),
+            ))
+        }
+    }
+    if args.follow {
+        check_follow_compatibility(&args)?;
+    }
+    if args.append.is_some() {
+        check_append_compatibility(&args)?;
+    }
+    if args.checkpoint.is_some() {
+        check_checkpoint_compatibility(&args)?;
+    }
+    if args.watch_dir.is_some() {
+        check_watch_compatibility(&args)?;
+    }
+    if args.repair_clock_steps && matches!(args.log_pattern, LogPattern::Custom(_)) {
+        return Err(ArgsError::Invalid("--repair-clock-steps only corrects lines with the default layout, not with --log-pattern"
+                .to_string()));
+    }
+    if args.first_tick.is_some() && args.time_source != TimeSource::Ticks {
+        return Err(ArgsError::Invalid(
+            "--first-tick needs --time-source ticks".to_string(),
+        ));
+    }
+    if args.output_utc && args.time_zone.is_none() {
+        return Err(ArgsError::Invalid(
+            "--output-utc needs --timezone, the zone the log is stamped in".to_string(),
+        ));
+    }
+    Ok(args)
+}
+
+/// Reads the logs of the date from the directory as one log, starting at the first message of the date unless
+/// --start-date-time is given.
+fn select_logs_of_date(
+    args: &mut AppArgs,
+    log_dir: &str,
+    date: &NaiveDate,
+) -> Result<(), ArgsError> {
+    let logs_of_date = dated_logs::find_logs_of_date(log_dir, date, &args.log_pattern)
+        .map_err(ArgsError::Invalid)?;
+    args.tlcfi_log_file = logs_of_date[0].0.clone();
+    args.dated_log_files = logs_of_date
+        .iter()
+        .map(|(log_file, _)| log_file.clone())
+        .collect();
+    if args.start_date_time.is_none() {
+        args.start_date_time = Some(dated_logs::start_of_date(date, &logs_of_date));
+    }
+    Ok(())
+}
+
+/// Following writes a single file while the log grows, which rules out the options that need the whole log or several files.
+fn check_follow_compatibility(args: &AppArgs) -> Result<(), ArgsError> {
+    let incompatible_option = if args.dry_run {
+        Some("--dry-run")
+    } else if args.duration_in_s.is_some() {
+        Some("--duration")
+    } else if args.limit.is_some() {
+        Some("--limit")
+    } else if args.skip_until_first_signal {
+        Some("--skip-until-first-signal")
+    } else if args.split != SplitMode::None {
+        Some("--split")
+    } else if args.max_messages.is_some() {
+        Some("--max-messages")
+    } else if args.max_output_size.is_some() {
+        Some("--max-output-size")
+    } else if args.reject_file.is_some() {
+        Some("--reject-file")
+    } else if args.predictions_csv.is_some() {
+        Some("--predictions-csv")
+    } else if args.strict_rpc {
+        Some("--strict-rpc")
+    } else if args.dated_log_files.len() > 1 {
+        Some("--date with more than one log")
+    } else if compressed_logs::is_gzip_file_name(&args.tlcfi_log_file)
+        || zip_archive::is_zip_file_name(&args.tlcfi_log_file)
+    {
+        Some("a compressed log")
+    } else {
+        None
+    };
+    match incompatible_option {
+        Some(option) => Err(ArgsError::Invalid(format!(
+            "--follow and {} can't be used together",
+            option
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Appending writes to a single existing file, so options that name, split or number output files don't apply.
+fn check_append_compatibility(args: &AppArgs) -> Result<(), ArgsError> {
+    let incompatible_option = if args.follow {
+        Some("--follow")
+    } else if args.split != SplitMode::None {
+        Some("--split")
+    } else if args.max_messages.is_some() {
+        Some("--max-messages")
+    } else if args.max_output_size.is_some() {
+        Some("--max-output-size")
+    } else if args.renumber_ids {
+        Some("--renumber-ids")
+    } else if args.cache_dir.is_some() {
+        Some("--cache-dir")
+    } else if args.skip_until_first_signal {
+        Some("--skip-until-first-signal")
+    } else {
+        None
+    };
+    match incompatible_option {
+        Some(option) => Err(ArgsError::Invalid(format!(
+            "--append and {} can't be used together",
+            option
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Watching converts every log on its own once it's complete, which rules out the options for a single log that
+/// is still growing or continued.
+fn check_watch_compatibility(args: &AppArgs) -> Result<(), ArgsError> {
+    let incompatible_option = if args.follow {
+        Some("--follow")
+    } else if args.append.is_some() {
+        Some("--append")
+    } else if args.checkpoint.is_some() {
+        Some("--checkpoint")
+    } else if !args.dated_log_files.is_empty() {
+        Some("--date")
+    } else if args.validate_mapping {
+        Some("--validate-mapping")
+    } else if args.generate_mapping.is_some() {
+        Some("--generate-mapping")
+    } else if args.list_objects {
+        Some("--list-objects")
+    } else {
+        None
+    };
+    match incompatible_option {
+        Some(option) => Err(ArgsError::Invalid(format!(
+            "--watch and {} can't be used together",
+            option
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// A checkpointed conversion reads the log in batches from its start to its end and writes a single file,
+/// which rules out the options that need the whole log, more files or the state of earlier batches.
+fn check_checkpoint_compatibility(args: &AppArgs) -> Result<(), ArgsError> {
+    if !args.is_chronological {
+        return Err(ArgsError::Invalid(
+            "--checkpoint needs a chronological log, add --chronological".to_string(),
+        ));
+    }
+    let incompatible_option = if args.follow {
+        Some("--follow")
+    } else if args.append.is_some() {
+        Some("--append")
+    } else if args.dry_run {
+        Some("--dry-run")
+    } else if args.pace {
+        Some("--pace")
+    } else if args.duration_in_s.is_some() {
+        Some("--duration")
+    } else if args.limit.is_some() {
+        Some("--limit")
+    } else if args.skip_until_first_signal {
+        Some("--skip-until-first-signal")
+    } else if args.split != SplitMode::None {
+        Some("--split")
+    } else if args.max_messages.is_some() {
+        Some("--max-messages")
+    } else if args.max_output_size.is_some() {
+        Some("--max-output-size")
+    } else if args.name_date != NameDate::WindowStart {
+        Some("--name-date")
+    } else if args.reject_file.is_some() {
+        Some("--reject-file")
+    } else if args.predictions_csv.is_some() {
+        Some("--predictions-csv")
+    } else if args.dedupe {
+        Some("--dedupe")
+    } else if args.reset_snapshot {
+        Some("--reset-snapshot")
+    } else if args.repair_clock_steps {
+        Some("--repair-clock-steps")
+    } else if args.renumber_ids {
+        Some("--renumber-ids")
+    } else if args.mapping_from_log {
+        Some("--mapping-from-log")
+    } else if args.cache_dir.is_some() {
+        Some("--cache-dir")
+    } else if args.dated_log_files.len() > 1 {
+        Some("--date with more than one log")
+    } else if compressed_logs::is_gzip_file_name(&args.tlcfi_log_file)
+        || zip_archive::is_zip_file_name(&args.tlcfi_log_file)
+    {
+        Some("a compressed log")
+    } else {
+        None
+    };
+    match incompatible_option {
+        Some(option) => Err(ArgsError::Invalid(format!(
+            "--checkpoint and {} can't be used together",
+            option
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn parse_signal_state_encoding(pargs: &mut pico_args::Arguments) -> SignalStateEncoding {
+    if pargs.contains("--detailed-signal-states") {
+        SignalStateEncoding::Detailed
+    } else {
+        SignalStateEncoding::Collapsed
+    }
+}
+
+/// Parses whether the logs are chronological from `--chronological`, which may be followed by `true` or `false`,
+/// and `--reverse`, which means the newest line comes first. The logs aren't chronological by default.
+///
+/// pico_args always takes the next argument as the value of an option, so the arguments are taken out by hand.
+fn parse_is_chronological(pargs: &mut pico_args::Arguments) -> Result<bool, ArgsError> {
+    let args = std::mem::replace(pargs, pico_args::Arguments::from_vec(Vec::new())).finish();
+    let mut remaining_args = Vec::with_capacity(args.len());
+    let mut chronological = None;
+    let mut reverse = false;
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next() {
+        let arg_str = arg.to_str().unwrap_or_default();
+        if arg_str == "--reverse" {
+            reverse = true;
+        } else if arg_str == "--chronological" {
+            let value = match args.peek().and_then(|value| value.to_str()) {
+                Some(value) if !value.starts_with('-') => {
+                    let is_chronological = parse_chronological_value(value)?;
+                    args.next();
+                    is_chronological
+                }
+                _ => true,
+            };
+            chronological = Some(value);
+        } else if let Some(value) = arg_str.strip_prefix("--chronological=") {
+            chronological = Some(parse_chronological_value(value)?);
+        } else {
+            remaining_args.push(arg);
+        }
+    }
+    *pargs = pico_args::Arguments::from_vec(remaining_args);
+    match (chronological, reverse) {
+        (Some(_), true) => Err(ArgsError::Invalid(
+            "--chronological and --reverse can't be used together".to_string(),
+        )),
+        (Some(is_chronological), false) => Ok(is_chronological),
+        (None, _) => Ok(false),
+    }
+}
+
+fn parse_chronological_value(value: &str) -> Result<bool, ArgsError> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(ArgsError::Invalid(format!(
+                "'{}' isn't a log order, use --chronological, --chronological true, --chronological false or --reverse",
+                value
+            ))),
+    }
+}
+
+fn parse_verbosity(pargs: &mut pico_args::Arguments) -> Result<Verbosity, ArgsError> {
+    match (
+        pargs.contains(["-q", "--quiet"]),
+        pargs.contains(["-v", "--verbose"]),
+    ) {
+        (true, true) => Err(ArgsError::Invalid(
+            "--quiet and --verbose can't be used together".to_string(),
+        )),
+        (true, false) => Ok(Verbosity::Quiet),
+        (false, true) => Ok(Verbosity::Verbose),
+        (false, false) => Ok(Verbosity::Normal),
+    }
+}
+
+fn parse_conversion_mode(pargs: &mut pico_args::Arguments) -> Result<ConversionMode, ArgsError> {
+    match (
+        pargs.contains("--signals-only"),
+        pargs.contains("--detectors-only"),
+    ) {
+        (true, true) => Err(ArgsError::Invalid(
+            "--signals-only and --detectors-only can't be used together".to_string(),
+        )),
+        (true, false) => Ok(ConversionMode::SignalsOnly),
+        (false, true) => Ok(ConversionMode::DetectorsOnly),
+        (false, false) => Ok(ConversionMode::All),
+    }
+}
+
+fn parse_detector_filter(
+    pargs: &mut pico_args::Arguments,
+) -> Result<Option<NameFilter>, ArgsError> {
+    let included =
+        pargs.opt_value_from_fn("--filter-detectors", change_filtering::parse_name_patterns)?;
+    let excluded =
+        pargs.opt_value_from_fn("--exclude-detectors", change_filtering::parse_name_patterns)?;
+    match (included, excluded) {
+        (Some(_), Some(_)) => Err(ArgsError::Invalid(
+            "--filter-detectors and --exclude-detectors can't be used together".to_string(),
+        )),
+        (Some(names), None) => Ok(Some(NameFilter::Include(names))),
+        (None, Some(names)) => Ok(Some(NameFilter::Exclude(names))),
+        (None, None) => Ok(None),
+    }
+}
+
+fn version_info() -> String {
+    let supported_messages: Vec<String> = tlcfi_assimilator::supported_vlog_messages()
+        .iter()
+        .map(|message_type| format!("{} ({:?})", message_type.code(), message_type))
+        .collect();
+    let capabilities = tlcfi_assimilator::capabilities();
+    format!(
+        "TLC-FI Assimilator {}\nSupported VLog messages: {}\nCapabilities: status snapshots: {}, binary output: {}, prio messages: {}, requests: {}",
+        env!("CARGO_PKG_VERSION"),
+        supported_messages.join(", "),
+        capabilities.status_snapshots,
+        capabilities.binary_output,
+        capabilities.prio_messages,
+        capabilities.requests
+    )
+}
+
+fn parse_date_time(arg: &str) -> Result<NaiveDateTime, String> {
+    // <Year-month-day format (ISO 8601). Same to %Y-%m-%d><T><Hour-minute-second format. Same to %H:%M:%S><Similar to .%f but left-aligned. These all consume the leading dot.>
+    match NaiveDateTime::parse_from_str(arg, "%FT%T%.3f") {
+        Ok(date_time) => Ok(date_time),
+        Err(error) => Err(format!(
+            "Failed to transform argument {} into a date time:\n{}",
+            arg, error
+        )),
+    }
+}
+
+/// How far the start date time may be outside of the log before it's warned about, 10 minutes.
+const DEFAULT_START_TOLERANCE_S: u64 = 600;
+
+/// The earliest and latest start accepted as epoch milliseconds, outside of which it's probably in seconds or mistyped.
+const MIN_EPOCH_MS: i64 = 946_684_800_000; // 2000-01-01
+const MAX_EPOCH_MS: i64 = 4_102_444_800_000; // 2100-01-01
+
+/// Parses the start given with `--start-date-time` or `--epoch-ms`, None when neither is given.
+fn parse_start(
+    pargs: &mut pico_args::Arguments,
+    time_zone: Option<TimeZone>,
+) -> Result<Option<NaiveDateTime>, ArgsError> {
+    let start_date_time = pargs.opt_value_from_str::<_, String>("--start-date-time")?;
+    let epoch_ms = pargs.opt_value_from_str::<_, String>("--epoch-ms")?;
+    let start = match (start_date_time, epoch_ms) {
+        (Some(_), Some(_)) => Err("give either --start-date-time or --epoch-ms".to_string()),
+        (Some(start_date_time), None) => parse_start_date_time(&start_date_time, time_zone),
+        (None, Some(epoch_ms)) => parse_epoch_ms(&epoch_ms)
+            .map(|utc| time_zone.map_or(utc, |time_zone| time_zone.from_utc(&utc))),
+        (None, None) => return Ok(None),
+    };
+    start.map(Some).map_err(ArgsError::Invalid)
+}
+
+/// Parses a start date time in ISO 8601 format, optionally with an offset from UTC, or as epoch milliseconds when it's
+/// only digits. Starts with an offset and epoch milliseconds are moved to the time zone the log is stamped in.
+fn parse_start_date_time(arg: &str, time_zone: Option<TimeZone>) -> Result<NaiveDateTime, String> {
+    if !arg.is_empty() && arg.chars().all(|c| c.is_ascii_digit()) {
+        parse_epoch_ms(arg).map(|utc| time_zone.map_or(utc, |time_zone| time_zone.from_utc(&utc)))
+    } else if let Ok(date_time) = DateTime::parse_from_rfc3339(arg) {
+        let offset_s = date_time.offset().local_minus_utc();
+        match time_zone {
+            Some(time_zone) => Ok(time_zone.from_utc(&date_time.naive_utc())),
+            None if offset_s == 0 => Ok(date_time.naive_utc()),
+            // Without a time zone the log is taken as UTC, which a local offset probably wasn't meant for
+            None => Err(format!(
+                "The start date time {} has the offset {}, but the time zone the log is stamped in isn't known. Give it with --timezone, or give the start in UTC with Z",
+                arg,
+                date_time.offset()
+            )),
+        }
+    } else {
+        parse_date_time(arg)
+    }
+}
+
+/// Parses milliseconds since 1970-01-01 UTC to the UTC date time, rejecting the ones before 2000 or from 2100 on.
+fn parse_epoch_ms(arg: &str) -> Result<NaiveDateTime, String> {
+    let epoch_ms = arg.parse::<i64>().map_err(|_| {
+        format!(
+            "Failed to transform argument {} into epoch milliseconds, expected a number",
+            arg
+        )
+    })?;
+    if !(MIN_EPOCH_MS..MAX_EPOCH_MS).contains(&epoch_ms) {
+        return Err(format!(
+            "The epoch milliseconds {} aren't between 2000 and 2100, are they seconds instead of milliseconds?",
+            arg
+        ));
+    }
+    Ok(NaiveDateTime::from_timestamp(
+        epoch_ms.div_euclid(1000),
+        (epoch_ms.rem_euclid(1000) * 1_000_000) as u32,
+    ))
+}
+
+/// Parses a TLC name for the V-Log information message, which has room for
+/// [MAX_TLC_NAME_LENGTH](../tlcfi_assimilator/vlog_transformer/constant.MAX_TLC_NAME_LENGTH.html) characters.
+fn parse_tlc_name(arg: &str) -> Result<String, String> {
+    let length = arg.chars().count();
+    if arg.trim().is_empty() {
+        Err("The TLC name can't be empty".to_string())
+    } else if length > vlog_transformer::MAX_TLC_NAME_LENGTH {
+        Err(format!(
+            "The TLC name '{}' has {} characters, but the V-Log information message only has room for {}",
+            arg,
+            length,
+            vlog_transformer::MAX_TLC_NAME_LENGTH
+        ))
+    } else {
+        Ok(arg.to_string())
+    }
+}
+
+/// Parses a duration given either as plain seconds (`3600`) or with units (`1h30m`, `90s`) into seconds.
+fn parse_duration(arg: &str) -> Result<u64, String> {
+    let too_long = || {
+        format!(
+            "The duration {} is too long, it can be at most {}s",
+            arg, MAX_DURATION_S
+        )
+    };
+    if let Ok(seconds) = arg.parse::<u64>() {
+        return Some(seconds)
+            .filter(|seconds| *seconds <= MAX_DURATION_S)
+            .ok_or_else(too_long);
+    }
+
+    let mut total_seconds = 0;
+    let mut number = String::new();
+    for character in arg.chars() {
+        if character.is_ascii_digit() {
+            number.push(character);
+            continue;
+        }
+        let multiplier = match character {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => {
+                return Err(format!(
+                    "Unknown unit '{}' in duration {}, use h, m or s",
+                    character, arg
+                ))
+            }
+        };
+        let amount: u64 = number.parse().map_err(|_| {
+            format!(
+                "Expected a number before '{}' in duration {}",
+                character, arg
+            )
+        })?;
+        total_seconds = amount
+            .checked_mul(multiplier)
+            .and_then(|seconds| seconds.checked_add(total_seconds))
+            .filter(|seconds| *seconds <= MAX_DURATION_S)
+            .ok_or_else(too_long)?;
+        number.clear();
+    }
+
+    if !number.is_empty() || arg.is_empty() {
+        return Err(format!(
+            "Failed to transform argument {} into a duration, use seconds or units like 1h30m",
+            arg
+        ));
+    }
+    Ok(total_seconds)
+}
+
+/// The longest duration, whose milliseconds still fit in the signed 64 bits the times are computed with.
+const MAX_DURATION_S: u64 = i64::MAX as u64 / 1000;
+
+/// Formats seconds like [parse_duration](fn.parse_duration.html) takes them, e.g. `24h` or `1h30m5s`.
+fn format_duration(total_seconds: u64) -> String {
+    let units = [
+        (total_seconds / 3600, 'h'),
+        (total_seconds / 60 % 60, 'm'),
+        (total_seconds % 60, 's'),
+    ];
+    let duration: String = units
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, unit)| format!("{}{}", count, unit))
+        .collect();
+    if duration.is_empty() {
+        "0s".to_string()
+    } else {
+        duration
+    }
+}
+
+fn get_start_date_time_from_file<S: AsRef<str>>(
+    sorted_lines: impl IntoIterator<Item = S>,
+    log_pattern: &LogPattern,
+) -> Result<NaiveDateTime, String> {
+    let timestamp = sorted_lines
+        .into_iter()
+        // if it is a logline
+        .filter(|line| log_pattern.split(line.as_ref()).len() == 3)
+        .find_map(|line| log_pattern.timestamp(line.as_ref()).map(str::to_string));
+    let start_date_time = match timestamp {
+        Some(timestamp) => tlcfi_parsing::parse_log_timestamp(&timestamp),
+        None => Err("the log has no line with a time, a direction and a message".to_string()),
+    };
+
+    match start_date_time {
+        Ok(date_time) => Ok(date_time),
+        Err(error) => Err(format!("--start-date-time wasn't given and we couldn't extract it from the log file. Failed with error: {}", error)),
+    }
+}
+
+fn check_file_existence(file_name: &str) -> Result<String, String> {
+    match File::open(file_name) {
+        Ok(_) => Ok(file_name.to_string()),
+        Err(error) => Err(format!(
+            "File name passed as argument '{}' could not be opened. Did you make a typo?\n{}",
+            file_name, error
+        )),
+    }
+}
+
+/// Checks that the mapping file opens and has a TLC name, when it `requires_tlc_name`,
+/// and the mappings the conversion mode needs, reporting all of its problems in one message.
+fn validate_mapping_file(
+    file_name: &str,
+    conversion_mode: ConversionMode,
+    requires_tlc_name: bool,
+) -> Result<String, String> {
+    let mut problems = Vec::new();
+    match vlog_transformer::load_tlc_name(file_name) {
+        Ok(Some(_)) => (),
+        Ok(None) if !requires_tlc_name => (),
+        Ok(None) => problems.push("No TLC name found in the mapping file!".to_string()),
+        Err(_) => return check_file_existence(file_name),
+    }
+    let mut mapping_types = Vec::new();
+    if conversion_mode.converts_signals() {
+        mapping_types.push("Signals");
+    }
+    if conversion_mode.converts_detectors() {
+        mapping_types.push("Detectors");
+    }
+    for mapping_type in mapping_types {
+        if let Err(error) = vlog_transformer::load_mappings(file_name, mapping_type) {
+            problems.push(error.to_string());
+        }
+    }
+    if problems.is_empty() {
+        Ok(file_name.to_string())
+    } else {
+        Err(format!(
+            "The mapping file '{}' can't be used:\n{}",
+            file_name,
+            problems.join("\n")
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AppArgs {
+    dry_run: bool,
+    /// Only checks the structure of the mapping file
+    validate_mapping: bool,
+    /// The file to write a mapping file skeleton to instead of converting
+    generate_mapping: Option<String>,
+    /// Prints the signals and detectors in the log instead of converting
+    list_objects: bool,
+    list_format: log_stats::ListFormat,
+    follow: bool,
+    reset_snapshot: bool,
+    name_date: NameDate,
+    is_chronological: bool,
+    conversion_mode: ConversionMode,
+    /// The direction of the messages that are converted
+    direction: MessageDirection,
+    invert_direction: bool,
+    /// Where the times of the messages are taken from
+    time_source: TimeSource,
+    /// How the log lines are split into their time, their direction and their TLC-FI message
+    log_pattern: LogPattern,
+    repair_clock_steps: bool,
+    signal_state_encoding: SignalStateEncoding,
+    amber_policy: AmberPolicy,
+    /// Whether states that repeat the last state of their object are dropped
+    dedupe: bool,
+    /// Starts the output at the first signal change, dropping the changes before it
+    skip_until_first_signal: bool,
+    min_mapped_fraction: f64,
+    max_id_gap: u16,
+    renumber_ids: bool,
+    /// Takes the mapping from the meta in the log, a mapping file is only checked against it
+    mapping_from_log: bool,
+    pace: bool,
+    pace_speed: f64,
+    show_progress: bool,
+    strict: bool,
+    /// Fails the conversion when the TLC sent a JSON-RPC error response
+    strict_rpc: bool,
+    /// The VLog file to append the output to instead of writing a new one
+    append: Option<String>,
+    /// The file that keeps how far the conversion got, so it can continue after an interruption
+    checkpoint: Option<String>,
+    /// Whether the VLog messages are also written to stdout
+    tee: bool,
+    /// The directory whose new logs are converted, instead of converting a single log
+    watch_dir: Option<String>,
+    /// The names of the logs that are converted when watching, with `*` and `?` wildcards
+    watch_pattern: String,
+    /// The logs of `--date`, which are read as one log
+    dated_log_files: Vec<String>,
+    /// The number of log lines that can't be parsed before the conversion is given up, unlimited when None
+    max_errors: Option<usize>,
+    /// The file the skipped log lines are written to
+    reject_file: Option<String>,
+    /// The CSV file the predictions of the signal states are written to
+    predictions_csv: Option<String>,
+    /// The file the JSON summary of the conversion is written to
+    summary_json: Option<String>,
+    overwrite: bool,
+    duration_in_s: Option<u64>,
+    /// The number of changes after which the conversion stops, unlimited when None
+    limit: Option<usize>,
+    signal_filter: Option<NameFilter>,
+    detector_filter: Option<NameFilter>,
+    start_date_time: Option<NaiveDateTime>,
+    /// The tick of the start date time, instead of the tick of the first message
+    first_tick: Option<u64>,
+    tick_unit: TickUnit,
+    /// The log levels of the lines that are converted, in upper case, all levels when None
+    log_levels: Option<Vec<String>>,
+    /// The logger name of the TLC-FI lines, any line that splits like one is read when None
+    logger_name: Option<String>,
+    /// Methods of vendors that send their state updates like `UpdateState`
+    update_methods: Vec<String>,
+    /// How far the start date time may be before or after the log before it's warned about
+    start_tolerance_s: u64,
+    /// The time zone the log is stamped in, UTC when None
+    time_zone: Option<TimeZone>,
+    /// Whether the output is in UTC instead of the time zone of the log
+    output_utc: bool,
+    tlcfi_log_file: String,
+    verbosity: Verbosity,
+    vlog_tlcfi_mapping_file: String,
+    /// The directory the mapping file was picked from, when a directory was given
+    mapping_dir: Option<String>,
+    /// The TLC name that replaces the one in the mapping file
+    tlc_name: Option<String>,
+    cache_dir: Option<String>,
+    /// The directory the output files are written to, the working directory when None
+    output_dir: Option<String>,
+    output_template: FileNameTemplate,
+    split: SplitMode,
+    line_ending: LineEnding,
+    max_messages: Option<usize>,
+    max_output_size: Option<u64>,
+    size_policy: SizePolicy,
+}
+
+impl Default for AppArgs {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            validate_mapping: false,
+            generate_mapping: None,
+            list_objects: false,
+            list_format: log_stats::ListFormat::Table,
+            follow: false,
+            reset_snapshot: false,
+            name_date: NameDate::WindowStart,
+            is_chronological: false,
+            conversion_mode: ConversionMode::All,
+            direction: MessageDirection::In,
+            invert_direction: false,
+            time_source: TimeSource::Ticks,
+            log_pattern: LogPattern::SmartTraffic,
+            repair_clock_steps: false,
+            signal_state_encoding: SignalStateEncoding::Collapsed,
+            amber_policy: AmberPolicy::Keep,
+            dedupe: false,
+            skip_until_first_signal: false,
+            min_mapped_fraction: 0.9,
+            max_id_gap: 10,
+            renumber_ids: false,
+            mapping_from_log: false,
+            pace: false,
+            pace_speed: 1.0,
+            show_progress: true,
+            strict: false,
+            strict_rpc: false,
+            append: None,
+            checkpoint: None,
+            tee: false,
+            watch_dir: None,
+            watch_pattern: "*.txt".to_string(),
+            dated_log_files: Vec::new(),
+            max_errors: None,
+            reject_file: None,
+            predictions_csv: None,
+            summary_json: None,
+            overwrite: false,
+            duration_in_s: Option::None,
+            limit: None,
+            signal_filter: Option::None,
+            detector_filter: Option::None,
+            start_date_time: Option::None,
+            first_tick: None,
+            tick_unit: TickUnit::Milliseconds,
+            log_levels: None,
+            logger_name: None,
+            update_methods: Vec::new(),
+            start_tolerance_s: DEFAULT_START_TOLERANCE_S,
+            time_zone: None,
+            output_utc: false,
+            tlcfi_log_file: "tlcfi.txt".to_string(),
+            verbosity: Verbosity::Normal,
+            vlog_tlcfi_mapping_file: String::new(),
+            mapping_dir: None,
+            tlc_name: None,
+            cache_dir: Option::None,
+            output_dir: Option::None,
+            output_template: FileNameTemplate::default(),
+            split: SplitMode::None,
+            line_ending: LineEnding::Crlf,
+            max_messages: None,
+            max_output_size: None,
+            size_policy: SizePolicy::Abort,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use chrono::{NaiveDate, NaiveDateTime};
+    use std::collections::BTreeMap;
+    use std::fs::read_to_string;
+    use std::io::Read;
+    use tlcfi_assimilator::{self, DetectorState, WarningCollector};
+
+    const RELATIVE_TLCFI_FILE_PATH: &str = "./tlcfi.txt";
+    const RELATIVE_VLOG_MAPPING_FILE_PATH: &str = "./vlog_tlcfi_mapping.txt";
+
+    fn get_test_start_time() -> NaiveDateTime {
+        NaiveDate::from_ymd(2021, 12, 15).and_hms(11, 00, 00)
+    }
+
+    #[test]
+    fn checking_file_existence_of_valid_file_should_return_ok() {
+        let file_existence = check_file_existence(RELATIVE_TLCFI_FILE_PATH);
+        assert!(file_existence.is_ok());
+    }
+
+    #[test]
+    fn checking_file_existence_of_invalid_file_should_return_err() {
+        let file_existence = check_file_existence("./thisfiledoesnot.exist");
+        assert!(file_existence.is_err());
+    }
+
+    #[test]
+    fn parse_date_time_of_valid_iso_8601_str_should_return_ok() {
+        let valid_time_stamp = "2021-12-15T11:00:00.000";
+        let parsed_date_time = parse_date_time(valid_time_stamp);
+        assert_eq!(parsed_date_time, Ok(get_test_start_time()));
+    }
+
+    #[test]
+    fn parse_date_time_of_invalid_iso_8601_str_should_return_err() {
+        assert!(parse_date_time("2021-12-15T11:00:00,000").is_err());
+        assert!(parse_date_time("2021-12-15 11:00:00.000").is_err());
+    }
+
+    #[test]
+    fn epoch_milliseconds_should_parse_to_the_same_start_as_the_iso_form() {
+        let amsterdam: TimeZone = "Europe/Amsterdam".parse().unwrap();
+
+        assert_eq!(
+            parse_start_date_time("1639566000000", None),
+            parse_date_time("2021-12-15T11:00:00.000")
+        );
+        assert_eq!(
+            parse_start_date_time("1639566000250", Some(amsterdam)),
+            parse_date_time("2021-12-15T12:00:00.250")
+        );
+        assert_eq!(
+            parse_start_date_time("2021-12-15T11:00:00.000", Some(amsterdam)),
+            Ok(get_test_start_time())
+        );
+    }
+
+    #[test]
+    fn a_start_date_time_with_an_offset_should_be_moved_to_the_time_zone_of_the_log() {
+        let amsterdam: TimeZone = "Europe/Amsterdam".parse().unwrap();
+
+        assert_eq!(
+            parse_start_date_time("2021-12-15T11:00:00.000", None),
+            Ok(get_test_start_time())
+        );
+        assert_eq!(
+            parse_start_date_time("2021-12-15T11:00:00.000Z", None),
+            Ok(get_test_start_time())
+        );
+        assert_eq!(
+            parse_start_date_time("2021-12-15T12:00:00.000+01:00", Some(amsterdam)),
+            parse_date_time("2021-12-15T12:00:00.000")
+        );
+        assert_eq!(
+            parse_start_date_time("2021-12-15T11:00:00.000Z", Some(amsterdam)),
+            parse_date_time("2021-12-15T12:00:00.000")
+        );
+        assert_eq!(
+            parse_start_date_time("2021-12-15T11:00:00.000+01:00", Some(TimeZone::default())),
+            parse_date_time("2021-12-15T10:00:00.000")
+        );
+    }
+
+    #[test]
+    fn a_start_date_time_with_a_local_offset_but_no_time_zone_should_return_err() {
+        let error = parse_start_date_time("2021-12-15T12:00:00.000+01:00", None).unwrap_err();
+
+        assert!(error.contains("has the offset +01:00"));
+        assert!(parse_start_date_time("2021-12-15T12:00:00.000+25:00", None).is_err());
+    }
+
+    #[test]
+    fn epoch_milliseconds_outside_of_2000_until_2100_should_return_err() {
+        assert!(parse_epoch_ms("946684800000").is_ok());
+        assert!(parse_epoch_ms("946684799999").is_err());
+        assert!(parse_epoch_ms("4102444799999").is_ok());
+        assert!(parse_epoch_ms("4102444800000").is_err());
+        // Seconds instead of milliseconds
+        assert!(parse_epoch_ms("1639566000").is_err());
+        assert!(parse_epoch_ms("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn giving_both_start_date_time_and_epoch_ms_should_return_err() {
+        let mut pargs = pico_args::Arguments::from_vec(vec![
+            "--start-date-time".into(),
+            "2021-12-15T11:00:00.000".into(),
+            "--epoch-ms".into(),
+            "1639566000000".into(),
+        ]);
+
+        assert!(parse_start(&mut pargs, None).is_err());
+    }
+
+    #[test]
+    fn parse_duration_of_plain_seconds_should_return_ok() {
+        assert_eq!(parse_duration("3600"), Ok(3600));
+    }
+
+    #[test]
+    fn parse_duration_with_units_should_return_the_total_seconds() {
+        assert_eq!(parse_duration("1h30m"), Ok(5400));
+        assert_eq!(parse_duration("2m5s"), Ok(125));
+        assert_eq!(parse_duration("1h"), Ok(3600));
+    }
+
+    #[test]
+    fn parse_duration_of_invalid_str_should_return_err() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("1d").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("1h30").is_err());
+    }
+
+    #[test]
+    fn parse_duration_that_overflows_should_return_err() {
+        assert!(parse_duration("99999999999999999999h").is_err());
+        assert_eq!(
+            parse_duration("9999999999999999h"),
+            Err(format!(
+                "The duration 9999999999999999h is too long, it can be at most {}s",
+                MAX_DURATION_S
+            ))
+        );
+        assert!(parse_duration("5124095576030431h5124095576030431h").is_err());
+        assert!(parse_duration("18446744073709551615").is_err());
+        assert_eq!(
+            parse_duration(&MAX_DURATION_S.to_string()),
+            Ok(MAX_DURATION_S)
+        );
+    }
+
+    #[test]
+    fn limiting_changes_to_a_duration_should_keep_changes_exactly_at_the_boundary() {
+        let mut changes = vec![
+            TimestampedChanges {
+                ms_from_beginning: 0,
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 60_000,
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 60_001,
+                ..Default::default()
+            },
+        ];
+
+        limit_changes_to_duration(&mut changes, 60);
+        limit_changes_to_duration(&mut changes, u64::MAX);
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[1].ms_from_beginning, 60_000);
+    }
+
+    #[test]
+    fn skipping_until_the_first_signal_should_make_its_change_the_start() {
+        let change = |ms_from_beginning: u64, signal_name: Option<&str>| TimestampedChanges {
+            ms_from_beginning,
+            signal_names: signal_name.into_iter().map(str::to_string).collect(),
+            ..Default::default()
+        };
+        let mut changes = vec![
+            change(0, None),
+            change(2000, None),
+            change(5000, Some("02")),
+            change(6000, None),
+        ];
+        let mut detectors_only = vec![change(0, None)];
+
+        let skipped_ms = skip_until_first_signal(&mut changes);
+
+        assert_eq!(skipped_ms, Some(5000));
+        assert_eq!(changes, vec![change(0, Some("02")), change(1000, None)]);
+        assert_eq!(skip_until_first_signal(&mut detectors_only), None);
+        assert!(detectors_only.is_empty());
+    }
+
+    #[test]
+    fn a_vlog_file_skipping_until_the_first_signal_should_start_at_its_change() {
+        let output_dir = std::env::temp_dir().join("tlcfi_assimilator_skip_until_first_signal");
+        let _ = fs::remove_dir_all(&output_dir);
+        fs::create_dir_all(&output_dir).unwrap();
+        let line = |time: &str, ticks: u64, id: &str, object_type: u8| {
+            format!(
+                "2021-12-15 {} INFO  tlcFiMessages:41 - IN - {{\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{{\"ticks\":{},\"update\":[{{\"objects\":{{\"ids\":[\"{}\"],\"type\":{}}},\"states\":[{{\"state\":1}}]}}]}}}}",
+                time, ticks, id, object_type
+            )
+        };
+        let log = [
+            line("12:00:06,000", 7000, "D611", 4),
+            line("12:00:05,000", 6000, "02", 3),
+            line("12:00:02,000", 3000, "D612", 4),
+            line("12:00:00,000", 1000, "D611", 4),
+        ]
+        .join("\n");
+        let log_file = output_dir.join("tlcfi.txt");
+        fs::write(&log_file, log).unwrap();
+        let app_args = AppArgs {
+            tlcfi_log_file: log_file.to_string_lossy().to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            output_dir: Some(output_dir.to_string_lossy().to_string()),
+            verbosity: Verbosity::Quiet,
+            skip_until_first_signal: true,
+            ..Default::default()
+        };
+
+        let report = convert(&app_args).unwrap();
+        let vlog = read_to_string(&report.file_names[0]).unwrap();
+        fs::remove_dir_all(&output_dir).unwrap();
+
+        assert_eq!(
+            report.start_time,
+            Some(NaiveDate::from_ymd(2021, 12, 15).and_hms(12, 0, 5))
+        );
+        assert!(report.file_names[0].ends_with("3031_20211215_120005.vlg"));
+        assert_eq!(report.change_count, 2);
+        assert_eq!(vlog.lines().next(), Some("012021121512000500"));
+    }
+
+    #[test]
+    fn a_limited_conversion_should_write_a_whole_file_with_the_first_changes() {
+        let output_dir = std::env::temp_dir().join("tlcfi_assimilator_limit");
+        let _ = fs::remove_dir_all(&output_dir);
+        let app_args = AppArgs {
+            tlcfi_log_file: "./tlcfi_with_amber.txt".to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            output_dir: Some(output_dir.to_string_lossy().to_string()),
+            verbosity: Verbosity::Quiet,
+            overwrite: true,
+            ..Default::default()
+        };
+
+        let unlimited = convert(&app_args).unwrap();
+        let limited_args = AppArgs {
+            limit: Some(1),
+            ..app_args
+        };
+        let limited = convert(&limited_args).unwrap();
+        let vlog = read_to_string(&limited.file_names[0]).unwrap();
+        fs::remove_dir_all(&output_dir).unwrap();
+
+        assert_eq!(limited.change_count, 1);
+        assert_eq!(limited.stats.truncated_changes, unlimited.change_count - 1);
+        let lines: Vec<&str> = vlog.lines().collect();
+        assert!(lines[0].starts_with("01"));
+        assert!(lines[1].starts_with("04"));
+        assert!(vlog.ends_with("\r\n"));
+        assert!(
+            conversion_summary_lines(&limited.stats, &get_test_start_time(), &limited_args)
+                .contains(&format!(
+                    "  output truncated: {} change(s) left out after the --limit of 1",
+                    unlimited.change_count - 1
+                ))
+        );
+    }
+
+    #[test]
+    fn a_start_date_time_a_day_before_the_log_should_be_warned_about_or_fail_in_strict_mode() {
+        let output_dir = std::env::temp_dir().join("tlcfi_assimilator_start_mismatch");
+        let _ = fs::remove_dir_all(&output_dir);
+        let app_args = AppArgs {
+            tlcfi_log_file: "./tlcfi_with_amber.txt".to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            start_date_time: parse_date_time("2021-12-14T12:00:00.000").ok(),
+            output_dir: Some(output_dir.to_string_lossy().to_string()),
+            verbosity: Verbosity::Quiet,
+            ..Default::default()
+        };
+
+        let report = convert(&app_args).unwrap();
+        let strict_failure = convert(&AppArgs {
+            strict: true,
+            overwrite: true,
+            ..app_args
+        })
+        .unwrap_err();
+        fs::remove_dir_all(&output_dir).unwrap();
+
+        assert!(report.warnings.iter().any(|warning| warning.starts_with(
+            "Warning: the start date time 2021-12-14 12:00:00 is 24h before the oldest line of the log at 2021-12-15 12:00:00"
+        )));
+        assert_eq!(strict_failure.exit_code, EXIT_CODE_INPUT_ERROR);
+        assert!(strict_failure
+            .message
+            .contains("is 24h before the oldest line"));
+    }
+
+    #[test]
+    fn a_start_date_time_within_the_log_or_the_tolerance_should_not_be_warned_about() {
+        let lines = vec![
+            "2021-12-15 12:00:00,000 - IN - {}".to_string(),
+            "garbage".to_string(),
+            "2021-12-15 13:00:00,000 - IN - {}".to_string(),
+        ];
+        let start = |date_time: &str| parse_date_time(date_time).unwrap();
+
+        assert_eq!(
+            describe_start_mismatch(
+                &start("2021-12-15T11:50:00.000"),
+                &lines,
+                lines.iter().rev(),
+                &LogPattern::default(),
+                600
+            ),
+            None
+        );
+        assert_eq!(
+            describe_start_mismatch(
+                &start("2021-12-15T12:30:00.000"),
+                &lines,
+                lines.iter().rev(),
+                &LogPattern::default(),
+                600
+            ),
+            None
+        );
+        assert!(describe_start_mismatch(
+            &start("2021-12-15T11:49:59.000"),
+            &lines,
+            lines.iter().rev(),
+            &LogPattern::default(),
+            600
+        )
+        .is_some());
+        assert_eq!(
+            describe_start_mismatch(&start("2021-12-15T14:30:05.000"), &lines, lines.iter().rev(), &LogPattern::default(), 600),
+            Some("the start date time 2021-12-15 14:30:05 is 1h30m5s after the newest line of the log at 2021-12-15 13:00:00".to_string())
+        );
+    }
+
+    #[test]
+    fn parsing_both_detector_include_and_exclude_filters_should_return_err() {
+        let mut pargs = pico_args::Arguments::from_vec(vec![
+            "--filter-detectors".into(),
+            "D712".into(),
+            "--exclude-detectors".into(),
+            "D713".into(),
+        ]);
+
+        assert!(parse_detector_filter(&mut pargs).is_err());
+    }
+
+    #[test]
+    fn parsing_a_detector_exclude_filter_should_return_an_exclude_filter() {
+        let mut pargs =
+            pico_args::Arguments::from_vec(vec!["--exclude-detectors".into(), "D712,D713".into()]);
+
+        assert_eq!(
+            parse_detector_filter(&mut pargs).unwrap(),
+            Some(NameFilter::Exclude(vec!["D712".into(), "D713".into()]))
+        );
+    }
+
+    #[test]
+    fn parsing_both_signals_only_and_detectors_only_should_return_err() {
+        let mut pargs = pico_args::Arguments::from_vec(vec![
+            "--signals-only".into(),
+            "--detectors-only".into(),
+        ]);
+
+        assert!(parse_conversion_mode(&mut pargs).is_err());
+    }
+
+    #[test]
+    fn an_excluded_unmapped_detector_should_produce_no_messages_and_no_mapping_panic() {
+        let mut changes = vec![TimestampedChanges {
+            ms_from_beginning: 100,
+            detector_names: vec!["D999".to_string()],
+            detector_states: vec![DetectorState::OCCUPIED],
+            ..Default::default()
+        }];
+
+        change_filtering::filter_detectors(&mut changes, &NameFilter::Exclude(vec!["D999".into()]));
+        let vlog_messages = vlog_transformer::to_vlog(
+            changes,
+            &get_test_start_time(),
+            RELATIVE_VLOG_MAPPING_FILE_PATH,
+            "test",
+            vlog_transformer::TransformOptions::default(),
+            &mut ConversionStats::default(),
+        );
+
+        assert!(!vlog_messages
+            .iter()
+            .any(|message| message.starts_with("06")));
+    }
+
+    #[test]
+    fn a_dry_run_of_the_test_files_should_find_no_problems() {
+        let mut data = AssimilationData {
+            start_time: get_test_start_time(),
+            sorted_lines: sort_lines(RELATIVE_TLCFI_FILE_PATH, &false, &mut Console::default())
+                .unwrap(),
+            ..Default::default()
+        };
+        let app_args = AppArgs {
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            ..Default::default()
+        };
+        read_lines_and_save_changes(&mut data, &app_args).unwrap();
+
+        assert!(find_dry_run_problems(&data.changes, &app_args).is_empty());
+    }
+
+    #[test]
+    fn a_slice_of_a_log_with_the_first_tick_should_line_up_with_the_whole_log() {
+        let lines = sort_lines(RELATIVE_TLCFI_FILE_PATH, &false, &mut Console::default()).unwrap();
+        let slice = lines[lines.len() / 2..].to_vec();
+        let app_args = AppArgs {
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            ..Default::default()
+        };
+        let mut whole_log = AssimilationData {
+            start_time: get_test_start_time(),
+            sorted_lines: lines,
+            ..Default::default()
+        };
+        read_lines_and_save_changes(&mut whole_log, &app_args).unwrap();
+        let mut sliced_log = AssimilationData {
+            start_time: get_test_start_time(),
+            sorted_lines: slice.clone(),
+            ..Default::default()
+        };
+        let mut sliced_log_with_first_tick = AssimilationData {
+            start_time: get_test_start_time(),
+            sorted_lines: slice,
+            first_tick: whole_log.stats.first_tick,
+            ..Default::default()
+        };
+
+        read_lines_and_save_changes(&mut sliced_log, &app_args).unwrap();
+        read_lines_and_save_changes(&mut sliced_log_with_first_tick, &app_args).unwrap();
+
+        let slice_start = whole_log.changes.len() - sliced_log_with_first_tick.changes.len();
+        assert_eq!(
+            sliced_log_with_first_tick.changes,
+            whole_log.changes[slice_start..]
+        );
+        assert_ne!(sliced_log.changes, whole_log.changes[slice_start..]);
+    }
+
+    #[test]
+    fn ticks_of_100us_should_give_a_tenth_of_the_deciseconds_of_ticks_of_1ms() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_tick_unit");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let log = [(1000, 8), (61_000, 6), (301_000, 3)]
+            .iter()
+            .map(|(ticks, state)| {
+                format!(
+                    "2021-12-15 12:00:00,000 INFO  tlcFiMessages:41 - IN - {{\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{{\"ticks\":{},\"update\":[{{\"objects\":{{\"ids\":[\"02\"],\"type\":3}},\"states\":[{{\"state\":{}}}]}}]}}}}\n",
+                    ticks, state
+                )
+            })
+            .collect::<String>();
+        let log_file = test_dir.join("tlcfi.txt");
+        fs::write(&log_file, log).unwrap();
+        let deciseconds_of_changes = |tick_unit: TickUnit| {
+            let report = convert(&AppArgs {
+                tlcfi_log_file: log_file.to_string_lossy().to_string(),
+                vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+                is_chronological: true,
+                tick_unit,
+                output_dir: Some(test_dir.to_string_lossy().to_string()),
+                verbosity: Verbosity::Quiet,
+                overwrite: true,
+                ..Default::default()
+            })
+            .unwrap();
+            let vlog = read_to_string(&report.file_names[0]).unwrap();
+            let start = parse_date_time("2021-12-15T12:00:00.000").unwrap();
+            vlog_decoding::decode_messages(vlog.lines())
+                .iter()
+                .filter(|decoded| decoded.message.starts_with("0E"))
+                .map(|decoded| (decoded.time.unwrap() - start).num_milliseconds() / 100)
+                .collect::<Vec<i64>>()
+        };
+
+        let in_ms = deciseconds_of_changes(TickUnit::Milliseconds);
+        let in_100us = deciseconds_of_changes(TickUnit::HundredMicroseconds);
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert_eq!(in_ms, vec![0, 600, 3000]);
+        assert_eq!(in_100us, vec![0, 60, 300]);
+    }
+
+    #[test]
+    fn a_dry_run_should_report_names_missing_from_the_mapping_instead_of_panicking() {
+        let changes = vec![
+            TimestampedChanges {
+                ms_from_beginning: 100,
+                detector_names: vec!["D611".to_string(), "D999".to_string()],
+                detector_states: vec![DetectorState::OCCUPIED, DetectorState::FREE],
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 200,
+                signal_names: vec!["99".to_string()],
+                signal_states: vec![tlcfi_assimilator::SignalState::Red],
+                ..Default::default()
+            },
+        ];
+        let app_args = AppArgs {
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            find_dry_run_problems(&changes, &app_args),
+            vec![
+                "signals missing from the mapping file: 99",
+                "detectors missing from the mapping file: D999"
+            ]
+        );
+    }
+
+    #[test]
+    fn a_dry_run_without_detector_mappings_should_only_be_a_problem_when_converting_detectors() {
+        let changes = vec![TimestampedChanges {
+            ms_from_beginning: 100,
+            signal_names: vec!["02".to_string()],
+            signal_states: vec![tlcfi_assimilator::SignalState::Red],
+            ..Default::default()
+        }];
+        let mut app_args = AppArgs {
+            vlog_tlcfi_mapping_file: "./vlog_tlcfi_mapping_signals_only.txt".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(find_dry_run_problems(&changes, &app_args).len(), 1);
+        app_args.conversion_mode = ConversionMode::SignalsOnly;
+        assert!(find_dry_run_problems(&changes, &app_args).is_empty());
+    }
+
+    /// A writer that fails once more than a given amount of bytes would have been written.
+    struct FailingWriter {
+        remaining_bytes: usize,
+    }
+
+    impl Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if buf.len() > self.remaining_bytes {
+                return Err(std::io::Error::other("No space left on device"));
+            }
+            self.remaining_bytes -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn a_failing_writer_should_report_how_much_was_written() {
+        let vlog_messages = vec![
+            "012021121511000000".to_string(),
+            "0600A10A01".to_string(),
+            "0601410A00".to_string(),
+        ];
+        let mut writer = FailingWriter {
+            remaining_bytes: 32,
+        };
+
+        let failure =
+            write_vlog_messages(&mut writer, &vlog_messages, LineEnding::Crlf, None).unwrap_err();
+
+        assert_eq!(failure.written_messages, 2);
+        assert_eq!(
+            failure.written_until,
+            Some(NaiveDate::from_ymd(2021, 12, 15).and_hms(11, 0, 1))
+        );
+    }
+
+    #[test]
+    fn a_partial_output_file_should_be_moved_aside() {
+        let file_name = std::env::temp_dir()
+            .join("tlcfi_assimilator_partial_test.vlg")
+            .to_string_lossy()
+            .to_string();
+        File::create(&file_name).unwrap();
+
+        let partial_file_name = move_to_partial_file(&file_name);
+
+        assert_eq!(partial_file_name, format!("{}.partial", file_name));
+        assert!(File::open(&file_name).is_err());
+        assert!(File::open(&partial_file_name).is_ok());
+        fs::remove_file(partial_file_name).unwrap();
+    }
+
+    #[test]
+    fn reading_an_empty_line_should_not_result_in_any_changes_added() {
+        let mut data = AssimilationData {
+            start_time: get_test_start_time(),
+            sorted_lines: vec![String::from("")],
+            first_tick: Option::Some(0),
+            ..Default::default()
+        };
+
+        read_lines_and_save_changes(&mut data, &AppArgs::default()).unwrap();
+
+        assert!(data.changes.is_empty());
+    }
+
+    #[test]
+    fn reading_an_out_line_should_not_result_in_any_changes_added() {
+        let mut data = AssimilationData {
+            start_time: get_test_start_time(),
+            sorted_lines: vec![String::from("2021-12-15 12:59:59,794 INFO  tlcFiMessages:41 - OUT - {\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087974612,\"update\":[{\"objects\":{\"ids\":[\"D681\"],\"type\":4},\"states\":[{\"state\":0}]}]}}")],
+            first_tick: Option::Some(0),
+            ..Default::default()
+        };
+
+        read_lines_and_save_changes(&mut data, &AppArgs::default()).unwrap();
+
+        assert!(data.changes.is_empty());
+    }
+
+    #[test]
+    fn reading_a_crafted_three_line_log_should_count_the_lines_and_messages() {
+        let mut data = AssimilationData {
+            start_time: get_test_start_time(),
+            sorted_lines: vec![
+                String::from("2021-12-15 11:00:00,100 INFO  tlcFiMessages:41 - IN - {\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4000,\"update\":[{\"objects\":{\"ids\":[\"D611\"],\"type\":4},\"states\":[{\"state\":1}]}]}}"),
+                String::from("2021-12-15 11:00:00,200 INFO  tlcFiMessages:41 - OUT - {\"jsonrpc\":\"2.0\",\"method\":\"ReadMeta\",\"id\":1}"),
+                String::from("garbage"),
+            ],
+            console: Console::new(Verbosity::Quiet),
+            ..Default::default()
+        };
+        let app_args = AppArgs {
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            ..Default::default()
+        };
+
+        read_lines_and_save_changes(&mut data, &app_args).unwrap();
+        vlog_transformer::to_vlog(
+            data.changes,
+            &get_test_start_time(),
+            RELATIVE_VLOG_MAPPING_FILE_PATH,
+            "test",
+            vlog_transformer::TransformOptions {
+                verbosity: Verbosity::Quiet,
+                ..Default::default()
+            },
+            &mut data.stats,
+        );
+
+        assert_eq!(
+            data.stats,
+            ConversionStats {
+                lines_read: 3,
+                lines_skipped: 1,
+                line_errors: LineErrors {
+                    unsplittable: 1,
+                    first_lines: vec!["garbage".to_string()],
+                    ..Default::default()
+                },
+                lines_before_start: 0,
+                lines_of_other_levels: 0,
+                lines_of_other_loggers: 0,
+                rejected_lines: 0,
+                signal_predictions: 0,
+                in_lines: 1,
+                out_lines: 1,
+                signal_change_messages: 0,
+                detector_change_messages: 1,
+                output_change_messages: 0,
+                time_references: 1,
+                first_written_change_ms: Some(0),
+                last_written_change_ms: Some(0),
+                tick_overflows: 0,
+                controller_resets: Vec::new(),
+                retired_objects: Vec::new(),
+                special_vehicle_events: Vec::new(),
+                sessions: Vec::new(),
+                rpc_errors: Vec::new(),
+                estimated_output_bytes: 0,
+                output_bytes: 0,
+                first_tick: Some(4000),
+                last_tick: Some(4000),
+                unsupported_messages: 0,
+                other_methods: BTreeMap::new(),
+                intersection_state_changes: 0,
+                unknown_intersection_states: 0,
+                null_signal_states: 0,
+                null_detector_states: 0,
+                null_output_states: 0,
+                unmapped_output_states: 0,
+                collapsed_amber_states: 0,
+                suppressed_signal_states: 0,
+                suppressed_detector_states: 0,
+                truncated_changes: 0,
+                clock_steps: Vec::new(),
+                transform_warnings: WarningCollector::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn the_conversion_summary_should_mention_tick_events_and_direction_inversion() {
+        let stats = ConversionStats {
+            lines_read: 10,
+            controller_resets: vec![tlcfi_assimilator::ControllerReset {
+                ms_from_beginning: 2000,
+                previous_tick: 1002000,
+                tick: 500,
+                changes_before: 3,
+            }],
+            ..Default::default()
+        };
+        let app_args = AppArgs {
+            invert_direction: true,
+            ..Default::default()
+        };
+
+        let lines = conversion_summary_lines(&stats, &get_test_start_time(), &app_args);
+
+        assert!(lines.contains(&"  tick events: 0 overflow(s), 1 reset(s)".to_string()));
+        assert!(lines.contains(&"  direction inversion was active".to_string()));
+        assert!(lines.contains(
+            &"  controller reset at 2021-12-15 11:00:02: the tick jumped back 1001500 from 1002000 to 500"
+                .to_string()
+        ));
+    }
+
+    #[test]
+    fn a_detector_removed_from_the_session_should_be_retired() {
+        let mut console = Console::new(Verbosity::Quiet);
+        let mut data = AssimilationData {
+            start_time: get_test_start_time(),
+            sorted_lines: sort_lines("./tlcfi_with_removal.txt", &false, &mut console).unwrap(),
+            console,
+            ..Default::default()
+        };
+
+        read_lines_and_save_changes(&mut data, &AppArgs::default()).unwrap();
+        controller_resets::insert_reset_snapshots(&mut data.changes, &data.stats.controller_resets);
+
+        assert_eq!(
+            data.stats.retired_objects,
+            vec![tlcfi_assimilator::RetiredObject {
+                ms_from_beginning: 3_600_000,
+                name: "D611".to_string(),
+                is_detector: true,
+            }]
+        );
+        assert_eq!(
+            data.console.all_warnings(),
+            vec!["Warning: detector 'D611' changed at 2021-12-15 13:30:00 after it was removed from the session at 2021-12-15 12:00:00."]
+        );
+        let snapshot: Vec<&TimestampedChanges> = data
+            .changes
+            .iter()
+            .filter(|change| change.ms_from_beginning == 5_400_000)
+            .skip(1)
+            .collect();
+        assert_eq!(snapshot[0].signal_names, vec!["02"]);
+        assert_eq!(
+            snapshot[0].signal_states,
+            vec![tlcfi_assimilator::SignalState::Unavailable]
+        );
+        assert_eq!(snapshot[1].detector_names, vec!["D611", "D612"]);
+        assert_eq!(
+            snapshot[1].detector_states,
+            vec![DetectorState::FAULTY, DetectorState::FAULTY]
+        );
+        assert!(data
+            .changes
+            .iter()
+            .any(|change| change.ms_from_beginning == 3_600_000
+                && change.detector_names == vec!["D611"]
+                && change.detector_states == vec![DetectorState::FAULTY]));
+        assert!(
+            conversion_summary_lines(&data.stats, &get_test_start_time(), &AppArgs::default())
+                .contains(
+                    &"  detector 'D611' removed from the session at 2021-12-15 12:00:00"
+                        .to_string()
+                )
+        );
+    }
+
+    #[test]
+    fn reading_a_log_with_a_mid_day_reset_should_record_it_and_insert_a_snapshot() {
+        let mut console = Console::new(Verbosity::Quiet);
+        let mut data = AssimilationData {
+            sorted_lines: sort_lines("./tlcfi_with_reset.txt", &false, &mut console).unwrap(),
+            console,
+            ..Default::default()
+        };
+
+        read_lines_and_save_changes(&mut data, &AppArgs::default()).unwrap();
+        controller_resets::insert_reset_snapshots(&mut data.changes, &data.stats.controller_resets);
+
+        assert_eq!(
+            data.stats.controller_resets,
+            vec![tlcfi_assimilator::ControllerReset {
+                ms_from_beginning: 2000,
+                previous_tick: 1002000,
+                tick: 500,
+                changes_before: 3,
+            }]
+        );
+        let changes_at_reset: Vec<&TimestampedChanges> = data
+            .changes
+            .iter()
+            .filter(|change| change.ms_from_beginning == 2000)
+            .collect();
+        assert_eq!(changes_at_reset.len(), 4);
+        assert_eq!(
+            changes_at_reset[0].signal_states,
+            vec![tlcfi_assimilator::SignalState::Amber]
+        );
+        assert_eq!(
+            changes_at_reset[1].signal_states,
+            vec![tlcfi_assimilator::SignalState::Unavailable]
+        );
+        assert_eq!(
+            changes_at_reset[2].detector_states,
+            vec![DetectorState::FAULTY]
+        );
+        assert_eq!(
+            changes_at_reset[3].detector_states,
+            vec![DetectorState::OCCUPIED]
+        );
+    }
+
+    #[test]
+    fn a_file_split_off_at_an_hour_boundary_should_start_with_its_own_preamble() {
+        let changes = vec![
+            TimestampedChanges {
+                ms_from_beginning: 3_599_900,
+                signal_names: vec!["02".to_string()],
+                signal_states: vec![tlcfi_assimilator::SignalState::Green],
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 3_600_500,
+                signal_names: vec!["02".to_string()],
+                signal_states: vec![tlcfi_assimilator::SignalState::Amber],
+                ..Default::default()
+            },
+        ];
+
+        let mut parts =
+            output_splitting::split_changes(changes, &get_test_start_time(), SplitMode::Hourly);
+        let second_part = parts.pop().unwrap();
+        let vlog_messages = vlog_transformer::to_vlog(
+            second_part.changes,
+            &second_part.start_date_time,
+            RELATIVE_VLOG_MAPPING_FILE_PATH,
+            "3031",
+            vlog_transformer::TransformOptions::default(),
+            &mut ConversionStats::default(),
+        );
+
+        assert_eq!(parts.len(), 1);
+        assert_eq!(
+            create_file_name("3031", &second_part.start_date_time, &AppArgs::default()),
+            "3031_20211215_120000.vlg"
+        );
+        assert_eq!(vlog_messages[0], "012021121512000000");
+        assert!(vlog_messages[1].starts_with("04"));
+        // Half a second after the new time reference
+        assert!(vlog_messages[2].starts_with("0E005"));
+    }
+
+    #[test]
+    fn a_conversion_over_the_maximum_number_of_messages_should_continue_in_valid_parts() {
+        let max_messages = 10;
+        // Two messages of preamble and 23 changes make 2.5 times the maximum
+        let changes: Vec<TimestampedChanges> = (0..23)
+            .map(|index| TimestampedChanges {
+                ms_from_beginning: index * 7_000,
+                signal_names: vec!["02".to_string()],
+                signal_states: vec![if index % 2 == 0 {
+                    tlcfi_assimilator::SignalState::Green
+                } else {
+                    tlcfi_assimilator::SignalState::Red
+                }],
+                ..Default::default()
+            })
+            .collect();
+        let vlog_messages = vlog_transformer::to_vlog(
+            changes,
+            &get_test_start_time(),
+            RELATIVE_VLOG_MAPPING_FILE_PATH,
+            "3031",
+            vlog_transformer::TransformOptions::default(),
+            &mut ConversionStats::default(),
+        );
+        let times_of = |messages: &[String]| -> Vec<NaiveDateTime> {
+            let mut clock = vlog_decoding::MessageClock::default();
+            messages
+                .iter()
+                .filter(|message| message.starts_with("0E"))
+                .filter_map(|message| clock.time_of(message))
+                .collect()
+        };
+        let expected_times = times_of(&vlog_messages);
+
+        let files = output_splitting::split_messages(vlog_messages.clone(), max_messages);
+
+        assert_eq!(vlog_messages.len(), 25);
+        assert_eq!(files.len(), 3);
+        let mut split_times = Vec::new();
+        for file in &files {
+            assert!(file.len() <= max_messages);
+            assert_eq!(file[0].len(), vlog_messages[0].len());
+            assert!(file[0].starts_with("01"));
+            assert_eq!(file[1], vlog_messages[1]);
+            assert!(!file.last().unwrap().starts_with("01"));
+            split_times.extend(times_of(file));
+        }
+        assert_eq!(split_times, expected_times);
+        assert_eq!(
+            output_splitting::part_file_name(
+                &create_file_name("3031", &get_test_start_time(), &AppArgs::default()),
+                2
+            ),
+            "3031_20211215_110000_part3.vlg"
+        );
+    }
+
+    #[test]
+    fn renumbered_ids_should_be_used_in_the_generated_messages() {
+        let mapping_file = std::env::temp_dir().join("tlcfi_assimilator_sparse_mapping.txt");
+        fs::write(&mapping_file, "// TLC\n3031\n\n// Signals\n0, 02\n60, 03\n").unwrap();
+        let mapping_file = mapping_file.to_str().unwrap().to_string();
+        let app_args = AppArgs {
+            vlog_tlcfi_mapping_file: mapping_file.clone(),
+            conversion_mode: ConversionMode::SignalsOnly,
+            ..Default::default()
+        };
+        let date_time = NaiveDate::from_ymd(2021, 12, 15).and_hms(9, 0, 0);
+        let changes = || {
+            vec![TimestampedChanges {
+                ms_from_beginning: 0,
+                signal_names: vec!["03".to_string()],
+                signal_states: vec![tlcfi_assimilator::SignalState::Green],
+                ..Default::default()
+            }]
+        };
+        let to_vlog_with = |mapping_file: &str| {
+            vlog_transformer::to_vlog(
+                changes(),
+                &date_time,
+                mapping_file,
+                "3031",
+                vlog_transformer::TransformOptions {
+                    conversion_mode: ConversionMode::SignalsOnly,
+                    ..Default::default()
+                },
+                &mut ConversionStats::default(),
+            )
+        };
+
+        let mut console = Console::new(Verbosity::Quiet);
+        warn_about_mapping_mismatch(&changes(), &app_args, &mut console).unwrap();
+        let renumbered_mapping_file =
+            write_renumbered_mapping(&app_args, "3031", &date_time).unwrap();
+        let sparse_messages = to_vlog_with(&mapping_file);
+        let dense_messages = to_vlog_with(&renumbered_mapping_file);
+        fs::remove_file(&mapping_file).unwrap();
+        fs::remove_file(&renumbered_mapping_file).unwrap();
+
+        assert!(console.all_warnings()[0]
+            .contains("jump from 0 to 60, which implies 61 signals for 2 mapped ones"));
+        assert_eq!(renumbered_mapping_file, "3031_20211215_090000_mapping.txt");
+        assert_eq!(sparse_messages[2], "0E00013C01");
+        assert_eq!(dense_messages[2], "0E00010101");
+    }
+
+    #[test]
+    fn reading_lines_that_do_not_split_should_be_counted() {
+        let mut data = AssimilationData {
+            start_time: get_test_start_time(),
+            sorted_lines: vec![String::from("garbage"), String::from("more garbage")],
+            first_tick: Option::Some(0),
+            console: Console::new(Verbosity::Quiet),
+            ..Default::default()
+        };
+
+        read_lines_and_save_changes(&mut data, &AppArgs::default()).unwrap();
+
+        assert_eq!(
+            data.console.summary_lines(),
+            vec!["skipped 2 line(s) that did not split on '- '"]
+        );
+    }
+
+    #[test]
+    fn command_line_options_should_override_the_config_file() {
+        let args = vec!["--config", "./conversion.toml", "--duration", "60", "-v"]
+            .into_iter()
+            .map(OsString::from)
+            .collect();
+
+        let app_args = parse_args(args).unwrap();
+
+        assert_eq!(app_args.duration_in_s, Some(60));
+        assert_eq!(app_args.verbosity, Verbosity::Verbose);
+        assert_eq!(app_args.start_date_time, Some(get_test_start_time()));
+        assert_eq!(
+            app_args.signal_filter,
+            Some(NameFilter::Include(vec!["02".into(), "03".into()]))
+        );
+        assert_eq!(app_args.vlog_tlcfi_mapping_file, "vlog_tlcfi_mapping.txt");
+    }
+
+    #[test]
+    fn a_mapping_dir_on_the_command_line_should_override_the_mapping_file_of_the_config_file() {
+        let mapping_dir = std::env::temp_dir().join("tlcfi_assimilator_config_mapping_dir");
+        let _ = fs::remove_dir_all(&mapping_dir);
+        fs::create_dir_all(&mapping_dir).unwrap();
+        let mapping_file = mapping_dir.join("3031.txt");
+        fs::copy(RELATIVE_VLOG_MAPPING_FILE_PATH, &mapping_file).unwrap();
+        let args = vec![
+            OsString::from("--config"),
+            OsString::from("./conversion.toml"),
+            OsString::from("--mapping-dir"),
+            mapping_dir.clone().into_os_string(),
+            OsString::from("--tlc-name"),
+            OsString::from("3031"),
+        ];
+
+        let app_args = parse_args(args).unwrap();
+        fs::remove_dir_all(&mapping_dir).unwrap();
+
+        assert_eq!(
+            app_args.vlog_tlcfi_mapping_file,
+            mapping_file.to_string_lossy()
+        );
+    }
+
+    #[test]
+    fn an_invalid_value_in_the_config_file_should_name_the_file_and_key() {
+        let config_file = std::env::temp_dir().join("tlcfi_assimilator_invalid_config.toml");
+        fs::write(&config_file, "start-date-time = \"yesterday\"\n").unwrap();
+        let args = vec![
+            OsString::from("--config"),
+            config_file.clone().into_os_string(),
+        ];
+
+        let error = parse_args(args).unwrap_err();
+        fs::remove_file(&config_file).unwrap();
+
+        assert!(matches!(error, ArgsError::Invalid(_)));
+        let error = error.to_string();
+        assert!(error.starts_with("the value of 'start-date-time' in the config file '"));
+        assert!(error.contains("tlcfi_assimilator_invalid_config.toml"));
+        assert!(!error.contains("failed to parse"));
+    }
+
+    #[test]
+    fn the_logs_of_the_date_should_be_read_from_the_first_message_of_the_date() {
+        let log_dir = std::env::temp_dir().join("tlcfi_assimilator_log_dir");
+        let _ = fs::remove_dir_all(&log_dir);
+        fs::create_dir_all(&log_dir).unwrap();
+        fs::copy(
+            "./tlcfi_with_amber.txt",
+            log_dir.join("tlcfi-2021-12-15.txt"),
+        )
+        .unwrap();
+        fs::write(log_dir.join("tlcfi-2021-12-16.txt"), "").unwrap();
+        let log_dir_name = log_dir.to_string_lossy().to_string();
+        let options = |options: &[&str]| {
+            let mut pargs =
+                pico_args::Arguments::from_vec(options.iter().map(OsString::from).collect());
+            parse_options(&mut pargs)
+        };
+
+        let app_args = options(&["--log-dir", &log_dir_name, "--date", "2021-12-15"]);
+        let without_log_dir = options(&["--date", "2021-12-15"]);
+        fs::remove_dir_all(&log_dir).unwrap();
+
+        let app_args = app_args.unwrap();
+        let log_file = log_dir
+            .join("tlcfi-2021-12-15.txt")
+            .to_string_lossy()
+            .to_string();
+        assert_eq!(app_args.tlcfi_log_file, log_file);
+        assert_eq!(app_args.dated_log_files, vec![log_file]);
+        assert_eq!(
+            app_args.start_date_time,
+            Some(NaiveDate::from_ymd(2021, 12, 15).and_hms(12, 0, 0))
+        );
+        assert!(without_log_dir
+            .unwrap_err()
+            .to_string()
+            .contains("--log-dir and --date need each other"));
+    }
+
+    #[test]
+    fn parsing_both_quiet_and_verbose_should_return_err() {
+        let mut pargs = pico_args::Arguments::from_vec(vec!["--quiet".into(), "-v".into()]);
+
+        assert!(parse_verbosity(&mut pargs).is_err());
+    }
+
+    #[test]
+    fn reading_a_valid_line_should_mutate_changes() {
+        let mut data = AssimilationData {
+            start_time: get_test_start_time(),
+            sorted_lines: vec![String::from("2021-12-15 12:59:59,794 INFO  tlcFiMessages:41 - IN - {\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087,\"update\":[{\"objects\":{\"ids\":[\"D681\"],\"type\":4},\"states\":[{\"state\":0}]}]}}")],
+            first_tick: Option::Some(4000),
+            ..Default::default()
+        };
+
+        read_lines_and_save_changes(&mut data, &AppArgs::default()).unwrap();
+
+        assert!(!data.changes.is_empty());
+        let change = &data.changes[0];
+        assert_eq!(change.ms_from_beginning, 4087 - data.first_tick.unwrap());
+        assert_eq!(change.detector_names[0], "D681");
+        assert_eq!(change.detector_states[0], DetectorState::FREE);
+    }
+
+    #[test]
+    fn reading_a_valid_line_without_a_first_tick_should_set_the_first_tick_to_the_one_of_the_message(
+    ) {
+        let mut data = AssimilationData {
+            start_time: get_test_start_time(),
+            sorted_lines: vec![String::from("2021-12-15 12:59:59,794 INFO  tlcFiMessages:41 - IN - {\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087974612,\"update\":[{\"objects\":{\"ids\":[\"D681\"],\"type\":4},\"states\":[{\"state\":0}]}]}}")],
+            ..Default::default()
+        };
+
+        read_lines_and_save_changes(&mut data, &AppArgs::default()).unwrap();
+
+        assert!(!data.changes.is_empty());
+        assert_eq!(data.changes[0].ms_from_beginning, 0); // it being 0 means this is the very first message handled, and first tick is equal to it
+    }
+
+    #[test]
+    fn reading_an_out_line_with_inverted_direction_should_mutate_changes() {
+        let mut data = AssimilationData {
+            start_time: get_test_start_time(),
+            sorted_lines: vec![String::from("2021-12-15 12:59:59,794 INFO  tlcFiMessages:41 - OUT - {\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087,\"update\":[{\"objects\":{\"ids\":[\"D681\"],\"type\":4},\"states\":[{\"state\":0}]}]}}")],
+            first_tick: Option::Some(4000),
+            ..Default::default()
+        };
+        let app_args = AppArgs {
+            invert_direction: true,
+            ..Default::default()
+        };
+
+        read_lines_and_save_changes(&mut data, &app_args).unwrap();
+
+        assert_eq!(data.changes.len(), 1);
+        assert_eq!(data.changes[0].detector_names[0], "D681");
+    }
+
+    #[test]
+    fn reading_inverted_logs_with_inverted_direction_should_equal_reading_the_original_logs() {
+        let original_lines =
+            sort_lines(RELATIVE_TLCFI_FILE_PATH, &false, &mut Console::default()).unwrap();
+        let inverted_lines: Vec<String> = original_lines
+            .iter()
+            .map(|line| {
+                line.replacen("- IN - ", "- TMP - ", 1)
+                    .replacen("- OUT - ", "- IN - ", 1)
+                    .replacen("- TMP - ", "- OUT - ", 1)
+            })
+            .collect();
+        let mut original_data = AssimilationData {
+            start_time: get_test_start_time(),
+            sorted_lines: original_lines,
+            ..Default::default()
+        };
+        let mut inverted_data = AssimilationData {
+            start_time: get_test_start_time(),
+            sorted_lines: inverted_lines,
+            ..Default::default()
+        };
+        let inverted_args = AppArgs {
+            invert_direction: true,
+            ..Default::default()
+        };
+
+        read_lines_and_save_changes(&mut original_data, &AppArgs::default()).unwrap();
+        read_lines_and_save_changes(&mut inverted_data, &inverted_args).unwrap();
+
+        assert!(!original_data.changes.is_empty());
+        assert_eq!(original_data.changes, inverted_data.changes);
+    }
+
+    #[test]
+    fn creating_a_vlog_file_name_should_use_the_tlc_name_and_format_the_date_time_correctly() {
+        let tlc_name = "test";
+        let date_time = get_test_start_time();
+
+        let vlog_file_name = create_file_name(tlc_name, &date_time, &AppArgs::default());
+
+        assert_eq!(vlog_file_name, "test_20211215_110000.vlg");
+    }
+
+    #[test]
+    fn creating_a_vlog_file_name_should_remove_ms_from_time() {
+        let tlc_name = "test";
+        let date_time = NaiveDate::from_ymd(2021, 12, 15).and_hms_milli(11, 22, 33, 444);
+
+        let vlog_file_name = create_file_name(tlc_name, &date_time, &AppArgs::default());
+
+        assert_eq!(vlog_file_name, "test_20211215_112233.vlg");
+    }
+
+    fn get_changes_spanning_midnight() -> (NaiveDateTime, Vec<TimestampedChanges>) {
+        let window_start = NaiveDate::from_ymd(2021, 12, 15).and_hms(23, 50, 0);
+        let changes = [120_000, 660_000, 700_000, 800_000]
+            .iter()
+            .map(|ms_from_beginning| TimestampedChanges {
+                ms_from_beginning: *ms_from_beginning,
+                ..Default::default()
+            })
+            .collect();
+        (window_start, changes)
+    }
+
+    #[test]
+    fn naming_after_the_window_start_should_use_the_start_date_time() {
+        let (window_start, changes) = get_changes_spanning_midnight();
+
+        let date_time = choose_file_name_date_time(NameDate::WindowStart, &window_start, &changes);
+
+        assert_eq!(
+            create_file_name("test", &date_time, &AppArgs::default()),
+            "test_20211215_235000.vlg"
+        );
+    }
+
+    #[test]
+    fn naming_after_the_first_change_should_use_the_moment_of_the_first_change() {
+        let (window_start, changes) = get_changes_spanning_midnight();
+
+        let date_time = choose_file_name_date_time(NameDate::FirstChange, &window_start, &changes);
+
+        assert_eq!(
+            create_file_name("test", &date_time, &AppArgs::default()),
+            "test_20211215_235200.vlg"
+        );
+    }
+
+    #[test]
+    fn naming_after_the_majority_day_should_use_the_first_change_on_the_day_with_most_changes() {
+        let (window_start, changes) = get_changes_spanning_midnight();
+
+        let date_time = choose_file_name_date_time(NameDate::MajorityDay, &window_start, &changes);
+
+        assert_eq!(
+            create_file_name("test", &date_time, &AppArgs::default()),
+            "test_20211216_000100.vlg"
+        );
+    }
+
+    #[test]
+    fn parsing_an_unknown_name_date_should_return_err() {
+        assert_eq!("majority-day".parse(), Ok(NameDate::MajorityDay));
+        assert_eq!(NameDate::MajorityDay.name(), "majority-day");
+        assert!("tomorrow".parse::<NameDate>().is_err());
+    }
+
+    #[test]
+    fn getting_start_date_time_from_a_file_should_interpret_log_timestamps_as_naivedatetime() {
+        let expected_start_date_time = parse_date_time("2021-12-15T11:00:00.074").unwrap();
+        let lines = vec![String::from("2021-12-15 11:00:00,074 INFO  tlcFiMessages:41 - OUT - {\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087974612,\"update\":[{\"objects\":{\"ids\":[\"D681\"],\"type\":4},\"states\":[{\"state\":0}]}]}}")];
+
+        let start_date_time_from_log =
+            get_start_date_time_from_file(&lines, &LogPattern::default());
+
+        assert!(start_date_time_from_log.is_ok());
+        assert_eq!(start_date_time_from_log.unwrap(), expected_start_date_time);
+    }
+
+    #[test]
+    fn the_start_date_time_should_be_taken_from_timestamps_of_any_form_or_name_the_bad_one() {
+        let start_of = |timestamp: &str| {
+            get_start_date_time_from_file(
+                &[format!("{} INFO  tlcFiMessages:41 - IN - {{}}", timestamp)],
+                &LogPattern::default(),
+            )
+        };
+
+        assert_eq!(
+            start_of("2021-12-15 11:00:00.074").unwrap(),
+            parse_date_time("2021-12-15T11:00:00.074").unwrap()
+        );
+        assert_eq!(
+            start_of("2021-12-15 11:00:00").unwrap(),
+            parse_date_time("2021-12-15T11:00:00.000").unwrap()
+        );
+        assert!(start_of("15-12-2021 11:00:00")
+            .unwrap_err()
+            .to_string()
+            .contains("'15-12-2021 11:00:00'"));
+    }
+
+    /// Uses input files ./tlcfi.txt and ./vlog_tlcfi_mapping.txt for an integration test, and compares it with an expected vlog output: ./expected_vlog_output.vlg
+    #[test]
+    fn integration_test() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_integration");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let expected_vlog_output = read_to_string("./expected_vlog_output.vlg").unwrap();
+        let app_args = AppArgs {
+            is_chronological: false,
+            start_date_time: Some(get_test_start_time()),
+            tlcfi_log_file: RELATIVE_TLCFI_FILE_PATH.to_string(),
+            overwrite: true,
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        run_with_args(app_args.clone());
+        let actual_vlog_output = read_to_string(test_dir.join("3031_20211215_110000.vlg")).unwrap();
+        run_with_args(AppArgs {
+            line_ending: LineEnding::Lf,
+            ..app_args
+        });
+        let actual_lf_vlog_output =
+            read_to_string(test_dir.join("3031_20211215_110000.vlg")).unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        let expected_lines: Vec<&str> = expected_vlog_output.lines().collect();
+        assert_eq!(
+            actual_vlog_output.split("\r\n").collect::<Vec<&str>>(),
+            [expected_lines.as_slice(), &[""]].concat()
+        );
+        assert_eq!(
+            actual_lf_vlog_output.split('\n').collect::<Vec<&str>>(),
+            [expected_lines.as_slice(), &[""]].concat()
+        );
+    }
+
+    /// Same as the integration test, but only converting signals with a mapping file that has no detectors.
+    /// Uses a different start time so it doesn't write to the same file as the other integration tests.
+    #[test]
+    fn integration_test_signals_only() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_signals_only");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let app_args = AppArgs {
+            start_date_time: Some(get_test_start_time() + chrono::Duration::seconds(1)),
+            conversion_mode: ConversionMode::SignalsOnly,
+            tlcfi_log_file: RELATIVE_TLCFI_FILE_PATH.to_string(),
+            overwrite: true,
+            vlog_tlcfi_mapping_file: "./vlog_tlcfi_mapping_signals_only.txt".to_string(),
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        run_with_args(app_args);
+        let actual_vlog_output = read_to_string(test_dir.join("3031_20211215_110001.vlg")).unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        let actual_lines: Vec<&str> = actual_vlog_output.split_whitespace().collect();
+        assert!(actual_lines.iter().any(|line| line.starts_with("0E")));
+        assert!(!actual_lines.iter().any(|line| line.starts_with("06")));
+    }
+
+    /// Same as the integration test, but only converting detectors.
+    #[test]
+    fn integration_test_detectors_only() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_detectors_only");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let app_args = AppArgs {
+            start_date_time: Some(get_test_start_time() + chrono::Duration::seconds(2)),
+            conversion_mode: ConversionMode::DetectorsOnly,
+            tlcfi_log_file: RELATIVE_TLCFI_FILE_PATH.to_string(),
+            overwrite: true,
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        run_with_args(app_args);
+        let actual_vlog_output = read_to_string(test_dir.join("3031_20211215_110002.vlg")).unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        let actual_lines: Vec<&str> = actual_vlog_output.split_whitespace().collect();
+        assert!(actual_lines.iter().any(|line| line.starts_with("06")));
+        assert!(!actual_lines.iter().any(|line| line.starts_with("0E")));
+    }
+
+    /// Converts the gzipped copy ./tlcfi.txt.gz, which should give exactly the same VLog file as the plain log.
+    #[test]
+    fn integration_test_gzipped() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_gzipped");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let app_args = AppArgs {
+            start_date_time: Some(get_test_start_time() + chrono::Duration::seconds(3)),
+            tlcfi_log_file: RELATIVE_TLCFI_FILE_PATH.to_string(),
+            overwrite: true,
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        run_with_args(app_args.clone());
+        let plain_vlog_output = fs::read(test_dir.join("3031_20211215_110003.vlg")).unwrap();
+        run_with_args(AppArgs {
+            tlcfi_log_file: "./tlcfi.txt.gz".to_string(),
+            ..app_args
+        });
+        let gzipped_vlog_output = fs::read(test_dir.join("3031_20211215_110003.vlg")).unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert!(plain_vlog_output.len() > 100);
+        assert!(plain_vlog_output == gzipped_vlog_output);
+    }
+
+    #[test]
+    fn a_vlog_file_over_the_maximum_size_should_follow_the_size_policy() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_size_policy");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let app_args = AppArgs {
+            start_date_time: Some(get_test_start_time() + chrono::Duration::seconds(4)),
+            tlcfi_log_file: RELATIVE_TLCFI_FILE_PATH.to_string(),
+            overwrite: true,
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            max_output_size: Some(100_000),
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let aborted = convert(&app_args);
+        let aborted_file_exists = test_dir.join("3031_20211215_110004.vlg").exists();
+        let report = convert(&AppArgs {
+            size_policy: SizePolicy::Compress,
+            ..app_args
+        })
+        .unwrap();
+        let mut decompressed = Vec::new();
+        compressed_logs::open_log(
+            &test_dir
+                .join("3031_20211215_110004.vlg.gz")
+                .to_string_lossy(),
+        )
+        .and_then(|mut vlog| vlog.read_to_end(&mut decompressed))
+        .unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert_eq!(aborted.err().unwrap().exit_code, EXIT_CODE_OUTPUT_ERROR);
+        assert!(!aborted_file_exists);
+        assert_eq!(
+            report.file_names,
+            vec![test_dir
+                .join("3031_20211215_110004.vlg.gz")
+                .to_string_lossy()]
+        );
+        assert!(report.stats.output_bytes > 100_000);
+        assert_eq!(
+            report.stats.output_bytes,
+            report.stats.estimated_output_bytes
+        );
+        assert_eq!(decompressed.len() as u64, report.stats.output_bytes);
+    }
+
+    #[test]
+    fn integration_test_zip_archive() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_zip_archive");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let app_args = AppArgs {
+            start_date_time: Some(get_test_start_time() + chrono::Duration::seconds(6)),
+            tlcfi_log_file: "./logs_20211215.zip".to_string(),
+            overwrite: true,
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let mut archive =
+            zip::ZipArchive::new(File::open(&app_args.tlcfi_log_file).unwrap()).unwrap();
+        let concatenated_log_file =
+            std::env::temp_dir().join("tlcfi_assimilator_extracted_logs.txt");
+        let mut concatenated_log = Vec::new();
+        for name in ["tlcfi.txt", "tlcfi.txt.1", "tlcfi.txt.2"].iter() {
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_end(&mut concatenated_log)
+                .unwrap();
+        }
+        fs::write(&concatenated_log_file, concatenated_log).unwrap();
+
+        let report = convert(&app_args).unwrap();
+        let zip_vlog_output = fs::read(test_dir.join("3031_20211215_110006.vlg")).unwrap();
+        convert(&AppArgs {
+            tlcfi_log_file: concatenated_log_file.to_str().unwrap().to_string(),
+            ..app_args
+        })
+        .unwrap();
+        let extracted_vlog_output = fs::read(test_dir.join("3031_20211215_110006.vlg")).unwrap();
+        fs::remove_file(&concatenated_log_file).unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        // The start time is a few seconds into the logs, the lines before it are skipped
+        assert!(report.stats.lines_read + report.stats.lines_before_start == 6000);
+        assert!(report.stats.lines_before_start > 0);
+        assert!(report
+            .warnings
+            .iter()
+            .any(|warning| warning.contains("'readme.txt'")));
+        assert!(zip_vlog_output.len() > 100);
+        assert!(zip_vlog_output == extracted_vlog_output);
+    }
+
+    #[test]
+    fn names_with_stray_whitespace_should_use_their_mapping_entry() {
+        let log_line = |ids: &str| {
+            format!(
+                r#"2021-12-15 11:00:00,000 INFO tlcFiMessages:41 - IN - {{"jsonrpc":"2.0","method":"UpdateState","params":{{"ticks":1000,"update":[{{"objects":{{"ids":[{}],"type":4}},"states":[{{"state":1}},{{"state":1}}]}}]}}}}"#,
+                ids
+            )
+        };
+        let vlog_messages_of = |line: String| {
+            let mut data = AssimilationData {
+                sorted_lines: vec![line],
+                console: Console::new(Verbosity::Quiet),
+                ..Default::default()
+            };
+            read_lines_and_save_changes(&mut data, &AppArgs::default()).unwrap();
+            vlog_transformer::to_vlog(
+                data.changes,
+                &get_test_start_time(),
+                RELATIVE_VLOG_MAPPING_FILE_PATH,
+                "3031",
+                vlog_transformer::TransformOptions::default(),
+                &mut ConversionStats::default(),
+            )
+        };
+
+        let clean_messages = vlog_messages_of(log_line(r#""D612","D683""#));
+        let messages = vlog_messages_of(log_line("\"D612 \",\"\u{a0}D683\""));
+
+        // Detector 1 and 10 occupied
+        assert_eq!(clean_messages.last().unwrap(), "06000201010A01");
+        assert_eq!(messages, clean_messages);
+    }
+
+    #[test]
+    fn each_amber_policy_should_write_its_own_state_for_amber() {
+        let vlog_messages_with = |amber_policy: AmberPolicy| {
+            let mut console = Console::new(Verbosity::Quiet);
+            let mut data = AssimilationData {
+                sorted_lines: sort_lines("./tlcfi_with_amber.txt", &false, &mut console).unwrap(),
+                console,
+                ..Default::default()
+            };
+            read_lines_and_save_changes(&mut data, &AppArgs::default()).unwrap();
+            let collapsed_count = amber_policy::collapse_amber(&mut data.changes, amber_policy);
+            let vlog_messages = vlog_transformer::to_vlog(
+                data.changes,
+                &get_test_start_time(),
+                RELATIVE_VLOG_MAPPING_FILE_PATH,
+                "3031",
+                vlog_transformer::TransformOptions::default(),
+                &mut ConversionStats::default(),
+            );
+            (vlog_messages[2..].to_vec(), collapsed_count)
+        };
+
+        // Green, amber after 3 seconds and red after 6 seconds for signal group 02
+        assert_eq!(
+            vlog_messages_with(AmberPolicy::Keep),
+            (
+                vec![
+                    "0E00010001".to_string(),
+                    "0E01E10002".to_string(),
+                    "0E03C10000".to_string()
+                ],
+                0
+            )
+        );
+        assert_eq!(
+            vlog_messages_with(AmberPolicy::AsGreen),
+            (
+                vec![
+                    "0E00010001".to_string(),
+                    "0E01E10001".to_string(),
+                    "0E03C10000".to_string()
+                ],
+                1
+            )
+        );
+        assert_eq!(
+            vlog_messages_with(AmberPolicy::AsRed),
+            (
+                vec![
+                    "0E00010001".to_string(),
+                    "0E01E10000".to_string(),
+                    "0E03C10000".to_string()
+                ],
+                1
+            )
+        );
+    }
+
+    #[test]
+    fn failures_should_exit_with_the_code_of_their_category() {
+        let mapping_file = std::env::temp_dir().join("tlcfi_assimilator_incomplete_mapping.txt");
+        fs::write(&mapping_file, "// TLC\n3031\n\n// Signals\n0, 03\n").unwrap();
+        let app_args = AppArgs {
+            conversion_mode: ConversionMode::SignalsOnly,
+            tlcfi_log_file: "./tlcfi_with_amber.txt".to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            verbosity: Verbosity::Quiet,
+            dry_run: true,
+            ..Default::default()
+        };
+
+        assert_eq!(run_with_args(app_args.clone()), 0);
+        assert_eq!(
+            run_with_args(AppArgs {
+                tlcfi_log_file: "./no_such_log.txt".to_string(),
+                ..app_args.clone()
+            }),
+            EXIT_CODE_INPUT_ERROR
+        );
+        assert_eq!(
+            run_with_args(AppArgs {
+                tlcfi_log_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+                ..app_args.clone()
+            }),
+            EXIT_CODE_NO_MESSAGES
+        );
+        assert_eq!(
+            run_with_args(AppArgs {
+                vlog_tlcfi_mapping_file: mapping_file.to_string_lossy().to_string(),
+                dry_run: false,
+                ..app_args
+            }),
+            EXIT_CODE_MAPPING_ERROR
+        );
+        fs::remove_file(mapping_file).unwrap();
+    }
+
+    #[test]
+    fn an_existing_vlog_file_should_only_be_replaced_with_overwrite() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_overwrite");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let app_args = AppArgs {
+            start_date_time: Some(get_test_start_time() + chrono::Duration::seconds(7)),
+            tlcfi_log_file: "./tlcfi_with_amber.txt".to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            verbosity: Verbosity::Quiet,
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let file_name = test_dir.join("3031_20211215_110007.vlg");
+        fs::write(&file_name, "earlier output").unwrap();
+
+        let refused = convert(&app_args);
+        let kept_output = read_to_string(&file_name).unwrap();
+        let report = convert(&AppArgs {
+            overwrite: true,
+            ..app_args
+        })
+        .unwrap();
+        let replaced_output = read_to_string(&file_name).unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        let failure = refused.err().unwrap();
+        assert_eq!(failure.exit_code, EXIT_CODE_OUTPUT_ERROR);
+        assert!(failure.message.contains("--overwrite"));
+        assert_eq!(kept_output, "earlier output");
+        assert_eq!(
+            report.file_names,
+            vec![test_dir.join("3031_20211215_110007.vlg").to_string_lossy()]
+        );
+        assert!(replaced_output.starts_with("012021121511000700"));
+    }
+
+    #[test]
+    fn a_cached_conversion_should_be_restored_to_the_output_dir() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_cached_output_dir");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let app_args = AppArgs {
+            start_date_time: Some(get_test_start_time()),
+            tlcfi_log_file: "./tlcfi_with_amber.txt".to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            verbosity: Verbosity::Quiet,
+            overwrite: true,
+            cache_dir: Some(test_dir.join("cache").to_string_lossy().to_string()),
+            output_dir: Some(test_dir.join("vlog").to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let report = convert(&app_args).unwrap();
+        let converted_output = read_to_string(&report.file_names[0]).unwrap();
+        fs::remove_dir_all(test_dir.join("vlog")).unwrap();
+        let cached_report = convert(&app_args).unwrap();
+        let restored_output = read_to_string(&cached_report.file_names[0]).unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert_eq!(cached_report.file_names, report.file_names);
+        assert!(converted_output.len() > 100);
+        assert_eq!(restored_output, converted_output);
+    }
+
+    #[test]
+    fn a_cache_hit_should_not_replace_an_existing_file_without_overwrite() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_cached_overwrite");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let app_args = AppArgs {
+            start_date_time: Some(get_test_start_time()),
+            tlcfi_log_file: "./tlcfi_with_amber.txt".to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            verbosity: Verbosity::Quiet,
+            cache_dir: Some(test_dir.join("cache").to_string_lossy().to_string()),
+            output_dir: Some(test_dir.join("vlog").to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let report = convert(&app_args).unwrap();
+        fs::write(&report.file_names[0], "earlier output").unwrap();
+        let failure = convert(&app_args).unwrap_err();
+        let kept_output = read_to_string(&report.file_names[0]).unwrap();
+        let overwritten = convert(&AppArgs {
+            overwrite: true,
+            ..app_args.clone()
+        });
+        let restored_output = read_to_string(&report.file_names[0]).unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert_eq!(failure.exit_code, EXIT_CODE_OUTPUT_ERROR);
+        assert!(failure.message.contains("--overwrite"));
+        assert_eq!(kept_output, "earlier output");
+        assert!(overwritten.is_ok());
+        assert!(restored_output.starts_with("01"));
+    }
+
+    #[test]
+    fn a_conversion_with_outputs_besides_the_vlog_file_should_not_be_cached() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_uncached_outputs");
+        let _ = fs::remove_dir_all(&test_dir);
+        let reject_file = test_dir.join("rejected.txt");
+        let app_args = AppArgs {
+            start_date_time: Some(get_test_start_time()),
+            tlcfi_log_file: "./tlcfi_with_amber.txt".to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            verbosity: Verbosity::Quiet,
+            overwrite: true,
+            cache_dir: Some(test_dir.join("cache").to_string_lossy().to_string()),
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            reject_file: Some(reject_file.to_string_lossy().to_string()),
+            ..Default::default()
+        };
 
-        read_lines_and_save_changes(&mut data);
+        convert(&app_args).unwrap();
+        let cache_is_empty = !test_dir.join("cache").exists();
+        fs::remove_dir_all(&test_dir).unwrap();
 
-        assert!(!data.changes.is_empty());
-        assert_eq!(data.changes[0].ms_from_beginning, 0); // it being 0 means this is the very first message handled, and first tick is equal to it
+        assert!(cache_is_empty);
     }
 
     #[test]
-    fn creating_a_vlog_file_name_should_use_the_tlc_name_and_format_the_date_time_correctly() {
-        let tlc_name = "test";
-        let date_time = get_test_start_time();
+    fn a_cache_hit_should_report_the_stats_of_the_conversion() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_cached_summary");
+        let _ = fs::remove_dir_all(&test_dir);
+        let summary_file = test_dir.join("conversion.json");
+        let app_args = AppArgs {
+            tlcfi_log_file: "./tlcfi_with_amber.txt".to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            verbosity: Verbosity::Quiet,
+            overwrite: true,
+            cache_dir: Some(test_dir.join("cache").to_string_lossy().to_string()),
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            summary_json: Some(summary_file.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let read_summary = || {
+            let summary: ConversionSummary =
+                serde_json::from_str(&read_to_string(&summary_file).unwrap()).unwrap();
+            ConversionSummary {
+                runtime_ms: 0,
+                ..summary
+            }
+        };
 
-        let vlog_file_name = create_file_name(tlc_name, &date_time);
+        let uncached = convert(&AppArgs {
+            cache_dir: None,
+            ..app_args.clone()
+        })
+        .unwrap();
+        run_with_args(app_args.clone());
+        let missed_summary = read_summary();
+        let hit = convert(&app_args).unwrap();
+        run_with_args(app_args);
+        let hit_summary = read_summary();
+        fs::remove_dir_all(&test_dir).unwrap();
 
-        assert_eq!(vlog_file_name, "test_20211215_110000.vlg");
+        assert_eq!(hit.stats, uncached.stats);
+        assert_eq!(hit.start_time, uncached.start_time);
+        assert_eq!(hit.warnings, uncached.warnings);
+        assert_eq!(hit.tlc_name, Some("3031".to_string()));
+        assert_eq!(hit_summary, missed_summary);
+        assert_eq!(hit_summary.messages["external_signal_group_status"], 3);
     }
 
     #[test]
-    fn creating_a_vlog_file_name_should_remove_ms_from_time() {
-        let tlc_name = "test";
-        let date_time = NaiveDate::from_ymd(2021, 12, 15).and_hms_milli(11, 22, 33, 444);
+    fn listing_objects_should_not_need_a_mapping_file_and_take_its_format() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect();
 
-        let vlog_file_name = create_file_name(tlc_name, &date_time);
+        let app_args = parse_args(args(&["--list-objects", "--format", "csv"])).unwrap();
+        let without_list_objects = parse_args(args(&["--format", "csv"])).unwrap_err();
 
-        assert_eq!(vlog_file_name, "test_20211215_112233.vlg");
+        assert!(app_args.list_objects);
+        assert_eq!(app_args.list_format, log_stats::ListFormat::Csv);
+        assert!(without_list_objects
+            .to_string()
+            .contains("--format only applies to --list-objects"));
     }
 
     #[test]
-    fn getting_start_date_time_from_a_file_should_interpret_log_timestamps_as_naivedatetime() {
-        let expected_start_date_time = parse_date_time("2021-12-15T11:00:00.074").unwrap();
-        let lines = vec![String::from("2021-12-15 11:00:00,074 INFO  tlcFiMessages:41 - OUT - {\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087974612,\"update\":[{\"objects\":{\"ids\":[\"D681\"],\"type\":4},\"states\":[{\"state\":0}]}]}}")];
+    fn a_mapping_file_without_detectors_should_be_rejected_when_parsing_arguments() {
+        let mapping_file = std::env::temp_dir().join("tlcfi_assimilator_no_detectors_mapping.txt");
+        fs::write(&mapping_file, "// TLC\n3031\n\n// Signals\n0, 02\n").unwrap();
+        let args = |extra_args: &[&str]| {
+            let mut args = vec![
+                OsString::from("--mapping-file"),
+                mapping_file.clone().into_os_string(),
+            ];
+            args.extend(extra_args.iter().map(OsString::from));
+            args
+        };
 
-        let start_date_time_from_log = get_start_date_time_from_file(&lines);
+        let error = parse_args(args(&[])).unwrap_err();
+        let signals_only_args = parse_args(args(&["--signals-only"]));
+        fs::remove_file(&mapping_file).unwrap();
 
-        assert!(start_date_time_from_log.is_ok());
-        assert_eq!(start_date_time_from_log.unwrap(), expected_start_date_time);
+        assert!(matches!(error, ArgsError::Invalid(_)));
+        let error = error.to_string();
+        assert!(error.contains("No Detectors mappings found in the mapping file!"));
+        assert!(!error.contains("Signals"));
+        assert!(signals_only_args.is_ok());
     }
 
-    /// Uses input files ./tlcfi.txt and ./vlog_tlcfi_mapping.txt for an integration test, and compares it with an expected vlog output: ./expected_vlog_output.vlg
     #[test]
-    fn integration_test() {
-        let expected_vlog_output = read_to_string("./expected_vlog_output.vlg").unwrap();
-        let app_args = AppArgs {
-            is_chronological: false,
+    fn the_tlc_name_option_should_replace_the_name_of_the_mapping_file() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_tlc_name");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        // A mapping file without a TLC name can be used when the name is given
+        let mapping_file = test_dir.join("mapping.txt");
+        fs::copy(RELATIVE_VLOG_MAPPING_FILE_PATH, &mapping_file).unwrap();
+        let contents = read_to_string(&mapping_file).unwrap();
+        fs::write(&mapping_file, contents.replacen("// TLC\n3031\n", "", 1)).unwrap();
+        let args = |tlc_name: &str| {
+            vec![
+                OsString::from("--tlc-name"),
+                OsString::from(tlc_name),
+                OsString::from("--mapping-file"),
+                mapping_file.clone().into_os_string(),
+            ]
+        };
+
+        let too_long = parse_args(args("123456789012345678901")).unwrap_err();
+        let app_args = parse_args(args("SHARED")).unwrap();
+        let conversion = convert(&AppArgs {
             start_date_time: Some(get_test_start_time()),
             tlcfi_log_file: RELATIVE_TLCFI_FILE_PATH.to_string(),
+            verbosity: Verbosity::Quiet,
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..app_args
+        });
+        let vlog_file = test_dir.join("SHARED_20211215_110000.vlg");
+        let vlog = read_to_string(&vlog_file).unwrap_or_default();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert!(too_long.to_string().contains("only has room for 20"));
+        assert_eq!(
+            conversion.unwrap().file_names,
+            vec![vlog_file.to_string_lossy().to_string()]
+        );
+        assert_eq!(
+            vlog.lines().nth(1),
+            Some("040300005348415245442020202020202020202020202020")
+        );
+    }
+
+    #[test]
+    fn all_problems_of_a_mapping_file_should_be_reported_together() {
+        let mapping_file = std::env::temp_dir().join("tlcfi_assimilator_empty_mapping.txt");
+        fs::write(&mapping_file, "// Signals\n\n// Detectors\n").unwrap();
+
+        let error = validate_mapping_file(
+            &mapping_file.to_string_lossy(),
+            ConversionMode::default(),
+            true,
+        )
+        .unwrap_err();
+        fs::remove_file(&mapping_file).unwrap();
+
+        assert!(error.contains("No TLC name found in the mapping file!"));
+        assert!(error.contains("No Signals mappings found in the mapping file!"));
+        assert!(error.contains("No Detectors mappings found in the mapping file!"));
+        assert!(
+            validate_mapping_file("./missing_mapping.txt", ConversionMode::default(), true)
+                .unwrap_err()
+                .contains("could not be opened")
+        );
+    }
+
+    #[test]
+    fn validating_a_mapping_file_should_exit_with_the_mapping_error_code_on_problems() {
+        let mapping_file = std::env::temp_dir().join("tlcfi_assimilator_duplicate_mapping.txt");
+        fs::write(
+            &mapping_file,
+            "// TLC\n3031\n\n// Signals\n0, 02\n1, 02\n\n// Detectors\n0, D611\n",
+        )
+        .unwrap();
+        let args = |mapping_file: &str| {
+            vec![
+                OsString::from("--validate-mapping"),
+                OsString::from("--quiet"),
+                OsString::from("--mapping-file"),
+                OsString::from(mapping_file),
+            ]
+        };
+
+        let exit_code = run_with_args(parse_args(args(&mapping_file.to_string_lossy())).unwrap());
+        fs::remove_file(&mapping_file).unwrap();
+
+        assert_eq!(exit_code, EXIT_CODE_MAPPING_ERROR);
+        assert_eq!(
+            run_with_args(parse_args(args(RELATIVE_VLOG_MAPPING_FILE_PATH)).unwrap()),
+            0
+        );
+    }
+
+    #[test]
+    fn a_generated_mapping_should_convert_the_log_it_was_generated_from() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_generated_mapping");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let mapping_file = test_dir.join("mapping.txt").to_string_lossy().to_string();
+        let args = vec![
+            "--generate-mapping",
+            &mapping_file,
+            "--quiet",
+            "--tlcfi-log-file",
+            RELATIVE_TLCFI_FILE_PATH,
+        ]
+        .into_iter()
+        .map(OsString::from)
+        .collect();
+
+        let exit_code = run_with_args(parse_args(args).unwrap());
+        let contents = read_to_string(&mapping_file).unwrap();
+        let conversion = convert(&AppArgs {
+            start_date_time: Some(get_test_start_time()),
+            tlcfi_log_file: RELATIVE_TLCFI_FILE_PATH.to_string(),
+            vlog_tlcfi_mapping_file: mapping_file.clone(),
+            verbosity: Verbosity::Quiet,
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        });
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert_eq!(exit_code, 0);
+        assert!(contents.starts_with("// TLC\nEDIT_TLC_NAME\n\n// Signals\n0, "));
+        assert_eq!(
+            conversion.unwrap().file_names,
+            vec![test_dir
+                .join("EDIT_TLC_NAME_20211215_110000.vlg")
+                .to_string_lossy()
+                .to_string()]
+        );
+    }
+
+    #[test]
+    fn the_log_order_should_accept_the_plain_flag_the_boolean_values_and_reverse() {
+        let is_chronological = |args: &[&str]| {
+            let mut args: Vec<OsString> = args.iter().map(OsString::from).collect();
+            args.push(OsString::from("--mapping-file"));
+            args.push(OsString::from(RELATIVE_VLOG_MAPPING_FILE_PATH));
+            parse_args(args).map(|app_args| app_args.is_chronological)
+        };
+
+        assert!(is_chronological(&["--chronological"]).unwrap());
+        assert!(is_chronological(&["--chronological", "true"]).unwrap());
+        assert!(!is_chronological(&["--chronological", "false"]).unwrap());
+        assert!(!is_chronological(&["--reverse"]).unwrap());
+        assert!(is_chronological(&["--chronological", "--duration", "60"]).unwrap());
+        assert!(is_chronological(&["--chronological", "maybe"])
+            .unwrap_err()
+            .to_string()
+            .contains(
+                "use --chronological, --chronological true, --chronological false or --reverse"
+            ));
+        assert!(is_chronological(&["--chronological", "--reverse"]).is_err());
+    }
+
+    #[test]
+    fn a_log_with_only_out_messages_should_fail_without_writing_a_vlog_file() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_out_only");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let log_file = test_dir.join("tlcfi.txt");
+        let log = read_to_string("./tlcfi_with_amber.txt").unwrap();
+        fs::write(&log_file, log.replace(" - IN - ", " - OUT - ")).unwrap();
+
+        let conversion = convert(&AppArgs {
+            start_date_time: Some(get_test_start_time()),
+            tlcfi_log_file: log_file.to_string_lossy().to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            verbosity: Verbosity::Quiet,
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        });
+        let written_files = fs::read_dir(&test_dir).unwrap().count();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        let failure = conversion.unwrap_err();
+        assert_eq!(failure.exit_code, EXIT_CODE_NO_MESSAGES);
+        assert!(failure.message.ends_with(
+            "examined 3 line(s): 0 IN, 3 OUT, no first tick found in the in direction."
+        ));
+        assert_eq!(written_files, 1);
+    }
+
+    #[test]
+    fn an_error_response_of_the_tlc_should_be_reported_or_fail_with_strict_rpc() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_rpc_error");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let log_file = test_dir.join("tlcfi.txt");
+        let log = read_to_string("./tlcfi_with_amber.txt").unwrap();
+        fs::write(
+            &log_file,
+            format!(
+                "2021-12-15 12:00:05,000 INFO  tlcFiMessages:41 - IN - {{\"jsonrpc\":\"2.0\",\"error\":{{\"code\":-32602,\"message\":\"Invalid params\"}},\"id\":12}}\n{}",
+                log
+            ),
+        )
+        .unwrap();
+        let app_args = AppArgs {
+            start_date_time: Some(get_test_start_time()),
+            tlcfi_log_file: log_file.to_string_lossy().to_string(),
             vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            verbosity: Verbosity::Quiet,
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..Default::default()
         };
 
-        run_with_args(app_args);
-        let actual_vlog_output = read_to_string("./3031_20211215_110000.vlg").unwrap();
+        let strict_conversion = convert(&AppArgs {
+            strict_rpc: true,
+            ..app_args.clone()
+        });
+        let conversion = convert(&app_args);
+        fs::remove_dir_all(&test_dir).unwrap();
 
-        let mut expected_lines = Vec::new();
-        expected_lines.extend(expected_vlog_output.split_whitespace().into_iter());
-        for (i, actual_line) in actual_vlog_output.split_whitespace().enumerate() {
-            assert_eq!(actual_line, expected_lines[i]);
-        }
+        let failure = strict_conversion.unwrap_err();
+        assert_eq!(failure.exit_code, EXIT_CODE_INPUT_ERROR);
+        assert_eq!(
+            failure.message,
+            "Error: the TLC sent 1 JSON-RPC error response(s), the first is error -32602 'Invalid params' for request 12 at 2021-12-15 12:00:05"
+        );
+        let stats = conversion.unwrap().stats;
+        assert_eq!(stats.rpc_errors.len(), 1);
+        assert_eq!(stats.lines_skipped, 0);
+    }
+
+    #[test]
+    fn the_positional_mapping_file_should_still_be_accepted() {
+        let args = vec!["--duration", "60", "./vlog_tlcfi_mapping.txt"]
+            .into_iter()
+            .map(OsString::from)
+            .collect();
+
+        let app_args = parse_args(args).unwrap();
+
+        assert_eq!(app_args.vlog_tlcfi_mapping_file, "./vlog_tlcfi_mapping.txt");
+    }
+
+    #[test]
+    fn a_step_of_the_log_clock_should_be_reported_and_timed_by_the_ticks() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_clock_step");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let app_args = AppArgs {
+            tlcfi_log_file: "./tlcfi_with_clock_step.txt".to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            conversion_mode: ConversionMode::SignalsOnly,
+            verbosity: Verbosity::Quiet,
+            strict: true,
+            overwrite: true,
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let report = convert(&app_args).unwrap();
+        let vlog = read_to_string(test_dir.join("3031_20211215_120000.vlg")).unwrap();
+        let repaired_report = convert(&AppArgs {
+            repair_clock_steps: true,
+            ..app_args
+        })
+        .unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert_eq!(
+            vlog.lines().collect::<Vec<&str>>(),
+            vec![
+                "012021121512000000",
+                "040300003330333120202020202020202020202020202020",
+                "0E00010001",
+                "0E01E10002",
+                "0E03C10000",
+                "0E05A10001",
+            ]
+        );
+        assert_eq!(report.stats.clock_steps.len(), 1);
+        assert_eq!(report.stats.clock_steps[0].step_ms, -30_000);
+        assert!(!report.stats.clock_steps[0].repaired);
+        assert!(repaired_report.stats.clock_steps[0].repaired);
+        assert!(
+            conversion_summary_lines(&report.stats, &get_test_start_time(), &AppArgs::default())
+                .contains(&"  clock step: the log clock jumped back 30.000 s between 2021-12-15 12:00:03 and 2021-12-15 11:59:36, line times until 2021-12-15 11:59:39 are off, ticks were used for the timing".to_string())
+        );
+    }
+
+    #[test]
+    fn a_name_in_both_mappings_should_be_warned_about() {
+        let mapping_file = std::env::temp_dir().join("tlcfi_assimilator_ambiguous_names.txt");
+        fs::write(
+            &mapping_file,
+            "// TLC\n3031\n\n// Signals\n0, 02\n1, 03\n\n// Detectors\n0, 03 \n1, D611\n",
+        )
+        .unwrap();
+        let app_args = AppArgs {
+            vlog_tlcfi_mapping_file: mapping_file.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let changes = vec![TimestampedChanges {
+            ms_from_beginning: 0,
+            signal_names: vec!["03".to_string()],
+            signal_states: vec![tlcfi_assimilator::SignalState::Green],
+            ..Default::default()
+        }];
+
+        let mut console = Console::new(Verbosity::Quiet);
+        warn_about_mapping_mismatch(&changes, &app_args, &mut console).unwrap();
+        fs::remove_file(&mapping_file).unwrap();
+
+        assert!(console
+            .all_warnings()
+            .iter()
+            .any(|warning| warning.contains("both signals and detectors named 03")));
+    }
+
+    #[test]
+    fn a_mapping_of_another_intersection_should_be_warned_about_or_fail_in_strict_mode() {
+        let mapping_file = std::env::temp_dir().join("tlcfi_assimilator_other_intersection.txt");
+        fs::write(
+            &mapping_file,
+            "// TLC\n3031\n\n// Signals\n0, 02\n1, 03\n\n// Detectors\n0, D021\n1, D031\n",
+        )
+        .unwrap();
+        let app_args = AppArgs {
+            vlog_tlcfi_mapping_file: mapping_file.to_string_lossy().to_string(),
+            tlcfi_log_file: "other_intersection.log".to_string(),
+            ..Default::default()
+        };
+        let changes = vec![TimestampedChanges {
+            ms_from_beginning: 0,
+            signal_names: vec!["21".to_string(), "22".to_string()],
+            signal_states: vec![
+                tlcfi_assimilator::SignalState::Green,
+                tlcfi_assimilator::SignalState::Red,
+            ],
+            ..Default::default()
+        }];
+
+        let mut console = Console::new(Verbosity::Quiet);
+        warn_about_mapping_mismatch(&changes, &app_args, &mut console).unwrap();
+        let strict_failure = warn_about_mapping_mismatch(
+            &changes,
+            &AppArgs {
+                strict: true,
+                ..app_args.clone()
+            },
+            &mut Console::new(Verbosity::Quiet),
+        )
+        .unwrap_err();
+        fs::remove_file(&mapping_file).unwrap();
+
+        assert!(console.all_warnings()[0]
+            .contains("probably doesn't belong to the log file 'other_intersection.log'"));
+        assert_eq!(strict_failure.exit_code, EXIT_CODE_MAPPING_ERROR);
+        assert!(strict_failure
+            .message
+            .contains("probably doesn't belong to the log file 'other_intersection.log'"));
+    }
+
+    #[test]
+    fn messages_before_the_start_date_time_should_be_skipped() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_before_start_output");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let log_file = std::env::temp_dir().join("tlcfi_assimilator_before_start.txt");
+        let line = |time: &str, ticks: u64, state: u8| {
+            format!(
+                "2021-12-15 {} INFO  tlcFiMessages:41 - IN - {{\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{{\"ticks\":{},\"update\":[{{\"objects\":{{\"ids\":[\"02\"],\"type\":3}},\"states\":[{{\"state\":{}}}]}}]}}}}",
+                time, ticks, state
+            )
+        };
+        let log = [
+            line("12:00:00,000", 1000, 6),
+            line("12:05:00,000", 301_000, 3),
+            line("12:10:00,000", 601_000, 6),
+            line("12:10:03,000", 604_000, 3),
+        ];
+        fs::write(&log_file, log.join("\n")).unwrap();
+        let app_args = AppArgs {
+            start_date_time: Some(
+                NaiveDateTime::parse_from_str("2021-12-15 12:10:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+            ),
+            is_chronological: true,
+            conversion_mode: ConversionMode::SignalsOnly,
+            tlcfi_log_file: log_file.to_string_lossy().to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            verbosity: Verbosity::Quiet,
+            overwrite: true,
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let report = convert(&app_args).unwrap();
+        let vlog = read_to_string(test_dir.join("3031_20211215_121000.vlg")).unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+        fs::remove_file(&log_file).unwrap();
+
+        assert_eq!(
+            vlog.lines().collect::<Vec<&str>>(),
+            vec![
+                "012021121512100000",
+                "040300003330333120202020202020202020202020202020",
+                "0E00010001",
+                "0E01E10000",
+            ]
+        );
+        assert_eq!(report.change_count, 2);
+        assert_eq!(report.stats.lines_before_start, 2);
+    }
+
+    #[test]
+    fn the_output_template_should_create_its_directories() {
+        let output_dir = std::env::temp_dir().join("tlcfi_assimilator_output_template");
+        let _ = fs::remove_dir_all(&output_dir);
+        let app_args = AppArgs {
+            tlcfi_log_file: "./tlcfi_with_amber.txt".to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            verbosity: Verbosity::Quiet,
+            output_dir: Some(output_dir.to_string_lossy().to_string()),
+            output_template: "{date}/{tlc}/{tlc}-{date}T{time}.{ext}".parse().unwrap(),
+            ..Default::default()
+        };
+
+        let report = convert(&app_args).unwrap();
+        let vlog = read_to_string(output_dir.join("20211215/3031/3031-20211215T120000.vlg"));
+        fs::remove_dir_all(&output_dir).unwrap();
+
+        assert_eq!(
+            report.file_names,
+            vec![output_dir
+                .join("20211215/3031/3031-20211215T120000.vlg")
+                .to_string_lossy()
+                .to_string()]
+        );
+        assert!(vlog.unwrap().starts_with("012021121512000000"));
+    }
+
+    #[test]
+    fn teed_messages_should_reach_the_file_and_stdout_alike() {
+        let output_dir = std::env::temp_dir().join("tlcfi_assimilator_tee");
+        let _ = fs::remove_dir_all(&output_dir);
+        let report = convert(&AppArgs {
+            tlcfi_log_file: "./tlcfi_with_amber.txt".to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            verbosity: Verbosity::Quiet,
+            output_dir: Some(output_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        let vlog = read_to_string(&report.file_names[0]).unwrap();
+        fs::remove_dir_all(&output_dir).unwrap();
+        let vlog_messages: Vec<String> = vlog.lines().map(|line| line.to_string()).collect();
+
+        let mut file = Vec::new();
+        let mut stdout = Vec::new();
+        write_vlog_messages(
+            &mut tee::Tee::new(&mut file, &mut stdout),
+            &vlog_messages,
+            LineEnding::Crlf,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(String::from_utf8(file).unwrap(), vlog);
+        assert_eq!(String::from_utf8(stdout).unwrap(), vlog);
+    }
+
+    #[test]
+    fn messages_should_end_with_the_chosen_line_ending() {
+        let vlog_messages = vec!["012021121511000000".to_string(), "0600A10A01".to_string()];
+        let write_with = |line_ending| {
+            let mut written = Vec::new();
+            write_vlog_messages(&mut written, &vlog_messages, line_ending, None).unwrap();
+            String::from_utf8(written).unwrap()
+        };
+
+        assert_eq!(
+            write_with(LineEnding::Crlf),
+            "012021121511000000\r\n0600A10A01\r\n"
+        );
+        assert_eq!(
+            write_with(LineEnding::Lf),
+            "012021121511000000\n0600A10A01\n"
+        );
+        assert_eq!("lf".parse(), Ok(LineEnding::Lf));
+        assert!("cr".parse::<LineEnding>().is_err());
+    }
+
+    #[test]
+    fn strict_mode_should_stop_at_a_line_with_an_unknown_state() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_strict_parsing");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let log_file = test_dir.join("bad_line.txt");
+        let log = read_to_string("./tlcfi_with_amber.txt").unwrap();
+        let bad_line = log
+            .lines()
+            .nth(1)
+            .unwrap()
+            .replace("{\"state\":8}", "{\"state\":99}");
+        fs::write(
+            &log_file,
+            log.replace(log.lines().nth(1).unwrap(), &bad_line),
+        )
+        .unwrap();
+        let app_args = AppArgs {
+            tlcfi_log_file: log_file.to_string_lossy().to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            conversion_mode: ConversionMode::SignalsOnly,
+            verbosity: Verbosity::Quiet,
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            overwrite: true,
+            ..Default::default()
+        };
+
+        let lenient_report = convert(&app_args).unwrap();
+        let strict_failure = convert(&AppArgs {
+            strict: true,
+            ..app_args
+        })
+        .unwrap_err();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert_eq!(lenient_report.file_names.len(), 1);
+        assert_eq!(lenient_report.stats.lines_skipped, 1);
+        assert_eq!(strict_failure.exit_code, EXIT_CODE_INPUT_ERROR);
+        assert!(strict_failure
+            .message
+            .starts_with("Error: line 2 of the log can't be parsed: "));
+        assert!(strict_failure.message.ends_with(&bad_line));
+    }
+
+    #[test]
+    fn a_log_read_from_its_end_should_convert_like_its_lines_in_chronological_order() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_read_from_end");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(test_dir.join("chronological")).unwrap();
+        let chronological_log = test_dir.join("chronological.txt");
+        let log = read_to_string("./tlcfi_with_amber.txt").unwrap();
+        let mut lines: Vec<&str> = log.lines().collect();
+        lines.reverse();
+        fs::write(&chronological_log, lines.join("\n")).unwrap();
+        let app_args = AppArgs {
+            tlcfi_log_file: "./tlcfi_with_amber.txt".to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            start_date_time: Some(parse_date_time("2021-12-15T12:00:02.000").unwrap()),
+            verbosity: Verbosity::Quiet,
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let from_end_report = convert(&app_args).unwrap();
+        let chronological_report = convert(&AppArgs {
+            tlcfi_log_file: chronological_log.to_string_lossy().to_string(),
+            is_chronological: true,
+            output_dir: Some(test_dir.join("chronological").to_string_lossy().to_string()),
+            ..app_args
+        })
+        .unwrap();
+        let from_end_vlog = read_to_string(&from_end_report.file_names[0]).unwrap();
+        let chronological_vlog = read_to_string(&chronological_report.file_names[0]).unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert_eq!(from_end_vlog, chronological_vlog);
+        assert_eq!(from_end_report.stats.lines_before_start, 1);
+        assert_eq!(chronological_report.stats.lines_before_start, 1);
+        assert_eq!(from_end_report.stats.lines_read, 2);
+    }
+
+    #[test]
+    fn the_json_summary_should_describe_the_conversion() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_summary_json");
+        let _ = fs::remove_dir_all(&test_dir);
+        let summary_file = test_dir.join("summary").join("conversion.json");
+        let app_args = AppArgs {
+            tlcfi_log_file: "./tlcfi_with_amber.txt".to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            verbosity: Verbosity::Quiet,
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            summary_json: Some(summary_file.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let exit_code = run_with_args(app_args);
+        let json = json::parse(&read_to_string(&summary_file).unwrap()).unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert_eq!(exit_code, 0);
+        let summary: ConversionSummary = serde_json::from_str(&json.dump()).unwrap();
+        assert_eq!(summary.tlc_name, Some("3031".to_string()));
+        assert_eq!(
+            summary.input_files,
+            vec!["./tlcfi_with_amber.txt".to_string()]
+        );
+        assert_eq!(summary.output_files.len(), 1);
+        assert!(summary.output_files[0].ends_with("3031_20211215_120000.vlg"));
+        assert_eq!(json["start_time"], "2021-12-15T12:00:00.000");
+        assert_eq!(json["messages"]["vlog_information"], 1);
+        assert_eq!(json["messages"]["external_signal_group_status"], 3);
+        assert_eq!(json["skipped_lines"]["no-split"], 0);
+    }
+
+    #[test]
+    fn every_skipped_line_should_be_written_to_the_reject_file_with_its_reason() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_reject_file");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let log_file = test_dir.join("tlcfi.txt");
+        let reject_file = test_dir.join("rejected.txt");
+        let update = |ticks: &str, state: &str| {
+            format!(
+                "INFO  tlcFiMessages:41 - IN - {{\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{{{}\"update\":[{{\"objects\":{{\"ids\":[\"02\"],\"type\":3}},\"states\":[{{\"state\":{}}}]}}]}}}}",
+                ticks, state
+            )
+        };
+        let lines = [
+            format!("2021-12-15 12:00:00,000 {}", update("", "6")),
+            format!("2021-12-15 12:00:01,000 {}", update("\"ticks\":1000,", "6")),
+            "garbage".to_string(),
+            "2021-12-15 12:00:02,000 INFO  tlcFiMessages:41 - IN - {\"jsonrpc\":".to_string(),
+            format!(
+                "2021-12-15 12:00:03,000 {}",
+                update("\"ticks\":3000,", "99")
+            ),
+            format!("2021-12-15 12:00:04,000 {}", update("\"ticks\":4000,", "3")),
+        ];
+        fs::write(&log_file, lines.join("\n")).unwrap();
+        let app_args = AppArgs {
+            tlcfi_log_file: log_file.to_string_lossy().to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            is_chronological: true,
+            dry_run: true,
+            verbosity: Verbosity::Quiet,
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            reject_file: Some(reject_file.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let report = convert(&app_args).unwrap();
+        let rejected = read_to_string(&reject_file).unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert_eq!(
+            rejected,
+            format!(
+                "no-first-tick 1 {}\nno-split 3 {}\njson-error 4 {}\nunknown-state 5 {}\n",
+                lines[0], lines[2], lines[3], lines[4]
+            )
+        );
+        assert_eq!(report.stats.rejected_lines, 4);
+    }
+
+    #[test]
+    fn the_predictions_of_the_log_should_be_written_to_the_predictions_csv() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_predictions_csv");
+        let _ = fs::remove_dir_all(&test_dir);
+        let predictions_csv = test_dir.join("predictions.csv");
+        let app_args = AppArgs {
+            tlcfi_log_file: "./tlcfi.txt".to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            dry_run: true,
+            verbosity: Verbosity::Quiet,
+            predictions_csv: Some(predictions_csv.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let report = convert(&app_args).unwrap();
+        let csv = read_to_string(&predictions_csv).unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "signal_name,ms_from_beginning,predicted_state,likely_end_ms,min_end_ms,max_end_ms"
+        );
+        assert!(report.stats.signal_predictions > 0);
+        assert_eq!(lines.len(), report.stats.signal_predictions + 1);
+        assert!(lines[1..].iter().all(|line| line.split(',').count() == 6));
+        assert!(check_follow_compatibility(&AppArgs {
+            predictions_csv: Some("predictions.csv".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn the_mapping_from_the_meta_in_the_log_should_convert_like_the_mapping_file() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_mapping_from_log");
+        let _ = fs::remove_dir_all(&test_dir);
+        let convert_to = |output_dir: &str, mapping_file: &str, mapping_from_log: bool| {
+            convert(&AppArgs {
+                tlcfi_log_file: "./tlcfi_with_meta.txt".to_string(),
+                vlog_tlcfi_mapping_file: mapping_file.to_string(),
+                mapping_from_log,
+                output_dir: Some(test_dir.join(output_dir).to_string_lossy().to_string()),
+                verbosity: Verbosity::Quiet,
+                ..Default::default()
+            })
+            .unwrap()
+        };
+
+        let from_log = convert_to("from_log", "", true);
+        let from_file = convert_to("from_file", RELATIVE_VLOG_MAPPING_FILE_PATH, false);
+        let vlog_from_log = read_to_string(&from_log.file_names[0]).unwrap();
+        let vlog_from_file = read_to_string(&from_file.file_names[0]).unwrap();
+        let mapping_from_log = read_to_string(
+            test_dir
+                .join("from_log")
+                .join("3031_20211215_120000_mapping_from_log.txt"),
+        )
+        .unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert_eq!(vlog_from_log, vlog_from_file);
+        assert_eq!(from_log.tlc_name, Some("3031".to_string()));
+        assert!(mapping_from_log.starts_with("// TLC\n3031\n\n// Signals\n0, 02\n1, 03\n"));
+        assert!(from_file.warnings.is_empty());
+        assert_eq!(from_file.stats.sessions.len(), 1);
+    }
+
+    #[test]
+    fn a_mapping_file_that_differs_from_the_meta_in_the_log_should_be_warned_about() {
+        let mapping_file = std::env::temp_dir().join("tlcfi_assimilator_meta_mismatch.txt");
+        fs::write(
+            &mapping_file,
+            read_to_string(RELATIVE_VLOG_MAPPING_FILE_PATH)
+                .unwrap()
+                .replace("0, 02\n1, 03\n", "0, 03\n1, 02\n"),
+        )
+        .unwrap();
+        let mapping_file = mapping_file.to_string_lossy().to_string();
+
+        let report = convert(&AppArgs {
+            tlcfi_log_file: "./tlcfi_with_meta.txt".to_string(),
+            vlog_tlcfi_mapping_file: mapping_file.clone(),
+            dry_run: true,
+            verbosity: Verbosity::Quiet,
+            ..Default::default()
+        })
+        .unwrap();
+        let without_meta = convert(&AppArgs {
+            tlcfi_log_file: "./tlcfi_with_amber.txt".to_string(),
+            mapping_from_log: true,
+            dry_run: true,
+            verbosity: Verbosity::Quiet,
+            ..Default::default()
+        });
+        fs::remove_file(&mapping_file).unwrap();
+
+        assert_eq!(
+            report.warnings,
+            vec![format!(
+                "Warning: the mapping file '{}' doesn't match the meta in the log: signal '02' has VLog id 1 in the mapping file and index 0 in the log; signal '03' has VLog id 0 in the mapping file and index 1 in the log.",
+                mapping_file
+            )]
+        );
+        assert_eq!(without_meta.unwrap_err().exit_code, EXIT_CODE_MAPPING_ERROR);
+    }
+
+    #[test]
+    fn without_skipped_lines_no_reject_file_should_be_created() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_no_reject_file");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let reject_file = test_dir.join("rejected.txt");
+        let app_args = AppArgs {
+            tlcfi_log_file: "./tlcfi_with_amber.txt".to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            dry_run: true,
+            verbosity: Verbosity::Quiet,
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            reject_file: Some(reject_file.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let report = convert(&app_args).unwrap();
+        let created = reject_file.exists();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert!(!created);
+        assert_eq!(report.stats.rejected_lines, 0);
+    }
+
+    #[test]
+    fn more_line_errors_than_allowed_should_stop_before_writing() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_max_errors");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let log_file = test_dir.join("corrupted.txt");
+        let log = read_to_string("./tlcfi_with_amber.txt").unwrap();
+        fs::write(
+            &log_file,
+            log.replace("{\"state\":8}", "{\"state\":99}")
+                .replacen("- IN - ", "-IN-", 1),
+        )
+        .unwrap();
+        let app_args = AppArgs {
+            tlcfi_log_file: log_file.to_string_lossy().to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            conversion_mode: ConversionMode::SignalsOnly,
+            verbosity: Verbosity::Quiet,
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            max_errors: Some(1),
+            ..Default::default()
+        };
+
+        let failure = convert(&app_args).unwrap_err();
+        let written_before_failure = test_dir.join("3031_20211215_120000.vlg").exists();
+        let report = convert(&AppArgs {
+            max_errors: Some(2),
+            ..app_args
+        })
+        .unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert_eq!(failure.exit_code, EXIT_CODE_INPUT_ERROR);
+        assert!(failure.message.starts_with(
+            "Error: 2 log line(s) can't be parsed, more than the 1 allowed with --max-errors (1 unsplittable, 0 invalid JSON, 1 unknown state, 0 malformed message). The first of them:\n"
+        ));
+        assert!(failure.message.contains("\"state\":99"));
+        assert!(!written_before_failure);
+        assert_eq!(report.file_names.len(), 1);
+        assert_eq!(report.stats.line_errors.total(), 2);
+    }
+
+    #[test]
+    fn an_appended_log_should_continue_the_timeline_of_the_file() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_append");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let log = read_to_string("./tlcfi_with_amber.txt").unwrap();
+        let later_log_file = test_dir.join("afternoon.txt");
+        fs::write(&later_log_file, log.replace(" 12:00:", " 12:10:")).unwrap();
+        let app_args = AppArgs {
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            conversion_mode: ConversionMode::SignalsOnly,
+            verbosity: Verbosity::Quiet,
+            show_progress: false,
+            ..Default::default()
+        };
+
+        let vlog_file = convert(&AppArgs {
+            tlcfi_log_file: "./tlcfi_with_amber.txt".to_string(),
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..app_args.clone()
+        })
+        .unwrap()
+        .file_names
+        .remove(0);
+        let appending_args = AppArgs {
+            tlcfi_log_file: later_log_file.to_string_lossy().to_string(),
+            append: Some(vlog_file.clone()),
+            ..app_args.clone()
+        };
+        let report = convert(&appending_args).unwrap();
+        let refused = convert(&AppArgs {
+            tlcfi_log_file: "./tlcfi_with_amber.txt".to_string(),
+            ..appending_args
+        });
+        let vlog = read_to_string(&vlog_file).unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        let mut clock = vlog_decoding::MessageClock::default();
+        let times: Vec<NaiveDateTime> = vlog
+            .lines()
+            .filter_map(|message| clock.time_of(message))
+            .collect();
+        assert_eq!(report.file_names, vec![vlog_file]);
+        assert!(times.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(
+            times.last(),
+            Some(&NaiveDate::from_ymd(2021, 12, 15).and_hms(12, 10, 6))
+        );
+        assert_eq!(
+            vlog.lines()
+                .filter(|message| message.starts_with("04"))
+                .count(),
+            1
+        );
+        assert_eq!(
+            vlog.lines()
+                .filter(|message| message.starts_with("01"))
+                .count(),
+            2
+        );
+        assert!(vlog.ends_with("\r\n"));
+        assert_eq!(refused.unwrap_err().exit_code, EXIT_CODE_INPUT_ERROR);
+    }
+
+    #[test]
+    fn a_log_across_the_end_of_summer_time_should_shift_the_time_references() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_time_zone");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let line = |time: &str, ticks: u64, state: u8| {
+            format!("2021-10-31 {},000 INFO  tlcFiMessages:41 - IN - {{\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{{\"ticks\":{},\"update\":[{{\"objects\":{{\"ids\":[\"02\"],\"type\":3}},\"states\":[{{\"state\":{}}}]}}]}}}}", time, ticks, state)
+        };
+        // Newest first, the clock was set back from 03:00 to 02:00 between the last two lines
+        let log_file = test_dir.join("fallback.txt");
+        fs::write(
+            &log_file,
+            [
+                line("02:05:00", 901_000, 3),
+                line("02:59:00", 541_000, 8),
+                line("02:50:00", 1000, 6),
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+        let app_args = AppArgs {
+            tlcfi_log_file: log_file.to_string_lossy().to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            conversion_mode: ConversionMode::SignalsOnly,
+            verbosity: Verbosity::Quiet,
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            time_zone: Some("Europe/Amsterdam".parse().unwrap()),
+            ..Default::default()
+        };
+        let time_references = |file_name: &str| -> Vec<String> {
+            read_to_string(file_name)
+                .unwrap()
+                .lines()
+                .filter(|message| message.starts_with("01"))
+                .map(|message| message.to_string())
+                .collect()
+        };
+
+        let utc_report = convert(&AppArgs {
+            output_utc: true,
+            ..app_args.clone()
+        })
+        .unwrap();
+        let local_report = convert(&app_args).unwrap();
+        let utc_references = time_references(&utc_report.file_names[0]);
+        let local_references = time_references(&local_report.file_names[0]);
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert!(utc_report.file_names[0].ends_with("3031_20211031_005000.vlg"));
+        assert_eq!(
+            utc_references,
+            vec![
+                "012021103100500000",
+                "012021103100590000",
+                "012021103101050000"
+            ]
+        );
+        assert!(local_report.file_names[0].ends_with("3031_20211031_025000.vlg"));
+        assert_eq!(
+            local_references,
+            vec![
+                "012021103102500000",
+                "012021103102590000",
+                "012021103102050000"
+            ]
+        );
+        assert!(utc_report.stats.clock_steps.is_empty());
+        assert!(local_report
+            .warnings
+            .iter()
+            .any(|warning| warning.contains("goes back from")));
+    }
+
+    #[test]
+    fn rotated_logs_should_be_converted_as_one_log_in_the_order_of_their_times() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_rotated_logs");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let app_args = AppArgs {
+            tlcfi_log_file: "./tlcfi_rotated.txt".to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            conversion_mode: ConversionMode::SignalsOnly,
+            verbosity: Verbosity::Quiet,
+            output_template: "{tlc}_{date}_{time}_rotated.{ext}".parse().unwrap(),
+            line_ending: LineEnding::Lf,
+            overwrite: true,
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let report = convert(&app_args).unwrap();
+        let vlog = read_to_string(test_dir.join("3031_20211215_120000_rotated.vlg")).unwrap();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert_eq!(report.stats.lines_read, 6);
+        assert_eq!(
+            vlog,
+            read_to_string("./expected_rotated_vlog_output.vlg").unwrap()
+        );
+    }
+
+    #[test]
+    fn a_killed_checkpointed_conversion_should_resume_to_the_output_of_an_uninterrupted_one() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_checkpoint");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        // The oldest lines of the log, oldest first
+        let log = read_to_string(RELATIVE_TLCFI_FILE_PATH).unwrap();
+        let mut lines: Vec<&str> = log.lines().rev().take(2000).collect();
+        lines.push("");
+        let log_file = test_dir.join("tlcfi.txt");
+        fs::write(&log_file, lines.join("\n")).unwrap();
+        let checkpoint_file = test_dir.join("checkpoint.json");
+        let checkpoint_file = checkpoint_file.to_str().unwrap();
+        let app_args = AppArgs {
+            tlcfi_log_file: log_file.to_string_lossy().to_string(),
+            vlog_tlcfi_mapping_file: RELATIVE_VLOG_MAPPING_FILE_PATH.to_string(),
+            is_chronological: true,
+            verbosity: Verbosity::Quiet,
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let uninterrupted = convert(&app_args).unwrap();
+        let uninterrupted_vlog = read_to_string(&uninterrupted.file_names[0]).unwrap();
+        fs::remove_file(&uninterrupted.file_names[0]).unwrap();
+        let mut checkpoints = 0;
+        let killed = convert_checkpointed(&app_args, checkpoint_file, 500, &mut |_| {
+            checkpoints += 1;
+            checkpoints < 2
+        })
+        .unwrap();
+        // Output written after the last checkpoint, before the conversion was killed
+        let mut vlog_file = OpenOptions::new()
+            .append(true)
+            .open(&killed.file_names[0])
+            .unwrap();
+        write!(vlog_file, "0E7D01").unwrap();
+        drop(vlog_file);
+        let killed_vlog_size = fs::metadata(&killed.file_names[0]).unwrap().len();
+        let other_options = AppArgs {
+            first_tick: Some(1000),
+            ..app_args.clone()
+        };
+        let refused = convert_checkpointed(&other_options, checkpoint_file, 500, &mut |_| true);
+        let resumed = convert_checkpointed(&app_args, checkpoint_file, 500, &mut |_| true).unwrap();
+        let resumed_vlog = read_to_string(&resumed.file_names[0]).unwrap();
+        let is_checkpoint_removed = !std::path::Path::new(checkpoint_file).exists();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert!(refused
+            .unwrap_err()
+            .message
+            .contains("is of a conversion with other options"));
+        assert!(killed_vlog_size < resumed_vlog.len() as u64);
+        assert_eq!(resumed.file_names, killed.file_names);
+        assert_eq!(resumed_vlog, uninterrupted_vlog);
+        assert_eq!(resumed.change_count, uninterrupted.change_count);
+        assert_eq!(resumed.vlog_message_count, uninterrupted.vlog_message_count);
+        assert_eq!(resumed.stats.lines_read, uninterrupted.stats.lines_read);
+        assert_eq!(
+            resumed.stats.signal_change_messages,
+            uninterrupted.stats.signal_change_messages
+        );
+        assert!(is_checkpoint_removed);
     }
 }
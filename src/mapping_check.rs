@@ -0,0 +1,421 @@
+//! Heuristics that detect a VLog TLC FI mapping file that belongs to a different intersection than the TLC-FI log.
+
+use std::collections::{HashMap, HashSet};
+
+use tlcfi_assimilator::TimestampedChanges;
+
+/// The names of all signals and detectors that appear in a set of changes.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ObservedNames {
+    pub signals: HashSet<String>,
+    pub detectors: HashSet<String>,
+}
+
+/// Collects the names of all signals and detectors in the given changes.
+pub fn collect_observed_names(changes: &[TimestampedChanges]) -> ObservedNames {
+    let mut observed_names = ObservedNames::default();
+    for change in changes {
+        observed_names
+            .signals
+            .extend(change.signal_names.iter().cloned());
+        observed_names
+            .detectors
+            .extend(change.detector_names.iter().cloned());
+    }
+    observed_names
+}
+
+/// The observed names that don't appear in the mappings, sorted.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct UnmappedNames {
+    pub signals: Vec<String>,
+    pub detectors: Vec<String>,
+}
+
+/// Finds the observed names that can't be transformed into VLog, because they're missing from the mappings.
+pub fn find_unmapped_names(
+    observed_names: &ObservedNames,
+    signal_mapping: &HashMap<String, i16>,
+    detector_mapping: &HashMap<String, i16>,
+) -> UnmappedNames {
+    let find_unmapped = |names: &HashSet<String>, mapping: &HashMap<String, i16>| {
+        let mut unmapped: Vec<String> = names
+            .iter()
+            .filter(|name| !mapping.contains_key(*name))
+            .cloned()
+            .collect();
+        unmapped.sort();
+        unmapped
+    };
+    UnmappedNames {
+        signals: find_unmapped(&observed_names.signals, signal_mapping),
+        detectors: find_unmapped(&observed_names.detectors, detector_mapping),
+    }
+}
+
+/// Returns the reason the mappings seem to belong to another intersection, or None if they look plausible.
+///
+/// They don't match if fewer than `min_mapped_fraction` of the observed names are mapped,
+/// or if none of the observed detector name prefixes (e.g. `D` for `D611`) appear in the detector mapping.
+pub fn find_mapping_mismatch(
+    observed_names: &ObservedNames,
+    signal_mapping: &HashMap<String, i16>,
+    detector_mapping: &HashMap<String, i16>,
+    min_mapped_fraction: f64,
+) -> Option<String> {
+    let observed_amount = observed_names.signals.len() + observed_names.detectors.len();
+    if observed_amount == 0 {
+        return None;
+    }
+
+    let mapped_amount = observed_names
+        .signals
+        .iter()
+        .filter(|name| signal_mapping.contains_key(*name))
+        .count()
+        + observed_names
+            .detectors
+            .iter()
+            .filter(|name| detector_mapping.contains_key(*name))
+            .count();
+    let mapped_fraction = mapped_amount as f64 / observed_amount as f64;
+    if mapped_fraction < min_mapped_fraction {
+        return Some(format!(
+            "only {} of the {} signals and detectors in the log are mapped ({:.0}%, expected at least {:.0}%)",
+            mapped_amount,
+            observed_amount,
+            mapped_fraction * 100.0,
+            min_mapped_fraction * 100.0
+        ));
+    }
+
+    let observed_prefixes: HashSet<&str> = observed_names
+        .detectors
+        .iter()
+        .map(|name| name_prefix(name))
+        .collect();
+    let mapped_prefixes: HashSet<&str> = detector_mapping.keys().map(|name| name_prefix(name)).collect();
+    if !observed_prefixes.is_empty()
+        && !mapped_prefixes.is_empty()
+        && observed_prefixes.is_disjoint(&mapped_prefixes)
+    {
+        let mut observed_prefixes: Vec<&str> = observed_prefixes.into_iter().collect();
+        observed_prefixes.sort_unstable();
+        return Some(format!(
+            "none of the detector name prefixes in the log ({}) appear in the mapping",
+            observed_prefixes.join(", ")
+        ));
+    }
+
+    None
+}
+
+/// Finds the names that are in both the signal and the detector mapping after normalization, sorted.
+/// The object type of a message decides which mapping is used for it, but such a name is most likely a typo.
+pub fn find_ambiguous_names(
+    signal_mapping: &HashMap<String, i16>,
+    detector_mapping: &HashMap<String, i16>,
+) -> Vec<String> {
+    let mut ambiguous_names: Vec<String> = signal_mapping
+        .keys()
+        .filter(|name| detector_mapping.contains_key(*name))
+        .cloned()
+        .collect();
+    ambiguous_names.sort();
+    ambiguous_names
+}
+
+/// A jump in the VLog ids of a mapping that is larger than allowed, e.g. from id 11 to id 60.
+#[derive(Debug, PartialEq, Eq)]
+pub struct IdGap {
+    /// The id before the jump, None when the ids start with it
+    pub from_id: Option<i16>,
+    pub to_id: i16,
+}
+
+impl IdGap {
+    /// Describes the gap for warnings, e.g. `jump from 11 to 60` or `start at 20`.
+    pub fn describe(&self) -> String {
+        match self.from_id {
+            Some(from_id) => format!("jump from {} to {}", from_id, self.to_id),
+            None => format!("start at {}", self.to_id),
+        }
+    }
+}
+
+/// Finds the jumps between consecutive VLog ids that skip more than `max_gap` ids. Ids are expected to start at 0,
+/// so a first id above `max_gap` is a gap at the start.
+pub fn find_id_gaps(mapping: &HashMap<String, i16>, max_gap: u16) -> Vec<IdGap> {
+    let mut ids: Vec<i16> = mapping.values().cloned().collect();
+    ids.sort_unstable();
+    ids.dedup();
+
+    let mut gaps = Vec::new();
+    let mut previous_id = None;
+    for id in ids {
+        // The ids before the first one are skipped as well
+        let skipped_ids =
+            i32::from(id) - previous_id.map_or(0, |previous_id| i32::from(previous_id) + 1);
+        if skipped_ids > i32::from(max_gap) {
+            gaps.push(IdGap {
+                from_id: previous_id,
+                to_id: id,
+            });
+        }
+        previous_id = Some(id);
+    }
+    gaps
+}
+
+/// Numbers the VLog ids from 0 without gaps, keeping their order.
+pub fn renumber_densely(mapping: &HashMap<String, i16>) -> HashMap<String, i16> {
+    let mut names: Vec<(&String, &i16)> = mapping.iter().collect();
+    names.sort_by(|(name1, id1), (name2, id2)| id1.cmp(id2).then(name1.cmp(name2)));
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(new_id, (name, _))| (name.clone(), new_id as i16))
+        .collect()
+}
+
+/// Formats mappings the way a VLog TLC FI mapping file is written, so it can be loaded again.
+pub fn format_mapping_file(
+    tlc_name: &str,
+    signal_mapping: &HashMap<String, i16>,
+    detector_mapping: &HashMap<String, i16>,
+) -> String {
+    let mut contents = format!("// TLC\n{}\n", tlc_name);
+    for (mapping_type, mapping) in
+        [("Signals", signal_mapping), ("Detectors", detector_mapping)].iter()
+    {
+        if mapping.is_empty() {
+            continue;
+        }
+        let mut entries: Vec<(&String, &i16)> = mapping.iter().collect();
+        entries.sort_by_key(|(_, id)| **id);
+        contents.push_str(&format!("\n// {}\n", mapping_type));
+        for (name, id) in entries {
+            contents.push_str(&format!("{}, {}\n", id, name));
+        }
+    }
+    contents
+}
+
+/// The leading non-digit part of a name, e.g. `Drk` for `Drk481`.
+fn name_prefix(name: &str) -> &str {
+    let prefix_end = name
+        .find(|character: char| character.is_ascii_digit())
+        .unwrap_or(name.len());
+    &name[..prefix_end]
+}
+
+/// Parses a fraction between 0 and 1, e.g. `0.9`.
+pub fn parse_fraction(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        _ => Err(format!(
+            "Failed to transform argument {} into a fraction, expected a number between 0 and 1",
+            arg
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use tlcfi_assimilator::{vlog_transformer, DetectorState, SignalState};
+
+    fn to_mapping(names: &[&str]) -> HashMap<String, i16> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| (name.to_string(), id as i16))
+            .collect()
+    }
+
+    fn to_observed_names(signals: &[&str], detectors: &[&str]) -> ObservedNames {
+        ObservedNames {
+            signals: signals.iter().map(|name| name.to_string()).collect(),
+            detectors: detectors.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn collecting_observed_names_should_gather_signals_and_detectors_once() {
+        let changes = vec![
+            TimestampedChanges {
+                signal_names: vec!["02".to_string(), "03".to_string()],
+                signal_states: vec![SignalState::Red, SignalState::Red],
+                ..Default::default()
+            },
+            TimestampedChanges {
+                signal_names: vec!["02".to_string()],
+                signal_states: vec![SignalState::Green],
+                ..Default::default()
+            },
+            TimestampedChanges {
+                detector_names: vec!["D611".to_string()],
+                detector_states: vec![DetectorState::FREE],
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(
+            collect_observed_names(&changes),
+            to_observed_names(&["02", "03"], &["D611"])
+        );
+    }
+
+    #[test]
+    fn a_fully_mapped_log_should_not_be_a_mismatch() {
+        let observed_names = to_observed_names(&["02", "03"], &["D611"]);
+
+        let mismatch = find_mapping_mismatch(
+            &observed_names,
+            &to_mapping(&["02", "03", "04"]),
+            &to_mapping(&["D611", "D612"]),
+            0.9,
+        );
+
+        assert_eq!(mismatch, None);
+    }
+
+    #[test]
+    fn too_few_mapped_names_should_be_a_mismatch() {
+        let observed_names = to_observed_names(&["02", "03", "31", "32"], &["D611"]);
+
+        let mismatch = find_mapping_mismatch(
+            &observed_names,
+            &to_mapping(&["02", "03"]),
+            &to_mapping(&["D611"]),
+            0.9,
+        );
+
+        assert!(mismatch.unwrap().starts_with("only 3 of the 5"));
+    }
+
+    #[test]
+    fn disjoint_detector_prefixes_should_be_a_mismatch() {
+        let observed_names = to_observed_names(&["02"], &["DK611", "DK612"]);
+
+        let mismatch = find_mapping_mismatch(
+            &observed_names,
+            &to_mapping(&["02"]),
+            &to_mapping(&["D611", "D612"]),
+            0.0,
+        );
+
+        assert!(mismatch.unwrap().contains("(DK)"));
+    }
+
+    #[test]
+    fn finding_unmapped_names_should_return_the_sorted_missing_names_per_type() {
+        let observed_names = to_observed_names(&["32", "02", "31"], &["D611", "D999"]);
+
+        let unmapped_names = find_unmapped_names(
+            &observed_names,
+            &to_mapping(&["02"]),
+            &to_mapping(&["D611"]),
+        );
+
+        assert_eq!(
+            unmapped_names,
+            UnmappedNames {
+                signals: vec!["31".to_string(), "32".to_string()],
+                detectors: vec!["D999".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn name_prefix_should_stop_at_the_first_digit() {
+        assert_eq!(name_prefix("Drk481"), "Drk");
+        assert_eq!(name_prefix("D611"), "D");
+        assert_eq!(name_prefix("02"), "");
+    }
+
+    #[test]
+    fn parse_fraction_outside_of_0_and_1_should_return_err() {
+        assert_eq!(parse_fraction("0.5"), Ok(0.5));
+        assert!(parse_fraction("1.5").is_err());
+        assert!(parse_fraction("-0.1").is_err());
+    }
+
+    #[test]
+    fn a_jump_larger_than_the_max_gap_should_be_found() {
+        let mapping: HashMap<String, i16> = [
+            ("02".to_string(), 0),
+            ("03".to_string(), 1),
+            ("04".to_string(), 11),
+            ("05".to_string(), 60),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        assert_eq!(
+            find_id_gaps(&mapping, 10),
+            vec![IdGap {
+                from_id: Some(11),
+                to_id: 60
+            }]
+        );
+        assert!(find_id_gaps(&mapping, 48).is_empty());
+    }
+
+    #[test]
+    fn ids_that_start_late_should_be_a_gap_at_the_start() {
+        let mapping: HashMap<String, i16> = [("02".to_string(), 20), ("03".to_string(), 21)]
+            .iter()
+            .cloned()
+            .collect();
+
+        let gaps = find_id_gaps(&mapping, 10);
+
+        assert_eq!(
+            gaps,
+            vec![IdGap {
+                from_id: None,
+                to_id: 20
+            }]
+        );
+        assert_eq!(gaps[0].describe(), "start at 20");
+        assert!(find_id_gaps(&mapping, 20).is_empty());
+    }
+
+    #[test]
+    fn renumbering_densely_should_keep_the_order_of_the_ids() {
+        let mapping: HashMap<String, i16> = [("D611".to_string(), 60), ("D612".to_string(), 11)]
+            .iter()
+            .cloned()
+            .collect();
+
+        let renumbered = renumber_densely(&mapping);
+
+        assert_eq!(renumbered["D612"], 0);
+        assert_eq!(renumbered["D611"], 1);
+        assert_eq!(
+            format_mapping_file("3031", &HashMap::new(), &renumbered),
+            "// TLC\n3031\n\n// Detectors\n0, D612\n1, D611\n"
+        );
+    }
+
+    #[test]
+    fn names_in_both_mappings_should_be_ambiguous() {
+        let mapping_file = std::env::temp_dir().join("tlcfi_assimilator_ambiguous_mapping.txt");
+        fs::write(
+            &mapping_file,
+            "// TLC\n3031\n\n// Signals\n0, 08\n1, 09\n\n// Detectors\n0,  08\n1, D611\n",
+        )
+        .unwrap();
+        let load = |mapping_type| {
+            vlog_transformer::load_mappings(&mapping_file.to_string_lossy(), mapping_type).unwrap()
+        };
+
+        let ambiguous_names = find_ambiguous_names(&load("Signals"), &load("Detectors"));
+        fs::remove_file(&mapping_file).unwrap();
+
+        assert_eq!(ambiguous_names, vec!["08".to_string()]);
+    }
+}
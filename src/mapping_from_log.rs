@@ -0,0 +1,126 @@
+//! Takes the mapping from the `ReadMeta` responses in a log instead of from a mapping file, for `--mapping-from-log`,
+//! and checks a mapping file against that meta when there are both.
+
+use std::collections::BTreeSet;
+
+use tlcfi_assimilator::TlcMeta;
+
+use crate::mapping_check;
+
+/// Formats the meta the way a mapping file is written, with the indices as VLog ids.
+pub fn format_mapping_file(meta: &TlcMeta, tlc_name: &str) -> String {
+    let mut contents = mapping_check::format_mapping_file(tlc_name, &meta.signals, &meta.detectors);
+    if !meta.outputs.is_empty() {
+        let mut outputs: Vec<(&String, &i16)> = meta.outputs.iter().collect();
+        outputs.sort_by_key(|(_, index)| **index);
+        contents.push_str("\n// Outputs\n");
+        for (name, index) in outputs {
+            contents.push_str(&format!("{}, {}\n", index, name));
+        }
+    }
+    contents
+}
+
+/// Describes how the mapping file, read into a [TlcMeta], differs from the meta in the log.
+/// The object types the log has no meta of aren't compared, a log can have the meta of only some of them.
+pub fn find_meta_mismatches(meta: &TlcMeta, mapping: &TlcMeta) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    if let (Some(meta_name), Some(mapping_name)) = (&meta.tlc_name, &mapping.tlc_name) {
+        if meta_name != mapping_name {
+            mismatches.push(format!(
+                "the TLC is named '{}' in the mapping file and '{}' in the log",
+                mapping_name, meta_name
+            ));
+        }
+    }
+    let object_types = [
+        ("signal", &meta.signals, &mapping.signals),
+        ("detector", &meta.detectors, &mapping.detectors),
+        ("output", &meta.outputs, &mapping.outputs),
+    ];
+    for (object_type, indices, vlog_ids) in object_types.iter() {
+        if indices.is_empty() {
+            continue;
+        }
+        let names: BTreeSet<&String> = indices.keys().chain(vlog_ids.keys()).collect();
+        for name in names {
+            match (vlog_ids.get(name), indices.get(name)) {
+                (Some(vlog_id), Some(index)) if vlog_id != index => mismatches.push(format!(
+                    "{} '{}' has VLog id {} in the mapping file and index {} in the log",
+                    object_type, name, vlog_id, index
+                )),
+                (None, Some(_)) => mismatches.push(format!(
+                    "{} '{}' is in the log but not in the mapping file",
+                    object_type, name
+                )),
+                (Some(_), None) => mismatches.push(format!(
+                    "{} '{}' is in the mapping file but not in the log",
+                    object_type, name
+                )),
+                _ => {}
+            }
+        }
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+    use tlcfi_assimilator::vlog_transformer;
+
+    fn to_mapping(entries: &[(&str, i16)]) -> HashMap<String, i16> {
+        entries
+            .iter()
+            .map(|(name, id)| (name.to_string(), *id))
+            .collect()
+    }
+
+    #[test]
+    fn the_mapping_of_the_meta_should_be_read_back_with_the_indices_as_ids() {
+        let meta = TlcMeta {
+            tlc_name: Some("3031".to_string()),
+            signals: to_mapping(&[("03", 1), ("02", 0)]),
+            detectors: to_mapping(&[("D611", 0)]),
+            outputs: to_mapping(&[("U01", 4)]),
+        };
+
+        let contents = format_mapping_file(&meta, "3031");
+
+        assert_eq!(
+            contents,
+            "// TLC\n3031\n\n// Signals\n0, 02\n1, 03\n\n// Detectors\n0, D611\n\n// Outputs\n4, U01\n"
+        );
+        assert_eq!(
+            vlog_transformer::read_mappings(contents.as_bytes(), "Outputs").unwrap(),
+            meta.outputs
+        );
+    }
+
+    #[test]
+    fn differences_with_the_mapping_file_should_be_described_per_object() {
+        let meta = TlcMeta {
+            tlc_name: Some("3031".to_string()),
+            signals: to_mapping(&[("02", 0), ("03", 1), ("04", 2)]),
+            ..Default::default()
+        };
+        let mapping = TlcMeta {
+            tlc_name: Some("3032".to_string()),
+            signals: to_mapping(&[("02", 0), ("03", 2), ("05", 3)]),
+            detectors: to_mapping(&[("D611", 0)]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            find_meta_mismatches(&meta, &mapping),
+            vec![
+                "the TLC is named '3032' in the mapping file and '3031' in the log",
+                "signal '03' has VLog id 2 in the mapping file and index 1 in the log",
+                "signal '04' is in the log but not in the mapping file",
+                "signal '05' is in the mapping file but not in the log",
+            ]
+        );
+        assert!(find_meta_mismatches(&meta, &meta).is_empty());
+    }
+}
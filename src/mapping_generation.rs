@@ -0,0 +1,119 @@
+//! Generates a VLog TLC FI mapping file skeleton from the names in a TLC-FI log, for `--generate-mapping`.
+
+use std::collections::HashMap;
+
+use tlcfi_assimilator::TimestampedChanges;
+
+use crate::mapping_check;
+
+/// The TLC name written in a generated mapping file, which the user replaces with the real one.
+pub const PLACEHOLDER_TLC_NAME: &str = "EDIT_TLC_NAME";
+
+/// The names of the signals and detectors in a log, each once, in the order they first appear.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct NamesInOrder {
+    pub signals: Vec<String>,
+    pub detectors: Vec<String>,
+}
+
+/// Collects the names of the signals and detectors in the order of their first change,
+/// and within a set of changes in the order of the message.
+pub fn collect_names_in_order(changes: &[TimestampedChanges]) -> NamesInOrder {
+    let mut names_in_order = NamesInOrder::default();
+    for change in changes {
+        for name in &change.signal_names {
+            if !names_in_order.signals.contains(name) {
+                names_in_order.signals.push(name.clone());
+            }
+        }
+        for name in &change.detector_names {
+            if !names_in_order.detectors.contains(name) {
+                names_in_order.detectors.push(name.clone());
+            }
+        }
+    }
+    names_in_order
+}
+
+/// Formats a mapping file with the [PLACEHOLDER_TLC_NAME](constant.PLACEHOLDER_TLC_NAME.html) and sequential VLog ids
+/// from 0 for the signals and the detectors, in the order of the names.
+pub fn generate_mapping_file(names_in_order: &NamesInOrder) -> String {
+    let to_mapping = |names: &[String]| -> HashMap<String, i16> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| (name.clone(), id as i16))
+            .collect()
+    };
+    mapping_check::format_mapping_file(
+        PLACEHOLDER_TLC_NAME,
+        &to_mapping(&names_in_order.signals),
+        &to_mapping(&names_in_order.detectors),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tlcfi_assimilator::{vlog_transformer, DetectorState, SignalState};
+
+    fn to_strings(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn names_should_be_ordered_by_their_first_appearance_and_not_by_name() {
+        let changes = vec![
+            TimestampedChanges {
+                signal_names: to_strings(&["08", "02"]),
+                signal_states: vec![SignalState::Red, SignalState::Red],
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 100,
+                detector_names: to_strings(&["D612", "D611"]),
+                detector_states: vec![DetectorState::FREE, DetectorState::FREE],
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 200,
+                signal_names: to_strings(&["02", "05", "08"]),
+                signal_states: vec![SignalState::Green, SignalState::Red, SignalState::Green],
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(
+            collect_names_in_order(&changes),
+            NamesInOrder {
+                signals: to_strings(&["08", "02", "05"]),
+                detectors: to_strings(&["D612", "D611"]),
+            }
+        );
+    }
+
+    #[test]
+    fn a_generated_mapping_file_should_be_read_back_with_sequential_ids() {
+        let names_in_order = NamesInOrder {
+            signals: to_strings(&["08", "02"]),
+            detectors: to_strings(&["D611"]),
+        };
+
+        let contents = generate_mapping_file(&names_in_order);
+
+        assert_eq!(
+            contents,
+            "// TLC\nEDIT_TLC_NAME\n\n// Signals\n0, 08\n1, 02\n\n// Detectors\n0, D611\n"
+        );
+        assert_eq!(
+            vlog_transformer::read_tlc_name(contents.as_bytes()),
+            Some(PLACEHOLDER_TLC_NAME.to_string())
+        );
+        let signal_mapping =
+            vlog_transformer::read_mappings(contents.as_bytes(), "Signals").unwrap();
+        assert_eq!((signal_mapping["08"], signal_mapping["02"]), (0, 1));
+        let detector_mapping =
+            vlog_transformer::read_mappings(contents.as_bytes(), "Detectors").unwrap();
+        assert_eq!(detector_mapping["D611"], 0);
+    }
+}
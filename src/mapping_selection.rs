@@ -0,0 +1,160 @@
+//! Picks the mapping file of a log from a directory with a mapping file per TLC, for `--mapping-file DIR`.
+//!
+//! The TLC names of the mapping files are matched against the names the log could be for: the `--tlc-name` hint
+//! when it's given, otherwise the parts of the log's path, like `3031` in `logs/3031/tlcfi.txt`.
+
+use std::{fs, path::Path};
+
+use tlcfi_assimilator::vlog_transformer;
+
+/// The TLC names a log could be for, the hint or the alphanumeric parts of the path of the log.
+pub fn tlc_name_candidates(tlc_name_hint: Option<&str>, tlcfi_log_file: &str) -> Vec<String> {
+    match tlc_name_hint {
+        Some(tlc_name) => vec![tlc_name.to_string()],
+        None => tlcfi_log_file
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|part| !part.is_empty())
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+/// Returns the only mapping file in the directory whose TLC name is one of the candidates. Fails with the available
+/// TLC names when no file or more than one file matches.
+pub fn select_mapping_file(mapping_dir: &str, candidates: &[String]) -> Result<String, String> {
+    let mut mapping_files: Vec<(String, String)> = fs::read_dir(mapping_dir)
+        .map_err(|error| {
+            format!(
+                "failed to read the mapping directory '{}': {}",
+                mapping_dir, error
+            )
+        })?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let file_name = path.to_string_lossy().to_string();
+            match vlog_transformer::load_tlc_name(&file_name) {
+                Ok(Some(tlc_name)) => Some((tlc_name, file_name)),
+                _ => None,
+            }
+        })
+        .collect();
+    mapping_files.sort();
+
+    let available_names = || {
+        let names: Vec<&str> = mapping_files
+            .iter()
+            .map(|(tlc_name, _)| tlc_name.as_str())
+            .collect();
+        if names.is_empty() {
+            "none".to_string()
+        } else {
+            names.join(", ")
+        }
+    };
+    let matches: Vec<&(String, String)> = mapping_files
+        .iter()
+        .filter(|(tlc_name, _)| {
+            candidates
+                .iter()
+                .any(|candidate| candidate.eq_ignore_ascii_case(tlc_name))
+        })
+        .collect();
+    match matches.as_slice() {
+        [(_, file_name)] => Ok(file_name.clone()),
+        [] => Err(format!(
+            "none of the mapping files in '{}' is for the TLC of the log, give its name with --tlc-name. The available TLC names are: {}",
+            mapping_dir,
+            available_names()
+        )),
+        _ => {
+            let file_names: Vec<String> = matches
+                .iter()
+                .map(|(tlc_name, file_name)| {
+                    let file_name = Path::new(file_name)
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    format!("{} ({})", file_name, tlc_name)
+                })
+                .collect();
+            Err(format!(
+                "more than one mapping file in '{}' matches the log: {}. The available TLC names are: {}",
+                mapping_dir,
+                file_names.join(", "),
+                available_names()
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write_mapping_dir(name: &str, tlc_names: &[(&str, &str)]) -> std::path::PathBuf {
+        let mapping_dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&mapping_dir);
+        fs::create_dir_all(&mapping_dir).unwrap();
+        let mapping = fs::read_to_string("./vlog_tlcfi_mapping.txt").unwrap();
+        for (file_name, tlc_name) in tlc_names {
+            fs::write(
+                mapping_dir.join(file_name),
+                mapping.replacen("3031", tlc_name, 1),
+            )
+            .unwrap();
+        }
+        mapping_dir
+    }
+
+    #[test]
+    fn the_mapping_file_of_the_tlc_of_the_log_should_be_selected() {
+        let mapping_dir = write_mapping_dir(
+            "tlcfi_assimilator_mapping_selection",
+            &[("a.txt", "3031"), ("b.txt", "3032"), ("c.txt", "3033")],
+        );
+        let mapping_dir_name = mapping_dir.to_string_lossy().to_string();
+
+        let by_hint = select_mapping_file(
+            &mapping_dir_name,
+            &tlc_name_candidates(Some("3032"), "tlcfi.txt"),
+        );
+        let by_path = select_mapping_file(
+            &mapping_dir_name,
+            &tlc_name_candidates(None, "logs/3033/tlcfi.txt"),
+        );
+        let without_match =
+            select_mapping_file(&mapping_dir_name, &tlc_name_candidates(None, "tlcfi.txt"));
+        fs::remove_dir_all(&mapping_dir).unwrap();
+
+        assert_eq!(
+            by_hint,
+            Ok(mapping_dir.join("b.txt").to_string_lossy().to_string())
+        );
+        assert_eq!(
+            by_path,
+            Ok(mapping_dir.join("c.txt").to_string_lossy().to_string())
+        );
+        assert!(without_match
+            .unwrap_err()
+            .ends_with("The available TLC names are: 3031, 3032, 3033"));
+    }
+
+    #[test]
+    fn more_than_one_matching_mapping_file_should_be_rejected() {
+        let mapping_dir = write_mapping_dir(
+            "tlcfi_assimilator_ambiguous_mapping_selection",
+            &[("a.txt", "3031"), ("b.txt", "3031"), ("c.txt", "3033")],
+        );
+
+        let selection = select_mapping_file(
+            &mapping_dir.to_string_lossy(),
+            &tlc_name_candidates(Some("3031"), "tlcfi.txt"),
+        );
+        fs::remove_dir_all(&mapping_dir).unwrap();
+
+        assert!(selection
+            .unwrap_err()
+            .contains("matches the log: a.txt (3031), b.txt (3031)."));
+    }
+}
@@ -0,0 +1,163 @@
+//! Checks a VLog TLC FI mapping file for structural problems, for `--validate-mapping`.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead},
+};
+
+use tlcfi_assimilator::vlog_transformer::{self, MappingSection};
+
+/// The mapping types that a mapping file has a section for.
+const MAPPING_TYPES: [&str; 2] = ["Signals", "Detectors"];
+/// The mapping types that a mapping file may have a section for, which are only checked when it's there.
+const OPTIONAL_MAPPING_TYPES: [&str; 1] = ["Outputs"];
+
+/// Reads the whole mapping file and returns all of its structural problems, in the order of the checks.
+///
+/// It finds missing TLC, Signals and Detectors sections, rows that aren't a VLog id and a TLC FI name,
+/// rows with more than two fields, VLog ids that don't fit in a byte, duplicate names and ids within a section
+/// and rows that aren't read because they follow an empty line. The optional Outputs section is checked the same
+/// way when the file has one.
+pub fn validate_mapping(content: &str) -> io::Result<Vec<String>> {
+    let mut problems = find_tlc_problems(content.as_bytes())?;
+    for mapping_type in MAPPING_TYPES.iter() {
+        let section = vlog_transformer::read_mapping_section(content.as_bytes(), mapping_type)?;
+        problems.extend(find_section_problems(&section, mapping_type));
+    }
+    for mapping_type in OPTIONAL_MAPPING_TYPES.iter() {
+        let section = vlog_transformer::read_mapping_section(content.as_bytes(), mapping_type)?;
+        if section.header_line_number.is_some() {
+            problems.extend(find_section_problems(&section, mapping_type));
+        }
+    }
+    Ok(problems)
+}
+
+/// Checks that there is a comment containing `TLC` with the TLC name on the next line that isn't empty.
+fn find_tlc_problems<R: BufRead>(reader: R) -> io::Result<Vec<String>> {
+    let mut header_line_number = None;
+    for (line_index, line) in reader.lines().enumerate() {
+        let read_line = line?.trim().to_string();
+        match header_line_number {
+            None if read_line.contains("//") && read_line.contains("TLC") => {
+                header_line_number = Some(line_index + 1)
+            }
+            None => {}
+            Some(_) if read_line.is_empty() => {}
+            Some(header_line_number) if read_line.contains("//") => {
+                return Ok(vec![format!(
+                    "line {}: the TLC section has no TLC name",
+                    header_line_number
+                )])
+            }
+            Some(_) => return Ok(Vec::new()),
+        }
+    }
+    Ok(vec![match header_line_number {
+        Some(header_line_number) => format!(
+            "line {}: the TLC section has no TLC name",
+            header_line_number
+        ),
+        None => "there is no TLC section".to_string(),
+    }])
+}
+
+fn find_section_problems(section: &MappingSection, mapping_type: &str) -> Vec<String> {
+    let header_line_number = match section.header_line_number {
+        Some(header_line_number) => header_line_number,
+        None => return vec![format!("there is no {} section", mapping_type)],
+    };
+    let mut problems = Vec::new();
+    if section.rows.is_empty() && section.problems.is_empty() {
+        problems.push(format!(
+            "line {}: the {} section has no mappings",
+            header_line_number, mapping_type
+        ));
+    }
+    problems.extend(section.problems.iter().cloned());
+
+    let mut lines_of_names: HashMap<&str, usize> = HashMap::new();
+    let mut lines_of_ids: HashMap<i16, usize> = HashMap::new();
+    for row in &section.rows {
+        if row.field_count != 2 {
+            problems.push(format!(
+                "line {}: expected 2 comma separated fields in the {} mappings, got {}",
+                row.line_number, mapping_type, row.field_count
+            ));
+        }
+        if !(0..=i16::from(u8::MAX)).contains(&row.vlog_id) {
+            problems.push(format!(
+                "line {}: VLog id {} in the {} mappings doesn't fit in a byte",
+                row.line_number, row.vlog_id, mapping_type
+            ));
+        }
+        match lines_of_names.get(row.name.as_str()) {
+            Some(first_line_number) => problems.push(format!(
+                "line {}: {} is already mapped on line {} of the {} mappings",
+                row.line_number, row.name, first_line_number, mapping_type
+            )),
+            None => {
+                lines_of_names.insert(&row.name, row.line_number);
+            }
+        }
+        match lines_of_ids.get(&row.vlog_id) {
+            Some(first_line_number) => problems.push(format!(
+                "line {}: VLog id {} is already used on line {} of the {} mappings",
+                row.line_number, row.vlog_id, first_line_number, mapping_type
+            )),
+            None => {
+                lines_of_ids.insert(row.vlog_id, row.line_number);
+            }
+        }
+    }
+
+    for line_number in &section.ignored_line_numbers {
+        problems.push(format!(
+            "line {}: this row isn't read, because an empty line ends the {} mappings before it",
+            line_number, mapping_type
+        ));
+    }
+    problems
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn the_test_mapping_file_should_have_no_problems() {
+        let content = std::fs::read_to_string("./vlog_tlcfi_mapping.txt").unwrap();
+
+        assert_eq!(validate_mapping(&content).unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn every_structural_problem_should_be_reported_with_its_line() {
+        let content = "// TLC\n\n// Signals\n0, 02\n1, 02\n1, 03\n300, 04\n5, 05, x\n\n6, 06\n";
+
+        assert_eq!(
+            validate_mapping(content).unwrap(),
+            vec![
+                "line 1: the TLC section has no TLC name",
+                "line 5: 02 is already mapped on line 4 of the Signals mappings",
+                "line 6: VLog id 1 is already used on line 5 of the Signals mappings",
+                "line 7: VLog id 300 in the Signals mappings doesn't fit in a byte",
+                "line 8: expected 2 comma separated fields in the Signals mappings, got 3",
+                "line 10: this row isn't read, because an empty line ends the Signals mappings before it",
+                "there is no Detectors section",
+            ]
+        );
+    }
+
+    #[test]
+    fn a_file_without_sections_should_miss_all_of_them() {
+        assert_eq!(
+            validate_mapping("3031\n").unwrap(),
+            vec![
+                "there is no TLC section",
+                "there is no Signals section",
+                "there is no Detectors section",
+            ]
+        );
+    }
+}
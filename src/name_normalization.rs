@@ -0,0 +1,44 @@
+//! Normalizes the names of signal groups and detectors, so a name that a TLC sends with stray whitespace
+//! or with decomposed accents still matches its entry in the mapping file.
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Trims the name, turns whitespace such as non-breaking spaces into plain spaces and composes accented letters
+/// (Unicode normalization form C).
+pub fn normalize_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|character| {
+            if character.is_whitespace() {
+                ' '
+            } else {
+                character
+            }
+        })
+        .nfc()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn surrounding_whitespace_should_be_trimmed() {
+        assert_eq!(normalize_name("D712 "), "D712");
+        assert_eq!(normalize_name("\u{a0}D712\t"), "D712");
+    }
+
+    #[test]
+    fn whitespace_within_a_name_should_become_a_plain_space() {
+        assert_eq!(normalize_name("D\u{a0}712"), "D 712");
+    }
+
+    #[test]
+    fn decomposed_accents_should_be_composed() {
+        assert_eq!(normalize_name("Bru\u{308}gge"), "Brügge");
+        assert_eq!(normalize_name("Brügge"), "Brügge");
+        assert_eq!(normalize_name("\u{301}D712"), "\u{301}D712");
+        assert_eq!(normalize_name("Bo\u{323}\u{302}"), "Bộ");
+    }
+}
@@ -0,0 +1,78 @@
+//! Keeps VLog files within the size an archive accepts, by checking the estimated size before writing.
+
+use std::str::FromStr;
+
+use serde::Serialize;
+
+/// What to do with a VLog file that is estimated to be larger than `--max-output-size`.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize)]
+pub enum SizePolicy {
+    /// Stop before writing anything
+    #[default]
+    Abort,
+    /// Write the file gzip compressed
+    Compress,
+    /// Continue in numbered part files, like `--max-messages`
+    Rotate,
+}
+
+impl FromStr for SizePolicy {
+    type Err = String;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        match arg {
+            "abort" => Ok(SizePolicy::Abort),
+            "compress" => Ok(SizePolicy::Compress),
+            "rotate" => Ok(SizePolicy::Rotate),
+            _ => Err(format!(
+                "'{}' isn't a size policy, use abort, compress or rotate",
+                arg
+            )),
+        }
+    }
+}
+
+/// Parses a size in bytes, optionally with a K, M or G suffix for powers of 1024 (e.g. 500M).
+pub fn parse_size(arg: &str) -> Result<u64, String> {
+    let size_error = || {
+        format!(
+            "Failed to transform argument {} into a size, use bytes or a number with K, M or G (e.g. 500M)",
+            arg
+        )
+    };
+    let without_bytes_unit = arg.strip_suffix('B').unwrap_or(arg);
+    let (number, multiplier) = [("K", 1 << 10), ("M", 1 << 20), ("G", 1 << 30)]
+        .iter()
+        .find_map(|(unit, multiplier)| {
+            without_bytes_unit
+                .strip_suffix(unit)
+                .map(|number| (number, *multiplier))
+        })
+        .unwrap_or((without_bytes_unit, 1));
+    match number.parse::<u64>() {
+        Ok(size) if size > 0 => size.checked_mul(multiplier).ok_or_else(size_error),
+        _ => Err(size_error()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parsing_sizes_should_support_units() {
+        assert_eq!(parse_size("1500"), Ok(1500));
+        assert_eq!(parse_size("2K"), Ok(2048));
+        assert_eq!(parse_size("500MB"), Ok(500 * 1024 * 1024));
+        assert_eq!(parse_size("1G"), Ok(1024 * 1024 * 1024));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("5T").is_err());
+    }
+
+    #[test]
+    fn parsing_an_unknown_size_policy_should_return_err() {
+        assert!("truncate".parse::<SizePolicy>().is_err());
+        assert_eq!("rotate".parse::<SizePolicy>(), Ok(SizePolicy::Rotate));
+    }
+}
@@ -0,0 +1,323 @@
+//! Splits the changes over several VLog files, for tools that expect files of a limited length.
+
+use std::str::FromStr;
+
+use chrono::{Duration, NaiveDateTime, Timelike};
+use serde::Serialize;
+use tlcfi_assimilator::{TimestampedChanges, VLogMessageType};
+
+/// The time reference and V-Log information that every VLog file starts with.
+const PREAMBLE_LENGTH: usize = 2;
+
+/// How the VLog output is split over files.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize)]
+pub enum SplitMode {
+    /// Everything goes into a single file
+    #[default]
+    None,
+    /// A new file is started at every hour boundary
+    Hourly,
+}
+
+impl FromStr for SplitMode {
+    type Err = String;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        match arg {
+            "none" => Ok(SplitMode::None),
+            "hourly" => Ok(SplitMode::Hourly),
+            _ => Err(format!(
+                "'{}' isn't a way to split, use none or hourly",
+                arg
+            )),
+        }
+    }
+}
+
+/// The changes for a single VLog file, with their ms relative to the start of the file.
+#[derive(Debug, PartialEq)]
+pub struct OutputPart {
+    pub start_date_time: NaiveDateTime,
+    pub changes: Vec<TimestampedChanges>,
+}
+
+impl OutputPart {
+    /// The ms between the start of the conversion and the start of this part.
+    pub fn offset_ms(&self, start_date_time: &NaiveDateTime) -> u64 {
+        (self.start_date_time - *start_date_time).num_milliseconds() as u64
+    }
+}
+
+/// Splits the changes into parts according to the [SplitMode](enum.SplitMode.html). There is always at least one part.
+pub fn split_changes(
+    changes: Vec<TimestampedChanges>,
+    start_date_time: &NaiveDateTime,
+    split_mode: SplitMode,
+) -> Vec<OutputPart> {
+    match split_mode {
+        SplitMode::None => vec![OutputPart {
+            start_date_time: *start_date_time,
+            changes,
+        }],
+        SplitMode::Hourly => split_changes_hourly(changes, start_date_time),
+    }
+}
+
+/// Puts the changes of every wall-clock hour in their own part, starting at the hour boundary.
+/// Changes exactly on a boundary belong to the new hour. Hours without changes don't get a part.
+fn split_changes_hourly(
+    changes: Vec<TimestampedChanges>,
+    start_date_time: &NaiveDateTime,
+) -> Vec<OutputPart> {
+    let mut parts: Vec<OutputPart> = Vec::new();
+    for mut change in changes {
+        let change_date_time =
+            *start_date_time + Duration::milliseconds(change.ms_from_beginning as i64);
+        // The first hour starts at the start of the conversion rather than at its boundary
+        let part_start = start_of_hour(&change_date_time).max(*start_date_time);
+        if parts.last().map(|part| part.start_date_time) != Some(part_start) {
+            parts.push(OutputPart {
+                start_date_time: part_start,
+                changes: Vec::new(),
+            });
+        }
+        let part = parts.last_mut().expect("A part was just added");
+        change.ms_from_beginning -= part.offset_ms(start_date_time);
+        part.changes.push(change);
+    }
+
+    if parts.is_empty() {
+        parts.push(OutputPart {
+            start_date_time: *start_date_time,
+            changes: Vec::new(),
+        });
+    }
+    parts
+}
+
+/// Parses the maximum number of messages per file, which has to leave room for the preamble and a change.
+pub fn parse_max_messages(arg: &str) -> Result<usize, String> {
+    match arg.parse::<usize>() {
+        Ok(max_messages) if max_messages > PREAMBLE_LENGTH => Ok(max_messages),
+        _ => Err(format!(
+            "Failed to transform argument {} into a maximum number of messages, expected a number above {}",
+            arg, PREAMBLE_LENGTH
+        )),
+    }
+}
+
+/// Splits the VLog messages of a file over files of at most `max_messages` messages, time references included.
+///
+/// Every continuation starts with the time reference in effect and the V-Log information of the first file,
+/// so the deltas of its changes stay valid. A time reference never ends a full file, it moves to the next one instead.
+pub fn split_messages(messages: Vec<String>, max_messages: usize) -> Vec<Vec<String>> {
+    if messages.len() <= max_messages || messages.len() < PREAMBLE_LENGTH {
+        return vec![messages];
+    }
+    let time_reference_code = format!("{:02X}", VLogMessageType::TimeReference.code());
+    let mut time_reference = messages[0].clone();
+    let vlog_info = messages[1].clone();
+    let mut files: Vec<Vec<String>> = vec![Vec::new()];
+    for message in messages {
+        let full_file = files.last_mut().expect("There is always a file");
+        if full_file.len() >= max_messages {
+            if full_file.len() > PREAMBLE_LENGTH
+                && matches!(full_file.last(), Some(last) if last.starts_with(&time_reference_code))
+            {
+                full_file.pop();
+            }
+            files.push(vec![time_reference.clone(), vlog_info.clone()]);
+        }
+        let is_time_reference = message.starts_with(&time_reference_code);
+        if is_time_reference {
+            time_reference = message.clone();
+        }
+        let is_continuation = files.len() > 1;
+        let file = files.last_mut().expect("There is always a file");
+        if is_time_reference && is_continuation && file.len() == PREAMBLE_LENGTH {
+            // A continuation doesn't need the earlier reference when it starts with a new one
+            file[0] = message;
+        } else {
+            file.push(message);
+        }
+    }
+    files
+}
+
+/// The most messages per file that keeps every file within `max_size` bytes, going by the longest message.
+/// There's always room for the preamble and a change, even if that exceeds the size.
+pub fn max_messages_within_size(messages: &[String], max_size: u64) -> usize {
+    let longest_line = messages
+        .iter()
+        .map(|message| message.len() as u64 + 2)
+        .max()
+        .unwrap_or(1);
+    ((max_size / longest_line) as usize).max(PREAMBLE_LENGTH + 1)
+}
+
+/// Adds `_part2`, `_part3` etc. to the file name of every file after the first.
+pub fn part_file_name(file_name: &str, part_index: usize) -> String {
+    if part_index == 0 {
+        return file_name.to_string();
+    }
+    match file_name.strip_suffix(".vlg") {
+        Some(stem) => format!("{}_part{}.vlg", stem, part_index + 1),
+        None => format!("{}_part{}", file_name, part_index + 1),
+    }
+}
+
+fn start_of_hour(date_time: &NaiveDateTime) -> NaiveDateTime {
+    date_time.date().and_hms(date_time.hour(), 0, 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_test_start_date_time() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2021-12-15T11:30:00.000", "%FT%T%.3f")
+            .expect("Use a valid time stamp for tests!")
+    }
+
+    fn change_at(ms_from_beginning: u64) -> TimestampedChanges {
+        TimestampedChanges {
+            ms_from_beginning,
+            signal_names: vec!["02".to_string()],
+            signal_states: vec![tlcfi_assimilator::SignalState::Green],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn splitting_hourly_should_start_a_part_at_every_crossed_hour_boundary() {
+        let half_an_hour_ms = 30 * 60 * 1000;
+        let changes = vec![
+            change_at(1000),
+            change_at(half_an_hour_ms - 1),
+            change_at(half_an_hour_ms),
+            change_at(half_an_hour_ms + 2 * 60 * 60 * 1000 + 500),
+        ];
+
+        let parts = split_changes(changes, &get_test_start_date_time(), SplitMode::Hourly);
+
+        let part_starts: Vec<String> = parts
+            .iter()
+            .map(|part| part.start_date_time.to_string())
+            .collect();
+        assert_eq!(
+            part_starts,
+            vec![
+                "2021-12-15 11:30:00",
+                "2021-12-15 12:00:00",
+                "2021-12-15 14:00:00"
+            ]
+        );
+        let part_ms: Vec<Vec<u64>> = parts
+            .iter()
+            .map(|part| {
+                part.changes
+                    .iter()
+                    .map(|change| change.ms_from_beginning)
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            part_ms,
+            vec![vec![1000, half_an_hour_ms - 1], vec![0], vec![500]]
+        );
+    }
+
+    #[test]
+    fn splitting_without_changes_should_still_result_in_one_part() {
+        let parts = split_changes(Vec::new(), &get_test_start_date_time(), SplitMode::Hourly);
+
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].start_date_time, get_test_start_date_time());
+    }
+
+    #[test]
+    fn parsing_an_unknown_split_mode_should_return_err() {
+        assert!("daily".parse::<SplitMode>().is_err());
+        assert_eq!("hourly".parse::<SplitMode>(), Ok(SplitMode::Hourly));
+    }
+
+    fn messages_with_time_references(references_at: &[usize], count: usize) -> Vec<String> {
+        let mut messages = vec!["012021121511300000".to_string(), "04030000".to_string()];
+        for index in 0..count {
+            if references_at.contains(&index) {
+                messages.push(format!("01202112151130{:02}00", index));
+            } else {
+                messages.push(format!("0E{:03}10201", index));
+            }
+        }
+        messages
+    }
+
+    #[test]
+    fn splitting_messages_should_start_every_part_with_the_preamble() {
+        let messages = messages_with_time_references(&[], 6);
+
+        let files = split_messages(messages, 4);
+
+        let lengths: Vec<usize> = files.iter().map(|file| file.len()).collect();
+        assert_eq!(lengths, vec![4, 4, 4]);
+        for file in &files {
+            assert_eq!(file[0], "012021121511300000");
+            assert_eq!(file[1], "04030000");
+        }
+        assert_eq!(files[2][3], "0E00510201");
+    }
+
+    #[test]
+    fn splitting_messages_should_keep_a_time_reference_with_its_changes() {
+        // The reference at index 1 would otherwise end the first file
+        let messages = messages_with_time_references(&[1], 4);
+
+        let files = split_messages(messages, 4);
+
+        assert_eq!(
+            files,
+            vec![
+                vec!["012021121511300000", "04030000", "0E00010201"],
+                vec!["012021121511300100", "04030000", "0E00210201", "0E00310201"],
+            ]
+        );
+    }
+
+    #[test]
+    fn messages_that_fit_should_stay_in_one_file() {
+        let messages = messages_with_time_references(&[], 2);
+
+        assert_eq!(split_messages(messages.clone(), 4), vec![messages]);
+    }
+
+    #[test]
+    fn continuations_should_be_named_after_their_part() {
+        assert_eq!(
+            part_file_name("3031_20211215_113000.vlg", 0),
+            "3031_20211215_113000.vlg"
+        );
+        assert_eq!(
+            part_file_name("3031_20211215_113000.vlg", 1),
+            "3031_20211215_113000_part2.vlg"
+        );
+    }
+
+    #[test]
+    fn a_maximum_without_room_for_changes_should_return_err() {
+        assert!(parse_max_messages("2").is_err());
+        assert_eq!(parse_max_messages("3"), Ok(3));
+    }
+
+    #[test]
+    fn the_maximum_messages_within_a_size_should_go_by_the_longest_message() {
+        let messages = vec![
+            "012021121511300000".to_string(),
+            "04030000".to_string(),
+            "0E00010201".to_string(),
+        ];
+
+        assert_eq!(max_messages_within_size(&messages, 200), 10);
+        assert_eq!(max_messages_within_size(&messages, 10), 3);
+    }
+}
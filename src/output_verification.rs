@@ -0,0 +1,232 @@
+//! Checks the VLog message stream for defects that have indicated bugs in writing the header or continuing in a new file:
+//! duplicate adjacent lines, more than one V-Log information message and time references that go back in time.
+//!
+//! The check looks at one message at a time and only remembers the previous one, so it can run along with following a log.
+
+use chrono::NaiveDateTime;
+use tlcfi_assimilator::VLogMessageType;
+
+use crate::vlog_decoding;
+
+/// Only this many defects are kept for reporting, the rest are only counted.
+const MAX_KEPT_DEFECTS: usize = 10;
+
+/// Something in the message stream that a correct conversion never produces.
+#[derive(Debug, PartialEq, Clone)]
+pub enum OutputDefect {
+    /// The message at this index is the same as the one before it
+    DuplicateLine { index: usize, message: String },
+    /// Another V-Log information message at this index, where only the first message of a stream should be one
+    RepeatedInfoMessage { index: usize },
+    /// The time reference at this index is earlier than the previous one
+    TimeReferenceOutOfOrder {
+        index: usize,
+        previous: NaiveDateTime,
+        time: NaiveDateTime,
+    },
+}
+
+impl OutputDefect {
+    pub fn describe(&self) -> String {
+        match self {
+            OutputDefect::DuplicateLine { index, message } => format!(
+                "message {} is a duplicate of the message before it: {}",
+                index + 1,
+                message
+            ),
+            OutputDefect::RepeatedInfoMessage { index } => {
+                format!(
+                    "message {} is a second V-Log information message",
+                    index + 1
+                )
+            }
+            OutputDefect::TimeReferenceOutOfOrder {
+                index,
+                previous,
+                time,
+            } => format!(
+                "the time reference at message {} goes back from {} to {}",
+                index + 1,
+                previous,
+                time
+            ),
+        }
+    }
+}
+
+/// Checks messages one at a time, in the order they are written.
+#[derive(Debug, Default)]
+pub struct OutputVerifier {
+    message_count: usize,
+    previous_message: Option<String>,
+    previous_time_reference: Option<NaiveDateTime>,
+    has_info_message: bool,
+    defects: Vec<OutputDefect>,
+    defect_count: usize,
+}
+
+impl OutputVerifier {
+    pub fn check(&mut self, message: &str) {
+        let index = self.message_count;
+        self.message_count += 1;
+
+        if self.previous_message.as_deref() == Some(message) {
+            self.add_defect(OutputDefect::DuplicateLine {
+                index,
+                message: message.to_string(),
+            });
+        }
+        let message_type = message
+            .get(0..2)
+            .and_then(|code| u8::from_str_radix(code, 16).ok());
+        if message_type == Some(VLogMessageType::VLogInformation.code()) {
+            if self.has_info_message {
+                self.add_defect(OutputDefect::RepeatedInfoMessage { index });
+            }
+            self.has_info_message = true;
+        } else if message_type == Some(VLogMessageType::TimeReference.code()) {
+            if let Some(time) = vlog_decoding::parse_time_reference(message) {
+                if let Some(previous) = self.previous_time_reference {
+                    if time < previous {
+                        self.add_defect(OutputDefect::TimeReferenceOutOfOrder {
+                            index,
+                            previous,
+                            time,
+                        });
+                    }
+                }
+                self.previous_time_reference = Some(time);
+            }
+        }
+
+        match &mut self.previous_message {
+            Some(previous_message) => {
+                previous_message.clear();
+                previous_message.push_str(message);
+            }
+            None => self.previous_message = Some(message.to_string()),
+        }
+    }
+
+    /// Passes the messages through while checking them, so the check can be part of writing them.
+    pub fn pass<'v, 'm, I>(&'v mut self, messages: I) -> impl Iterator<Item = &'m String> + 'v
+    where
+        I: Iterator<Item = &'m String> + 'v,
+        'm: 'v,
+    {
+        messages.inspect(move |message| self.check(message))
+    }
+
+    /// The first defects that were found.
+    pub fn defects(&self) -> &[OutputDefect] {
+        &self.defects
+    }
+
+    /// All defects that were found, including the ones that weren't kept.
+    pub fn defect_count(&self) -> usize {
+        self.defect_count
+    }
+
+    fn add_defect(&mut self, defect: OutputDefect) {
+        self.defect_count += 1;
+        if self.defects.len() < MAX_KEPT_DEFECTS {
+            self.defects.push(defect);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_test_messages() -> Vec<String> {
+        vec![
+            "012021121511000000".to_string(),
+            "040300003031".to_string(),
+            "0E00010001".to_string(),
+            "0E00510000".to_string(),
+            "012021121511050000".to_string(),
+            "0E00010001".to_string(),
+        ]
+    }
+
+    fn defects_of(messages: &[String]) -> Vec<OutputDefect> {
+        let mut verifier = OutputVerifier::default();
+        messages.iter().for_each(|message| verifier.check(message));
+        verifier.defects().to_vec()
+    }
+
+    #[test]
+    fn a_correct_stream_should_have_no_defects() {
+        assert!(defects_of(&get_test_messages()).is_empty());
+    }
+
+    #[test]
+    fn a_duplicated_line_should_be_found() {
+        let mut messages = get_test_messages();
+        messages.insert(3, "0E00010001".to_string());
+
+        assert_eq!(
+            defects_of(&messages),
+            vec![OutputDefect::DuplicateLine {
+                index: 3,
+                message: "0E00010001".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn a_second_info_message_should_be_found() {
+        let mut messages = get_test_messages();
+        messages.insert(5, "040300003031".to_string());
+
+        assert_eq!(
+            defects_of(&messages),
+            vec![OutputDefect::RepeatedInfoMessage { index: 5 }]
+        );
+    }
+
+    #[test]
+    fn a_time_reference_going_back_should_be_found() {
+        let mut messages = get_test_messages();
+        messages[4] = "012021121510550000".to_string();
+
+        assert_eq!(
+            defects_of(&messages),
+            vec![OutputDefect::TimeReferenceOutOfOrder {
+                index: 4,
+                previous: chrono::NaiveDate::from_ymd(2021, 12, 15).and_hms(11, 0, 0),
+                time: chrono::NaiveDate::from_ymd(2021, 12, 15).and_hms(10, 55, 0),
+            }]
+        );
+    }
+
+    #[test]
+    fn passing_messages_through_should_check_them_in_batches() {
+        let messages = get_test_messages();
+        let mut verifier = OutputVerifier::default();
+
+        let first_batch: Vec<&String> = verifier.pass(messages[..3].iter()).collect();
+        let second_batch: Vec<&String> = verifier.pass(messages[2..].iter()).collect();
+
+        assert_eq!(first_batch.len() + second_batch.len(), messages.len() + 1);
+        // The batches overlap at the third message, which is found as a duplicate across them
+        assert_eq!(
+            verifier.defects(),
+            &[OutputDefect::DuplicateLine {
+                index: 3,
+                message: "0E00010001".to_string()
+            }][..]
+        );
+    }
+
+    #[test]
+    fn only_the_first_defects_should_be_kept() {
+        let messages = vec!["0E00010001".to_string(); 20];
+        let mut verifier = OutputVerifier::default();
+        messages.iter().for_each(|message| verifier.check(message));
+
+        assert_eq!(verifier.defects().len(), MAX_KEPT_DEFECTS);
+        assert_eq!(verifier.defect_count(), 19);
+    }
+}
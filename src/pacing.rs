@@ -0,0 +1,199 @@
+//! Delays the emission of VLog messages until their wall-clock time has passed, for consumers that read a growing file.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::NaiveDateTime;
+
+use crate::vlog_decoding::MessageClock;
+
+/// How long a wait sleeps at most before looking whether a stop was requested.
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Paces VLog messages by the time encoded in them.
+/// The time of a change message is the last time reference plus its time delta.
+pub struct Pacer {
+    started_at: Instant,
+    speed: f64,
+    first_reference: Option<NaiveDateTime>,
+    clock: MessageClock,
+    stop_requested: Option<&'static AtomicBool>,
+}
+
+impl Pacer {
+    /// Creates a pacer that replays `speed` times as fast as real time, starting now.
+    pub fn new(speed: f64) -> Self {
+        Self {
+            started_at: Instant::now(),
+            speed,
+            first_reference: Option::None,
+            clock: MessageClock::default(),
+            stop_requested: Option::None,
+        }
+    }
+
+    /// Lets setting the flag end a wait early, so Ctrl-C doesn't have to wait for the next message to be due.
+    pub fn stopped_by(mut self, stop_requested: &'static AtomicBool) -> Self {
+        self.stop_requested = Some(stop_requested);
+        self
+    }
+
+    /// Blocks until the given message is due. Returns false when a stop was requested before that.
+    pub fn wait_for(&mut self, message: &str) -> bool {
+        let due_at = Instant::now() + self.wait_duration(message, self.started_at.elapsed());
+        loop {
+            if self.is_stop_requested() {
+                return false;
+            }
+            let wait = due_at.saturating_duration_since(Instant::now());
+            if wait.is_zero() {
+                return true;
+            }
+            thread::sleep(wait.min(STOP_CHECK_INTERVAL));
+        }
+    }
+
+    fn is_stop_requested(&self) -> bool {
+        self.stop_requested
+            .is_some_and(|stop_requested| stop_requested.load(Ordering::SeqCst))
+    }
+
+    fn wait_duration(&mut self, message: &str, elapsed: Duration) -> Duration {
+        match self.due_ms(message) {
+            Some(due_ms) => {
+                let due = Duration::from_secs_f64(due_ms as f64 / 1000.0 / self.speed);
+                due.checked_sub(elapsed).unwrap_or(Duration::ZERO)
+            }
+            None => Duration::ZERO,
+        }
+    }
+
+    /// The amount of ms after the first time reference at which the given message should be emitted,
+    /// or None if the message doesn't carry a time.
+    fn due_ms(&mut self, message: &str) -> Option<u64> {
+        let message_time = self.clock.time_of(message)?;
+        let first_reference = *self.first_reference.get_or_insert(message_time);
+        Some((message_time - first_reference).num_milliseconds().max(0) as u64)
+    }
+}
+
+/// Parses the replay speed multiplier, which has to be a positive number.
+pub fn parse_pace_speed(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        _ => Err(format!(
+            "Failed to transform argument {} into a pace speed, expected a positive number",
+            arg
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TEST_MESSAGES: [&str; 5] = [
+        "012021121511000000",
+        "040300003330333120202020202020202020202020202020",
+        "060A110A01",
+        "012021121511050000",
+        "0E01410A02",
+    ];
+
+    #[test]
+    fn change_messages_should_be_due_at_the_last_time_reference_plus_their_delta() {
+        let mut pacer = Pacer::new(1.0);
+
+        let due: Vec<Option<u64>> = TEST_MESSAGES.iter().map(|m| pacer.due_ms(m)).collect();
+
+        assert_eq!(
+            due,
+            vec![Some(0), None, Some(16_100), Some(300_000), Some(302_000)]
+        );
+    }
+
+    #[test]
+    fn messages_that_are_already_due_should_not_wait() {
+        let mut pacer = Pacer::new(1.0);
+        pacer.due_ms(TEST_MESSAGES[0]);
+
+        assert_eq!(
+            pacer.wait_duration(TEST_MESSAGES[2], Duration::from_secs(20)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn a_faster_pace_speed_should_shorten_the_wait() {
+        let mut normal_pacer = Pacer::new(1.0);
+        let mut fast_pacer = Pacer::new(10.0);
+        normal_pacer.due_ms(TEST_MESSAGES[0]);
+        fast_pacer.due_ms(TEST_MESSAGES[0]);
+
+        assert_eq!(
+            normal_pacer.wait_duration(TEST_MESSAGES[2], Duration::from_secs(1)),
+            Duration::from_millis(15_100)
+        );
+        assert_eq!(
+            fast_pacer.wait_duration(TEST_MESSAGES[2], Duration::from_secs(1)),
+            Duration::from_millis(610)
+        );
+    }
+
+    #[test]
+    fn a_time_reference_between_changes_should_be_due_at_its_own_time() {
+        let mut pacer = Pacer::new(1.0);
+        pacer.due_ms("012021121511000000");
+
+        // A change at 299 seconds, the time reference at 5 minutes and a change 1 second after it
+        let waits: Vec<Duration> = vec![
+            ("06BAE10A01", Duration::ZERO),
+            ("012021121511050000", Duration::from_secs(299)),
+            ("0600A10A00", Duration::from_secs(300)),
+        ]
+        .into_iter()
+        .map(|(message, elapsed)| pacer.wait_duration(message, elapsed))
+        .collect();
+
+        assert_eq!(
+            waits,
+            vec![
+                Duration::from_secs(299),
+                Duration::from_secs(1),
+                Duration::from_secs(1)
+            ]
+        );
+    }
+
+    #[test]
+    fn a_stop_request_should_end_the_wait_for_a_message() {
+        static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+        let mut pacer = Pacer::new(1.0).stopped_by(&STOP_REQUESTED);
+        let stopper = thread::spawn(|| {
+            thread::sleep(Duration::from_millis(200));
+            STOP_REQUESTED.store(true, Ordering::SeqCst);
+        });
+
+        let started_at = Instant::now();
+        let is_first_due = pacer.wait_for(TEST_MESSAGES[0]);
+        let is_later_due = pacer.wait_for(TEST_MESSAGES[3]);
+        let waiting_time = started_at.elapsed();
+        stopper.join().unwrap();
+
+        assert!(is_first_due);
+        assert!(!is_later_due);
+        assert!(waiting_time < Duration::from_secs(10));
+        assert!(!pacer.wait_for(TEST_MESSAGES[4]));
+    }
+
+    #[test]
+    fn parse_pace_speed_of_non_positive_numbers_should_return_err() {
+        assert_eq!(parse_pace_speed("2.5"), Ok(2.5));
+        assert!(parse_pace_speed("0").is_err());
+        assert!(parse_pace_speed("-1").is_err());
+        assert!(parse_pace_speed("fast").is_err());
+    }
+}
@@ -0,0 +1,51 @@
+//! Writes the predictions of the TLC of when the future states of the signal groups end, for `--predictions-csv`.
+//!
+//! Every prediction is a CSV line `signal_name,ms_from_beginning,predicted_state,likely_end_ms,min_end_ms,max_end_ms`,
+//! with the ends in ms from the start of the conversion like the time of the message it's in.
+
+use tlcfi_assimilator::SignalPrediction;
+
+const HEADER: &str =
+    "signal_name,ms_from_beginning,predicted_state,likely_end_ms,min_end_ms,max_end_ms";
+
+/// The predictions in the order of the log, after a header. An end the prediction doesn't have is left empty.
+pub fn to_csv(predictions: &[SignalPrediction]) -> String {
+    let format_end =
+        |end_ms: Option<u64>| end_ms.map_or_else(String::new, |end_ms| end_ms.to_string());
+    let mut csv = format!("{}\n", HEADER);
+    for prediction in predictions {
+        csv.push_str(&format!(
+            "{},{},{:?},{},{},{}\n",
+            prediction.signal_name,
+            prediction.ms_from_beginning,
+            prediction.predicted_state,
+            format_end(prediction.likely_end_ms),
+            format_end(prediction.min_end_ms),
+            format_end(prediction.max_end_ms)
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tlcfi_assimilator::SignalState;
+
+    #[test]
+    fn missing_ends_should_be_written_as_empty_fields() {
+        let predictions = vec![SignalPrediction {
+            signal_name: "01".to_string(),
+            ms_from_beginning: 1000,
+            predicted_state: SignalState::Green,
+            likely_end_ms: Some(8200),
+            min_end_ms: None,
+            max_end_ms: Some(19_600),
+        }];
+
+        assert_eq!(
+            to_csv(&predictions),
+            format!("{}\n01,1000,Green,8200,,19600\n", HEADER)
+        );
+    }
+}
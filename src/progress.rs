@@ -0,0 +1,129 @@
+//! Reports the progress of reading and converting a log on stderr, since a large log takes minutes.
+
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+/// Progress is printed at most this often.
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Counts the lines and bytes that are done and prints how far along that is, at most once per [REPORT_INTERVAL](constant.REPORT_INTERVAL.html).
+pub struct Progress {
+    /// What is being done, e.g. `Reading the log`
+    activity: &'static str,
+    /// None when the size isn't known in advance, then only the lines are counted
+    total_bytes: Option<u64>,
+    done_bytes: u64,
+    done_lines: usize,
+    started: Instant,
+    last_report: Option<Instant>,
+    is_enabled: bool,
+}
+
+impl Progress {
+    pub fn new(activity: &'static str, total_bytes: Option<u64>, is_enabled: bool) -> Progress {
+        Progress {
+            activity,
+            total_bytes,
+            done_bytes: 0,
+            done_lines: 0,
+            started: Instant::now(),
+            last_report: None,
+            is_enabled,
+        }
+    }
+
+    /// Counts a line of the given length, without its line break, and prints the progress when it's time to.
+    pub fn line_done(&mut self, line_length: usize) {
+        self.done_lines += 1;
+        // Counting the line break as well
+        self.done_bytes += line_length as u64 + 1;
+        if !self.is_enabled {
+            return;
+        }
+        let now = Instant::now();
+        let since_last_report = now.duration_since(self.last_report.unwrap_or(self.started));
+        if since_last_report >= REPORT_INTERVAL {
+            eprint!("\r{}", self.status_line(now.duration_since(self.started)));
+            self.last_report = Some(now);
+        }
+    }
+
+    /// Ends the progress line with the final numbers, if any progress was printed.
+    pub fn finish(&mut self) {
+        if self.is_enabled && self.last_report.is_some() {
+            eprintln!("\r{}", self.status_line(self.started.elapsed()));
+        }
+    }
+
+    fn status_line(&self, elapsed: Duration) -> String {
+        let lines_per_second = (self.done_lines as f64 / elapsed.as_secs_f64().max(0.001)) as u64;
+        match self.total_bytes {
+            Some(total_bytes) if total_bytes > 0 => format!(
+                "{}: {}% ({} lines, {} lines/s)",
+                self.activity,
+                (self.done_bytes * 100 / total_bytes).min(100),
+                self.done_lines,
+                lines_per_second
+            ),
+            _ => format!(
+                "{}: {} lines ({} lines/s)",
+                self.activity, self.done_lines, lines_per_second
+            ),
+        }
+    }
+}
+
+/// The size of a regular file. None for compressed files, whose lines add up to more than the file size,
+/// and for stdin or other pipes, whose size isn't known in advance.
+pub fn log_file_size(file_name: &str, is_compressed: bool) -> Option<u64> {
+    if is_compressed {
+        return None;
+    }
+    fs::metadata(file_name)
+        .ok()
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn the_status_should_show_the_percentage_of_a_known_total() {
+        let mut progress = Progress::new("Reading the log", Some(40), false);
+        for _ in 0..2 {
+            progress.line_done(9);
+        }
+
+        assert_eq!(
+            progress.status_line(Duration::from_millis(500)),
+            "Reading the log: 50% (2 lines, 4 lines/s)"
+        );
+    }
+
+    #[test]
+    fn the_status_should_count_lines_when_the_total_is_unknown() {
+        let mut progress = Progress::new("Reading the log", None, false);
+        for _ in 0..3 {
+            progress.line_done(100);
+        }
+
+        assert_eq!(
+            progress.status_line(Duration::from_secs(2)),
+            "Reading the log: 3 lines (1 lines/s)"
+        );
+    }
+
+    #[test]
+    fn pipes_and_compressed_logs_should_have_an_unknown_size() {
+        assert_eq!(
+            log_file_size("./tlcfi_with_reset.txt", false),
+            Some(fs::metadata("./tlcfi_with_reset.txt").unwrap().len())
+        );
+        assert_eq!(log_file_size("./tlcfi.txt.gz", true), None);
+        assert_eq!(log_file_size(".", false), None);
+    }
+}
@@ -0,0 +1,198 @@
+//! Reads a log that has its newest line first from its end, so its lines come out oldest first without reading the
+//! whole file into memory before they can be put in order.
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+use tlcfi_assimilator::Console;
+
+/// The number of bytes that is read from the file at a time.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Iterates over the lines of a file from its last line to its first, like `BufRead::lines` in reverse.
+///
+/// Only a block and the line that continues in the block before it are kept in memory.
+pub struct ReverseLines<R> {
+    reader: R,
+    block_size: usize,
+    /// The position in the file of the start of the buffer
+    position: u64,
+    /// The bytes after the position up to the start of the last returned line, without its line break
+    buffer: Vec<u8>,
+    is_done: bool,
+}
+
+impl<R: Read + Seek> ReverseLines<R> {
+    pub fn new(reader: R) -> io::Result<ReverseLines<R>> {
+        ReverseLines::with_block_size(reader, BLOCK_SIZE)
+    }
+
+    pub fn with_block_size(mut reader: R, block_size: usize) -> io::Result<ReverseLines<R>> {
+        let mut position = reader.seek(SeekFrom::End(0))?;
+        let is_done = position == 0;
+        // The line break of the last line doesn't start another line
+        if position > 0 {
+            let mut last_byte = [0];
+            reader.seek(SeekFrom::Start(position - 1))?;
+            reader.read_exact(&mut last_byte)?;
+            if last_byte[0] == b'\n' {
+                position -= 1;
+            }
+        }
+        Ok(ReverseLines {
+            reader,
+            block_size,
+            position,
+            buffer: Vec::new(),
+            is_done,
+        })
+    }
+
+    /// Puts the block before the buffer in front of it.
+    fn read_previous_block(&mut self) -> io::Result<()> {
+        let block_size = self.block_size.min(self.position as usize);
+        self.position -= block_size as u64;
+        let mut block = vec![0; block_size];
+        self.reader.seek(SeekFrom::Start(self.position))?;
+        self.reader.read_exact(&mut block)?;
+        block.append(&mut self.buffer);
+        self.buffer = block;
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Iterator for ReverseLines<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<io::Result<String>> {
+        if self.is_done {
+            return None;
+        }
+        loop {
+            if let Some(line_break) = self.buffer.iter().rposition(|&byte| byte == b'\n') {
+                let line = self.buffer.split_off(line_break + 1);
+                self.buffer.truncate(line_break);
+                return Some(to_line(line));
+            }
+            if self.position == 0 {
+                self.is_done = true;
+                return Some(to_line(std::mem::take(&mut self.buffer)));
+            }
+            if let Err(error) = self.read_previous_block() {
+                self.is_done = true;
+                return Some(Err(error));
+            }
+        }
+    }
+}
+
+/// Decodes a line without its line break, which may end with a carriage return.
+fn to_line(mut bytes: Vec<u8>) -> io::Result<String> {
+    if bytes.last() == Some(&b'\r') {
+        bytes.pop();
+    }
+    String::from_utf8(bytes).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Reads the lines of a log with its newest line first, oldest first, calling `on_line` with the length of every
+/// line that was read. Lines that can't be read are counted in a warning like they are when reading forwards.
+pub fn read_lines_oldest_first<R: Read + Seek>(
+    reader: R,
+    console: &mut Console,
+    on_line: &mut dyn FnMut(usize),
+) -> io::Result<Vec<String>> {
+    let mut time_sorted_lines = Vec::new();
+    for_each_line_oldest_first(reader, console, &mut |line| {
+        on_line(line.len());
+        time_sorted_lines.push(line);
+    })?;
+    Ok(time_sorted_lines)
+}
+
+/// Passes the lines of a log with its newest line first to `on_line`, oldest first, without keeping them. Lines that
+/// can't be read are counted in a warning.
+pub fn for_each_line_oldest_first<R: Read + Seek>(
+    reader: R,
+    console: &mut Console,
+    on_line: &mut dyn FnMut(String),
+) -> io::Result<()> {
+    for line_res in ReverseLines::new(reader)? {
+        match line_res {
+            Ok(line) => on_line(line),
+            Err(error) => {
+                console.repeated_warning("failed to read {} line(s)", &format!("{:?}", error))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Opens a log with its newest line first to iterate over its lines oldest first, leaving out the lines that can't be
+/// read. [for_each_line_oldest_first](fn.for_each_line_oldest_first.html) warns about those.
+pub fn lines_oldest_first(file_name: &str) -> io::Result<impl Iterator<Item = String>> {
+    Ok(ReverseLines::new(File::open(file_name)?)?.filter_map(Result::ok))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{BufRead, Cursor};
+
+    fn reverse_lines(content: &str, block_size: usize) -> Vec<String> {
+        ReverseLines::with_block_size(Cursor::new(content.as_bytes()), block_size)
+            .unwrap()
+            .map(|line| line.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn lines_split_over_blocks_should_be_read_whole() {
+        let content = "first line\nsecond\n\nthird line that is longer than a block\nlast\n";
+        let mut expected_lines: Vec<String> = content
+            .as_bytes()
+            .lines()
+            .map(|line| line.unwrap())
+            .collect();
+        expected_lines.reverse();
+
+        for block_size in 1..=content.len() + 1 {
+            assert_eq!(reverse_lines(content, block_size), expected_lines);
+        }
+    }
+
+    #[test]
+    fn the_last_line_without_line_break_and_crlf_line_endings_should_be_read_like_forwards() {
+        for content in [
+            "first\r\nsecond\r\nlast",
+            "first\r\nsecond\r\n",
+            "\n",
+            "",
+            "ë\r\nä",
+        ] {
+            let mut expected_lines: Vec<String> = content
+                .as_bytes()
+                .lines()
+                .map(|line| line.unwrap())
+                .collect();
+            expected_lines.reverse();
+
+            for block_size in 1..=4 {
+                assert_eq!(reverse_lines(content, block_size), expected_lines);
+            }
+        }
+    }
+
+    #[test]
+    fn a_line_that_is_not_utf8_should_be_warned_about_and_skipped() {
+        let mut console = Console::default();
+        let content = b"first\n\xff\xfe\nlast\n".to_vec();
+
+        let lines =
+            read_lines_oldest_first(Cursor::new(content), &mut console, &mut |_| {}).unwrap();
+
+        assert_eq!(lines, vec!["last", "first"]);
+        assert_eq!(console.summary_lines().len(), 1);
+    }
+}
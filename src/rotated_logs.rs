@@ -0,0 +1,108 @@
+//! Reads a log that was rotated by log4j, which leaves `tlcfi.txt` with older lines in `tlcfi.txt.1` … `tlcfi.txt.9`,
+//! as one stream of lines.
+//!
+//! The rotated files are ordered by the timestamp of their first line rather than their number, as operators
+//! sometimes rename them.
+
+use std::{
+    cmp::Reverse,
+    io::{self, BufRead, Cursor, Read},
+    path::Path,
+};
+
+use tlcfi_assimilator::Console;
+
+use crate::{compressed_logs, zip_archive};
+
+/// The log file with its rotated siblings, the log file first and the siblings in the order of their number.
+/// Only the log file itself when it has no siblings.
+pub fn find_rotated_logs(file_name: &str) -> Vec<String> {
+    let path = Path::new(file_name);
+    let mut rotated_logs = Vec::new();
+    if let (Some(base_name), Ok(entries)) = (
+        path.file_name().and_then(|name| name.to_str()),
+        path.parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."))
+            .read_dir(),
+    ) {
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let number = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix(base_name))
+                .and_then(|suffix| suffix.strip_prefix('.'))
+                .and_then(|number| number.parse::<u32>().ok());
+            if let Some(number) = number {
+                rotated_logs.push((number, path.with_file_name(entry.file_name())));
+            }
+        }
+    }
+    rotated_logs.sort();
+    let mut file_names = vec![file_name.to_string()];
+    file_names.extend(
+        rotated_logs
+            .into_iter()
+            .map(|(_, path)| path.to_string_lossy().to_string()),
+    );
+    file_names
+}
+
+/// Opens the rotated logs as one stream of lines, in the same way as the logs in a ZIP archive.
+///
+/// The logs are ordered by the timestamp of their first line, in the same direction as the lines in a log:
+/// oldest first for a chronological log, newest first otherwise. Reversing the stream of a log that isn't
+/// chronological then reverses every log before the logs are put one after the other, oldest first.
+pub fn open_logs(
+    file_names: &[String],
+    is_chronological: bool,
+    console: &mut Console,
+) -> io::Result<Box<dyn BufRead>> {
+    let mut logs = Vec::new();
+    for file_name in file_names {
+        let mut data = Vec::new();
+        compressed_logs::open_log(file_name)?.read_to_end(&mut data)?;
+        match zip_archive::first_log_line_time(&data[..]) {
+            Some(first_line_time) => logs.push((first_line_time, data)),
+            None => console.warning(&format!(
+                "Warning: skipped the rotated log '{}', it doesn't contain TLC-FI log lines.",
+                file_name
+            )),
+        }
+    }
+    if is_chronological {
+        logs.sort_by_key(|(first_line_time, _)| *first_line_time);
+    } else {
+        logs.sort_by_key(|(first_line_time, _)| Reverse(*first_line_time));
+    }
+
+    let mut lines = Vec::new();
+    for (_, data) in logs {
+        lines.extend_from_slice(&data);
+        if !lines.is_empty() && !lines.ends_with(b"\n") {
+            lines.push(b'\n');
+        }
+    }
+    Ok(Box::new(Cursor::new(lines)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn siblings_should_be_found_in_the_order_of_their_number() {
+        assert_eq!(
+            find_rotated_logs("./tlcfi_rotated.txt"),
+            vec![
+                "./tlcfi_rotated.txt".to_string(),
+                "./tlcfi_rotated.txt.1".to_string(),
+                "./tlcfi_rotated.txt.2".to_string(),
+            ]
+        );
+        assert_eq!(
+            find_rotated_logs("./tlcfi.txt"),
+            vec!["./tlcfi.txt".to_string()]
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Prints the schema of an export as JSON, so teams consuming it don't have to reverse engineer the columns.
+//!
+//! The schema is built from the same column definitions the exporter writes its header with, so the two can't drift apart.
+
+use std::str::FromStr;
+
+use json::{object, JsonValue};
+
+use crate::log_stats;
+
+const SCHEMA_HELP: &str = "\
+TLC-FI Assimilator schema
+
+USAGE:
+  tlcfi_assimilator schema [OPTIONS]
+
+FLAGS:
+  -h, --help                Prints help information
+
+OPTIONS:
+  --format STRING           The export to print the schema of: csv, the output of the stats subcommand [default: csv]
+";
+
+/// The type of the values in a field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldType {
+    String,
+    UnsignedInteger,
+}
+
+impl FieldType {
+    fn name(&self) -> &'static str {
+        match self {
+            FieldType::String => "string",
+            FieldType::UnsignedInteger => "uint64",
+        }
+    }
+}
+
+/// A single column of an export.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSchema {
+    pub name: &'static str,
+    pub field_type: FieldType,
+    pub nullable: bool,
+    /// The only values the field can have, empty when any value of the type is possible
+    pub enum_values: Vec<&'static str>,
+}
+
+impl FieldSchema {
+    fn to_json(&self) -> JsonValue {
+        let mut field = object! {
+            "name" => self.name,
+            "type" => self.field_type.name(),
+            "nullable" => self.nullable,
+        };
+        if !self.enum_values.is_empty() {
+            field["enum"] = self.enum_values.clone().into();
+        }
+        field
+    }
+}
+
+/// The exports that have a schema.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        match arg {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" | "parquet" => Err(format!(
+                "there is no {} export, only the CSV of the stats subcommand has a schema",
+                arg
+            )),
+            _ => Err(format!("unknown format '{}', use csv", arg)),
+        }
+    }
+}
+
+/// Parses the `schema` arguments and prints the schema. Returns the exit code.
+pub fn run_schema() -> i32 {
+    let mut pargs = pico_args::Arguments::from_env();
+    // Drop the "schema" subcommand itself
+    let _ = pargs.subcommand();
+
+    if pargs.contains(["-h", "--help"]) {
+        println!("{}", SCHEMA_HELP);
+        return 0;
+    }
+
+    let format = match pargs.opt_value_from_str::<_, ExportFormat>("--format") {
+        Ok(format) => format.unwrap_or_default(),
+        Err(error) => {
+            eprintln!("Error: {}.", error);
+            println!("{}", SCHEMA_HELP);
+            return 1;
+        }
+    };
+    println!("{}", schema_json(format).pretty(2));
+    0
+}
+
+fn schema_json(format: ExportFormat) -> JsonValue {
+    let (export, fields) = match format {
+        ExportFormat::Csv => ("stats", log_stats::csv_schema()),
+    };
+    object! {
+        "format" => "csv",
+        "export" => export,
+        "fields" => fields.iter().map(FieldSchema::to_json).collect::<Vec<JsonValue>>(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tlcfi_assimilator::{DetectorState, TimestampedChanges};
+
+    #[test]
+    fn the_csv_schema_should_describe_every_field_of_a_sample_record() {
+        let schema = schema_json(ExportFormat::Csv);
+        let csv = log_stats::to_csv(&log_stats::collect_object_stats(&[TimestampedChanges {
+            ms_from_beginning: 100,
+            detector_names: vec!["D611".to_string()],
+            detector_states: vec![DetectorState::OCCUPIED],
+            ..Default::default()
+        }]));
+        let mut lines = csv.lines();
+        let header: Vec<&str> = lines.next().unwrap().split(',').collect();
+        let record: Vec<&str> = lines.next().unwrap().split(',').collect();
+
+        let fields: Vec<&JsonValue> = schema["fields"].members().collect();
+        let field_names: Vec<&str> = fields
+            .iter()
+            .map(|field| field["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(field_names, header);
+        assert_eq!(record.len(), fields.len());
+        for (field, value) in fields.iter().zip(record) {
+            match field["type"].as_str().unwrap() {
+                "uint64" => assert!(value.parse::<u64>().is_ok(), "{} isn't a uint64", value),
+                _ => assert!(!value.is_empty()),
+            }
+            if field["enum"].is_array() {
+                assert!(field["enum"].members().any(|allowed| allowed == value));
+            }
+        }
+    }
+
+    #[test]
+    fn formats_without_an_export_should_be_rejected() {
+        assert_eq!("csv".parse(), Ok(ExportFormat::Csv));
+        assert!("jsonl".parse::<ExportFormat>().is_err());
+        assert!("parquet".parse::<ExportFormat>().is_err());
+        assert!("xml".parse::<ExportFormat>().is_err());
+    }
+}
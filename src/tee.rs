@@ -0,0 +1,37 @@
+//! Duplicates the VLog output to stdout for `--tee`, to watch the messages while the file is written.
+
+use std::io::{self, Write};
+
+/// Writes everything to both writers, without buffering it itself.
+pub struct Tee<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: Write, B: Write> Tee<A, B> {
+    pub fn new(first: A, second: B) -> Tee<A, B> {
+        Tee { first, second }
+    }
+}
+
+impl<A: Write, B: Write> Write for Tee<A, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.first.write_all(buf)?;
+        self.second.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.first.flush()?;
+        self.second.flush()
+    }
+}
+
+/// Returns the writer, duplicating everything that is written to it to stdout when `is_teed` is set.
+pub fn tee_to_stdout<'a, W: Write + 'a>(writer: W, is_teed: bool) -> Box<dyn Write + 'a> {
+    if is_teed {
+        Box::new(Tee::new(writer, io::stdout()))
+    } else {
+        Box::new(writer)
+    }
+}
@@ -0,0 +1,231 @@
+//! Converts between local date times of a time zone and UTC, for logs stamped in local time.
+//!
+//! The zones are the ones of the tz database, like `Europe/Amsterdam`, or fixed offsets from UTC.
+
+use std::str::FromStr;
+
+use chrono::{
+    Duration, FixedOffset, LocalResult, NaiveDateTime, Offset, TimeZone as ChronoTimeZone,
+};
+use chrono_tz::Tz;
+use serde::{Serialize, Serializer};
+
+const SECONDS_PER_HOUR: i32 = 3600;
+
+/// How far a clock step may be from the change of the UTC offset to still be that change.
+const SUMMER_TIME_STEP_TOLERANCE_MS: i64 = 5_000;
+
+/// A time zone of the tz database or a fixed offset from UTC. The default is UTC.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TimeZone {
+    zone: Zone,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Zone {
+    Named(Tz),
+    Fixed(FixedOffset),
+}
+
+impl Default for TimeZone {
+    fn default() -> Self {
+        TimeZone {
+            zone: Zone::Named(Tz::UTC),
+        }
+    }
+}
+
+impl TimeZone {
+    /// The offset from UTC in seconds at the given UTC date time.
+    pub fn utc_offset_s(&self, utc: &NaiveDateTime) -> i32 {
+        match self.zone {
+            Zone::Named(tz) => tz.offset_from_utc_datetime(utc).fix().local_minus_utc(),
+            Zone::Fixed(offset) => offset.local_minus_utc(),
+        }
+    }
+
+    /// The local date time in this zone of the given UTC date time.
+    pub fn from_utc(&self, utc: &NaiveDateTime) -> NaiveDateTime {
+        *utc + Duration::seconds(self.utc_offset_s(utc) as i64)
+    }
+
+    /// The UTC date time of the given local date time in this zone.
+    /// Local times that occur twice when summer time ends are taken as the first, still in summer time, and
+    /// local times skipped when summer time starts are taken as if the clock wasn't moved yet.
+    pub fn to_utc(&self, local: &NaiveDateTime) -> NaiveDateTime {
+        let tz = match self.zone {
+            Zone::Named(tz) => tz,
+            Zone::Fixed(offset) => {
+                return *local - Duration::seconds(offset.local_minus_utc() as i64)
+            }
+        };
+        match tz.from_local_datetime(local) {
+            LocalResult::Single(date_time) => date_time.naive_utc(),
+            LocalResult::Ambiguous(earliest, _) => earliest.naive_utc(),
+            // The offset a day earlier is the one from before the clock was moved
+            LocalResult::None => {
+                let offset_before = tz
+                    .offset_from_utc_datetime(&(*local - Duration::days(1)))
+                    .fix();
+                *local - Duration::seconds(offset_before.local_minus_utc() as i64)
+            }
+        }
+    }
+
+    /// Whether a jump of the local clock by `step_ms` right after `local` is the change to or from summer time.
+    pub fn is_summer_time_change(&self, local: &NaiveDateTime, step_ms: i64) -> bool {
+        let utc = self.to_utc(local);
+        let hour = Duration::seconds(SECONDS_PER_HOUR as i64);
+        let offset_change_ms =
+            (self.utc_offset_s(&(utc + hour)) - self.utc_offset_s(&(utc - hour))) as i64 * 1000;
+        offset_change_ms != 0 && (step_ms - offset_change_ms).abs() <= SUMMER_TIME_STEP_TOLERANCE_MS
+    }
+}
+
+// Serialized as it's given on the command line
+impl Serialize for TimeZone {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.zone {
+            Zone::Named(tz) => serializer.serialize_str(tz.name()),
+            Zone::Fixed(offset) => serializer.collect_str(&offset),
+        }
+    }
+}
+
+impl FromStr for TimeZone {
+    type Err = String;
+
+    /// Parses a zone name like `Europe/Amsterdam`, or a fixed offset like `+01:00`.
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        let zone = match (arg.parse::<Tz>(), parse_fixed_offset(arg)) {
+            (Ok(tz), _) => Zone::Named(tz),
+            (_, Some(offset_s)) => Zone::Fixed(FixedOffset::east(offset_s)),
+            _ => {
+                return Err(format!(
+                    "'{}' isn't a supported time zone, use a zone of the tz database like Europe/Amsterdam or an offset like +01:00",
+                    arg
+                ))
+            }
+        };
+        Ok(TimeZone { zone })
+    }
+}
+
+/// Parses an offset like `+01:00` or `-05:30` into seconds.
+fn parse_fixed_offset(arg: &str) -> Option<i32> {
+    let sign = match arg.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let (hours, minutes) = arg.get(1..)?.split_once(':')?;
+    if hours.len() != 2 || minutes.len() != 2 {
+        return None;
+    }
+    let hours: i32 = hours.parse().ok().filter(|hours| *hours <= 14)?;
+    let minutes: i32 = minutes.parse().ok().filter(|minutes| *minutes < 60)?;
+    Some(sign * (hours * SECONDS_PER_HOUR + minutes * 60))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn amsterdam() -> TimeZone {
+        "Europe/Amsterdam".parse().unwrap()
+    }
+
+    #[test]
+    fn local_times_should_convert_with_the_offset_of_their_season() {
+        let winter = NaiveDate::from_ymd(2021, 12, 15).and_hms(12, 0, 0);
+        let summer = NaiveDate::from_ymd(2021, 7, 1).and_hms(12, 0, 0);
+
+        assert_eq!(
+            amsterdam().to_utc(&winter),
+            NaiveDate::from_ymd(2021, 12, 15).and_hms(11, 0, 0)
+        );
+        assert_eq!(
+            amsterdam().to_utc(&summer),
+            NaiveDate::from_ymd(2021, 7, 1).and_hms(10, 0, 0)
+        );
+        assert_eq!(amsterdam().from_utc(&amsterdam().to_utc(&summer)), summer);
+        assert_eq!(TimeZone::default().to_utc(&summer), summer);
+    }
+
+    #[test]
+    fn zones_outside_europe_should_follow_their_own_rules() {
+        let new_york: TimeZone = "America/New_York".parse().unwrap();
+        // Summer time in the US starts on the second Sunday of March
+        let after_change = NaiveDate::from_ymd(2021, 3, 20).and_hms(12, 0, 0);
+
+        assert_eq!(
+            new_york.to_utc(&after_change),
+            NaiveDate::from_ymd(2021, 3, 20).and_hms(16, 0, 0)
+        );
+        assert!(new_york.is_summer_time_change(
+            &NaiveDate::from_ymd(2021, 3, 14).and_hms(1, 59, 59),
+            3_600_000
+        ));
+    }
+
+    #[test]
+    fn the_repeated_hour_should_be_taken_as_summer_time() {
+        let repeated = NaiveDate::from_ymd(2021, 10, 31).and_hms(2, 30, 0);
+
+        assert_eq!(
+            amsterdam().to_utc(&repeated),
+            NaiveDate::from_ymd(2021, 10, 31).and_hms(0, 30, 0)
+        );
+        assert_eq!(
+            amsterdam().from_utc(&NaiveDate::from_ymd(2021, 10, 31).and_hms(1, 30, 0)),
+            repeated
+        );
+    }
+
+    #[test]
+    fn the_skipped_hour_should_be_taken_as_winter_time() {
+        let skipped = NaiveDate::from_ymd(2021, 3, 28).and_hms(2, 30, 0);
+
+        assert_eq!(
+            amsterdam().to_utc(&skipped),
+            NaiveDate::from_ymd(2021, 3, 28).and_hms(1, 30, 0)
+        );
+    }
+
+    #[test]
+    fn only_an_hour_around_the_change_should_be_a_summer_time_change() {
+        let before_fallback = NaiveDate::from_ymd(2021, 10, 31).and_hms(2, 59, 58);
+
+        assert!(amsterdam().is_summer_time_change(&before_fallback, -3_600_000));
+        assert!(!amsterdam().is_summer_time_change(&before_fallback, -30_000));
+        assert!(!amsterdam().is_summer_time_change(&before_fallback, 3_600_000));
+        assert!(!amsterdam().is_summer_time_change(
+            &NaiveDate::from_ymd(2021, 10, 30).and_hms(2, 59, 58),
+            -3_600_000
+        ));
+        assert!(!TimeZone::default().is_summer_time_change(&before_fallback, -3_600_000));
+    }
+
+    #[test]
+    fn zones_should_be_named_or_fixed_offsets() {
+        assert_eq!(
+            "+05:30".parse::<TimeZone>(),
+            Ok(TimeZone {
+                zone: Zone::Fixed(FixedOffset::east(19_800))
+            })
+        );
+        assert!("-03:00".parse::<TimeZone>().is_ok());
+        assert!("Mars/Olympus".parse::<TimeZone>().unwrap_err().starts_with(
+            "'Mars/Olympus' isn't a supported time zone, use a zone of the tz database"
+        ));
+        assert!("+1".parse::<TimeZone>().is_err());
+        assert_eq!(
+            serde_json::to_string(
+                &["Europe/Lisbon", "+05:30"].map(|zone| zone.parse::<TimeZone>().unwrap())
+            )
+            .unwrap(),
+            "[\"Europe/Lisbon\",\"+05:30\"]"
+        );
+    }
+}
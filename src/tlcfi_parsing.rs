@@ -1,82 +1,822 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, VecDeque},
+    convert::TryFrom,
+    io::{self, BufRead},
+};
+
+use chrono::NaiveDateTime;
 use json::{parse, JsonValue};
 
-use tlcfi_assimilator::{AssimilationData, TimestampedChanges};
+use crate::{
+    AssimilationData, Console, ControllerReset, DetectorState, IntersectionState, LineErrorKind,
+    MessageDirection, OutputState, RejectedLine, RetiredObject, RpcError, SessionInfo,
+    SignalPrediction, SignalState, SpecialVehicleEvent, TimeSource, TimestampedChanges,
+};
 
-const MAX_TICKS: u64 = 4294967295;
+use crate::log_pattern::LogPattern;
+use crate::name_normalization::normalize_name;
 
-pub fn find_first_tick(first_line_json: &str) -> Option<u64> {
-    let json_res = parse(first_line_json);
-    match json_res {
-        Ok(json_obj) => match &json_obj["params"]["ticks"] {
-            JsonValue::Number(number) => {
-                Option::Some(number.as_fixed_point_u64(0).expect(&format!(
-                    "The following TLC FI message was faulty and did not have a tick: {:#}",
-                    &json_obj
-                )))
+/// The largest TLC-FI tick, after which the ticks overflow to 0.
+pub const MAX_TICKS: u64 = 4294967295;
+
+const INVALID_JSON_ERROR: &str = "Failed to parse json string";
+
+const MISSING_LINE_TIME_ERROR: &str = "Expected a time at the start of the log line";
+
+/// Where the methods that carry states have their `update` list in their `params`. Some TLCs send detector
+/// transitions as events, with the update nested in the event. Messages of other methods are counted as unsupported.
+const UPDATE_PATHS: [(&str, &[&str]); 2] = [
+    ("UpdateState", &["update"]),
+    ("NotifyEvent", &["event", "update"]),
+];
+
+/// Reads the lines of a log, oldest first, calling `on_line` with the length of every line that was read.
+/// A log that isn't chronological has its newest line first, so its lines are reversed. Fails when the log can't be
+/// read to its end, e.g. when a compressed log is corrupt.
+pub fn read_lines<R: BufRead>(
+    reader: R,
+    is_chronological: bool,
+    console: &mut Console,
+    on_line: &mut dyn FnMut(usize),
+) -> io::Result<Vec<String>> {
+    let mut time_sorted_lines = Vec::new();
+    for line_res in reader.lines() {
+        match line_res {
+            Ok(line) => {
+                on_line(line.len());
+                time_sorted_lines.push(line);
+            }
+            // A line that isn't UTF-8 is skipped, any other error means the rest can't be read
+            Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+                console.repeated_warning("failed to read {} line(s)", &format!("{:?}", error))
+            }
+            Err(error) => return Err(error),
+        }
+    }
+    if !is_chronological {
+        time_sorted_lines.reverse();
+    }
+    Ok(time_sorted_lines)
+}
+
+/// A line that strict parsing stopped at.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LineProblem {
+    /// The index of the line in the sorted lines, oldest first
+    pub line_index: usize,
+    pub line: String,
+    pub reason: String,
+}
+
+/// Parses the sorted lines of the data into its changes, calling `on_line` with the length of every line before it's parsed.
+///
+/// Only the messages in the `direction` are converted, the first tick is taken from the first of them. The changes of
+/// both directions are ordered by their time, since the ticks of the two directions don't have to alternate in the log.
+/// When the time source of the data is the log, the times are taken from the line times and the ticks are ignored.
+///
+/// Lines that don't split or whose TLC-FI message fails to parse are counted in the line errors and skipped, unless
+/// `strict` is set, in which case parsing stops at the first of them. Lenient parsing never returns an error.
+/// A message that is wrapped onto lines without a timestamp is parsed as one line, and skipped like a line with invalid
+/// JSON when those lines end before the message does.
+pub fn parse_lines(
+    data: &mut AssimilationData,
+    direction: MessageDirection,
+    invert_direction: bool,
+    strict: bool,
+    on_line: &mut dyn FnMut(usize),
+) -> Result<(), LineProblem> {
+    // Taken out of the data while they're parsed, since parsing changes the data
+    let sorted_lines = std::mem::take(&mut data.sorted_lines);
+    let parsed = parse_line_stream(
+        data,
+        sorted_lines.iter().cloned(),
+        direction,
+        invert_direction,
+        strict,
+        on_line,
+    );
+    data.sorted_lines = sorted_lines;
+    parsed
+}
+
+/// Parses lines like [parse_lines](fn.parse_lines.html), taking them oldest first from an iterator instead of the
+/// sorted lines of the data, so a log doesn't have to be in memory to be parsed. Only the lines after a line that its
+/// message may be wrapped onto are read ahead.
+pub fn parse_line_stream<I: Iterator<Item = String>>(
+    data: &mut AssimilationData,
+    lines: I,
+    direction: MessageDirection,
+    invert_direction: bool,
+    strict: bool,
+    on_line: &mut dyn FnMut(usize),
+) -> Result<(), LineProblem> {
+    let mut lines = lines.peekable();
+    // The lines without a timestamp that were read ahead, but that the message before them isn't wrapped onto
+    let mut unjoined_lines = VecDeque::new();
+    let mut next_line_index = 0;
+    while let Some(line) = unjoined_lines.pop_front().or_else(|| lines.next()) {
+        let line_index = next_line_index;
+        next_line_index += 1;
+        on_line(line.len());
+        data.stats.lines_read += 1;
+        // Some appenders wrap long messages onto lines without a timestamp, which are joined back into one line
+        let mut next_lines = Vec::new();
+        if data.log_pattern.line_time(&line).is_some() {
+            while let Some(next_line) =
+                lines.next_if(|next_line| data.log_pattern.line_time(next_line).is_none())
+            {
+                next_lines.push(next_line);
+            }
+        }
+        let line = match join_wrapped_line(&line, &next_lines, &data.log_pattern) {
+            WrappedLine::Whole => {
+                unjoined_lines.extend(next_lines);
+                line
+            }
+            WrappedLine::Joined(joined_line, wrapped_lines) => {
+                unjoined_lines.extend(next_lines.split_off(wrapped_lines));
+                count_wrapped_lines(data, &next_lines, on_line);
+                next_line_index += wrapped_lines;
+                joined_line
+            }
+            WrappedLine::Unterminated(wrapped_lines) => {
+                if strict {
+                    return Err(LineProblem {
+                        line_index,
+                        line,
+                        reason: "the message is wrapped onto lines that end before it does"
+                            .to_string(),
+                    });
+                }
+                data.console.repeated_warning(
+                    "discarded {} message(s) wrapped onto lines that end before the message does",
+                    &line,
+                );
+                data.stats.lines_skipped += 1 + wrapped_lines;
+                data.stats
+                    .line_errors
+                    .add(LineErrorKind::InvalidJson, &line);
+                reject_line(
+                    data,
+                    line_index,
+                    LineErrorKind::InvalidJson.reject_reason(),
+                    &line,
+                );
+                count_wrapped_lines(data, &next_lines, on_line);
+                next_line_index += wrapped_lines;
+                continue;
+            }
+        };
+        // Checked before the line is split, since the lines of other levels can be large dumps that don't split well
+        if let (Some(log_levels), Some(level)) =
+            (&data.log_levels, data.log_pattern.log_level(&line))
+        {
+            if !log_levels
+                .iter()
+                .any(|log_level| level.eq_ignore_ascii_case(log_level))
+            {
+                data.stats.lines_of_other_levels += 1;
+                continue;
+            }
+        }
+        let filtered_line = line.replace("\"\"", "\"");
+        let split_line = data.log_pattern.split(&filtered_line);
+        // Other components can log into the same file, with messages that look like TLC-FI ones
+        if let Some(logger_name) = &data.logger_name {
+            if !split_line[0].contains(logger_name.as_str()) {
+                data.stats.lines_of_other_loggers += 1;
+                continue;
+            }
+        }
+
+        if split_line.len() != 3 {
+            if strict {
+                return Err(LineProblem {
+                    line_index,
+                    line,
+                    reason: "the line doesn't split into three parts on '- '".to_string(),
+                });
+            }
+            // This program is only familiar with lines that split into three parts with "- "
+            data.console
+                .repeated_warning("skipped {} line(s) that did not split on '- '", &line);
+            data.stats.lines_skipped += 1;
+            data.stats
+                .line_errors
+                .add(LineErrorKind::Unsplittable, &line);
+            reject_line(
+                data,
+                line_index,
+                LineErrorKind::Unsplittable.reject_reason(),
+                &line,
+            );
+            continue;
+        }
+
+        if split_line[1].contains("IN") {
+            data.stats.in_lines += 1;
+        } else if split_line[1].contains("OUT") {
+            data.stats.out_lines += 1;
+        }
+
+        // Sessions are registered and the meta is read before the first tick, by requests and responses in
+        // opposite directions
+        if may_be_session_setup(split_line[2])
+            && parse(split_line[2]).is_ok_and(|json_obj| {
+                record_session(&json_obj, data) || record_meta(&json_obj, data)
+            })
+        {
+            continue;
+        }
+
+        // Only consider messages in the chosen direction, from the TLC unless told otherwise.
+        if direction.accepts(split_line[1], invert_direction) {
+            // Error responses have no tick, so they are recorded before the first tick too
+            if split_line[2].contains("\"error\"")
+                && parse(split_line[2]).is_ok_and(|json_obj| {
+                    record_rpc_error(&json_obj, data.log_pattern.line_time(&line), data)
+                })
+            {
+                continue;
+            }
+            // A session starts at the first tick after its registration
+            if let Some(session) = data
+                .stats
+                .sessions
+                .last_mut()
+                .filter(|session| session.tick.is_none())
+            {
+                session.tick = find_first_tick(split_line[2], &data.update_methods);
+            }
+            if data.first_tick.is_none() && data.time_source == TimeSource::Ticks {
+                data.first_tick = find_first_tick(split_line[2], &data.update_methods);
+            }
+            if data.first_tick.is_some() || data.time_source == TimeSource::Log {
+                let parsed = match data.time_source {
+                    TimeSource::Ticks => parse_string(split_line[2], None, data),
+                    TimeSource::Log => match data.log_pattern.line_time(&line) {
+                        Some(line_time) => parse_string(split_line[2], Some(line_time), data),
+                        None => Err(MISSING_LINE_TIME_ERROR.to_string()),
+                    },
+                };
+                match parsed {
+                    Ok(timestamped_changes_res) => data.changes.extend(timestamped_changes_res),
+                    Err(error) if strict => {
+                        return Err(LineProblem {
+                            line_index,
+                            line,
+                            reason: error,
+                        })
+                    }
+                    Err(error) => {
+                        data.console.repeated_warning(
+                            "skipped {} line(s) with a TLC-FI message that failed to parse",
+                            &format!("{}: {}", error, line),
+                        );
+                        data.stats.lines_skipped += 1;
+                        let error_kind = message_error_kind(&error);
+                        data.stats.line_errors.add(error_kind, &line);
+                        reject_line(data, line_index, error_kind.reject_reason(), &line);
+                    }
+                }
+            } else if parse(split_line[2]).is_err() {
+                if strict {
+                    return Err(LineProblem {
+                        line_index,
+                        line,
+                        reason: INVALID_JSON_ERROR.to_string(),
+                    });
+                }
+                data.console.repeated_warning(
+                    "skipped {} line(s) with a TLC-FI message that failed to parse",
+                    &format!("{}: {}", INVALID_JSON_ERROR, line),
+                );
+                data.stats.lines_skipped += 1;
+                data.stats
+                    .line_errors
+                    .add(LineErrorKind::InvalidJson, &line);
+                reject_line(
+                    data,
+                    line_index,
+                    LineErrorKind::InvalidJson.reject_reason(),
+                    &line,
+                );
+            } else {
+                data.console
+                    .repeated_warning("skipped {} line(s) before the first tick was found", &line);
+                data.stats.lines_skipped += 1;
+                reject_line(data, line_index, "no-first-tick", &line);
             }
-            _ => Option::None,
+        }
+    }
+    if direction == MessageDirection::Both {
+        // A stable sort, so changes at the same time keep the order of the log
+        data.changes
+            .sort_by_key(|timestamped_changes| timestamped_changes.ms_from_beginning);
+    }
+    Ok(())
+}
+
+/// A log line, with the lines after it that a log appender wrapped its message onto.
+#[derive(Debug, PartialEq, Eq)]
+enum WrappedLine {
+    /// The message of the line isn't wrapped
+    Whole,
+    /// The line with the lines its message is wrapped onto appended, and the number of those lines
+    Joined(String, usize),
+    /// The message is wrapped onto the number of lines, but they end before the message does
+    Unterminated(usize),
+}
+
+/// Counts the lines a message is wrapped onto as read.
+fn count_wrapped_lines(
+    data: &mut AssimilationData,
+    wrapped_lines: &[String],
+    on_line: &mut dyn FnMut(usize),
+) {
+    for wrapped_line in wrapped_lines {
+        on_line(wrapped_line.len());
+        data.stats.lines_read += 1;
+    }
+}
+
+/// Joins a message that is wrapped onto the lines after its line, which have no timestamp, back into one line. The
+/// lines are appended until the braces of the message balance.
+fn join_wrapped_line(line: &str, next_lines: &[String], log_pattern: &LogPattern) -> WrappedLine {
+    let wrapped_lines = next_lines
+        .iter()
+        .take_while(|next_line| log_pattern.line_time(next_line).is_none())
+        .count();
+    let message_depth = |line: &str| brace_depth(log_pattern.split(line).get(2).unwrap_or(&""));
+    if wrapped_lines == 0 || log_pattern.line_time(line).is_none() || message_depth(line) <= 0 {
+        return WrappedLine::Whole;
+    }
+    let mut joined_line = line.to_string();
+    for (index, next_line) in next_lines[..wrapped_lines].iter().enumerate() {
+        joined_line.push_str(next_line);
+        if message_depth(&joined_line) <= 0 {
+            return WrappedLine::Joined(joined_line, index + 1);
+        }
+    }
+    WrappedLine::Unterminated(wrapped_lines)
+}
+
+/// How many more braces the JSON opens than it closes, leaving out the ones in strings.
+fn brace_depth(json_str: &str) -> i64 {
+    let (mut depth, mut in_string, mut is_escaped) = (0, false, false);
+    for character in json_str.chars() {
+        match character {
+            _ if is_escaped => is_escaped = false,
+            '\\' if in_string => is_escaped = true,
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+/// Keeps a skipped line for the reject file, when the data keeps them.
+fn reject_line(data: &mut AssimilationData, line_index: usize, reason: &'static str, line: &str) {
+    if let Some(rejected_lines) = &mut data.rejected_lines {
+        rejected_lines.push(RejectedLine {
+            line_index,
+            reason,
+            line: line.to_string(),
+        });
+    }
+}
+
+/// What was wrong with a TLC-FI message, from the error [parse_string] returned for it.
+fn message_error_kind(error: &str) -> LineErrorKind {
+    if error == INVALID_JSON_ERROR {
+        LineErrorKind::InvalidJson
+    } else if error.starts_with("Unknown ") {
+        LineErrorKind::UnknownState
+    } else {
+        LineErrorKind::MalformedMessage
+    }
+}
+
+/// Splits a log line into its time, its direction and its TLC-FI message on the first two `- `, since the message can
+/// have `- ` in it, like in the detector name `D7 - lus2`. A log line splits into these three parts.
+pub fn split_log_line(line: &str) -> Vec<&str> {
+    line.splitn(3, "- ").collect()
+}
+
+/// The time at the start of a log line, e.g. `2021-12-15 11:00:00,074`.
+pub fn log_line_time(line: &str) -> Option<NaiveDateTime> {
+    log_line_timestamp(line).and_then(|timestamp| parse_log_timestamp(timestamp).ok())
+}
+
+/// The text of the timestamp at the start of a log line: the date and the time with the milliseconds after a comma
+/// or a dot, or without milliseconds. Whether it's a valid time is up to [parse_log_timestamp].
+pub fn log_line_timestamp(line: &str) -> Option<&str> {
+    let seconds_end = "2021-12-15 11:00:00".len();
+    let rest = line.get(seconds_end..)?;
+    let fraction_length = match rest.chars().next() {
+        Some(',') | Some('.') => match rest[1..].chars().take_while(char::is_ascii_digit).count() {
+            0 => 0,
+            digits => 1 + digits,
         },
-        Err(_) => Option::None,
+        _ => 0,
+    };
+    Some(&line[..seconds_end + fraction_length])
+}
+
+/// Parses the timestamp of a log line, like `2021-12-15 11:00:00,074`, `2021-12-15 11:00:00.074` or
+/// `2021-12-15 11:00:00`. A `T` may separate the date and the time.
+pub fn parse_log_timestamp(timestamp: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(
+        &timestamp.replace(',', ".").replace('T', " "),
+        "%Y-%m-%d %H:%M:%S%.f",
+    )
+    .map_err(|_| {
+        format!(
+            "the log line starts with '{}' instead of a timestamp like 2021-12-15 11:00:00,074",
+            timestamp
+        )
+    })
+}
+
+/// The log level of a log line, the word after its timestamp, e.g. `INFO` in
+/// `2021-12-15 11:00:00,074 INFO  tlcFiMessages:41 - IN - {...}`. None for lines without a timestamp.
+pub fn log_level(line: &str) -> Option<&str> {
+    let timestamp = log_line_timestamp(line)?;
+    parse_log_timestamp(timestamp).ok()?;
+    line[timestamp.len()..].split_whitespace().next()
+}
+
+/// Whether the direction token of a log line (`IN` or `OUT`) denotes a message coming from the TLC.
+/// Some logging wrappers log from the TLC's perspective, for which `invert_direction` swaps the meaning.
+pub fn is_from_tlc(direction: &str, invert_direction: bool) -> bool {
+    if invert_direction {
+        direction.contains("OUT")
+    } else {
+        direction.contains("IN")
+    }
+}
+
+/// Returns the tick of a state update, since the messages with other methods can have ticks that aren't of an update.
+/// The `update_methods` of vendors are state updates too.
+pub fn find_first_tick(first_line_json: &str, update_methods: &[String]) -> Option<u64> {
+    let json_obj = parse(first_line_json).ok()?;
+    find_updates(&json_obj, update_methods)?;
+    tick_of(&json_obj)
+}
+
+/// Returns the tick of any message, also of the ones that aren't state updates.
+pub fn find_tick(line_json: &str) -> Option<u64> {
+    parse(line_json).ok().and_then(|json_obj| tick_of(&json_obj))
+}
+
+fn tick_of(json_obj: &JsonValue) -> Option<u64> {
+    // Ticks outside the range of TLC-FI ticks can't be the first tick
+    unquoted_number(&json_obj["params"]["ticks"])
+        .as_fixed_point_u64(0)
+        .filter(|tick| *tick <= MAX_TICKS)
+}
+
+/// Reads a number that some loggers write as a string, like `"ticks":"2181449574"`, as that number. Other values,
+/// also strings without a number, are kept as they are.
+fn unquoted_number(value: &JsonValue) -> Cow<'_, JsonValue> {
+    match value
+        .as_str()
+        .and_then(|text| text.trim().parse::<u64>().ok())
+    {
+        Some(number) => Cow::Owned(JsonValue::from(number)),
+        None => Cow::Borrowed(value),
+    }
+}
+
+/// Like [unquoted_number], counting the numbers that were written as a string in a warning.
+fn read_number<'a>(
+    value: &'a JsonValue,
+    field: &str,
+    data: &mut AssimilationData,
+) -> Cow<'a, JsonValue> {
+    let number = unquoted_number(value);
+    if let Cow::Owned(_) = number {
+        data.console.repeated_warning(
+            "read {} number(s) that were written as a string",
+            &format!("{} {}", field, value.dump()),
+        );
     }
+    number
 }
 
+/// Parses a TLC-FI message into its changes. Their time is relative to the first tick,
+/// or to the first line time of the data when the `line_time` of the message is given.
+/// The messages that register a session have no changes, their session is recorded in the stats. The responses with
+/// meta are kept in the meta of the data. Error responses are recorded in the stats, with the `line_time` when given.
 pub fn parse_string(
     json_str: &str,
+    line_time: Option<NaiveDateTime>,
     data: &mut AssimilationData,
 ) -> Result<Vec<TimestampedChanges>, String> {
     let json_res = parse(json_str);
     match json_res {
-        Ok(json_obj) => parse_json(json_obj, data),
-        Err(_) => Err("Failed to parse json string".to_string()),
+        Ok(json_obj) => {
+            if record_session(&json_obj, data)
+                || record_meta(&json_obj, data)
+                || record_rpc_error(&json_obj, line_time, data)
+            {
+                return Ok(Vec::new());
+            }
+            parse_json(json_obj, line_time, data)
+        }
+        Err(_) => Err(INVALID_JSON_ERROR.to_string()),
+    }
+}
+
+/// Whether a TLC-FI message can register a session or have meta, to only parse the lines that can be before the
+/// first tick.
+fn may_be_session_setup(json_str: &str) -> bool {
+    json_str.contains("\"Register\"")
+        || json_str.contains("\"sessionid\"")
+        || json_str.contains("\"meta\"")
+}
+
+/// Records a JSON-RPC error response, returning whether the message is one. Its code, message and id are kept when
+/// they are there.
+fn record_rpc_error(
+    json_obj: &JsonValue,
+    line_time: Option<NaiveDateTime>,
+    data: &mut AssimilationData,
+) -> bool {
+    let error = &json_obj["error"];
+    if !error.is_object() {
+        return false;
+    }
+    let id = &json_obj["id"];
+    data.stats.rpc_errors.push(RpcError {
+        line_time,
+        code: error["code"].as_i64(),
+        message: error["message"].as_str().map(str::to_string),
+        id: id.as_str().map(str::to_string).or_else(|| id.as_u64().map(|id| id.to_string())),
+    });
+    true
+}
+
+/// Records the session of a `Register` request or of the response of the TLC to it, returning whether the message
+/// is one of them. The fields are looked up by name and any of them can be absent, TLCs differ in what they send.
+fn record_session(json_obj: &JsonValue, data: &mut AssimilationData) -> bool {
+    if json_obj["method"].as_str() == Some("Register") {
+        let params = &json_obj["params"];
+        data.stats.sessions.push(SessionInfo {
+            tick: params["ticks"].as_u64(),
+            username: params["username"].as_str().map(str::to_string),
+            application_type: params["type"].as_u64(),
+            protocol_version: protocol_version(&params["version"]),
+            uri: params["uri"].as_str().map(str::to_string),
+            session_id: None,
+        });
+        true
+    } else if !json_obj["result"]["sessionid"].is_null() {
+        let result = &json_obj["result"];
+        // A response without its request in the log still starts a session
+        let has_request = data
+            .stats
+            .sessions
+            .last()
+            .is_some_and(|session| session.session_id.is_none());
+        if !has_request {
+            data.stats.sessions.push(SessionInfo::default());
+        }
+        if let Some(session) = data.stats.sessions.last_mut() {
+            let session_id = &result["sessionid"];
+            session.session_id = session_id
+                .as_str()
+                .map(str::to_string)
+                .or_else(|| session_id.as_u64().map(|session_id| session_id.to_string()));
+            session.protocol_version = session
+                .protocol_version
+                .take()
+                .or_else(|| protocol_version(&result["version"]));
+            session.tick = session.tick.or_else(|| result["ticks"].as_u64());
+        }
+        true
+    } else {
+        false
+    }
+}
+
+/// Keeps the objects of a `ReadMeta` response in the meta of the data, returning whether the message is one.
+/// The meta of an object is matched to its id by position, unless it has an `id` itself. Objects without an `index`
+/// can't be mapped, they are warned about.
+fn record_meta(json_obj: &JsonValue, data: &mut AssimilationData) -> bool {
+    let result = &json_obj["result"];
+    if !result["meta"].is_array() {
+        return false;
+    }
+    let object_type = result["objects"]["type"].as_u64();
+    let ids: Vec<&JsonValue> = result["objects"]["ids"].members().collect();
+    for (position, meta) in result["meta"].members().enumerate() {
+        let name = match meta["id"]
+            .as_str()
+            .or_else(|| ids.get(position).and_then(|id| id.as_str()))
+        {
+            Some(name) => normalized_name(name, data),
+            None => continue,
+        };
+        if object_type == Some(2) {
+            data.meta.tlc_name = Some(name);
+            continue;
+        }
+        let mapping = match object_type {
+            Some(3) => &mut data.meta.signals,
+            Some(4) => &mut data.meta.detectors,
+            Some(5) => &mut data.meta.outputs,
+            _ => continue,
+        };
+        match meta["index"].as_i16() {
+            Some(index) => {
+                mapping.insert(name, index);
+            }
+            None => data
+                .console
+                .repeated_warning("skipped {} object(s) in the meta without an index", &name),
+        }
+    }
+    true
+}
+
+/// The TLC-FI version of a `version` field, which is an object with a `major`, `minor` and `revision`, like `1.1.0`.
+/// A version without a revision is just `major.minor`.
+fn protocol_version(version: &JsonValue) -> Option<String> {
+    if let Some(version) = version.as_str() {
+        return Some(version.to_string());
+    }
+    let parts: Vec<String> = ["major", "minor", "revision"]
+        .iter()
+        .map_while(|part| version[*part].as_u64().map(|number| number.to_string()))
+        .collect();
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("."))
     }
 }
 
+/// The `update` list of a message, at the path of its method in [UPDATE_PATHS] or at the path of `UpdateState` for
+/// the `update_methods` of vendors. None for messages with other methods or without one.
+fn find_updates<'a>(json_obj: &'a JsonValue, update_methods: &[String]) -> Option<&'a JsonValue> {
+    let method = json_obj["method"].as_str()?;
+    let path = match UPDATE_PATHS.iter().find(|(name, _)| *name == method) {
+        Some((_, path)) => *path,
+        None if update_methods.iter().any(|name| name == method) => UPDATE_PATHS[0].1,
+        None => return None,
+    };
+    Some(
+        path.iter()
+            .fold(&json_obj["params"], |value, key| &value[*key]),
+    )
+}
+
+/// Parses every element of the `update` list of a message, which can batch the updates of several object types in
+/// one message, into a change per element at the time of the message.
 fn parse_json(
     json_obj: JsonValue,
+    line_time: Option<NaiveDateTime>,
     data: &mut AssimilationData,
 ) -> Result<Vec<TimestampedChanges>, String> {
-    let timestamped_changes = vec![];
-
-    let message_type = match &json_obj["params"]["update"][0]["objects"]["type"] {
-        JsonValue::Number(number) => number.as_fixed_point_u64(0).expect(&format!(
-            "The following TLC FI message was faulty and had a object type value that was outside the expected range: {:#}",
-            &json_obj
-        )),
-        _ => 0,
+    let updates: Vec<&JsonValue> = match find_updates(&json_obj, &data.update_methods) {
+        Some(updates) => updates.members().collect(),
+        None => {
+            data.stats.unsupported_messages += 1;
+            let method = json_obj["method"].as_str().unwrap_or("(none)");
+            *data.stats.other_methods.entry(method.to_string()).or_default() += 1;
+            return Ok(Vec::new());
+        }
     };
+    let mut change_types = Vec::new();
+    for update in &updates {
+        let object_type = read_number(
+            &update["objects"]["type"],
+            "params.update.objects.type",
+            data,
+        );
+        let change_type = match &*object_type {
+            JsonValue::Number(number) => match number.as_fixed_point_u64(0) {
+                Some(2) => Some(ChangeType::Intersection),
+                Some(3) => Some(ChangeType::Signal),
+                Some(4) => Some(ChangeType::Detector),
+                Some(5) => Some(ChangeType::Output),
+                Some(7) => Some(ChangeType::SpecialVehicleEvent),
+                Some(_) => None,
+                None => return Err(
+                    "Expected an object type in the expected range in params.update.objects.type"
+                        .to_string(),
+                ),
+            },
+            _ => None,
+        };
+        change_types.push(change_type);
+    }
+    // There are many valid message types we don't support (yet)
+    if change_types.iter().all(Option::is_none) {
+        data.stats.unsupported_messages += 1;
+        return Ok(Vec::new());
+    }
 
-    match message_type {
-        3 => parse_change_json(json_obj, data, timestamped_changes, ChangeType::Signal),
-        4 => parse_change_json(json_obj, data, timestamped_changes, ChangeType::Detector),
-        // There are many valid message types we don't support (yet)
-        _ => Ok(Vec::new()),
+    // Skipped change types still count for the tick bookkeeping, so overflows aren't mistaken for resets
+    let ms_from_beginning = find_ms_from_beginning(&json_obj, line_time, data)?;
+    let message_tick = unquoted_number(&json_obj["params"]["ticks"]).as_u64();
+    let mut timestamped_changes = Vec::new();
+    for (update, change_type) in updates.into_iter().zip(change_types) {
+        let change_type = match change_type {
+            Some(ChangeType::Signal) if data.conversion_mode.converts_signals() => {
+                ChangeType::Signal
+            }
+            Some(ChangeType::Detector) if data.conversion_mode.converts_detectors() => {
+                ChangeType::Detector
+            }
+            Some(ChangeType::Output) if data.conversion_mode.converts_outputs() => {
+                ChangeType::Output
+            }
+            // Intersections aren't written, so the conversion mode doesn't apply to them
+            Some(ChangeType::Intersection) => ChangeType::Intersection,
+            // Events don't change a state, they are kept in the stats for reports
+            Some(ChangeType::SpecialVehicleEvent) => {
+                let events = parse_special_vehicle_events(update, ms_from_beginning, data);
+                data.stats.special_vehicle_events.extend(events);
+                continue;
+            }
+            _ => continue,
+        };
+        // Removed objects are announced with an update that has `"deleted": true` (or `"removed": true`) instead of states
+        let is_removal =
+            update["deleted"].as_bool() == Some(true) || update["removed"].as_bool() == Some(true);
+        let changes = match (is_removal, change_type) {
+            // Only signal groups and detectors are retired
+            (true, ChangeType::Intersection) | (true, ChangeType::Output) => None,
+            (true, change_type) => {
+                parse_removal_json(update, ms_from_beginning, data, change_type)?
+            }
+            (false, change_type) => {
+                parse_change_json(update, ms_from_beginning, message_tick, data, change_type)?
+            }
+        };
+        timestamped_changes.extend(changes);
     }
+    Ok(timestamped_changes)
+}
+
+/// The tick of a message for an error about it, which the message may not have.
+fn describe_tick(message_tick: Option<u64>) -> String {
+    message_tick.map_or_else(|| "unknown".to_string(), |tick| tick.to_string())
 }
 
+/// Parses the states of an element of `params.update` into a change, None when it has no ids or states.
+/// The predictions of signal states are kept in the data when it keeps them, also for states without a current state.
 fn parse_change_json(
-    json_obj: JsonValue,
+    update: &JsonValue,
+    ms_from_beginning: u64,
+    message_tick: Option<u64>,
     data: &mut AssimilationData,
-    mut timestamped_changes: Vec<TimestampedChanges>,
     change_type: ChangeType,
-) -> Result<Vec<TimestampedChanges>, String> {
-    let ms_from_beginning = find_ms_from_beginning(&json_obj, data);
-
-    let update = &json_obj["params"]["update"][0];
-
+) -> Result<Option<TimestampedChanges>, String> {
     if update["objects"]["ids"] == JsonValue::Null {
-        Ok(timestamped_changes)
+        Ok(None)
     } else {
         let ids_vec = match &update["objects"]["ids"] {
             JsonValue::Array(vec) => vec,
             _ => return Err("Expected an array in params.update.objects.ids".to_string()),
         };
 
-        let states_vec = match &update["states"] {
+        let mut states_vec: &[JsonValue] = match &update["states"] {
             JsonValue::Array(vec) => vec,
             _ => return Err("Expected an array in params.update.states".to_string()),
         };
 
-        assert_eq!(ids_vec.len(), states_vec.len(), "We assume that the amount of IDs and detector states is equal, but for the following tlc-fi json it wasn't:\n{:#}.", json_obj);
+        // Some TLCs pad the states with nulls, which have no ID to belong to
+        if states_vec.len() > ids_vec.len()
+            && states_vec[ids_vec.len()..]
+                .iter()
+                .all(|state| state.is_null() || state["state"].is_null())
+        {
+            data.console.repeated_warning(
+                "ignored the trailing null states of {} message(s) with more states than IDs",
+                &format!(
+                    "{} IDs and {} states at tick {}",
+                    ids_vec.len(),
+                    states_vec.len(),
+                    describe_tick(message_tick)
+                ),
+            );
+            states_vec = &states_vec[..ids_vec.len()];
+        }
+
+        if ids_vec.len() != states_vec.len() {
+            return Err(format!(
+                "Expected as many states in params.update.states as IDs in params.update.objects.ids, got {} IDs and {} states at tick {}",
+                ids_vec.len(),
+                states_vec.len(),
+                describe_tick(message_tick)
+            ));
+        }
 
         let mut names = Vec::new();
         let mut states: Vec<u64> = Vec::new();
@@ -91,75 +831,380 @@ fn parse_change_json(
                     ),
                 };
 
-            let state_num = match &states_vec[i]["state"] {
-                JsonValue::Number(number) => number.as_fixed_point_u64(0).expect(&format!(
-                    "The following TLC FI message was faulty and had a state value that was outside the expected range: {:#}",
-                    &json_obj
-                )),
-                JsonValue::Null => continue,
-                _ => return Err("Expected a number in list of states in params.update.states".to_string()),
+            if let (ChangeType::Signal, Some(message_tick), true) =
+                (&change_type, message_tick, data.predictions.is_some())
+            {
+                parse_predictions(
+                    &states_vec[i]["predictions"],
+                    name,
+                    ms_from_beginning,
+                    message_tick,
+                    data,
+                );
+            }
+
+            let state = read_number(&states_vec[i]["state"], "params.update.states.state", data);
+            let state_num = match &*state {
+                JsonValue::Number(number) => number.as_fixed_point_u64(0).ok_or_else(|| {
+                    "Expected a state in the expected range in params.update.states".to_string()
+                })?,
+                JsonValue::Null => {
+                    match change_type {
+                        ChangeType::Signal => data.stats.null_signal_states += 1,
+                        ChangeType::Detector => data.stats.null_detector_states += 1,
+                        ChangeType::Output => data.stats.null_output_states += 1,
+                        _ => {}
+                    }
+                    continue;
+                }
+                _ => {
+                    return Err(
+                        "Expected a number in list of states in params.update.states".to_string(),
+                    )
+                }
             };
 
-            &names.push(name.to_string());
-            &states.push(state_num.into());
+            names.push(normalized_name(name, data));
+            states.push(state_num);
         }
+        warn_about_changes_after_retirement(&names, &change_type, ms_from_beginning, data);
 
-        if !names.is_empty() {
-            match change_type {
-                ChangeType::Detector => {
-                    let mut detector_states = Vec::new();
-                    for state in states {
-                        detector_states.push(state.into());
-                    }
-                    timestamped_changes.push(TimestampedChanges {
-                        ms_from_beginning,
-                        detector_names: names,
-                        detector_states,
-                        ..Default::default()
-                    });
+        if names.is_empty() {
+            return Ok(None);
+        }
+        match change_type {
+            ChangeType::Detector => {
+                let mut detector_states = Vec::new();
+                for (name, state) in names.iter().zip(states) {
+                    detector_states.push(DetectorState::try_from(state).map_err(|error| {
+                        format!("{} of detector '{}' in params.update.states", error, name)
+                    })?);
+                }
+                Ok(Some(TimestampedChanges {
+                    ms_from_beginning,
+                    detector_names: names,
+                    detector_states,
+                    ..Default::default()
+                }))
+            }
+            ChangeType::Signal => {
+                let mut signal_states = Vec::new();
+                for (name, state) in names.iter().zip(states) {
+                    signal_states.push(SignalState::try_from(state).map_err(|error| {
+                        format!("{} of signal '{}' in params.update.states", error, name)
+                    })?);
+                }
+                Ok(Some(TimestampedChanges {
+                    ms_from_beginning,
+                    signal_names: names,
+                    signal_states,
+                    ..Default::default()
+                }))
+            }
+            ChangeType::Output => {
+                let mut output_states = Vec::new();
+                for state in states {
+                    output_states.push(OutputState::from_tlc_fi_state(state).ok_or_else(|| {
+                        format!("Unknown output state {} in params.update.states", state)
+                    })?);
                 }
-                ChangeType::Signal => {
-                    let mut signal_states = Vec::new();
-                    for state in states {
-                        signal_states.push(state.into());
+                Ok(Some(TimestampedChanges {
+                    ms_from_beginning,
+                    output_names: names,
+                    output_states,
+                    ..Default::default()
+                }))
+            }
+            // Special vehicle events have no states, they are parsed by parse_special_vehicle_events
+            ChangeType::SpecialVehicleEvent => Ok(None),
+            ChangeType::Intersection => {
+                // Unknown intersection states are skipped, the states VLog can't hold anyway shouldn't cost the line
+                let mut intersection_names = Vec::new();
+                let mut intersection_states = Vec::new();
+                for (name, state) in names.into_iter().zip(states) {
+                    match IntersectionState::from_tlc_fi_state(state) {
+                        Some(intersection_state) => {
+                            intersection_names.push(name);
+                            intersection_states.push(intersection_state);
+                        }
+                        None => {
+                            data.stats.unknown_intersection_states += 1;
+                            data.console.repeated_warning(
+                                "skipped {} unknown intersection state(s)",
+                                &format!(
+                                    "Unknown intersection state {} of intersection {}",
+                                    state, name
+                                ),
+                            );
+                        }
                     }
-                    timestamped_changes.push(TimestampedChanges {
-                        ms_from_beginning,
-                        signal_names: names,
-                        signal_states,
-                        ..Default::default()
-                    });
                 }
+                if intersection_names.is_empty() {
+                    return Ok(None);
+                }
+                data.stats.intersection_state_changes += 1;
+                Ok(Some(TimestampedChanges {
+                    ms_from_beginning,
+                    intersection_names,
+                    intersection_states,
+                    ..Default::default()
+                }))
+            }
+        }
+    }
+}
+
+/// Keeps the predictions of a signal state, with their ticks converted to ms from beginning relative to the tick of
+/// the message, so they follow the same bookkeeping as the message. Predictions without a known state are skipped,
+/// the ends a prediction doesn't have are left empty.
+fn parse_predictions(
+    predictions: &JsonValue,
+    name: &str,
+    ms_from_beginning: u64,
+    message_tick: u64,
+    data: &mut AssimilationData,
+) {
+    let tick_unit = data.tick_unit;
+    let to_ms = |end: &JsonValue| {
+        let end_tick = end.as_u64().filter(|tick| *tick <= MAX_TICKS)?;
+        // An end before the tick of the message is after an overflow of the ticks
+        let ticks_after_message = if end_tick >= message_tick {
+            end_tick - message_tick
+        } else {
+            MAX_TICKS - message_tick + end_tick + 1
+        };
+        Some(ms_from_beginning + tick_unit.to_ms(ticks_after_message))
+    };
+    for prediction in predictions.members() {
+        let predicted_state = match prediction["state"].as_u64() {
+            Some(state) => SignalState::from_tlc_fi_state(state),
+            None => None,
+        };
+        let predicted_state = match predicted_state {
+            Some(predicted_state) => predicted_state,
+            None => {
+                data.console.repeated_warning(
+                    "skipped {} prediction(s) without a known signal state",
+                    &format!("Prediction {} of signal {}", prediction.dump(), name),
+                );
+                continue;
+            }
+        };
+        let signal_prediction = SignalPrediction {
+            signal_name: normalize_name(name),
+            ms_from_beginning,
+            predicted_state,
+            likely_end_ms: to_ms(&prediction["likelyEnd"]),
+            min_end_ms: to_ms(&prediction["minEnd"]),
+            max_end_ms: to_ms(&prediction["maxEnd"]),
+        };
+        if let Some(predictions) = &mut data.predictions {
+            predictions.push(signal_prediction);
+        }
+    }
+}
+
+/// Parses an element of `params.update` with special vehicle event generators into an event per generator.
+/// Every field of an event is optional, so a generator without a state still gets an event without attributes.
+fn parse_special_vehicle_events(
+    update: &JsonValue,
+    ms_from_beginning: u64,
+    data: &mut AssimilationData,
+) -> Vec<SpecialVehicleEvent> {
+    let mut events = Vec::new();
+    for (index, id) in update["objects"]["ids"].members().enumerate() {
+        let name = match id.as_str() {
+            Some(name) => normalized_name(name, data),
+            None => continue,
+        };
+        let mut attributes = BTreeMap::new();
+        collect_event_attributes(&update["states"][index], &mut attributes);
+        events.push(SpecialVehicleEvent {
+            ms_from_beginning,
+            name,
+            attributes,
+        });
+    }
+    events
+}
+
+/// Collects the text, number and boolean fields of an event state, including those of the objects in it.
+fn collect_event_attributes(state: &JsonValue, attributes: &mut BTreeMap<String, String>) {
+    for (key, value) in state.entries() {
+        match value {
+            JsonValue::Object(_) => collect_event_attributes(value, attributes),
+            JsonValue::Short(_)
+            | JsonValue::String(_)
+            | JsonValue::Number(_)
+            | JsonValue::Boolean(_) => {
+                attributes.insert(key.to_string(), value.to_string());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Normalizes a name at the boundary of the log, warning the first time normalizing changes it.
+fn normalized_name(name: &str, data: &mut AssimilationData) -> String {
+    let normalized = normalize_name(name);
+    if normalized != name && data.normalized_names.insert(name.to_string()) {
+        data.console.warning(&format!(
+            "Warning: the name {:?} in the log was normalized to {:?}.",
+            name, normalized
+        ));
+    }
+    normalized
+}
+
+/// Retires the removed objects. Removed signal groups become unavailable and removed detectors faulty,
+/// which VLog shows as not available.
+fn parse_removal_json(
+    update: &JsonValue,
+    ms_from_beginning: u64,
+    data: &mut AssimilationData,
+    change_type: ChangeType,
+) -> Result<Option<TimestampedChanges>, String> {
+    let ids_vec = match &update["objects"]["ids"] {
+        JsonValue::Array(vec) => vec,
+        _ => return Err("Expected an array in params.update.objects.ids".to_string()),
+    };
+    let mut names = Vec::new();
+    for id in ids_vec {
+        match id.as_str() {
+            Some(name) => names.push(normalized_name(name, data)),
+            None => {
+                return Err(
+                    "Expected a string (or short) in list of IDs in params.update.objects.ids"
+                        .to_string(),
+                )
             }
         }
-        Ok(timestamped_changes)
+    }
+
+    let is_detector = matches!(change_type, ChangeType::Detector);
+    for name in &names {
+        let retired_object = RetiredObject {
+            ms_from_beginning,
+            name: name.clone(),
+            is_detector,
+        };
+        data.console.verbose(&format!(
+            "The {} was removed from the session.",
+            retired_object.describe()
+        ));
+        data.stats.retired_objects.push(retired_object);
+    }
+
+    if names.is_empty() {
+        Ok(None)
+    } else if is_detector {
+        Ok(Some(TimestampedChanges {
+            ms_from_beginning,
+            detector_states: vec![DetectorState::FAULTY; names.len()],
+            detector_names: names,
+            ..Default::default()
+        }))
+    } else {
+        Ok(Some(TimestampedChanges {
+            ms_from_beginning,
+            signal_states: vec![SignalState::Unavailable; names.len()],
+            signal_names: names,
+            ..Default::default()
+        }))
     }
 }
 
-fn find_ms_from_beginning(json_obj: &JsonValue, data: &mut AssimilationData) -> u64 {
-    match json_obj["params"]["ticks"] {
+/// Warns about every change of an object that was removed from the session earlier.
+fn warn_about_changes_after_retirement(
+    names: &[String],
+    change_type: &ChangeType,
+    ms_from_beginning: u64,
+    data: &mut AssimilationData,
+) {
+    let is_detector = match change_type {
+        ChangeType::Detector => true,
+        ChangeType::Signal => false,
+        ChangeType::Output | ChangeType::Intersection | ChangeType::SpecialVehicleEvent => return,
+    };
+    for name in names {
+        let retired_object = data
+            .stats
+            .retired_objects
+            .iter()
+            .find(|retired| retired.is_detector == is_detector && &retired.name == name);
+        if let Some(retired_object) = retired_object {
+            let warning = format!(
+                "Warning: {} changed at {} after it was removed from the session at {}.",
+                retired_object.describe(),
+                data.start_time + chrono::Duration::milliseconds(ms_from_beginning as i64),
+                data.start_time
+                    + chrono::Duration::milliseconds(retired_object.ms_from_beginning as i64)
+            );
+            data.console.warning(&warning);
+        }
+    }
+}
+
+fn find_ms_from_beginning(
+    json_obj: &JsonValue,
+    line_time: Option<NaiveDateTime>,
+    data: &mut AssimilationData,
+) -> Result<u64, String> {
+    // The line times need no bookkeeping of overflows and resets, they only have to be after the first one
+    if let Some(line_time) = line_time {
+        let first_line_time = *data.first_line_time.get_or_insert(line_time);
+        let ms_from_beginning = (line_time - first_line_time).num_milliseconds();
+        if ms_from_beginning < 0 {
+            return Err(
+                "Expected the log line time not to be before the line time of the first message"
+                    .to_string(),
+            );
+        }
+        return Ok(ms_from_beginning as u64);
+    }
+    match *read_number(&json_obj["params"]["ticks"], "params.ticks", data) {
         JsonValue::Number(number) => {
-            let tick = number.as_fixed_point_u64(0).expect(&format!(
-                "The following TLC FI message was faulty and had a tick value that was outside the expected range: {:#}",
-                &json_obj
-            ));
+            let tick = number
+                .as_fixed_point_u64(0)
+                .filter(|tick| *tick <= MAX_TICKS)
+                .ok_or_else(|| {
+                    "Expected a tick in the range of TLC-FI ticks in params.ticks".to_string()
+                })?;
 
-            let first_tick = data
-                .first_tick
-                .expect("First tick has to be present by now.");
-            let ms_from_beginning;
-            if tick < first_tick {
-                ms_from_beginning = handle_tick_overflow_or_reset(data, first_tick, tick);
+            let first_tick = data.first_tick.ok_or_else(|| {
+                "Expected the first tick to be found before params.ticks".to_string()
+            })?;
+            // Overflows and resets are kept track of in ticks, since the ticks overflow at MAX_TICKS whatever their unit
+            let ticks_from_beginning = if tick < first_tick {
+                handle_tick_overflow_or_reset(data, first_tick, tick)
             } else {
-                ms_from_beginning = tick - first_tick + data.bonus_ms.unwrap_or(0);
-            }
+                tick - first_tick + data.bonus_ms.unwrap_or(0)
+            };
             data.previous_tick = Some(tick);
-            ms_from_beginning
-        }
-        _ => {
-            //TODO handle this better
-            1
+            data.stats.first_tick.get_or_insert(tick);
+            data.stats.last_tick = Some(tick);
+            Ok(data.tick_unit.to_ms(ticks_from_beginning))
         }
+        _ => Err(format!(
+            "Expected a tick in params.ticks, got none in the {} message of {}",
+            json_obj["method"].as_str().unwrap_or("(none)"),
+            describe_update_ids(json_obj, &data.update_methods)
+        )),
+    }
+}
+
+/// The ids of the objects a message updates, for an error about the message.
+fn describe_update_ids(json_obj: &JsonValue, update_methods: &[String]) -> String {
+    let ids: Vec<&str> = find_updates(json_obj, update_methods)
+        .into_iter()
+        .flat_map(JsonValue::members)
+        .flat_map(|update| update["objects"]["ids"].members())
+        .filter_map(JsonValue::as_str)
+        .collect();
+    if ids.is_empty() {
+        "no objects".to_string()
+    } else {
+        ids.join(", ")
     }
 }
 
@@ -168,21 +1213,33 @@ fn handle_tick_overflow_or_reset(
     first_tick: u64,
     tick: u64,
 ) -> u64 {
-    let previous_tick = data
-        .previous_tick
-        .expect("Of course we have a previous tick by now");
+    // The message with the first tick doesn't have to be one whose tick is kept track of
+    let previous_tick = data.previous_tick.unwrap_or(first_tick);
     let small_enough_difference = 5000;
     if MAX_TICKS - previous_tick < small_enough_difference {
-        println!("Tick overflow detected.");
+        data.console.verbose(&format!(
+            "Tick overflow detected after tick {}.",
+            previous_tick
+        ));
+        data.stats.tick_overflows += 1;
         data.bonus_ms = Some(MAX_TICKS - first_tick);
         data.first_tick = Some(tick);
         data.bonus_ms
             .expect("We just set this option with something.")
             + tick
     } else {
-        println!("Tick reset detected.");
+        data.console.verbose(&format!(
+            "Tick reset detected from tick {} to {}.",
+            previous_tick, tick
+        ));
         // a reset in the tlc has happened
         data.bonus_ms = Some(previous_tick - first_tick);
+        data.stats.controller_resets.push(ControllerReset {
+            ms_from_beginning: data.tick_unit.to_ms(previous_tick - first_tick),
+            previous_tick,
+            tick,
+            changes_before: data.changes.len(),
+        });
         data.first_tick = Some(tick);
         data.bonus_ms
             .expect("We just set this option with something.")
@@ -192,13 +1249,16 @@ fn handle_tick_overflow_or_reset(
 enum ChangeType {
     Detector,
     Signal,
+    Output,
+    Intersection,
+    SpecialVehicleEvent,
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::TickUnit;
     use json::object;
-    use tlcfi_assimilator;
 
     const TEST_DETECTOR_JSON: &str = "{\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087808637,\"update\":[{\"objects\":{\"ids\":[\"D713\"],\"type\":4},\"states\":[{\"state\":1}]}]}}";
 
@@ -215,59 +1275,426 @@ mod test {
 
     #[test]
     fn first_tick_should_be_tick_of_the_message() {
-        assert_eq!(find_first_tick(TEST_DETECTOR_JSON), Some(4087808637));
+        assert_eq!(find_first_tick(TEST_DETECTOR_JSON, &[]), Some(4087808637));
     }
 
     #[test]
     fn detector_change_jsons_should_be_parsed_properly() -> Result<(), String> {
-        let expected_changes = vec![tlcfi_assimilator::TimestampedChanges {
+        let expected_changes = vec![crate::TimestampedChanges {
             ms_from_beginning: 650,
             detector_names: vec!["D713".to_string()],
-            detector_states: vec![tlcfi_assimilator::DetectorState::OCCUPIED],
+            detector_states: vec![crate::DetectorState::OCCUPIED],
             ..Default::default()
         }];
 
         assert_eq!(
-            parse_string(TEST_DETECTOR_JSON, &mut get_test_data())?,
+            parse_string(TEST_DETECTOR_JSON, None, &mut get_test_data())?,
             expected_changes
         );
         Ok(())
     }
 
+    #[test]
+    fn detector_events_should_be_parsed_like_state_updates() -> Result<(), String> {
+        let notify_event_json = r#"{"jsonrpc":"2.0","method":"NotifyEvent","params":{"ticks":4087808637,"event":{"update":[{"objects":{"ids":["D713"],"type":4},"states":[{"state":1}]}]}}}"#;
+        let subscribe_json = r#"{"jsonrpc":"2.0","method":"Subscribe","params":{"objects":{"ids":["D713"],"type":4}},"id":"7"}"#;
+        let alive_json = r#"{"jsonrpc":"2.0","method":"Alive","params":{"ticks":4087808700}}"#;
+        let mut data = get_test_data();
+
+        let event_changes = parse_string(notify_event_json, None, &mut data)?;
+        let subscribe_changes = parse_string(subscribe_json, None, &mut data)?;
+        let alive_changes = parse_string(alive_json, None, &mut data)?;
+
+        assert_eq!(
+            event_changes,
+            parse_string(TEST_DETECTOR_JSON, None, &mut get_test_data())?
+        );
+        assert!(subscribe_changes.is_empty() && alive_changes.is_empty());
+        assert_eq!(data.stats.unsupported_messages, 2);
+        assert_eq!(data.previous_tick, Some(4087808637));
+        Ok(())
+    }
+
     #[test]
     fn signal_change_jsons_should_be_parsed_properly() -> Result<(), String> {
-        let expected_changes = vec![tlcfi_assimilator::TimestampedChanges {
+        let expected_changes = vec![crate::TimestampedChanges {
             ms_from_beginning: 864,
             signal_names: vec!["71".to_string()],
-            signal_states: vec![tlcfi_assimilator::SignalState::Green],
+            signal_states: vec![crate::SignalState::Green],
             ..Default::default()
         }];
 
         assert_eq!(
-            parse_string(TEST_SIGNAL_JSON, &mut get_test_data())?,
+            parse_string(TEST_SIGNAL_JSON, None, &mut get_test_data())?,
             expected_changes
         );
         Ok(())
     }
 
     #[test]
-    fn reset_ticks() {
-        let json_obj = object! {"params" => object! {"ticks" => 29224}};
-        let initial_tick = 293219704;
-        let previous_tick = 326765322;
-        let mut test_data = AssimilationData {
-            first_tick: Some(initial_tick),
-            previous_tick: Some(previous_tick),
-            ..Default::default()
-        };
-
-        let ms_from_beginning = find_ms_from_beginning(&json_obj, &mut test_data);
+    fn numbers_written_as_strings_should_be_read_as_numbers() -> Result<(), String> {
+        let quoted_json = TEST_SIGNAL_JSON
+            .replace("4087808851", "\"4087808851\"")
+            .replace("\"state\":6", "\"state\":\"6\"")
+            .replace("\"type\":3", "\"type\":\"3\"");
+        let mut data = get_test_data();
 
-        assert_ne!(0, ms_from_beginning);
-        assert_eq!(33545618, ms_from_beginning);
-    }
+        let changes = parse_string(&quoted_json, None, &mut data)?;
 
-    // tick reset: Tick in message (29224) wasn't bigger than initial tick (293219704)!
+        assert_eq!(
+            find_first_tick(&quoted_json, &[]),
+            find_first_tick(TEST_SIGNAL_JSON, &[])
+        );
+        assert_eq!(
+            changes,
+            parse_string(TEST_SIGNAL_JSON, None, &mut get_test_data())?
+        );
+        assert_eq!(data.stats.last_tick, Some(4087808851));
+        assert_eq!(
+            data.console.summary_lines(),
+            vec!["read 3 number(s) that were written as a string"]
+        );
+        assert!(parse_string(
+            &TEST_SIGNAL_JSON.replace("\"state\":6", "\"state\":\"green\""),
+            None,
+            &mut get_test_data()
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn a_pre_movement_signal_state_should_be_converted_instead_of_skipped() {
+        let mut data = AssimilationData {
+            sorted_lines: vec![
+                "2021-12-15 12:00:00,864 INFO  tlcFiMessages:41 - IN - {\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087808851,\"update\":[{\"objects\":{\"ids\":[\"71\",\"72\"],\"type\":3},\"states\":[{\"state\":4},{\"state\":3}]}]}}".to_string(),
+            ],
+            ..get_test_data()
+        };
+
+        parse_lines(&mut data, MessageDirection::In, false, false, &mut |_| {}).unwrap();
+
+        assert_eq!(data.stats.lines_skipped, 0);
+        assert_eq!(
+            data.changes[0].signal_states,
+            vec![crate::SignalState::PreMovement, crate::SignalState::Red]
+        );
+    }
+
+    #[test]
+    fn an_update_without_a_tick_should_be_skipped_or_fail_with_strict() {
+        let tickless_line = format!(
+            "2021-12-15 12:00:01,000 INFO  tlcFiMessages:41 - IN - {}",
+            TEST_SIGNAL_JSON
+                .replace("\"ticks\":4087808851,", "")
+                .replace("[\"71\"]", "[\"71\",\"72\"]")
+                .replace("[{\"state\":6}]", "[{\"state\":6},{\"state\":3}]")
+        );
+        let lines = vec![
+            format!(
+                "2021-12-15 12:00:00,000 INFO  tlcFiMessages:41 - IN - {}",
+                TEST_DETECTOR_JSON
+            ),
+            tickless_line.clone(),
+        ];
+        let mut data = AssimilationData {
+            sorted_lines: lines.clone(),
+            ..Default::default()
+        };
+        let mut strict_data = AssimilationData {
+            sorted_lines: lines,
+            ..Default::default()
+        };
+
+        parse_lines(&mut data, MessageDirection::In, false, false, &mut |_| {}).unwrap();
+        let problem =
+            parse_lines(&mut strict_data, MessageDirection::In, false, true, &mut |_| {})
+                .unwrap_err();
+
+        assert_eq!(data.changes.len(), 1);
+        assert_eq!(data.stats.lines_skipped, 1);
+        assert_eq!(data.stats.line_errors.malformed_messages, 1);
+        assert_eq!(problem.line_index, 1);
+        assert_eq!(problem.line, tickless_line);
+        assert_eq!(
+            problem.reason,
+            "Expected a tick in params.ticks, got none in the UpdateState message of 71, 72"
+        );
+    }
+
+    #[test]
+    fn states_that_do_not_match_the_ids_should_fail_the_message_unless_they_are_trailing_nulls() {
+        let with_states = |states: &str| {
+            TEST_SIGNAL_JSON.replace("[{\"state\":6}]", &format!("[{}]", states))
+        };
+        let mut data = get_test_data();
+
+        let equal = parse_string(&with_states("{\"state\":6}"), None, &mut data);
+        let shorter = parse_string(&with_states(""), None, &mut data);
+        let longer = parse_string(&with_states("{\"state\":6},{\"state\":3}"), None, &mut data);
+        let padded_states = with_states("{\"state\":6},null,{\"state\":null}");
+        let padded = parse_string(&padded_states, None, &mut data);
+
+        assert_eq!(equal.unwrap()[0].signal_states, vec![SignalState::Green]);
+        assert_eq!(
+            shorter.unwrap_err(),
+            "Expected as many states in params.update.states as IDs in params.update.objects.ids, got 1 IDs and 0 states at tick 4087808851"
+        );
+        assert!(longer.unwrap_err().ends_with("got 1 IDs and 2 states at tick 4087808851"));
+        assert_eq!(padded.unwrap()[0].signal_states, vec![SignalState::Green]);
+        assert_eq!(
+            data.console.summary_lines(),
+            vec!["ignored the trailing null states of 1 message(s) with more states than IDs"]
+        );
+    }
+
+    #[test]
+    fn entries_without_a_state_should_be_counted_per_object_type() -> Result<(), String> {
+        let mixed_json = "{\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087808851,\"update\":[{\"objects\":{\"ids\":[\"71\",\"72\"],\"type\":3},\"states\":[{\"state\":6},{\"predictions\":[]}]},{\"objects\":{\"ids\":[\"D713\",\"D714\"],\"type\":4},\"states\":[{\"state\":null},{\"state\":null}]}]}}";
+        let mut data = get_test_data();
+
+        let changes = parse_string(mixed_json, None, &mut data)?;
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].signal_names, vec!["71".to_string()]);
+        assert_eq!(
+            (
+                data.stats.null_signal_states,
+                data.stats.null_detector_states,
+                data.stats.null_output_states
+            ),
+            (1, 2, 0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn every_element_of_a_batched_update_should_become_a_change_at_the_same_time(
+    ) -> Result<(), String> {
+        let batched_json = "{\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087808851,\"update\":[{\"objects\":{\"ids\":[\"71\"],\"type\":3},\"states\":[{\"state\":6}]},{\"objects\":{\"ids\":[\"D713\",\"D714\"],\"type\":4},\"states\":[{\"state\":1},{\"state\":0}]}]}}";
+        let expected_changes = vec![
+            crate::TimestampedChanges {
+                ms_from_beginning: 864,
+                signal_names: vec!["71".to_string()],
+                signal_states: vec![crate::SignalState::Green],
+                ..Default::default()
+            },
+            crate::TimestampedChanges {
+                ms_from_beginning: 864,
+                detector_names: vec!["D713".to_string(), "D714".to_string()],
+                detector_states: vec![crate::DetectorState::OCCUPIED, crate::DetectorState::FREE],
+                ..Default::default()
+            },
+        ];
+        let mut signals_only = AssimilationData {
+            conversion_mode: crate::ConversionMode::SignalsOnly,
+            ..get_test_data()
+        };
+
+        assert_eq!(
+            parse_string(batched_json, None, &mut get_test_data())?,
+            expected_changes
+        );
+        assert_eq!(
+            parse_string(batched_json, None, &mut signals_only)?,
+            expected_changes[..1]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn intersection_states_should_be_parsed_and_unknown_ones_counted() -> Result<(), String> {
+        let intersection_json = "{\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087808851,\"update\":[{\"objects\":{\"ids\":[\"3031\"],\"type\":2},\"states\":[{\"state\":3,\"reqState\":6,\"stateTicks\":4087808851}]}]}}";
+        let unknown_state_json = "{\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087808951,\"update\":[{\"objects\":{\"ids\":[\"3031\"],\"type\":2},\"states\":[{\"state\":42}]},{\"objects\":{\"ids\":[\"71\"],\"type\":3},\"states\":[{\"state\":6}]}]}}";
+        let mut data = get_test_data();
+
+        let intersection_changes = parse_string(intersection_json, None, &mut data)?;
+        let unknown_state_changes = parse_string(unknown_state_json, None, &mut data)?;
+
+        assert_eq!(
+            intersection_changes,
+            vec![crate::TimestampedChanges {
+                ms_from_beginning: 864,
+                intersection_names: vec!["3031".to_string()],
+                intersection_states: vec![crate::IntersectionState::AllRed],
+                ..Default::default()
+            }]
+        );
+        assert_eq!(unknown_state_changes.len(), 1);
+        assert_eq!(
+            unknown_state_changes[0].signal_names,
+            vec!["71".to_string()]
+        );
+        assert_eq!(data.stats.intersection_state_changes, 1);
+        assert_eq!(data.stats.unknown_intersection_states, 1);
+        assert_eq!(data.stats.unsupported_messages, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn the_predictions_of_the_documented_example_should_be_timed_from_the_first_tick() -> Result<(), String> {
+        let example_json = "{\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":2181449574,\"update\":[{\"objects\":{\"ids\":[\"01\"],\"type\":3},\"states\":[{\"predictions\":[{\"likelyEnd\":2181456774,\"maxEnd\":2181468174,\"minEnd\":2181456774,\"state\":6},{\"likelyEnd\":2181460774,\"maxEnd\":2181472174,\"minEnd\":2181460774,\"state\":8}]}]}]}}";
+        let mut data = AssimilationData {
+            first_tick: Some(2181448574),
+            predictions: Some(Vec::new()),
+            ..Default::default()
+        };
+        let mut without_predictions = AssimilationData {
+            first_tick: Some(2181448574),
+            ..Default::default()
+        };
+
+        let changes = parse_string(example_json, None, &mut data)?;
+        parse_string(TEST_SIGNAL_JSON, None, &mut data)?;
+        parse_string(example_json, None, &mut without_predictions)?;
+
+        assert!(changes.is_empty());
+        assert_eq!(
+            data.predictions,
+            Some(vec![
+                crate::SignalPrediction {
+                    signal_name: "01".to_string(),
+                    ms_from_beginning: 1000,
+                    predicted_state: SignalState::Green,
+                    likely_end_ms: Some(8200),
+                    min_end_ms: Some(8200),
+                    max_end_ms: Some(19_600),
+                },
+                crate::SignalPrediction {
+                    signal_name: "01".to_string(),
+                    ms_from_beginning: 1000,
+                    predicted_state: SignalState::Amber,
+                    likely_end_ms: Some(12_200),
+                    min_end_ms: Some(12_200),
+                    max_end_ms: Some(23_600),
+                },
+            ])
+        );
+        assert_eq!(without_predictions.predictions, None);
+        Ok(())
+    }
+
+    #[test]
+    fn special_vehicle_events_should_be_kept_with_the_fields_they_have() -> Result<(), String> {
+        let event_json = "{\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087808851,\"update\":[{\"objects\":{\"ids\":[\"KAR1\",\"KAR2\"],\"type\":7},\"states\":[{\"event\":{\"vehicleType\":1,\"lineNr\":\"12\",\"direction\":2,\"route\":[1,2]}}]}]}}";
+        let mut data = get_test_data();
+
+        let changes = parse_string(event_json, None, &mut data)?;
+
+        assert!(changes.is_empty());
+        assert_eq!(data.stats.unsupported_messages, 0);
+        assert_eq!(data.stats.special_vehicle_events.len(), 2);
+        assert_eq!(
+            data.stats.special_vehicle_events[0].describe(),
+            "special vehicle event 'KAR1' (direction=2, lineNr=12, vehicleType=1)"
+        );
+        assert_eq!(data.stats.special_vehicle_events[0].ms_from_beginning, 864);
+        assert_eq!(
+            data.stats.special_vehicle_events[1].describe(),
+            "special vehicle event 'KAR2'"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn output_states_should_be_parsed_only_when_converting_all_objects() -> Result<(), String> {
+        let output_json = "{\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087808851,\"update\":[{\"objects\":{\"ids\":[\"U01\",\"U02\"],\"type\":5},\"states\":[{\"state\":1},{\"state\":0}]}]}}";
+        let unknown_state_json = "{\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087808851,\"update\":[{\"objects\":{\"ids\":[\"U01\"],\"type\":5},\"states\":[{\"state\":7}]}]}}";
+        let mut detectors_only = AssimilationData {
+            conversion_mode: crate::ConversionMode::DetectorsOnly,
+            ..get_test_data()
+        };
+
+        assert_eq!(
+            parse_string(output_json, None, &mut get_test_data())?,
+            vec![crate::TimestampedChanges {
+                ms_from_beginning: 864,
+                output_names: vec!["U01".to_string(), "U02".to_string()],
+                output_states: vec![crate::OutputState::On, crate::OutputState::Off],
+                ..Default::default()
+            }]
+        );
+        assert_eq!(
+            parse_string(output_json, None, &mut detectors_only)?,
+            Vec::new()
+        );
+        assert_eq!(
+            parse_string(unknown_state_json, None, &mut get_test_data()),
+            Err("Unknown output state 7 in params.update.states".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn signal_change_jsons_should_preserve_the_red_and_green_codes() -> Result<(), String> {
+        let signal_json = "{\"jsonrpc\":\"2.0\",\"method\":\"UpdateState\",\"params\":{\"ticks\":4087808851,\"update\":[{\"objects\":{\"ids\":[\"02\",\"03\",\"05\",\"06\"],\"type\":3},\"states\":[{\"state\":2},{\"state\":3},{\"state\":5},{\"state\":6}]}]}}";
+
+        let changes = parse_string(signal_json, None, &mut get_test_data())?;
+
+        assert_eq!(
+            changes[0].signal_states,
+            vec![
+                crate::SignalState::RedStopThenProceed,
+                crate::SignalState::Red,
+                crate::SignalState::GreenPermissive,
+                crate::SignalState::Green,
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn detector_change_jsons_should_be_skipped_when_only_converting_signals() -> Result<(), String> {
+        let mut test_data = AssimilationData {
+            conversion_mode: crate::ConversionMode::SignalsOnly,
+            ..get_test_data()
+        };
+
+        assert!(parse_string(TEST_DETECTOR_JSON, None, &mut test_data)?.is_empty());
+        assert_eq!(test_data.previous_tick, Some(4087808637));
+        assert!(!parse_string(TEST_SIGNAL_JSON, None, &mut test_data)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn signal_change_jsons_should_be_skipped_when_only_converting_detectors() -> Result<(), String> {
+        let mut test_data = AssimilationData {
+            conversion_mode: crate::ConversionMode::DetectorsOnly,
+            ..get_test_data()
+        };
+
+        assert!(parse_string(TEST_SIGNAL_JSON, None, &mut test_data)?.is_empty());
+        assert!(!parse_string(TEST_DETECTOR_JSON, None, &mut test_data)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn reset_ticks() {
+        let json_obj = object! {"params" => object! {"ticks" => 29224}};
+        let initial_tick = 293219704;
+        let previous_tick = 326765322;
+        let mut test_data = AssimilationData {
+            first_tick: Some(initial_tick),
+            previous_tick: Some(previous_tick),
+            ..Default::default()
+        };
+
+        let ms_from_beginning = find_ms_from_beginning(&json_obj, None, &mut test_data).unwrap();
+
+        assert_ne!(0, ms_from_beginning);
+        assert_eq!(33545618, ms_from_beginning);
+        assert_eq!(
+            test_data.stats.controller_resets,
+            vec![ControllerReset {
+                ms_from_beginning: 33545618,
+                previous_tick,
+                tick: 29224,
+                changes_before: 0,
+            }]
+        );
+    }
+
+    // tick reset: Tick in message (29224) wasn't bigger than initial tick (293219704)!
     // tick ovrfl: previous tick was close to 4294967295
     #[test]
     fn a_message_with_a_tick_smaller_than_the_initial_tick_should_overwrite_the_initial_tick_to_handle_an_overflow(
@@ -281,9 +1708,639 @@ mod test {
             ..Default::default()
         };
 
-        let ms_from_beginning = find_ms_from_beginning(&json_obj, &mut test_data);
+        let ms_from_beginning = find_ms_from_beginning(&json_obj, None, &mut test_data).unwrap();
 
         assert_ne!(0, ms_from_beginning);
         assert_eq!(412, ms_from_beginning);
+        assert_eq!(test_data.stats.tick_overflows, 1);
+    }
+
+    #[test]
+    fn a_removed_signal_should_become_unavailable() {
+        let mut data = AssimilationData {
+            first_tick: Some(1000),
+            ..Default::default()
+        };
+
+        let changes = parse_string(
+            r#"{"jsonrpc":"2.0","method":"UpdateState","params":{"ticks":3000,"update":[{"objects":{"ids":["02"],"type":3},"removed":true}]}}"#,
+            None,
+            &mut data,
+        )
+        .unwrap();
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].ms_from_beginning, 2000);
+        assert_eq!(changes[0].signal_states, vec![SignalState::Unavailable]);
+        assert_eq!(data.stats.retired_objects[0].describe(), "signal '02'");
+    }
+
+    #[test]
+    fn a_removed_detector_should_become_faulty() {
+        let mut data = AssimilationData {
+            first_tick: Some(1000),
+            ..Default::default()
+        };
+
+        let changes = parse_string(
+            r#"{"jsonrpc":"2.0","method":"UpdateState","params":{"ticks":3000,"update":[{"objects":{"ids":["D611"],"type":4},"deleted":true}]}}"#,
+            None,
+            &mut data,
+        )
+        .unwrap();
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].detector_names, vec!["D611"]);
+        assert_eq!(changes[0].detector_states, vec![DetectorState::FAULTY]);
+        assert_eq!(data.stats.retired_objects[0].describe(), "detector 'D611'");
+    }
+
+    #[test]
+    fn names_with_stray_whitespace_should_be_normalized_and_warned_about_once() -> Result<(), String>
+    {
+        let mut data = AssimilationData {
+            console: crate::Console::new(crate::Verbosity::Quiet),
+            ..get_test_data()
+        };
+        let detector_json = TEST_DETECTOR_JSON.replace("\"D713\"", "\"D713 \"");
+        let signal_json = TEST_SIGNAL_JSON.replace("\"71\"", "\"71\u{a0}\"");
+
+        let first_changes = parse_string(&detector_json, None, &mut data)?;
+        parse_string(&detector_json, None, &mut data)?;
+        let signal_changes = parse_string(&signal_json, None, &mut data)?;
+
+        assert_eq!(first_changes[0].detector_names, vec!["D713".to_string()]);
+        assert_eq!(signal_changes[0].signal_names, vec!["71".to_string()]);
+        assert_eq!(
+            data.console.all_warnings(),
+            vec![
+                "Warning: the name \"D713 \" in the log was normalized to \"D713\".".to_string(),
+                "Warning: the name \"71\\u{a0}\" in the log was normalized to \"71\".".to_string(),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn skipped_lines_should_be_counted_by_what_is_wrong_with_them() {
+        let line = |json: &str| {
+            format!(
+                "2021-12-15 12:00:00,000 INFO  tlcFiMessages:41 - IN - {}",
+                json
+            )
+        };
+        let mut data = AssimilationData {
+            sorted_lines: vec![
+                "garbage".to_string(),
+                line("{\"ticks\":"),
+                line(&TEST_SIGNAL_JSON.replace("\"state\":6", "\"state\":99")),
+                line(&TEST_SIGNAL_JSON.replace("\"states\"", "\"statuses\"")),
+                line(TEST_SIGNAL_JSON),
+            ],
+            ..get_test_data()
+        };
+
+        parse_lines(&mut data, MessageDirection::In, false, false, &mut |_| {}).unwrap();
+
+        let line_errors = &data.stats.line_errors;
+        assert_eq!(
+            (
+                line_errors.unsplittable,
+                line_errors.invalid_json,
+                line_errors.unknown_states,
+                line_errors.malformed_messages
+            ),
+            (1, 1, 1, 1)
+        );
+        assert_eq!(line_errors.first_lines, data.sorted_lines[..4].to_vec());
+        assert_eq!(data.stats.lines_skipped, 4);
+        assert_eq!(data.changes.len(), 1);
+    }
+
+    #[test]
+    fn an_unknown_detector_state_should_skip_the_line_instead_of_aborting_the_run() {
+        let unknown_json = TEST_DETECTOR_JSON.replace("\"state\":1", "\"state\":3");
+        let line = |json: &str| {
+            format!(
+                "2021-12-15 12:00:00,000 INFO  tlcFiMessages:41 - IN - {}",
+                json
+            )
+        };
+        let mut data = AssimilationData {
+            sorted_lines: vec![line(&unknown_json), line(TEST_DETECTOR_JSON)],
+            ..get_test_data()
+        };
+
+        let error = parse_string(&unknown_json, None, &mut get_test_data()).unwrap_err();
+        parse_lines(&mut data, MessageDirection::In, false, false, &mut |_| {}).unwrap();
+
+        assert_eq!(
+            error,
+            "Unknown detector state 3 of detector 'D713' in params.update.states"
+        );
+        assert_eq!(data.stats.line_errors.unknown_states, 1);
+        assert_eq!(data.stats.lines_skipped, 1);
+        assert_eq!(data.changes.len(), 1);
+        assert_eq!(data.changes[0].detector_states, vec![DetectorState::OCCUPIED]);
+    }
+
+    #[test]
+    fn only_state_updates_should_give_the_first_tick_and_changes() {
+        let line = |json: &str| {
+            format!(
+                "2021-12-15 12:00:00,000 INFO  tlcFiMessages:41 - IN - {}",
+                json
+            )
+        };
+        let read_meta_json = "{\"jsonrpc\":\"2.0\",\"id\":\"4\",\"method\":\"ReadMeta\",\"params\":{\"ticks\":1000},\"result\":{\"objects\":{\"ids\":[\"71\"],\"type\":3}}}";
+        let mut data = AssimilationData {
+            sorted_lines: vec![
+                line(read_meta_json),
+                line(&TEST_SIGNAL_JSON.replace("UpdateState", "UpdateStates")),
+                line(TEST_DETECTOR_JSON),
+                line(read_meta_json),
+                line(&TEST_SIGNAL_JSON.replace("UpdateState", "UpdateStates")),
+            ],
+            first_tick: None,
+            ..get_test_data()
+        };
+        let mut vendor_data = AssimilationData {
+            sorted_lines: data.sorted_lines.clone(),
+            update_methods: vec!["UpdateStates".to_string()],
+            ..get_test_data()
+        };
+
+        parse_lines(&mut data, MessageDirection::In, false, false, &mut |_| {}).unwrap();
+        parse_lines(&mut vendor_data, MessageDirection::In, false, false, &mut |_| {}).unwrap();
+
+        assert_eq!(data.first_tick, Some(4087808637));
+        assert_eq!(data.changes.len(), 1);
+        assert_eq!(data.changes[0].ms_from_beginning, 0);
+        let other_methods: Vec<(&str, usize)> = data
+            .stats
+            .other_methods
+            .iter()
+            .map(|(method, count)| (method.as_str(), *count))
+            .collect();
+        assert_eq!(other_methods, vec![("ReadMeta", 1), ("UpdateStates", 1)]);
+        assert_eq!(vendor_data.changes.len(), 3);
+        assert_eq!(vendor_data.changes[2].signal_names, vec!["71".to_string()]);
+        assert_eq!(vendor_data.stats.other_methods.len(), 1);
+    }
+
+    #[test]
+    fn error_responses_should_be_recorded_with_the_time_of_their_line() {
+        let line = |time: &str, json: &str| {
+            format!("2021-12-15 {} INFO  tlcFiMessages:41 - IN - {}", time, json)
+        };
+        let mut data = AssimilationData {
+            sorted_lines: vec![
+                line(
+                    "11:59:59,500",
+                    "{\"jsonrpc\":\"2.0\",\"error\":{\"code\":-32601,\"message\":\"Method not found\"},\"id\":\"7\"}",
+                ),
+                line("12:00:00,000", TEST_SIGNAL_JSON),
+                line(
+                    "12:00:01,000",
+                    "{\"jsonrpc\":\"2.0\",\"error\":{\"code\":-32000},\"id\":null}",
+                ),
+            ],
+            first_tick: None,
+            ..get_test_data()
+        };
+
+        parse_lines(&mut data, MessageDirection::In, false, true, &mut |_| {}).unwrap();
+
+        let descriptions: Vec<String> = data
+            .stats
+            .rpc_errors
+            .iter()
+            .map(RpcError::describe)
+            .collect();
+        assert_eq!(
+            descriptions,
+            vec![
+                "error -32601 'Method not found' for request 7 at 2021-12-15 11:59:59.500",
+                "error -32000 at 2021-12-15 12:00:01",
+            ]
+        );
+        assert_eq!(data.first_tick, Some(4087808851));
+        assert_eq!(data.changes.len(), 1);
+        assert_eq!(data.stats.lines_skipped, 0);
+    }
+
+    #[test]
+    fn only_the_messages_in_the_chosen_direction_should_be_converted() {
+        let line = |direction: &str, ticks: u64, id: &str| {
+            format!(
+                "2021-12-15 12:00:00,000 INFO  tlcFiMessages:41 - {} - {}",
+                direction,
+                TEST_SIGNAL_JSON
+                    .replace("4087808851", &ticks.to_string())
+                    .replace("\"71\"", &format!("\"{}\"", id))
+            )
+        };
+        let sorted_lines = vec![
+            line("IN", 1000, "02"),
+            line("IN", 2000, "02"),
+            line("OUT", 1500, "05"),
+            line("OUT", 3000, "05"),
+        ];
+        let parse_in_direction = |direction: MessageDirection| {
+            let mut data = AssimilationData {
+                sorted_lines: sorted_lines.clone(),
+                ..Default::default()
+            };
+            parse_lines(&mut data, direction, false, false, &mut |_| {}).unwrap();
+            let changes: Vec<(u64, String)> = data
+                .changes
+                .iter()
+                .map(|changes| (changes.ms_from_beginning, changes.signal_names.join(",")))
+                .collect();
+            (data.first_tick, changes)
+        };
+        let change = |ms_from_beginning: u64, id: &str| (ms_from_beginning, id.to_string());
+
+        assert_eq!(
+            parse_in_direction(MessageDirection::In),
+            (Some(1000), vec![change(0, "02"), change(1000, "02")])
+        );
+        assert_eq!(
+            parse_in_direction(MessageDirection::Out),
+            (Some(1500), vec![change(0, "05"), change(1500, "05")])
+        );
+        assert_eq!(
+            parse_in_direction(MessageDirection::Both),
+            (
+                Some(1000),
+                vec![
+                    change(0, "02"),
+                    change(500, "05"),
+                    change(1000, "02"),
+                    change(2000, "05")
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn every_registered_session_should_be_recorded_with_its_first_tick() {
+        let line = |direction: &str, json: &str| {
+            format!(
+                "2021-12-15 12:00:00,000 INFO  tlcFiMessages:41 - {} - {}",
+                direction, json
+            )
+        };
+        let mut data = AssimilationData {
+            sorted_lines: vec![
+                line("OUT", r#"{"jsonrpc":"2.0","method":"Register","id":1,"params":{"version":{"revision":0,"minor":1,"major":1},"type":1,"username":"tlcfi"}}"#),
+                line("IN", r#"{"jsonrpc":"2.0","id":1,"result":{"sessionid":"a1b2","version":{"major":1,"minor":1,"revision":0}}}"#),
+                line("IN", TEST_SIGNAL_JSON),
+                // A reconnect, of which only the response is logged
+                line("IN", r#"{"result":{"sessionid":7},"id":3,"jsonrpc":"2.0"}"#),
+                line("IN", &TEST_SIGNAL_JSON.replace("4087808851", "4087818851")),
+            ],
+            ..get_test_data()
+        };
+
+        parse_lines(&mut data, MessageDirection::In, false, false, &mut |_| {}).unwrap();
+
+        assert_eq!(
+            data.stats.sessions,
+            vec![
+                crate::SessionInfo {
+                    tick: Some(4087808851),
+                    username: Some("tlcfi".to_string()),
+                    application_type: Some(1),
+                    protocol_version: Some("1.1.0".to_string()),
+                    uri: None,
+                    session_id: Some("a1b2".to_string()),
+                },
+                crate::SessionInfo {
+                    tick: Some(4087818851),
+                    session_id: Some("7".to_string()),
+                    ..Default::default()
+                },
+            ]
+        );
+        assert_eq!(
+            data.stats.sessions[0].describe(),
+            "session 'a1b2' of 'tlcfi' (TLC-FI 1.1.0) from tick 4087808851"
+        );
+        assert_eq!(data.changes.len(), 2);
+        assert_eq!(data.stats.unsupported_messages, 0);
+    }
+
+    #[test]
+    fn the_objects_of_meta_responses_should_be_kept_with_their_index() {
+        let mut data = AssimilationData {
+            console: crate::Console::new(crate::Verbosity::Quiet),
+            ..get_test_data()
+        };
+
+        parse_string(r#"{"jsonrpc":"2.0","result":{"objects":{"type":2,"ids":["3031"]},"meta":[{"id":"3031"}]},"id":"2"}"#, None, &mut data).unwrap();
+        parse_string(r#"{"result":{"meta":[{"index":1},{"intersection":"3031"},{"index":0,"id":"03 "}],"objects":{"ids":["02","05","99"],"type":3}},"id":"3"}"#, None, &mut data).unwrap();
+
+        assert_eq!(data.meta.tlc_name, Some("3031".to_string()));
+        let mut signals: Vec<(&String, &i16)> = data.meta.signals.iter().collect();
+        signals.sort();
+        assert_eq!(
+            signals,
+            vec![(&"02".to_string(), &1), (&"03".to_string(), &0)]
+        );
+        assert_eq!(data.console.summary_lines().len(), 1);
+        assert_eq!(data.stats.unsupported_messages, 0);
+    }
+
+    /// The changes of the lines with these line times and ticks, with the time source.
+    fn changes_with_time_source(
+        times_and_ticks: &[(&str, u64)],
+        time_source: TimeSource,
+    ) -> Vec<TimestampedChanges> {
+        let mut data = AssimilationData {
+            time_source,
+            sorted_lines: times_and_ticks
+                .iter()
+                .map(|(time, ticks)| {
+                    format!(
+                        "2021-12-15 {} INFO  tlcFiMessages:41 - IN - {}",
+                        time,
+                        TEST_SIGNAL_JSON.replace("4087808851", &ticks.to_string())
+                    )
+                })
+                .collect(),
+            ..Default::default()
+        };
+        parse_lines(&mut data, MessageDirection::In, false, false, &mut |_| {}).unwrap();
+        data.changes
+    }
+
+    #[test]
+    fn both_time_sources_should_agree_on_a_clean_log() {
+        let times_and_ticks = [
+            ("12:00:00,000", 1000),
+            ("12:00:03,000", 4000),
+            ("12:00:06,250", 7250),
+        ];
+
+        let from_ticks = changes_with_time_source(&times_and_ticks, TimeSource::Ticks);
+        let from_log = changes_with_time_source(&times_and_ticks, TimeSource::Log);
+
+        assert_eq!(from_ticks, from_log);
+        assert_eq!(from_log[2].ms_from_beginning, 6250);
+    }
+
+    #[test]
+    fn the_log_time_source_should_keep_counting_through_a_tick_reset() {
+        let times_and_ticks = [
+            ("12:00:00,000", 1000),
+            ("12:00:02,000", 3000),
+            ("12:00:05,000", 500),
+            ("12:00:06,000", 1500),
+        ];
+        let ms_from_beginning = |time_source| {
+            changes_with_time_source(&times_and_ticks, time_source)
+                .iter()
+                .map(|changes| changes.ms_from_beginning)
+                .collect::<Vec<u64>>()
+        };
+
+        // The ticks can't tell how long the TLC was down, so the reset continues from the last tick before it
+        assert_eq!(
+            ms_from_beginning(TimeSource::Ticks),
+            vec![0, 2000, 2000, 3000]
+        );
+        assert_eq!(
+            ms_from_beginning(TimeSource::Log),
+            vec![0, 2000, 5000, 6000]
+        );
+    }
+
+    #[test]
+    fn ticks_of_another_unit_should_overflow_at_the_largest_tick() {
+        let line = |ticks: u64| {
+            format!(
+                "2021-12-15 12:00:00,000 INFO  tlcFiMessages:41 - IN - {}",
+                TEST_SIGNAL_JSON.replace("4087808851", &ticks.to_string())
+            )
+        };
+        let ms_from_beginning = |tick_unit: TickUnit| {
+            let mut data = AssimilationData {
+                sorted_lines: vec![
+                    line(MAX_TICKS - 7000),
+                    line(MAX_TICKS - 1000),
+                    line(2000),
+                ],
+                tick_unit,
+                ..Default::default()
+            };
+            parse_lines(&mut data, MessageDirection::In, false, false, &mut |_| {}).unwrap();
+            let ms_from_beginning: Vec<u64> = data
+                .changes
+                .iter()
+                .map(|changes| changes.ms_from_beginning)
+                .collect();
+            (ms_from_beginning, data.stats.tick_overflows)
+        };
+
+        assert_eq!(
+            ms_from_beginning(TickUnit::Milliseconds),
+            (vec![0, 6000, 9000], 1)
+        );
+        assert_eq!(
+            ms_from_beginning(TickUnit::HundredMicroseconds),
+            (vec![0, 600, 900], 1)
+        );
+    }
+
+    #[test]
+    fn lines_of_other_log_levels_should_be_skipped_before_parsing() {
+        let mut data = AssimilationData {
+            sorted_lines: vec![
+                "2021-12-15 12:00:00,000 DEBUG tlcFiMessages:41 - dump without a direction"
+                    .to_string(),
+                format!(
+                    "2021-12-15 12:00:00,000 INFO  tlcFiMessages:41 - IN - {}",
+                    TEST_SIGNAL_JSON
+                ),
+            ],
+            log_levels: Some(vec!["INFO".to_string(), "WARN".to_string()]),
+            ..Default::default()
+        };
+
+        parse_lines(&mut data, MessageDirection::In, false, true, &mut |_| {}).unwrap();
+
+        assert_eq!(log_level(&data.sorted_lines[0]), Some("DEBUG"));
+        assert_eq!(data.stats.lines_of_other_levels, 1);
+        assert_eq!(data.changes.len(), 1);
+    }
+
+    #[test]
+    fn json_of_other_loggers_should_not_become_the_first_tick() {
+        let foreign_json = TEST_SIGNAL_JSON.replace("4087808851", "1000");
+        let lines = vec![
+            format!(
+                "2021-12-15 12:00:00,000 INFO  otherComponent:12 - IN - {}",
+                foreign_json
+            ),
+            format!(
+                "2021-12-15 12:00:01,000 INFO  tlcFiMessages:41 - IN - {}",
+                TEST_SIGNAL_JSON
+            ),
+        ];
+        let parse_with_logger_name = |logger_name: Option<&str>| {
+            let mut data = AssimilationData {
+                sorted_lines: lines.clone(),
+                logger_name: logger_name.map(str::to_string),
+                ..Default::default()
+            };
+            parse_lines(&mut data, MessageDirection::In, false, true, &mut |_| {}).unwrap();
+            data
+        };
+
+        let permissive = parse_with_logger_name(None);
+        let with_logger_name = parse_with_logger_name(Some("tlcFiMessages"));
+
+        assert_eq!(permissive.first_tick, Some(1000));
+        assert_eq!(with_logger_name.first_tick, Some(4087808851));
+        assert_eq!(with_logger_name.changes.len(), 1);
+        assert_eq!(with_logger_name.stats.lines_of_other_loggers, 1);
+        assert_eq!(with_logger_name.stats.in_lines, 1);
+    }
+
+    #[test]
+    fn messages_wrapped_onto_lines_without_a_timestamp_should_be_joined() {
+        let prefix = "2021-12-15 12:00:00,864 INFO  tlcFiMessages:41 - IN - ";
+        let (start, end) = TEST_SIGNAL_JSON.split_at(60);
+        let (middle, end) = end.split_at(60);
+        let parse_wrapped = |lines: Vec<String>| {
+            let mut data = AssimilationData {
+                sorted_lines: lines,
+                ..get_test_data()
+            };
+            parse_lines(&mut data, MessageDirection::In, false, false, &mut |_| {}).unwrap();
+            data
+        };
+
+        let two_lines = parse_wrapped(vec![
+            format!("{}{}", prefix, start),
+            format!("{}{}", middle, end),
+        ]);
+        let three_lines = parse_wrapped(vec![
+            format!("{}{}", prefix, start),
+            middle.to_string(),
+            end.to_string(),
+            format!("{}{}", prefix, TEST_DETECTOR_JSON),
+        ]);
+        let unterminated = parse_wrapped(vec![
+            format!("{}{}", prefix, start),
+            middle.to_string(),
+            format!("{}{}", prefix, TEST_DETECTOR_JSON),
+        ]);
+
+        assert_eq!(two_lines.changes.len(), 1);
+        assert_eq!(two_lines.changes[0].signal_names, vec!["71"]);
+        assert_eq!(two_lines.stats.lines_skipped, 0);
+        assert_eq!(three_lines.changes.len(), 2);
+        assert_eq!(three_lines.stats.lines_read, 4);
+        assert_eq!(three_lines.stats.lines_skipped, 0);
+        assert_eq!(unterminated.changes.len(), 1);
+        assert_eq!(unterminated.changes[0].detector_names, vec!["D713"]);
+        assert_eq!(unterminated.stats.lines_skipped, 2);
+        assert_eq!(unterminated.stats.line_errors.invalid_json, 1);
+        assert_eq!(
+            unterminated.console.summary_lines(),
+            vec!["discarded 1 message(s) wrapped onto lines that end before the message does"]
+        );
+    }
+
+    #[test]
+    fn a_message_with_a_dash_and_space_in_it_should_still_split_into_three_parts() {
+        let line = format!(
+            "2021-12-15 12:00:00,650 INFO  tlcFiMessages:41 - IN - {}",
+            TEST_DETECTOR_JSON.replace("D713", "D7 - lus2")
+        );
+        let mut data = AssimilationData {
+            sorted_lines: vec![line.clone()],
+            ..get_test_data()
+        };
+
+        parse_lines(&mut data, MessageDirection::In, false, true, &mut |_| {}).unwrap();
+
+        assert_eq!(split_log_line(&line)[1], "IN ");
+        assert_eq!(data.stats.lines_skipped, 0);
+        assert_eq!(data.changes[0].detector_names, vec!["D7 - lus2"]);
+    }
+
+    #[test]
+    fn lines_of_a_log_pattern_should_be_parsed_like_the_default_layout() {
+        let parse_with_pattern = |lines: Vec<String>, log_pattern: LogPattern| {
+            let mut data = AssimilationData {
+                sorted_lines: lines,
+                log_pattern,
+                time_source: TimeSource::Log,
+                ..Default::default()
+            };
+            parse_lines(&mut data, MessageDirection::In, false, true, &mut |_| {}).unwrap();
+            data.changes
+        };
+        let pipe_pattern = r"^(?P<timestamp>\S+ \S+) \| (?P<direction>\w+) \| (?P<json>.*)$"
+            .parse()
+            .unwrap();
+
+        let default_changes = parse_with_pattern(
+            vec![
+                format!(
+                    "2021-12-15 12:00:00,000 INFO  tlcFiMessages:41 - IN - {}",
+                    TEST_DETECTOR_JSON
+                ),
+                format!(
+                    "2021-12-15 12:00:00,214 INFO  tlcFiMessages:41 - IN - {}",
+                    TEST_SIGNAL_JSON
+                ),
+            ],
+            LogPattern::default(),
+        );
+        let pipe_changes = parse_with_pattern(
+            vec![
+                format!("2021-12-15 12:00:00.000 | IN | {}", TEST_DETECTOR_JSON),
+                format!("2021-12-15 12:00:00.214 | IN | {}", TEST_SIGNAL_JSON),
+            ],
+            pipe_pattern,
+        );
+
+        assert_eq!(default_changes.len(), 2);
+        assert_eq!(default_changes[1].ms_from_beginning, 214);
+        assert_eq!(pipe_changes, default_changes);
+    }
+
+    #[test]
+    fn timestamps_with_a_comma_a_dot_or_without_milliseconds_should_be_read() {
+        let date = chrono::NaiveDate::from_ymd(2021, 12, 15);
+
+        for (timestamp, milliseconds) in [
+            ("2021-12-15 11:00:00,074", 74),
+            ("2021-12-15 11:00:00.074", 74),
+            ("2021-12-15 11:00:00", 0),
+        ] {
+            let line = format!("{} WARN  tlcFiMessages:41 - IN - {{}}", timestamp);
+            assert_eq!(
+                log_line_time(&line),
+                Some(date.and_hms_milli(11, 0, 0, milliseconds))
+            );
+            assert_eq!(log_line_timestamp(&line), Some(timestamp));
+            assert_eq!(log_level(&line), Some("WARN"));
+        }
+        assert_eq!(
+            parse_log_timestamp("15-12-2021 11:00:00"),
+            Err("the log line starts with '15-12-2021 11:00:00' instead of a timestamp like 2021-12-15 11:00:00,074".to_string())
+        );
+        assert_eq!(log_line_time("short line"), None);
+    }
+
+    #[test]
+    fn braces_in_strings_should_not_count_for_the_depth_of_a_message() {
+        assert_eq!(brace_depth(r#"{"name":"{a\"}"}"#), 0);
+        assert_eq!(brace_depth(r#"{"update":[{"objects":{"ids":["}"#), 3);
     }
 }
@@ -0,0 +1,221 @@
+//! Reads information back from VLog3 messages, such as the time they describe.
+
+use chrono::{Duration, NaiveDateTime};
+use tlcfi_assimilator::VLogMessageType;
+
+/// Reads the date time back from a VLog time reference message, e.g. `012021121511000520`.
+pub fn parse_time_reference(message: &str) -> Option<NaiveDateTime> {
+    let date_time = NaiveDateTime::parse_from_str(message.get(2..16)?, "%Y%m%d%H%M%S").ok()?;
+    let tenths: i64 = message.get(16..17)?.parse().ok()?;
+    Some(date_time + Duration::milliseconds(tenths * 100))
+}
+
+/// Keeps track of the time while reading VLog messages in order.
+/// Time references set the clock, change messages have a time delta relative to the last time reference.
+#[derive(Debug, Default)]
+pub struct MessageClock {
+    last_reference: Option<NaiveDateTime>,
+}
+
+impl MessageClock {
+    /// Returns the time the given message describes, or None if the message doesn't carry a time.
+    pub fn time_of(&mut self, message: &str) -> Option<NaiveDateTime> {
+        match message.get(0..2)? {
+            "01" => {
+                let reference = parse_time_reference(message)?;
+                self.last_reference = Some(reference);
+                Some(reference)
+            }
+            "06" | "0E" | "10" => {
+                let delta_in_ds = i64::from_str_radix(message.get(2..5)?, 16).ok()?;
+                Some(self.last_reference? + Duration::milliseconds(delta_in_ds * 100))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The information in a VLog message of one of the [supported types](../tlcfi_assimilator/fn.supported_vlog_messages.html).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MessageContent {
+    TimeReference,
+    VLogInformation {
+        version: String,
+        tlc_name: String,
+    },
+    /// The VLog ids of the detectors with their VLog state
+    DetectionInformation(Vec<(i16, i16)>),
+    /// The VLog ids of the signal groups with their VLog state
+    ExternalSignalGroupStatus(Vec<(i16, i16)>),
+    /// The VLog ids of the outputs with their VLog state
+    OutputInformation(Vec<(i16, i16)>),
+    /// A message of another type, or one that doesn't have the structure of its type
+    Unknown,
+}
+
+impl MessageContent {
+    pub fn message_type(&self) -> Option<VLogMessageType> {
+        match self {
+            MessageContent::TimeReference => Some(VLogMessageType::TimeReference),
+            MessageContent::VLogInformation { .. } => Some(VLogMessageType::VLogInformation),
+            MessageContent::DetectionInformation(_) => Some(VLogMessageType::DetectionInformation),
+            MessageContent::ExternalSignalGroupStatus(_) => {
+                Some(VLogMessageType::ExternalSignalGroupStatus)
+            }
+            MessageContent::OutputInformation(_) => Some(VLogMessageType::OutputInformation),
+            MessageContent::Unknown => None,
+        }
+    }
+}
+
+/// A VLog message with the time it describes.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DecodedMessage {
+    /// None for messages that don't carry a time, or change messages before the first time reference
+    pub time: Option<NaiveDateTime>,
+    pub content: MessageContent,
+    pub message: String,
+}
+
+/// Reads the information of a single message, without its time.
+pub fn decode_message(message: &str) -> MessageContent {
+    let decoded = match message.get(0..2) {
+        Some("01") => parse_time_reference(message).map(|_| MessageContent::TimeReference),
+        Some("04") => decode_vlog_information(message),
+        Some("06") => decode_id_state_pairs(message).map(MessageContent::DetectionInformation),
+        Some("0E") => decode_id_state_pairs(message).map(MessageContent::ExternalSignalGroupStatus),
+        Some("10") => decode_id_state_pairs(message).map(MessageContent::OutputInformation),
+        _ => None,
+    };
+    decoded.unwrap_or(MessageContent::Unknown)
+}
+
+/// Reads the messages in order, timing the change messages with the time references before them. Empty lines are skipped.
+pub fn decode_messages<'a, I: IntoIterator<Item = &'a str>>(messages: I) -> Vec<DecodedMessage> {
+    let mut clock = MessageClock::default();
+    messages
+        .into_iter()
+        .map(str::trim)
+        .filter(|message| !message.is_empty())
+        .map(|message| DecodedMessage {
+            time: clock.time_of(message),
+            content: decode_message(message),
+            message: message.to_string(),
+        })
+        .collect()
+}
+
+/// The version and the TLC name, written as UTF-16 code units of two hex digits and padded with spaces.
+fn decode_vlog_information(message: &str) -> Option<MessageContent> {
+    let version = message.get(2..8)?.to_string();
+    let encoded_tlc_name = message.get(8..)?;
+    if encoded_tlc_name.len() % 2 != 0 || !is_hex(encoded_tlc_name) {
+        return None;
+    }
+    let code_units = (0..encoded_tlc_name.len())
+        .step_by(2)
+        .map(|index| u16::from_str_radix(&encoded_tlc_name[index..index + 2], 16).ok())
+        .collect::<Option<Vec<u16>>>()?;
+    let tlc_name = String::from_utf16(&code_units).ok()?;
+    Some(MessageContent::VLogInformation {
+        version,
+        tlc_name: tlc_name.trim_end().to_string(),
+    })
+}
+
+/// The ids and states of a change message: a type, a time delta, an amount and then two hex digits for every id and state.
+fn decode_id_state_pairs(message: &str) -> Option<Vec<(i16, i16)>> {
+    let amount = usize::from_str_radix(message.get(5..6)?, 16).ok()?;
+    let pairs = message.get(6..)?;
+    if pairs.len() != amount * 4 || !is_hex(pairs) {
+        return None;
+    }
+    (0..amount)
+        .map(|index| {
+            let pair = &pairs[index * 4..index * 4 + 4];
+            Some((
+                i16::from_str_radix(&pair[0..2], 16).ok()?,
+                i16::from_str_radix(&pair[2..4], 16).ok()?,
+            ))
+        })
+        .collect()
+}
+
+/// Only ASCII hex digits can be sliced by byte index without splitting a character.
+fn is_hex(payload: &str) -> bool {
+    payload.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn parse_time_reference_should_include_tenths_of_seconds() {
+        assert_eq!(
+            parse_time_reference("012021121511000520"),
+            Some(NaiveDate::from_ymd(2021, 12, 15).and_hms_milli(11, 0, 5, 200))
+        );
+    }
+
+    #[test]
+    fn change_messages_should_be_timed_relative_to_the_last_time_reference() {
+        let mut clock = MessageClock::default();
+
+        assert_eq!(clock.time_of("0600A10A01"), None);
+        clock.time_of("012021121511000000");
+
+        assert_eq!(clock.time_of("040300003330333120"), None);
+        assert_eq!(
+            clock.time_of("060A110A01"),
+            Some(NaiveDate::from_ymd(2021, 12, 15).and_hms_milli(11, 0, 16, 100))
+        );
+    }
+
+    #[test]
+    fn change_messages_should_be_decoded_into_their_ids_and_states() {
+        assert_eq!(
+            decode_message("0E0A120A010B02"),
+            MessageContent::ExternalSignalGroupStatus(vec![(10, 1), (11, 2)])
+        );
+        assert_eq!(
+            decode_message("0600010A01"),
+            MessageContent::DetectionInformation(vec![(10, 1)])
+        );
+        assert_eq!(
+            decode_message("1000010A01"),
+            MessageContent::OutputInformation(vec![(10, 1)])
+        );
+        assert_eq!(decode_message("0600020A01"), MessageContent::Unknown);
+        assert_eq!(decode_message("0F00010A01"), MessageContent::Unknown);
+    }
+
+    #[test]
+    fn the_vlog_information_should_be_decoded_into_the_version_and_tlc_name() {
+        assert_eq!(
+            decode_message("040300003330333120202020202020202020202020202020"),
+            MessageContent::VLogInformation {
+                version: "030000".to_string(),
+                tlc_name: "3031".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn messages_with_non_ascii_characters_should_not_be_decoded() {
+        assert_eq!(decode_message("0E0011a\u{e9}a"), MessageContent::Unknown);
+        assert_eq!(decode_message("0403000033\u{e9}"), MessageContent::Unknown);
+    }
+
+    #[test]
+    fn decoded_change_messages_should_be_timed_and_empty_lines_skipped() {
+        let decoded = decode_messages(vec!["012021121511000000", "", "0600A10A01"]);
+
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(
+            decoded[1].time,
+            Some(NaiveDate::from_ymd(2021, 12, 15).and_hms_milli(11, 0, 1, 0))
+        );
+    }
+}
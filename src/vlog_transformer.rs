@@ -9,11 +9,36 @@ use std::{
 
 use chrono::{Datelike, Duration, NaiveDateTime, Timelike};
 
-use tlcfi_assimilator::TimestampedChanges;
+use crate::{
+    time_zone::TimeZone, ConversionMode, ConversionStats, SignalStateEncoding, TimestampedChanges,
+    VLogMessageType, Verbosity,
+};
+
+use crate::name_normalization::normalize_name;
 
 const TIME_REFERENCE_INTERVAL_IN_S: u64 = 300;
+/// The hex digits of a time reference message
+const TIME_REFERENCE_LENGTH: usize = 18;
+/// Messages end with `\r\n` by default, the longest line ending, so size estimates are never too small
+const LINE_SEPARATOR_LENGTH: u64 = 2;
+/// The most signal groups in a single signal change message
+const MAX_SIGNALS_PER_MESSAGE: usize = 10;
+/// The most outputs in a single output change message, the amount has a single hex digit
+const MAX_OUTPUTS_PER_MESSAGE: usize = 15;
+/// The characters of the TLC name field in the V-Log information message, shorter names are padded with spaces
+pub const MAX_TLC_NAME_LENGTH: usize = 20;
+
+/// Sets what [to_vlog](fn.to_vlog.html) converts and how.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TransformOptions {
+    pub conversion_mode: ConversionMode,
+    pub verbosity: Verbosity,
+    pub signal_state_encoding: SignalStateEncoding,
+    /// The zone of the start date time and the time references. The time between the references follows the ticks,
+    /// so a change to or from summer time gets a time reference of its own with the new local time.
+    pub time_zone: TimeZone,
+}
 
-// TODO Create enum for message types
 // TODO get rid of some to_string calls in favor of &str
 // TODO implement status messages every 5 minutes with the time reference messages. first handle all changes, and save the first change state of any entity, then build initial status message on that and insert in front
 // TODO merge common functionality of transform_signal_changes and transform_detector_changes
@@ -25,64 +50,327 @@ const TIME_REFERENCE_INTERVAL_IN_S: u64 = 300;
 /// Only the following types of VLog change messages are supported:
 /// * 6  - Detectie informatie
 /// * 14 - Externe signaalgroep status
+/// * 16 - Uitgang WUS informatie, for the outputs in the optional Outputs section of the mapping file
+///
+/// See [supported_vlog_messages](fn.supported_vlog_messages.html) for all message types that can be produced.
+///
+/// Mappings are only loaded for the object types that are converted according to the [ConversionMode](enum.ConversionMode.html) in the [TransformOptions](struct.TransformOptions.html).
+/// The written messages are counted in the given [ConversionStats](struct.ConversionStats.html).
 pub fn to_vlog(
     timestamped_changes_vec: Vec<TimestampedChanges>,
     start_date_time: &NaiveDateTime,
     vlog_tlcfi_mapping_file: &str,
     tlc_name: &str,
+    options: TransformOptions,
+    stats: &mut ConversionStats,
 ) -> Vec<String> {
-    let vlog_signal_name_mapping =
-        load_mappings(&vlog_tlcfi_mapping_file, "Signals").expect(&format!(
-            "Couldn't find Signal mappings in the given VLog TLC FI mapping file: {:?}",
-            &vlog_tlcfi_mapping_file
-        ));
-    let vlog_detector_name_mapping =
-        load_mappings(&vlog_tlcfi_mapping_file, "Detectors").expect(&format!(
-            "Couldn't find Detector mappings in the given VLog TLC FI mapping file: {:?}",
-            &vlog_tlcfi_mapping_file
-        ));
+    let mut transformer = VLogTransformer::new(start_date_time, vlog_tlcfi_mapping_file, options);
+    let mut vlog_messages = transformer.start(tlc_name, stats);
+    vlog_messages.extend(transformer.transform(timestamped_changes_vec, stats));
+    vlog_messages
+}
 
-    let mut vlog_messages: Vec<String> = Vec::new();
+/// Transforms changes into VLog messages in batches, keeping track of the last time reference between them.
+/// [to_vlog](fn.to_vlog.html) transforms everything in a single batch.
+pub struct VLogTransformer {
+    start_date_time: NaiveDateTime,
+    vlog_signal_name_mapping: HashMap<String, i16>,
+    vlog_detector_name_mapping: HashMap<String, i16>,
+    vlog_output_name_mapping: HashMap<String, i16>,
+    signal_state_encoding: SignalStateEncoding,
+    time_zone: TimeZone,
+    verbosity: Verbosity,
+    ms_of_last_time_reference: u64,
+}
 
-    let mut ms_of_last_time_reference = 0;
+impl VLogTransformer {
+    /// Loads the mappings for the object types that are converted according to the options.
+    pub fn new(
+        start_date_time: &NaiveDateTime,
+        vlog_tlcfi_mapping_file: &str,
+        options: TransformOptions,
+    ) -> VLogTransformer {
+        let signal_mapping = if options.conversion_mode.converts_signals() {
+            load_mappings(vlog_tlcfi_mapping_file, "Signals").unwrap_or_else(|_| {
+                panic!(
+                    "Couldn't find Signal mappings in the given VLog TLC FI mapping file: {:?}",
+                    vlog_tlcfi_mapping_file
+                )
+            })
+        } else {
+            HashMap::new()
+        };
+        let detector_mapping = if options.conversion_mode.converts_detectors() {
+            load_mappings(vlog_tlcfi_mapping_file, "Detectors").unwrap_or_else(|_| {
+                panic!(
+                    "Couldn't find Detector mappings in the given VLog TLC FI mapping file: {:?}",
+                    vlog_tlcfi_mapping_file
+                )
+            })
+        } else {
+            HashMap::new()
+        };
+        // The Outputs section is optional, without it the output changes are dropped
+        let output_mapping = if options.conversion_mode.converts_outputs() {
+            load_mappings(vlog_tlcfi_mapping_file, "Outputs").unwrap_or_default()
+        } else {
+            HashMap::new()
+        };
+        VLogTransformer::with_mappings(start_date_time, signal_mapping, detector_mapping, options)
+            .with_output_mappings(output_mapping)
+    }
+
+    /// Uses the given mappings of TLC FI names to VLog ids instead of loading them from a file.
+    /// Every name in the changes has to be in the mapping of its object type.
+    pub fn with_mappings(
+        start_date_time: &NaiveDateTime,
+        vlog_signal_name_mapping: HashMap<String, i16>,
+        vlog_detector_name_mapping: HashMap<String, i16>,
+        options: TransformOptions,
+    ) -> VLogTransformer {
+        if options.verbosity >= Verbosity::Verbose {
+            print_mappings("Signal", &vlog_signal_name_mapping);
+            print_mappings("Detector", &vlog_detector_name_mapping);
+        }
+
+        VLogTransformer {
+            start_date_time: *start_date_time,
+            vlog_signal_name_mapping,
+            vlog_detector_name_mapping,
+            vlog_output_name_mapping: HashMap::new(),
+            signal_state_encoding: options.signal_state_encoding,
+            time_zone: options.time_zone,
+            verbosity: options.verbosity,
+            ms_of_last_time_reference: 0,
+        }
+    }
 
-    vlog_messages.extend(insert_vlog_statuses(start_date_time, tlc_name));
+    /// Uses the given mapping of TLC FI output names to VLog ids. Without it, or for names that aren't in it,
+    /// output changes are dropped and counted instead of written.
+    pub fn with_output_mappings(
+        mut self,
+        vlog_output_name_mapping: HashMap<String, i16>,
+    ) -> VLogTransformer {
+        if self.verbosity >= Verbosity::Verbose {
+            print_mappings("Output", &vlog_output_name_mapping);
+        }
+        self.vlog_output_name_mapping = vlog_output_name_mapping;
+        self
+    }
+
+    /// Returns the time reference and VLog info message that start a VLog file.
+    pub fn start(&mut self, tlc_name: &str, stats: &mut ConversionStats) -> Vec<String> {
+        self.ms_of_last_time_reference = 0;
+        stats.time_references += 1;
+        insert_vlog_statuses(&self.start_date_time, tlc_name)
+    }
 
+    /// Transforms the next batch of changes, which have to come after the ones of earlier batches.
+    pub fn transform(
+        &mut self,
+        timestamped_changes_vec: Vec<TimestampedChanges>,
+        stats: &mut ConversionStats,
+    ) -> Vec<String> {
+        let mut vlog_messages: Vec<String> = Vec::new();
+
+        for mut timestamped_changes in timestamped_changes_vec {
+            let ms_from_beginning = timestamped_changes.ms_from_beginning;
+            // Changes that go back in time, e.g. after a controller reset, are written at the last time reference
+            let needs_time_reference = ms_from_beginning
+                .saturating_sub(self.ms_of_last_time_reference)
+                >= TIME_REFERENCE_INTERVAL_IN_S * 1000
+                || self.utc_offset_s(ms_from_beginning)
+                    != self.utc_offset_s(self.ms_of_last_time_reference);
+            let reference_for_changes = if needs_time_reference {
+                ms_from_beginning
+            } else {
+                self.ms_of_last_time_reference
+            };
+
+            // VLog has no message for the control state of an intersection yet, so those changes are skipped
+            if timestamped_changes.signal_names.is_empty()
+                && timestamped_changes.detector_names.is_empty()
+                && timestamped_changes.output_names.is_empty()
+            {
+                continue;
+            }
+            if !timestamped_changes.output_names.is_empty() {
+                self.drop_unmapped_outputs(&mut timestamped_changes, stats);
+            }
+            let is_signal_change = !timestamped_changes.signal_names.is_empty();
+            let is_detector_change = !timestamped_changes.detector_names.is_empty();
+            let change_messages = if is_signal_change {
+                transform_signal_changes(
+                    timestamped_changes,
+                    &self.vlog_signal_name_mapping,
+                    reference_for_changes,
+                    self.signal_state_encoding,
+                )
+            } else if is_detector_change {
+                transform_detector_changes(
+                    timestamped_changes,
+                    &self.vlog_detector_name_mapping,
+                    reference_for_changes,
+                )
+            } else {
+                transform_output_changes(
+                    timestamped_changes,
+                    &self.vlog_output_name_mapping,
+                    reference_for_changes,
+                )
+            };
+
+            // A change that doesn't result in any messages shouldn't trigger a time reference either
+            if change_messages.is_empty() {
+                continue;
+            }
+            if ms_from_beginning < self.ms_of_last_time_reference {
+                self.warn(
+                    stats,
+                    "wrote {} change(s) that went back in time at the last time reference",
+                    &format!(
+                        "a change at {} ms after a time reference at {} ms",
+                        ms_from_beginning, self.ms_of_last_time_reference
+                    ),
+                );
+            }
+            if needs_time_reference {
+                vlog_messages.push(get_time_reference(
+                    &self.start_date_time,
+                    ms_from_beginning,
+                    self.time_zone,
+                ));
+                self.ms_of_last_time_reference = ms_from_beginning;
+                stats.time_references += 1;
+            }
+            if is_signal_change {
+                stats.signal_change_messages += change_messages.len();
+            } else if is_detector_change {
+                stats.detector_change_messages += change_messages.len();
+            } else {
+                stats.output_change_messages += change_messages.len();
+            }
+            stats
+                .first_written_change_ms
+                .get_or_insert(ms_from_beginning);
+            stats.last_written_change_ms = Some(ms_from_beginning);
+            vlog_messages.extend(change_messages);
+        }
+
+        vlog_messages
+    }
+
+    /// The time reference the messages of the next batch are relative to, in ms after the start.
+    pub fn ms_of_last_time_reference(&self) -> u64 {
+        self.ms_of_last_time_reference
+    }
+
+    /// Continues after a time reference that was written by an earlier run, in ms after the start.
+    pub fn resume_at(&mut self, ms_of_last_time_reference: u64) {
+        self.ms_of_last_time_reference = ms_of_last_time_reference;
+    }
+
+    /// Removes the output states whose output isn't mapped, counting them and collecting a warning for them.
+    fn drop_unmapped_outputs(
+        &self,
+        timestamped_changes: &mut TimestampedChanges,
+        stats: &mut ConversionStats,
+    ) {
+        let names = std::mem::take(&mut timestamped_changes.output_names);
+        let states = std::mem::take(&mut timestamped_changes.output_states);
+        for (name, state) in names.into_iter().zip(states) {
+            if self.vlog_output_name_mapping.contains_key(&name) {
+                timestamped_changes.output_names.push(name);
+                timestamped_changes.output_states.push(state);
+            } else {
+                stats.unmapped_output_states += 1;
+                let category = if self.vlog_output_name_mapping.is_empty() {
+                    "dropped {} output state(s), the mapping file has no Outputs mappings"
+                } else {
+                    "dropped {} state(s) of outputs that aren't in the Outputs mappings"
+                };
+                self.warn(stats, category, &format!("Output {} is not mapped", name));
+            }
+        }
+    }
+
+    /// Collects a warning that can occur for many changes in the stats, printing it right away when verbose.
+    fn warn(&self, stats: &mut ConversionStats, category: &str, details: &str) {
+        stats.transform_warnings.add(category, details);
+        if self.verbosity >= Verbosity::Verbose {
+            eprintln!("{}: {}", category.replace("{}", "1"), details);
+        }
+    }
+
+    /// The offset from UTC of the time zone, the given ms after the start.
+    fn utc_offset_s(&self, ms_from_beginning: u64) -> i32 {
+        self.time_zone.utc_offset_s(
+            &(self.time_zone.to_utc(&self.start_date_time)
+                + Duration::milliseconds(ms_from_beginning as i64)),
+        )
+    }
+}
+
+/// Estimates the size in bytes of the VLog file that [to_vlog](fn.to_vlog.html) writes for the changes,
+/// from the number of messages of every type and their encoded lengths, without encoding them.
+/// Keep this in line with the encoding when message types are added, the summary compares it with the written size.
+pub fn estimate_vlog_size(timestamped_changes_vec: &[TimestampedChanges], tlc_name: &str) -> u64 {
+    let line_size = |length: usize| length as u64 + LINE_SEPARATOR_LENGTH;
+    let time_reference_size = line_size(TIME_REFERENCE_LENGTH);
+    let mut size = time_reference_size + line_size(get_vlog_info(tlc_name).len());
+
+    let mut ms_of_last_time_reference = 0;
     for timestamped_changes in timestamped_changes_vec {
-        if timestamped_changes.ms_from_beginning - ms_of_last_time_reference
+        // Type, time delta and amount, followed by an id and a state per object
+        let changes_size: u64 = if !timestamped_changes.signal_names.is_empty() {
+            timestamped_changes
+                .signal_names
+                .chunks(MAX_SIGNALS_PER_MESSAGE)
+                .map(|chunk| line_size(6 + 4 * chunk.len()))
+                .sum()
+        } else if !timestamped_changes.detector_names.is_empty() {
+            let detector_count = timestamped_changes.detector_names.len();
+            line_size(5 + detector_count.to_string().len() + 4 * detector_count)
+        } else if !timestamped_changes.output_names.is_empty() {
+            timestamped_changes
+                .output_names
+                .chunks(MAX_OUTPUTS_PER_MESSAGE)
+                .map(|chunk| line_size(6 + 4 * chunk.len()))
+                .sum()
+        } else {
+            0
+        };
+        if changes_size == 0 {
+            continue;
+        }
+        if timestamped_changes
+            .ms_from_beginning
+            .saturating_sub(ms_of_last_time_reference)
             >= TIME_REFERENCE_INTERVAL_IN_S * 1000
         {
-            vlog_messages.push(get_time_reference(
-                start_date_time,
-                timestamped_changes.ms_from_beginning,
-            ));
+            size += time_reference_size;
             ms_of_last_time_reference = timestamped_changes.ms_from_beginning;
         }
-        if !timestamped_changes.signal_names.is_empty() {
-            vlog_messages.extend(transform_signal_changes(
-                timestamped_changes,
-                &vlog_signal_name_mapping,
-                ms_of_last_time_reference,
-            ));
-        } else if !timestamped_changes.detector_names.is_empty() {
-            vlog_messages.push(transform_detector_changes(
-                timestamped_changes,
-                &vlog_detector_name_mapping,
-                ms_of_last_time_reference,
-            ));
-        }
+        size += changes_size;
     }
+    size
+}
 
-    vlog_messages
+fn print_mappings(mapping_type: &str, mappings: &HashMap<String, i16>) {
+    let mut sorted_mappings: Vec<(&String, &i16)> = mappings.iter().collect();
+    sorted_mappings.sort_by_key(|(_, vlog_id)| **vlog_id);
+    for (name, vlog_id) in sorted_mappings {
+        eprintln!("{} '{}' maps to VLog id {}", mapping_type, name, vlog_id);
+    }
 }
 
-pub fn load_tlc_name(file_name: &str) -> Option<String> {
-    let mapping_file = File::open(file_name).expect(&format!(
-        "Failed to open VLog TLC FI mapping file: {:?}",
-        &file_name
-    ));
+/// Loads the TLC name from the line after the comment containing `TLC`, None when there is no such line.
+pub fn load_tlc_name(file_name: &str) -> std::io::Result<Option<String>> {
+    Ok(read_tlc_name(BufReader::new(File::open(file_name)?)))
+}
 
-    let reader = BufReader::new(mapping_file);
+/// Reads the TLC name like [load_tlc_name](fn.load_tlc_name.html), from the content of a mapping file.
+pub fn read_tlc_name<R: BufRead>(reader: R) -> Option<String> {
     let mut tlc_name = Option::None;
     let mut next_line_has_info = false;
     for line_res in reader.lines() {
@@ -91,11 +379,9 @@ pub fn load_tlc_name(file_name: &str) -> Option<String> {
             if !next_line_has_info && read_line.contains("//") && read_line.contains("TLC") {
                 next_line_has_info = true;
             }
-            if next_line_has_info {
-                if !read_line.contains("//") && !read_line.is_empty() {
-                    tlc_name = Some(read_line.to_string());
-                    break;
-                }
+            if next_line_has_info && !read_line.contains("//") && !read_line.is_empty() {
+                tlc_name = Some(read_line.to_string());
+                break;
             }
         } else {
             eprintln!("Failed to read line {:?}", line_res)
@@ -105,47 +391,130 @@ pub fn load_tlc_name(file_name: &str) -> Option<String> {
     tlc_name
 }
 
-fn load_mappings(
+/// Loads the mappings of TLC FI names to VLog ids under the comment containing `mapping_type` (`Signals`, `Detectors`
+/// or `Outputs`).
+pub fn load_mappings(
     file_name: &str,
     mapping_type: &str,
 ) -> Result<HashMap<String, i16>, Box<dyn std::error::Error>> {
-    let mapping_file = File::open(file_name)?;
+    let mappings = read_mappings(BufReader::new(File::open(file_name)?), mapping_type)?;
+    if mappings.is_empty() {
+        Err(Box::new(std::io::Error::other(format!(
+            "No {} mappings found in the mapping file!",
+            mapping_type
+        ))))
+    } else {
+        Ok(mappings)
+    }
+}
 
-    let reader = BufReader::new(mapping_file);
-    let mut mappings = HashMap::new();
+/// Reads the mappings like [load_mappings](fn.load_mappings.html), from the content of a mapping file.
+/// The mappings are empty when the mapping file has none of the type. Fails on the first row that can't be read.
+pub fn read_mappings<R: BufRead>(
+    reader: R,
+    mapping_type: &str,
+) -> Result<HashMap<String, i16>, Box<dyn std::error::Error>> {
+    let section = read_mapping_section(reader, mapping_type)?;
+    match section.problems.first() {
+        Some(problem) => Err(problem.clone().into()),
+        None => Ok(section.mappings()),
+    }
+}
 
-    let mut next_line_has_info = false;
-    for line in reader.lines() {
+/// A row of the mappings in a mapping file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MappingRow {
+    pub line_number: usize,
+    pub vlog_id: i16,
+    /// The normalized TLC FI name
+    pub name: String,
+    /// The number of comma separated fields in the row, of which only the first two are used
+    pub field_count: usize,
+}
+
+/// The rows of a type of mappings in a mapping file, and the problems of the rows that couldn't be read.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct MappingSection {
+    /// The line number of the comment that starts the mappings, None when the file has no such comment
+    pub header_line_number: Option<usize>,
+    pub rows: Vec<MappingRow>,
+    /// The rows that don't have a VLog id and a TLC FI name, with their line numbers
+    pub problems: Vec<String>,
+    /// The line numbers of rows after the empty line or comment that ends the mappings, which aren't read
+    pub ignored_line_numbers: Vec<usize>,
+}
+
+impl MappingSection {
+    /// The VLog ids of the TLC FI names, the last row wins for names that are in several rows.
+    pub fn mappings(&self) -> HashMap<String, i16> {
+        self.rows
+            .iter()
+            .map(|row| (row.name.clone(), row.vlog_id))
+            .collect()
+    }
+}
+
+/// Reads all rows of the mappings under the comment containing `mapping_type` (`Signals`, `Detectors` or `Outputs`),
+/// collecting the rows that can't be read instead of stopping at the first one.
+///
+/// The mappings end at the first empty line or comment after them. Rows after that end, up to the next comment,
+/// are noted as ignored, as they are most likely meant to be mappings as well.
+pub fn read_mapping_section<R: BufRead>(
+    reader: R,
+    mapping_type: &str,
+) -> std::io::Result<MappingSection> {
+    let mut section = MappingSection::default();
+    let mut has_ended = false;
+    for (line_index, line) in reader.lines().enumerate() {
+        let line_number = line_index + 1;
         let read_line = line?.trim().to_string();
+        let is_comment = read_line.contains("//");
 
-        if !next_line_has_info && read_line.contains("//") && read_line.contains(mapping_type) {
-            next_line_has_info = true;
-        } else if next_line_has_info && !read_line.is_empty() && !read_line.contains("//") {
-            let mapping: Vec<&str> = read_line.split(",").collect();
-            mappings.insert(
-                mapping[1].trim().to_string(),
-                mapping[0].trim().parse::<i16>()?,
-            );
-        } else if next_line_has_info {
-            // "Stopping file parsings since we found an empty line when we expected info."
-            break;
+        if section.header_line_number.is_none() {
+            if is_comment && read_line.contains(mapping_type) {
+                section.header_line_number = Some(line_number);
+            }
+        } else if has_ended {
+            if is_comment {
+                break;
+            } else if !read_line.is_empty() {
+                section.ignored_line_numbers.push(line_number);
+            }
+        } else if read_line.is_empty() || is_comment {
+            has_ended = true;
+        } else {
+            let fields: Vec<&str> = read_line.split(',').collect();
+            let vlog_id = fields[0].trim().parse::<i16>();
+            match (fields.get(1), vlog_id) {
+                (Some(name), Ok(vlog_id)) => section.rows.push(MappingRow {
+                    line_number,
+                    vlog_id,
+                    name: normalize_name(name),
+                    field_count: fields.len(),
+                }),
+                (None, _) => section.problems.push(format!(
+                    "line {}: expected a VLog id and a TLC FI name separated by a comma in the {} mappings, got {:?}",
+                    line_number, mapping_type, read_line
+                )),
+                (Some(_), Err(error)) => section.problems.push(format!(
+                    "line {}: {:?} isn't a VLog id in the {} mappings: {}",
+                    line_number,
+                    fields[0].trim(),
+                    mapping_type,
+                    error
+                )),
+            }
         }
     }
 
-    if mappings.is_empty() {
-        Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("No {} mappings found in the mapping file!", mapping_type),
-        )))
-    } else {
-        Ok(mappings)
-    }
+    Ok(section)
 }
 
 fn transform_signal_changes(
     signal_changes: TimestampedChanges,
     vlog_signal_name_mapping: &HashMap<String, i16>,
     ms_of_last_time_reference: u64,
+    signal_state_encoding: SignalStateEncoding,
 ) -> Vec<String> {
     // The structure for a CHANGE_EXTERNAL_SIGNALGROUP_STATUS_WUS
     // description  hex digits
@@ -155,7 +524,7 @@ fn transform_signal_changes(
     // amount times
     //   id         2
     //   state      2
-    let message_type = "0E";
+    let message_type = VLogMessageType::ExternalSignalGroupStatus.code();
 
     let data_limit_split_changes = split_changes_on_data_limit_signal(signal_changes, 4);
 
@@ -164,9 +533,13 @@ fn transform_signal_changes(
     for changes in data_limit_split_changes {
         let data_amount = format!("{:X}", changes.signal_names.len());
         let static_string = format!(
-            "{:}{:03X}{:}",
+            "{:02X}{:03X}{:}",
             message_type,
-            from_tlcfi_time_to_vlog_time(changes.ms_from_beginning - ms_of_last_time_reference),
+            from_tlcfi_time_to_vlog_time(
+                changes
+                    .ms_from_beginning
+                    .saturating_sub(ms_of_last_time_reference)
+            ),
             data_amount
         );
 
@@ -178,10 +551,14 @@ fn transform_signal_changes(
             .map(|(index, name)| {
                 (
                     index,
-                    vlog_signal_name_mapping.get(name as &str).expect(&format!(
-                        "Couldn't find TLC FI signal name '{:?}' in VLog mapping file",
-                        name
-                    )),
+                    vlog_signal_name_mapping
+                        .get(name as &str)
+                        .unwrap_or_else(|| {
+                            panic!(
+                                "Couldn't find TLC FI signal name '{:?}' in VLog mapping file",
+                                name
+                            )
+                        }),
                 )
             })
             .collect();
@@ -193,7 +570,7 @@ fn transform_signal_changes(
             dynamic_string.push_str(&format!(
                 "{:02X}{:02X}",
                 signal_id,
-                changes.signal_states[index].to_vlog_state()
+                changes.signal_states[index].to_vlog_state_with(signal_state_encoding)
             ));
         }
         messages.push(format!("{}{}", static_string, dynamic_string))
@@ -229,7 +606,9 @@ fn split_changes_on_data_limit_signal(
         }
     }
 
-    split_changes.push(last_timestamped_changes);
+    if amount_in_timestampted_changes > 0 {
+        split_changes.push(last_timestamped_changes);
+    }
 
     split_changes
 }
@@ -238,7 +617,7 @@ fn transform_detector_changes(
     detector_changes: TimestampedChanges,
     vlog_detector_name_mapping: &HashMap<String, i16>,
     ms_of_last_time_reference: u64,
-) -> String {
+) -> Vec<String> {
     // The structure for a CHANGE_DETECTION_INFORMATION
     // description  hex digits
     // type         2
@@ -247,14 +626,20 @@ fn transform_detector_changes(
     // amount times
     //   id         2
     //   state      2
-    let message_type = "06";
+    let message_type = VLogMessageType::DetectionInformation.code();
+
+    if detector_changes.detector_names.is_empty() {
+        return Vec::new();
+    }
 
     let data_amount = format!("{:?}", detector_changes.detector_names.len());
     let static_string = format!(
-        "{:}{:03X}{:}",
+        "{:02X}{:03X}{:}",
         message_type,
         from_tlcfi_time_to_vlog_time(
-            detector_changes.ms_from_beginning - ms_of_last_time_reference
+            detector_changes
+                .ms_from_beginning
+                .saturating_sub(ms_of_last_time_reference)
         ),
         data_amount
     );
@@ -268,10 +653,12 @@ fn transform_detector_changes(
                 index,
                 vlog_detector_name_mapping
                     .get(name as &str)
-                    .expect(&format!(
-                        "Couldn't find TLC FI detector name '{:?}' in VLog mapping file",
-                        name
-                    )),
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "Couldn't find TLC FI detector name '{:?}' in VLog mapping file",
+                            name
+                        )
+                    }),
             )
         })
         .collect();
@@ -286,7 +673,51 @@ fn transform_detector_changes(
             detector_changes.detector_states[index].to_vlog_state()
         ));
     }
-    format!("{}{}", static_string, dynamic_string)
+    vec![format!("{}{}", static_string, dynamic_string)]
+}
+
+fn transform_output_changes(
+    output_changes: TimestampedChanges,
+    vlog_output_name_mapping: &HashMap<String, i16>,
+    ms_of_last_time_reference: u64,
+) -> Vec<String> {
+    // The structure for a CHANGE_OUTPUT_WUS
+    // description  hex digits
+    // type         2
+    // time delta   3
+    // data amount  1
+    // amount times
+    //   id         2
+    //   state      2
+    let message_type = VLogMessageType::OutputInformation.code();
+    let time_delta = from_tlcfi_time_to_vlog_time(
+        output_changes
+            .ms_from_beginning
+            .saturating_sub(ms_of_last_time_reference),
+    );
+
+    // Unmapped outputs are dropped before, so every name is mapped
+    let mut vlog_ids_and_states: Vec<(i16, i16)> = output_changes
+        .output_names
+        .iter()
+        .zip(&output_changes.output_states)
+        .filter_map(|(name, state)| {
+            vlog_output_name_mapping
+                .get(name)
+                .map(|vlog_id| (*vlog_id, state.to_vlog_state()))
+        })
+        .collect();
+    vlog_ids_and_states.sort_by_key(|(vlog_id, _)| *vlog_id);
+    vlog_ids_and_states
+        .chunks(MAX_OUTPUTS_PER_MESSAGE)
+        .map(|chunk| {
+            let mut message = format!("{:02X}{:03X}{:X}", message_type, time_delta, chunk.len());
+            for (vlog_id, vlog_state) in chunk {
+                message.push_str(&format!("{:02X}{:02X}", vlog_id, vlog_state));
+            }
+            message
+        })
+        .collect()
 }
 
 /// tlcfi time is in milliseconds, vlog time is in deciseconds
@@ -296,12 +727,17 @@ fn from_tlcfi_time_to_vlog_time(tlcfi_time: u64) -> u64 {
 
 fn insert_vlog_statuses(start_date_time: &NaiveDateTime, tlc_name: &str) -> Vec<String> {
     vec![
-        get_time_reference(start_date_time, 0),
+        get_time_reference(start_date_time, 0, TimeZone::default()),
         get_vlog_info(tlc_name),
     ]
 }
 
-fn get_time_reference(start_date_time: &NaiveDateTime, ms_since_beginning: u64) -> String {
+/// The time reference the given ms after the start date time, which is a local time of the time zone.
+fn get_time_reference(
+    start_date_time: &NaiveDateTime,
+    ms_since_beginning: u64,
+    time_zone: TimeZone,
+) -> String {
     // #Tijd referentiebericht zie 2.1.
     // 012021043008002450
     // Elements of the time are encoded in a way that they are readable
@@ -314,16 +750,20 @@ fn get_time_reference(start_date_time: &NaiveDateTime, ms_since_beginning: u64)
     // Second   15 -  8
     // Tenths   7  -  4
     // empty    3  -  0
-    let reference_time = start_date_time
+    let reference_time = time_zone
+        .to_utc(start_date_time)
         .checked_add_signed(Duration::milliseconds(
             ms_since_beginning
                 .try_into()
                 .expect("Failed to convert u64 into i64"),
         ))
-        .expect(&format!(
-            "Adding {:?} to date time {:?} caused an overflow error. Is our input correct?",
-            ms_since_beginning, start_date_time
-        ));
+        .unwrap_or_else(|| {
+            panic!(
+                "Adding {:?} to date time {:?} caused an overflow error. Is our input correct?",
+                ms_since_beginning, start_date_time
+            )
+        });
+    let reference_time = time_zone.from_utc(&reference_time);
     let date_string = format!(
         "{:02}{:02}{:02}",
         reference_time.year(),
@@ -335,9 +775,14 @@ fn get_time_reference(start_date_time: &NaiveDateTime, ms_since_beginning: u64)
         reference_time.hour(),
         reference_time.minute(),
         reference_time.second(),
-        reference_time.nanosecond() / 1_000_000_00
+        reference_time.nanosecond() / 100_000_000
+    );
+    let time_reference = format!(
+        "{:02X}{}{}0",
+        VLogMessageType::TimeReference.code(),
+        date_string,
+        time_string
     );
-    let time_reference = format!("{:02X}{}{}0", 1, date_string, time_string);
     time_reference
 }
 
@@ -355,15 +800,21 @@ fn get_vlog_info(tlc_name: &str) -> String {
             break;
         }
     }
-    if tlc_name.len() < 20 {
-        for _ in tlc_name.len()..20 {
+    if tlc_name.len() < MAX_TLC_NAME_LENGTH {
+        for _ in tlc_name.len()..MAX_TLC_NAME_LENGTH {
             encoded_tlc_name.push_str("20");
         }
     }
-    let vlog_info = format!("{:02X}{}{}", 4, "030000", &encoded_tlc_name);
+    let vlog_info = format!(
+        "{:02X}{}{}",
+        VLogMessageType::VLogInformation.code(),
+        "030000",
+        &encoded_tlc_name
+    );
     vlog_info
 }
 
+#[cfg(test)]
 mod test {
 
     use super::*;
@@ -419,7 +870,8 @@ mod test {
     #[test]
     fn get_time_reference_should_create_a_time_reference_message_based_on_the_ms_since_beginning() {
         let expected_time_reference = "012021121511000520";
-        let actual_time_reference = get_time_reference(&get_test_start_date_time(), 5212);
+        let actual_time_reference =
+            get_time_reference(&get_test_start_date_time(), 5212, TimeZone::default());
         assert_eq!(actual_time_reference, expected_time_reference);
     }
 
@@ -436,15 +888,16 @@ mod test {
         let detector_changes = TimestampedChanges {
             ms_from_beginning: 530,
             signal_names: vec!["11".to_string(), "71".to_string()],
-            signal_states: vec![
-                tlcfi_assimilator::SignalState::Amber,
-                tlcfi_assimilator::SignalState::Red,
-            ],
+            signal_states: vec![crate::SignalState::Amber, crate::SignalState::Red],
             ..Default::default()
         };
 
-        let actual_signal_change_message =
-            transform_signal_changes(detector_changes, &get_test_vlog_signal_name_mapping(), 180);
+        let actual_signal_change_message = transform_signal_changes(
+            detector_changes,
+            &get_test_vlog_signal_name_mapping(),
+            180,
+            SignalStateEncoding::Collapsed,
+        );
 
         assert_eq!(actual_signal_change_message, expected_signal_change_message);
     }
@@ -479,44 +932,45 @@ mod test {
                 "18".to_string(),
             ],
             signal_states: vec![
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
-                tlcfi_assimilator::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
+                crate::SignalState::Red,
             ],
             ..Default::default()
         };
 
-        let actual_signal_change_message =
-            transform_signal_changes(detector_changes, &get_test_vlog_signal_name_mapping(), 180);
+        let actual_signal_change_message = transform_signal_changes(
+            detector_changes,
+            &get_test_vlog_signal_name_mapping(),
+            180,
+            SignalStateEncoding::Collapsed,
+        );
 
         assert_eq!(actual_signal_change_message, expected_messages);
     }
 
     #[test]
     fn transform_detector_changes_should_create_a_vlog_sensor_change_message() {
-        let expected_sensor_change_message = "06005202000401";
+        let expected_sensor_change_message = vec!["06005202000401"];
         let detector_changes = TimestampedChanges {
             ms_from_beginning: 640,
             detector_names: vec!["D712".to_string(), "D713".to_string()],
-            detector_states: vec![
-                tlcfi_assimilator::DetectorState::OCCUPIED,
-                tlcfi_assimilator::DetectorState::FREE,
-            ],
+            detector_states: vec![crate::DetectorState::OCCUPIED, crate::DetectorState::FREE],
             ..Default::default()
         };
 
@@ -528,4 +982,456 @@ mod test {
 
         assert_eq!(actual_sensor_change_message, expected_sensor_change_message);
     }
+
+    #[test]
+    fn transform_signal_changes_without_any_changes_should_create_no_messages() {
+        let signal_changes = TimestampedChanges {
+            ms_from_beginning: 530,
+            ..Default::default()
+        };
+
+        let actual_signal_change_messages = transform_signal_changes(
+            signal_changes,
+            &get_test_vlog_signal_name_mapping(),
+            180,
+            SignalStateEncoding::Collapsed,
+        );
+
+        assert!(actual_signal_change_messages.is_empty());
+    }
+
+    #[test]
+    fn transforming_exactly_the_data_limit_of_signal_changes_should_not_make_an_empty_message() {
+        let signal_names: Vec<String> = (1..=10).map(|i| format!("{:02}", i)).collect();
+        let signal_changes = TimestampedChanges {
+            ms_from_beginning: 530,
+            signal_states: vec![crate::SignalState::Red; signal_names.len()],
+            signal_names,
+            ..Default::default()
+        };
+
+        let actual_signal_change_messages = transform_signal_changes(
+            signal_changes,
+            &get_test_vlog_signal_name_mapping(),
+            180,
+            SignalStateEncoding::Collapsed,
+        );
+
+        assert_eq!(
+            actual_signal_change_messages,
+            vec!["0E003A0000010002000300040005000600070008000900"]
+        );
+    }
+
+    #[test]
+    fn transform_detector_changes_without_any_changes_should_create_no_messages() {
+        let detector_changes = TimestampedChanges {
+            ms_from_beginning: 640,
+            ..Default::default()
+        };
+
+        let actual_sensor_change_messages = transform_detector_changes(
+            detector_changes,
+            &get_test_vlog_detector_name_mapping(),
+            80,
+        );
+
+        assert!(actual_sensor_change_messages.is_empty());
+    }
+
+    #[test]
+    fn changes_without_messages_should_not_trigger_a_time_reference() {
+        let changes = vec![
+            TimestampedChanges {
+                ms_from_beginning: 400_000,
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 400_100,
+                detector_names: vec!["D611".to_string()],
+                detector_states: vec![crate::DetectorState::OCCUPIED],
+                ..Default::default()
+            },
+        ];
+
+        let vlog_messages = to_vlog(
+            changes,
+            &get_test_start_date_time(),
+            "./vlog_tlcfi_mapping.txt",
+            TEST_TLC_NAME,
+            TransformOptions::default(),
+            &mut ConversionStats::default(),
+        );
+
+        assert_eq!(
+            vlog_messages[2..],
+            ["012021121511064010".to_string(), "0600010001".to_string()]
+        );
+    }
+
+    #[test]
+    fn changes_that_go_back_in_time_should_be_collected_as_a_warning() {
+        let detector_change_at = |ms_from_beginning| TimestampedChanges {
+            ms_from_beginning,
+            detector_names: vec!["D611".to_string()],
+            detector_states: vec![crate::DetectorState::OCCUPIED],
+            ..Default::default()
+        };
+        let mut stats = ConversionStats::default();
+
+        to_vlog(
+            vec![
+                detector_change_at(310_000),
+                detector_change_at(300_500),
+                detector_change_at(300_000),
+            ],
+            &get_test_start_date_time(),
+            "./vlog_tlcfi_mapping.txt",
+            TEST_TLC_NAME,
+            TransformOptions::default(),
+            &mut stats,
+        );
+
+        assert_eq!(
+            stats.transform_warnings.summary_lines_with_samples(),
+            vec![(
+                "wrote 2 change(s) that went back in time at the last time reference".to_string(),
+                "a change at 300500 ms after a time reference at 310000 ms"
+            )]
+        );
+    }
+
+    #[test]
+    fn time_references_should_be_inserted_across_batches() {
+        let detector_change_at = |ms_from_beginning| {
+            vec![TimestampedChanges {
+                ms_from_beginning,
+                detector_names: vec!["D611".to_string()],
+                detector_states: vec![crate::DetectorState::OCCUPIED],
+                ..Default::default()
+            }]
+        };
+        let mut stats = ConversionStats::default();
+        let mut transformer = VLogTransformer::new(
+            &get_test_start_date_time(),
+            "./vlog_tlcfi_mapping.txt",
+            TransformOptions::default(),
+        );
+
+        let preamble = transformer.start(TEST_TLC_NAME, &mut stats);
+        let first_batch = transformer.transform(detector_change_at(200_000), &mut stats);
+        let second_batch = transformer.transform(detector_change_at(310_000), &mut stats);
+        let third_batch = transformer.transform(detector_change_at(400_000), &mut stats);
+
+        assert_eq!(preamble.len(), 2);
+        assert_eq!(first_batch, vec!["067D010001".to_string()]);
+        assert_eq!(
+            second_batch,
+            vec!["012021121511051000".to_string(), "0600010001".to_string()]
+        );
+        // 90 seconds after the time reference of the second batch
+        assert_eq!(third_batch, vec!["0638410001".to_string()]);
+        assert_eq!(stats.time_references, 2);
+    }
+
+    #[test]
+    fn supported_vlog_messages_should_match_the_message_types_that_are_emitted() {
+        let changes = vec![
+            TimestampedChanges {
+                ms_from_beginning: 100,
+                signal_names: vec!["02".to_string()],
+                signal_states: vec![crate::SignalState::Green],
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 400_000,
+                detector_names: vec!["D611".to_string()],
+                detector_states: vec![crate::DetectorState::OCCUPIED],
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 400_100,
+                output_names: vec!["U01".to_string()],
+                output_states: vec![crate::OutputState::On],
+                ..Default::default()
+            },
+        ];
+
+        let vlog_messages = to_vlog(
+            changes,
+            &get_test_start_date_time(),
+            "./vlog_tlcfi_mapping.txt",
+            TEST_TLC_NAME,
+            TransformOptions::default(),
+            &mut ConversionStats::default(),
+        );
+
+        let mut emitted_codes: Vec<u8> = vlog_messages
+            .iter()
+            .map(|message| u8::from_str_radix(&message[0..2], 16).unwrap())
+            .collect();
+        emitted_codes.sort_unstable();
+        emitted_codes.dedup();
+        let mut supported_codes: Vec<u8> = crate::supported_vlog_messages()
+            .iter()
+            .map(|message_type| message_type.code())
+            .collect();
+        supported_codes.sort_unstable();
+        assert_eq!(emitted_codes, supported_codes);
+    }
+
+    #[test]
+    fn the_estimated_size_should_match_the_written_size() {
+        let signal_names: Vec<String> = ["02", "03", "04", "06", "07", "08", "58", "59", "61"]
+            .iter()
+            .cycle()
+            .take(12)
+            .map(|name| name.to_string())
+            .collect();
+        let changes = vec![
+            TimestampedChanges {
+                ms_from_beginning: 100,
+                signal_states: vec![crate::SignalState::Green; signal_names.len()],
+                signal_names,
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 200,
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 400_000,
+                detector_names: vec!["D611".to_string(); 11],
+                detector_states: vec![crate::DetectorState::OCCUPIED; 11],
+                ..Default::default()
+            },
+        ];
+        let estimated_size = estimate_vlog_size(&changes, TEST_TLC_NAME);
+
+        let vlog_messages = to_vlog(
+            changes,
+            &get_test_start_date_time(),
+            "./vlog_tlcfi_mapping.txt",
+            TEST_TLC_NAME,
+            TransformOptions::default(),
+            &mut ConversionStats::default(),
+        );
+
+        let written_size: usize = vlog_messages.iter().map(|message| message.len() + 2).sum();
+        assert_eq!(vlog_messages.len(), 6);
+        assert_eq!(estimated_size, written_size as u64);
+    }
+
+    #[test]
+    fn to_vlog_should_count_the_messages_it_writes() {
+        let changes = vec![
+            TimestampedChanges {
+                ms_from_beginning: 100,
+                signal_names: vec!["02".to_string()],
+                signal_states: vec![crate::SignalState::Green],
+                ..Default::default()
+            },
+            TimestampedChanges {
+                ms_from_beginning: 400_000,
+                detector_names: vec!["D611".to_string()],
+                detector_states: vec![crate::DetectorState::OCCUPIED],
+                ..Default::default()
+            },
+        ];
+        let mut stats = ConversionStats::default();
+
+        to_vlog(
+            changes,
+            &get_test_start_date_time(),
+            "./vlog_tlcfi_mapping.txt",
+            TEST_TLC_NAME,
+            TransformOptions::default(),
+            &mut stats,
+        );
+
+        assert_eq!(stats.signal_change_messages, 1);
+        assert_eq!(stats.detector_change_messages, 1);
+        assert_eq!(stats.time_references, 2);
+        assert_eq!(stats.first_written_change_ms, Some(100));
+        assert_eq!(stats.last_written_change_ms, Some(400_000));
+    }
+
+    #[test]
+    fn intersection_state_changes_should_not_be_written() {
+        let changes = vec![TimestampedChanges {
+            ms_from_beginning: 400_000,
+            intersection_names: vec!["3031".to_string()],
+            intersection_states: vec![crate::IntersectionState::AllRed],
+            ..Default::default()
+        }];
+        let mut stats = ConversionStats::default();
+
+        let vlog_messages = to_vlog(
+            changes,
+            &get_test_start_date_time(),
+            "./vlog_tlcfi_mapping.txt",
+            TEST_TLC_NAME,
+            TransformOptions::default(),
+            &mut stats,
+        );
+
+        assert_eq!(vlog_messages.len(), 2);
+        assert_eq!(stats.time_references, 1);
+        assert_eq!(stats.first_written_change_ms, None);
+    }
+
+    #[test]
+    fn mapped_output_changes_should_be_written_and_unmapped_ones_counted() {
+        let changes = vec![TimestampedChanges {
+            ms_from_beginning: 1_300,
+            output_names: vec!["U02".to_string(), "U01".to_string(), "U99".to_string()],
+            output_states: vec![
+                crate::OutputState::On,
+                crate::OutputState::Off,
+                crate::OutputState::On,
+            ],
+            ..Default::default()
+        }];
+        let mut stats = ConversionStats::default();
+
+        let vlog_messages = to_vlog(
+            changes,
+            &get_test_start_date_time(),
+            "./vlog_tlcfi_mapping.txt",
+            TEST_TLC_NAME,
+            TransformOptions::default(),
+            &mut stats,
+        );
+
+        assert_eq!(vlog_messages[2], "1000D200000101");
+        assert_eq!(stats.output_change_messages, 1);
+        assert_eq!(stats.unmapped_output_states, 1);
+        assert_eq!(stats.transform_warnings.summary_lines().len(), 1);
+    }
+
+    #[test]
+    fn output_changes_without_an_outputs_mapping_should_be_dropped_with_a_warning() {
+        let changes = vec![TimestampedChanges {
+            ms_from_beginning: 1_300,
+            output_names: vec!["U01".to_string(), "U02".to_string()],
+            output_states: vec![crate::OutputState::On, crate::OutputState::Off],
+            ..Default::default()
+        }];
+        let mut stats = ConversionStats::default();
+        let mut transformer = VLogTransformer::with_mappings(
+            &get_test_start_date_time(),
+            get_test_vlog_signal_name_mapping(),
+            get_test_vlog_detector_name_mapping(),
+            TransformOptions::default(),
+        );
+
+        let vlog_messages = transformer.transform(changes, &mut stats);
+
+        assert!(vlog_messages.is_empty());
+        assert_eq!(stats.output_change_messages, 0);
+        assert_eq!(stats.unmapped_output_states, 2);
+        assert_eq!(stats.first_written_change_ms, None);
+        assert_eq!(
+            stats.transform_warnings.summary_lines(),
+            vec!["dropped 2 output state(s), the mapping file has no Outputs mappings"]
+        );
+    }
+
+    #[test]
+    fn the_detailed_signal_state_encoding_should_keep_permissive_greens_apart() {
+        let signal_changes = TimestampedChanges {
+            ms_from_beginning: 0,
+            signal_names: vec!["02".to_string(), "03".to_string()],
+            signal_states: vec![
+                crate::SignalState::GreenPermissive,
+                crate::SignalState::Green,
+            ],
+            ..Default::default()
+        };
+        let mapping = get_test_vlog_signal_name_mapping();
+
+        let collapsed = transform_signal_changes(
+            TimestampedChanges {
+                signal_names: signal_changes.signal_names.clone(),
+                signal_states: signal_changes.signal_states.clone(),
+                ..Default::default()
+            },
+            &mapping,
+            0,
+            SignalStateEncoding::Collapsed,
+        );
+        let detailed =
+            transform_signal_changes(signal_changes, &mapping, 0, SignalStateEncoding::Detailed);
+
+        assert_eq!(collapsed, vec!["0E000201010201"]);
+        assert_eq!(detailed, vec!["0E000201070201"]);
+    }
+
+    #[test]
+    fn the_end_of_summer_time_should_get_a_time_reference_with_the_new_local_time() {
+        let detector_change_at = |ms_from_beginning| TimestampedChanges {
+            ms_from_beginning,
+            detector_names: vec!["D611".to_string()],
+            detector_states: vec![crate::DetectorState::OCCUPIED],
+            ..Default::default()
+        };
+        let start_date_time =
+            NaiveDateTime::parse_from_str("2021-10-31 02:58:00", "%Y-%m-%d %H:%M:%S").unwrap();
+
+        let vlog_messages = to_vlog(
+            vec![detector_change_at(60_000), detector_change_at(180_000)],
+            &start_date_time,
+            "./vlog_tlcfi_mapping.txt",
+            TEST_TLC_NAME,
+            TransformOptions {
+                time_zone: "Europe/Amsterdam".parse().unwrap(),
+                ..Default::default()
+            },
+            &mut ConversionStats::default(),
+        );
+
+        assert_eq!(
+            vlog_messages,
+            vec![
+                "012021103102580000".to_string(),
+                get_vlog_info(TEST_TLC_NAME),
+                "0625810001".to_string(),
+                "012021103102010000".to_string(),
+                "0600010001".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn reading_a_mapping_section_should_collect_every_problem() {
+        let content =
+            "// TLC\n3031\n// Signals\n1,02\n2\nx,05\n3,08,extra\n\n4,11\n// Detectors\n1,D611\n";
+
+        let section = read_mapping_section(content.as_bytes(), "Signals").unwrap();
+
+        assert_eq!(section.header_line_number, Some(3));
+        assert_eq!(
+            section
+                .rows
+                .iter()
+                .map(|row| (
+                    row.line_number,
+                    row.vlog_id,
+                    row.name.as_str(),
+                    row.field_count
+                ))
+                .collect::<Vec<_>>(),
+            vec![(4, 1, "02", 2), (7, 3, "08", 3)]
+        );
+        assert_eq!(section.problems.len(), 2);
+        assert!(section.problems[0].starts_with("line 5: expected a VLog id"));
+        assert!(section.problems[1].starts_with("line 6: \"x\" isn't a VLog id"));
+        assert_eq!(section.ignored_line_numbers, vec![9]);
+        assert_eq!(
+            read_mappings(content.as_bytes(), "Signals")
+                .unwrap_err()
+                .to_string(),
+            section.problems[0]
+        );
+    }
 }
@@ -0,0 +1,282 @@
+//! Converts the logs that appear in a directory, for running as a small daemon that picks up nightly uploads
+//! with `--watch DIR`.
+//!
+//! A log is converted once its size stayed the same between two polls, so a file that is still being uploaded
+//! isn't picked up halfway. Afterwards it's moved to the `processed` or `failed` directory next to it.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use tlcfi_assimilator::Console;
+
+use crate::{mapping_selection, AppArgs, ConversionFailure, ConversionReport};
+
+/// How long to wait between looking for new logs.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+const PROCESSED_DIR_NAME: &str = "processed";
+const FAILED_DIR_NAME: &str = "failed";
+
+/// Remembers the size of every log in the watched directory, to tell which ones are complete.
+#[derive(Debug, Default)]
+pub struct Watcher {
+    sizes: HashMap<PathBuf, u64>,
+}
+
+impl Watcher {
+    /// Returns the logs with a name matching the pattern whose size didn't change since the previous poll,
+    /// in the order of their names.
+    pub fn complete_logs(&mut self, watch_dir: &Path, pattern: &str) -> io::Result<Vec<PathBuf>> {
+        let mut sizes = HashMap::new();
+        for entry in fs::read_dir(watch_dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            let is_match = entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| matches_pattern(name, pattern));
+            if metadata.is_file() && is_match {
+                sizes.insert(entry.path(), metadata.len());
+            }
+        }
+        let mut complete_logs: Vec<PathBuf> = sizes
+            .iter()
+            .filter(|(path, size)| self.sizes.get(*path) == Some(size))
+            .map(|(path, _)| path.clone())
+            .collect();
+        complete_logs.sort();
+        // Complete logs are moved away, so they aren't remembered
+        for path in &complete_logs {
+            sizes.remove(path);
+        }
+        self.sizes = sizes;
+        Ok(complete_logs)
+    }
+}
+
+/// Whether the file name matches the pattern, in which `*` stands for any text and `?` for any character.
+pub fn matches_pattern(name: &str, pattern: &str) -> bool {
+    let name: Vec<char> = name.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    // The positions to continue from after the last `*`, for backtracking when the rest doesn't match
+    let (mut name_index, mut pattern_index) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None;
+    while name_index < name.len() {
+        match pattern.get(pattern_index) {
+            Some('*') => {
+                last_star = Some((pattern_index, name_index));
+                pattern_index += 1;
+            }
+            Some(&character) if character == '?' || character == name[name_index] => {
+                name_index += 1;
+                pattern_index += 1;
+            }
+            _ => match last_star {
+                Some((star_index, star_name_index)) => {
+                    pattern_index = star_index + 1;
+                    name_index = star_name_index + 1;
+                    last_star = Some((star_index, star_name_index + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[pattern_index..]
+        .iter()
+        .all(|&character| character == '*')
+}
+
+/// Converts the new logs of the watched directory until the program is stopped. Returns the exit code when the
+/// directory can't be read anymore, a log that fails to convert only shows up in the output.
+pub fn run_watch(app_args: &AppArgs, watch_dir: &str) -> i32 {
+    let console = Console::new(app_args.verbosity);
+    console.info(&format!(
+        "Watching '{}' for logs matching '{}', press Ctrl-C to stop.",
+        watch_dir, app_args.watch_pattern
+    ));
+    let mut watcher = Watcher::default();
+    loop {
+        if let Err(error) = convert_complete_logs(&mut watcher, app_args, watch_dir, &console) {
+            console.error(&format!(
+                "Error: failed to watch the directory '{}': {}",
+                watch_dir, error
+            ));
+            return crate::EXIT_CODE_INPUT_ERROR;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Converts the logs that are complete since the previous poll and moves each of them to `processed` or `failed`.
+/// Returns the log files with how their conversion ended.
+pub fn convert_complete_logs(
+    watcher: &mut Watcher,
+    app_args: &AppArgs,
+    watch_dir: &str,
+    console: &Console,
+) -> io::Result<Vec<(PathBuf, Result<ConversionReport, ConversionFailure>)>> {
+    let watch_dir = Path::new(watch_dir);
+    let mut results = Vec::new();
+    for log_file in watcher.complete_logs(watch_dir, &app_args.watch_pattern)? {
+        let log_name = log_file
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().to_string());
+        let outcome = convert_log(app_args, &log_file);
+        let target_dir = match &outcome {
+            Ok(report) => {
+                console.info(&format!(
+                    "Converted '{}' to: {}",
+                    log_name,
+                    report.file_names.join(", ")
+                ));
+                watch_dir.join(PROCESSED_DIR_NAME)
+            }
+            Err(failure) => {
+                console.error(&format!(
+                    "Failed to convert '{}' ({}): {}",
+                    log_name, failure.exit_code, failure.message
+                ));
+                watch_dir.join(FAILED_DIR_NAME)
+            }
+        };
+        fs::create_dir_all(&target_dir)?;
+        let moved_file = target_dir.join(&log_name);
+        fs::rename(&log_file, &moved_file)?;
+        results.push((moved_file, outcome));
+    }
+    Ok(results)
+}
+
+/// Converts a log with the options of the watcher, picking its mapping file when a mapping directory was given.
+fn convert_log(app_args: &AppArgs, log_file: &Path) -> Result<ConversionReport, ConversionFailure> {
+    let tlcfi_log_file = log_file.to_string_lossy().to_string();
+    let vlog_tlcfi_mapping_file = match &app_args.mapping_dir {
+        Some(mapping_dir) => {
+            let candidates = mapping_selection::tlc_name_candidates(
+                app_args.tlc_name.as_deref(),
+                &tlcfi_log_file,
+            );
+            mapping_selection::select_mapping_file(mapping_dir, &candidates)
+                .and_then(|mapping_file| {
+                    crate::validate_mapping_file(
+                        &mapping_file,
+                        app_args.conversion_mode,
+                        app_args.tlc_name.is_none(),
+                    )
+                })
+                .map_err(|error| ConversionFailure {
+                    exit_code: crate::EXIT_CODE_MAPPING_ERROR,
+                    message: format!("Error: {}", error),
+                })?
+        }
+        None => app_args.vlog_tlcfi_mapping_file.clone(),
+    };
+    crate::convert(&AppArgs {
+        tlcfi_log_file,
+        vlog_tlcfi_mapping_file,
+        ..app_args.clone()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tlcfi_assimilator::Verbosity;
+
+    #[test]
+    fn file_names_should_match_patterns_with_wildcards() {
+        assert!(matches_pattern("tlcfi.txt", "*.txt"));
+        assert!(matches_pattern("3031_tlcfi.txt", "3031_*.txt"));
+        assert!(matches_pattern("tlcfi.txt", "tlcfi.???"));
+        assert!(matches_pattern("a.txt.txt", "*.txt"));
+        assert!(!matches_pattern("tlcfi.txt.part", "*.txt"));
+        assert!(!matches_pattern("tlcfi.gz", "*.txt"));
+    }
+
+    #[test]
+    fn logs_dropped_in_the_watched_directory_should_be_converted_once_complete() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_watch");
+        let _ = fs::remove_dir_all(&test_dir);
+        let (watch_dir, mapping_dir, output_dir) = (
+            test_dir.join("incoming"),
+            test_dir.join("mappings"),
+            test_dir.join("vlog"),
+        );
+        for dir in [&watch_dir, &mapping_dir].iter() {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::copy("./vlog_tlcfi_mapping.txt", mapping_dir.join("3031.txt")).unwrap();
+        let log = fs::read_to_string("./tlcfi_with_amber.txt").unwrap();
+        let app_args = AppArgs {
+            mapping_dir: Some(mapping_dir.to_string_lossy().to_string()),
+            output_dir: Some(output_dir.to_string_lossy().to_string()),
+            verbosity: Verbosity::Quiet,
+            ..Default::default()
+        };
+        let watch_dir_name = watch_dir.to_string_lossy().to_string();
+        let console = Console::new(Verbosity::Quiet);
+        let mut watcher = Watcher::default();
+
+        fs::write(watch_dir.join("3031_noon.txt"), &log).unwrap();
+        fs::write(
+            watch_dir.join("3031_afternoon.txt"),
+            log.replace(" 12:", " 13:"),
+        )
+        .unwrap();
+        let first_poll =
+            convert_complete_logs(&mut watcher, &app_args, &watch_dir_name, &console).unwrap();
+        let second_poll =
+            convert_complete_logs(&mut watcher, &app_args, &watch_dir_name, &console).unwrap();
+        let third_poll =
+            convert_complete_logs(&mut watcher, &app_args, &watch_dir_name, &console).unwrap();
+        let outputs_exist = [
+            output_dir.join("3031_20211215_120000.vlg"),
+            output_dir.join("3031_20211215_130000.vlg"),
+        ]
+        .iter()
+        .all(|output| output.is_file());
+        let processed_exist = ["3031_afternoon.txt", "3031_noon.txt"]
+            .iter()
+            .all(|name| watch_dir.join(PROCESSED_DIR_NAME).join(name).is_file());
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert!(first_poll.is_empty());
+        assert_eq!(second_poll.len(), 2);
+        assert!(second_poll.iter().all(|(_, outcome)| outcome.is_ok()));
+        assert!(third_poll.is_empty());
+        assert!(outputs_exist);
+        assert!(processed_exist);
+    }
+
+    #[test]
+    fn a_log_that_fails_should_be_moved_to_failed_without_stopping_the_watcher() {
+        let test_dir = std::env::temp_dir().join("tlcfi_assimilator_watch_failure");
+        let _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).unwrap();
+        let app_args = AppArgs {
+            vlog_tlcfi_mapping_file: "./vlog_tlcfi_mapping.txt".to_string(),
+            output_dir: Some(test_dir.join("vlog").to_string_lossy().to_string()),
+            verbosity: Verbosity::Quiet,
+            ..Default::default()
+        };
+        let test_dir_name = test_dir.to_string_lossy().to_string();
+        let console = Console::new(Verbosity::Quiet);
+        let mut watcher = Watcher::default();
+
+        fs::write(test_dir.join("corrupt.txt"), "\u{0}garbage\n").unwrap();
+        convert_complete_logs(&mut watcher, &app_args, &test_dir_name, &console).unwrap();
+        let results =
+            convert_complete_logs(&mut watcher, &app_args, &test_dir_name, &console).unwrap();
+        let is_moved = test_dir.join(FAILED_DIR_NAME).join("corrupt.txt").is_file();
+        fs::remove_dir_all(&test_dir).unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert!(results[0].1.is_err());
+        assert!(is_moved);
+    }
+}
@@ -0,0 +1,196 @@
+//! Reads TLC-FI logs from a ZIP archive, such as a day of rotated log files that were zipped together.
+//!
+//! The entries are decompressed while their lines are read, so neither the archive nor an entry is held in memory.
+
+use std::{
+    cmp::Reverse,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek},
+};
+
+use chrono::NaiveDateTime;
+use tlcfi_assimilator::{tlcfi_parsing, Console};
+use zip::{result::ZipError, ZipArchive};
+
+const LOCAL_HEADER_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+
+/// Whether the file is a ZIP archive, judging by its `.zip` extension or its first bytes.
+pub fn is_zip_file(file_name: &str) -> bool {
+    if is_zip_file_name(file_name) {
+        return true;
+    }
+    let mut signature = [0; 4];
+    File::open(file_name)
+        .and_then(|mut file| file.read_exact(&mut signature))
+        .is_ok()
+        && signature == LOCAL_HEADER_SIGNATURE
+}
+
+/// Whether the file name looks like a ZIP archive.
+pub fn is_zip_file_name(file_name: &str) -> bool {
+    file_name.to_lowercase().ends_with(".zip")
+}
+
+/// Reads the lines of the TLC-FI logs in a ZIP archive as one log, like
+/// [read_lines](../tlcfi_assimilator/tlcfi_parsing/fn.read_lines.html) reads a single log.
+///
+/// The logs are ordered by the timestamp of their first line, in the same direction as the lines in a log:
+/// oldest first for a chronological log, newest first otherwise. Entries that aren't TLC-FI logs are skipped with a warning.
+pub fn read_logs(
+    file_name: &str,
+    is_chronological: bool,
+    console: &mut Console,
+    on_line: &mut dyn FnMut(usize),
+) -> io::Result<Vec<String>> {
+    let archive_error = |error: ZipError| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("failed to read the ZIP archive '{}': {}", file_name, error),
+        )
+    };
+    let mut archive =
+        ZipArchive::new(BufReader::new(File::open(file_name)?)).map_err(archive_error)?;
+
+    let mut logs = Vec::new();
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index).map_err(archive_error)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().to_string();
+        match first_log_line_time(BufReader::new(entry)) {
+            Some(first_line_time) => logs.push((first_line_time, index)),
+            None => console.warning(&format!(
+                "Warning: skipped '{}' in the ZIP archive, it doesn't contain TLC-FI log lines.",
+                name
+            )),
+        }
+    }
+    if is_chronological {
+        logs.sort_by_key(|(first_line_time, _)| *first_line_time);
+    } else {
+        logs.sort_by_key(|(first_line_time, _)| Reverse(*first_line_time));
+    }
+
+    let mut lines = Vec::new();
+    for (_, index) in logs {
+        lines.extend(read_entry_lines(&mut archive, index, console, on_line)?);
+    }
+    if !is_chronological {
+        lines.reverse();
+    }
+    Ok(lines)
+}
+
+/// Reads the lines of an entry as they are in the archive, checking its checksum at its end.
+fn read_entry_lines<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    index: usize,
+    console: &mut Console,
+    on_line: &mut dyn FnMut(usize),
+) -> io::Result<Vec<String>> {
+    let entry = archive.by_index(index)?;
+    let name = entry.name().to_string();
+    tlcfi_parsing::read_lines(BufReader::new(entry), true, console, on_line).map_err(|error| {
+        io::Error::new(
+            error.kind(),
+            format!("failed to read '{}' in the ZIP archive: {}", name, error),
+        )
+    })
+}
+
+/// The timestamp of the first line that splits like a TLC-FI log line, or None when there is no such line.
+/// Only the lines up to that line are read.
+pub fn first_log_line_time<R: BufRead>(log: R) -> Option<Option<NaiveDateTime>> {
+    log.split(b'\n')
+        .map_while(Result::ok)
+        .map(|line| String::from_utf8_lossy(&line).to_string())
+        .find(|line| tlcfi_parsing::split_log_line(&line.replace("\"\"", "\"")).len() == 3)
+        .map(|line| tlcfi_parsing::log_line_time(&line))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{fs, io::Write};
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    /// Writes a ZIP archive with deflated entries.
+    fn write_zip(file_name: &std::path::Path, files: &[(&str, &str)]) {
+        let mut archive = ZipWriter::new(File::create(file_name).unwrap());
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (name, content) in files {
+            if name.ends_with('/') {
+                archive.add_directory(*name, options).unwrap();
+            } else {
+                archive.start_file(*name, options).unwrap();
+                archive.write_all(content.as_bytes()).unwrap();
+            }
+        }
+        archive.finish().unwrap();
+    }
+
+    fn log_line(time: &str, state: u8) -> String {
+        format!(
+            "2021-12-15 {} INFO  tlcFiMessages:41 - IN - {{\"state\":{}}}\n",
+            time, state
+        )
+    }
+
+    #[test]
+    fn the_logs_should_be_read_in_the_order_of_their_first_line() {
+        let file_name = std::env::temp_dir().join("tlcfi_assimilator_zip_order.zip");
+        let newer_log = log_line("11:00:01,000", 2) + &log_line("11:00:02,000", 3);
+        let older_log = log_line("11:00:00,000", 1);
+        write_zip(
+            &file_name,
+            &[
+                ("logs/", ""),
+                ("tlcfi.txt", &newer_log),
+                ("readme.txt", "just some notes\n"),
+                ("tlcfi.txt.1", &older_log),
+            ],
+        );
+        let mut console = Console::new(tlcfi_assimilator::Verbosity::Quiet);
+
+        let lines = read_logs(file_name.to_str().unwrap(), true, &mut console, &mut |_| {});
+        fs::remove_file(&file_name).unwrap();
+
+        let lines = lines.unwrap();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("{\"state\":1}"));
+        assert!(lines[2].ends_with("{\"state\":3}"));
+        assert!(console
+            .all_warnings()
+            .iter()
+            .any(|warning| warning.contains("'readme.txt'")));
+    }
+
+    #[test]
+    fn a_corrupt_archive_should_return_err() {
+        let file_name = std::env::temp_dir().join("tlcfi_assimilator_zip_corrupt.zip");
+        fs::write(&file_name, "not a zip archive at all").unwrap();
+        let mut console = Console::new(tlcfi_assimilator::Verbosity::Quiet);
+
+        let result = read_logs(file_name.to_str().unwrap(), true, &mut console, &mut |_| {});
+        fs::remove_file(&file_name).unwrap();
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("tlcfi_assimilator_zip_corrupt.zip"));
+    }
+
+    #[test]
+    fn the_first_log_line_should_give_the_time_of_an_entry() {
+        let log = "garbage\n2021-12-15 11:00:00,074 INFO  tlcFiMessages:41 - IN - {}\n";
+
+        assert_eq!(
+            first_log_line_time(log.as_bytes()),
+            Some(Some(
+                chrono::NaiveDate::from_ymd(2021, 12, 15).and_hms_milli(11, 0, 0, 74)
+            ))
+        );
+        assert_eq!(first_log_line_time(&b"just some notes\n"[..]), None);
+    }
+}